@@ -0,0 +1,65 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use kv6::kv6::{KV6Format, VoxelData};
+
+/// A 64x64x64 model with every other height filled in each column.
+fn model() -> KV6Format {
+    let size = 64u32;
+    let mut format = KV6Format {
+        x_size: size,
+        y_size: size,
+        z_size: size,
+        ..Default::default()
+    };
+    for _ in 0..size * size {
+        for height in (0..size).step_by(2) {
+            format.voxels.push(VoxelData {
+                dummy: VoxelData::DUMMY,
+                height: height as u16,
+                ..Default::default()
+            });
+        }
+    }
+    format.xlen = vec![size * size / 2; size as usize];
+    format.ylen = vec![vec![size as u16 / 2; size as usize]; size as usize];
+    format
+}
+
+fn lookups(c: &mut Criterion) {
+    let data = model();
+    let grid = data.occupancy().unwrap();
+
+    let mut group = c.benchmark_group("lookups");
+    group.bench_function("get_voxel", |b| {
+        b.iter(|| {
+            let mut hits = 0;
+            for x in 0..64 {
+                for y in 0..64 {
+                    for z in 0..64 {
+                        hits += black_box(&data).get_voxel(x, y, z).is_some() as u32;
+                    }
+                }
+            }
+            hits
+        })
+    });
+    group.bench_function("occupancy", |b| {
+        b.iter(|| {
+            let mut hits = 0;
+            for x in 0..64 {
+                for y in 0..64 {
+                    for z in 0..64 {
+                        hits += black_box(&grid).contains(x, y, z) as u32;
+                    }
+                }
+            }
+            hits
+        })
+    });
+    group.bench_function("build", |b| {
+        b.iter(|| black_box(&data).occupancy().unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, lookups);
+criterion_main!(benches);
@@ -0,0 +1,60 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use kv6::{
+    kv6::{KV6Format, VoxelData},
+    utils::read_vec,
+};
+use scroll::LE;
+
+/// A 128x128 model with a full 128 voxel column everywhere, about 2 million
+/// voxels in total.
+fn big_model() -> Vec<u8> {
+    let (x, y, z) = (128u32, 128u32, 128u32);
+    let mut format = KV6Format {
+        x_size: x,
+        y_size: y,
+        z_size: z,
+        ..Default::default()
+    };
+    for _ in 0..x * y {
+        for height in 0..z {
+            format.voxels.push(VoxelData {
+                dummy: VoxelData::DUMMY,
+                height: height as u16,
+                ..Default::default()
+            });
+        }
+    }
+    format.xlen = vec![y * z; x as usize];
+    format.ylen = vec![vec![z as u16; y as usize]; x as usize];
+    format.to_bytes().unwrap()
+}
+
+fn voxels(c: &mut Criterion) {
+    let bytes = big_model();
+    let count = 128 * 128 * 128;
+    let block = &bytes[32..32 + count * 8];
+
+    let mut group = c.benchmark_group("voxels");
+    group.sample_size(20);
+    group.bench_function("bulk", |b| {
+        b.iter(|| {
+            black_box(block)
+                .chunks_exact(8)
+                .map(|chunk| VoxelData::from_bytes(chunk.try_into().unwrap()))
+                .collect::<Vec<_>>()
+        })
+    });
+    group.bench_function("per_field", |b| {
+        b.iter(|| {
+            let voxels: Vec<VoxelData> = read_vec(black_box(block), &mut 0, count, LE).unwrap();
+            voxels
+        })
+    });
+    group.bench_function("parse", |b| {
+        b.iter(|| KV6Format::parse(black_box(&bytes)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, voxels);
+criterion_main!(benches);
@@ -0,0 +1,75 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use kv6::kv6::{KV6Format, Kv6Soa, Rgb, VoxelData};
+
+/// A 128x128x128 model with every other height filled in each column.
+fn model() -> KV6Format {
+    let size = 128u32;
+    let mut format = KV6Format {
+        x_size: size,
+        y_size: size,
+        z_size: size,
+        ..Default::default()
+    };
+    for i in 0..size * size {
+        for height in (0..size).step_by(2) {
+            format.voxels.push(VoxelData {
+                red: i as u8,
+                green: height as u8,
+                dummy: VoxelData::DUMMY,
+                height: height as u16,
+                ..Default::default()
+            });
+        }
+    }
+    format.xlen = vec![size * size / 2; size as usize];
+    format.ylen = vec![vec![size as u16 / 2; size as usize]; size as usize];
+    format
+}
+
+fn invert(color: Rgb) -> Rgb {
+    Rgb::new(!color.r, !color.g, !color.b)
+}
+
+fn passes(c: &mut Criterion) {
+    let data = model();
+    let soa = Kv6Soa::from(&data);
+
+    let mut group = c.benchmark_group("color pass");
+    group.bench_function("aos", |b| {
+        let mut data = data.clone();
+        b.iter(|| {
+            for voxel in &mut black_box(&mut data).voxels {
+                voxel.set_color(invert(voxel.color()));
+            }
+        })
+    });
+    group.bench_function("soa", |b| {
+        let mut soa = soa.clone();
+        b.iter(|| black_box(&mut soa).map_colors(invert))
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("height sum");
+    group.bench_function("aos", |b| {
+        b.iter(|| {
+            black_box(&data)
+                .voxels
+                .iter()
+                .map(|voxel| voxel.height as u64)
+                .sum::<u64>()
+        })
+    });
+    group.bench_function("soa", |b| {
+        b.iter(|| {
+            black_box(&soa)
+                .heights
+                .iter()
+                .map(|&h| h as u64)
+                .sum::<u64>()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, passes);
+criterion_main!(benches);
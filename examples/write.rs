@@ -0,0 +1,30 @@
+use kv6::kv6::{KV6Builder, Rgb};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let radius = 8i32;
+    let size = (radius * 2 + 1) as u32;
+
+    let mut builder = KV6Builder::new(size, size, size);
+    for x in -radius..=radius {
+        for y in -radius..=radius {
+            for z in -radius..=radius {
+                if x * x + y * y + z * z <= radius * radius {
+                    // Shade from light at the top to dark at the bottom.
+                    let shade = (255 - (z + radius) * 8) as u8;
+                    let (x, y, z) = (
+                        (x + radius) as u32,
+                        (y + radius) as u32,
+                        (z + radius) as u16,
+                    );
+                    builder = builder.voxel(x, y, z, Rgb::new(shade, shade / 2, 32));
+                }
+            }
+        }
+    }
+
+    let data = builder.build()?;
+    data.save_to_path("sphere.kv6")?;
+    println!("wrote sphere.kv6: {}", data);
+
+    Ok(())
+}
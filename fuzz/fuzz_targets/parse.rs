@@ -0,0 +1,22 @@
+#![no_main]
+
+use kv6::kv6::{parse_all, KV6Format, Kv6Ref};
+use libfuzzer_sys::fuzz_target;
+
+// Every entry point that takes untrusted bytes. None of them may panic, and
+// the default limits keep allocations bounded.
+fuzz_target!(|data: &[u8]| {
+    let _ = KV6Format::parse(data);
+    let _ = KV6Format::parse_lossy(data);
+    let _ = KV6Format::peek_header(data);
+    let _ = parse_all(data);
+    if let Ok(view) = Kv6Ref::new(data) {
+        for voxel in view.voxels() {
+            let _ = voxel;
+        }
+        let _ = view.to_owned();
+    }
+    if let Ok((model, _)) = KV6Format::parse_lossy(data) {
+        let _ = model.to_bytes();
+    }
+});
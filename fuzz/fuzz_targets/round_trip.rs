@@ -0,0 +1,11 @@
+#![no_main]
+
+use kv6::kv6::KV6Format;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|model: KV6Format| {
+    let bytes = model.to_bytes().expect("generated models are always valid");
+    let (parsed, consumed) = KV6Format::parse(&bytes).expect("written models parse");
+    assert_eq!(consumed, bytes.len());
+    assert_eq!(parsed, model);
+});
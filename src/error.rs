@@ -0,0 +1,256 @@
+use crate::kv6::{CacheMismatch, FormatKind, ParseWarning, Section, ValidationIssue};
+use core::fmt;
+#[cfg(feature = "std")]
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Errors returned by the high-level reading and writing helpers.
+#[derive(Debug)]
+pub enum Kv6Error {
+    /// The input doesn't start with `"Kvxl"`. `detected` is the format it
+    /// looks like instead, if [`detect_format`](crate::kv6::detect_format)
+    /// recognizes it.
+    BadMagic {
+        found: u32,
+        detected: Option<FormatKind>,
+    },
+    /// The input ended partway through `section`. `offset` is where the first
+    /// record that couldn't be read starts.
+    Truncated { section: Section, offset: usize },
+    /// A header field is larger than the limit set in `ParseOptions`.
+    LimitExceeded {
+        field: &'static str,
+        value: u32,
+        limit: u32,
+    },
+    /// The caches for `x_size` by `y_size` columns don't fit in memory.
+    CacheOverflow { x_size: u32, y_size: u32 },
+    /// A grid with a cell for every position in the model doesn't fit in
+    /// memory.
+    GridOverflow {
+        x_size: u32,
+        y_size: u32,
+        z_size: u32,
+    },
+    /// A dense grid would have `cells` cells, more than the `limit` allowed.
+    GridTooLarge { cells: usize, limit: usize },
+    /// `(x, y, z)` is outside the model's grid.
+    OutOfBounds { x: u32, y: u32, z: u16 },
+    /// There's already a voxel at `(x, y, z)`.
+    Occupied { x: u32, y: u32, z: u16 },
+    /// `columns` column positions were given for a model with `voxels`
+    /// voxels, when there should be one per voxel.
+    ColumnCount { columns: usize, voxels: usize },
+    /// A count doesn't fit in the `u32` the format stores it in.
+    TooLarge { field: &'static str, value: usize },
+    /// The caches and the voxels don't describe the same model, so it can't be
+    /// written.
+    InconsistentSizes(CacheMismatch),
+    /// A column was pushed out of order. `expected` is `None` once every
+    /// column has been pushed, `found` is `None` if the model was finished
+    /// early.
+    ColumnOrder {
+        expected: Option<(u32, u32)>,
+        found: Option<(u32, u32)>,
+    },
+    /// The output buffer is too small; `required` bytes are needed in total.
+    BufferTooSmall { required: usize, available: usize },
+    /// Strict parsing refused something that is otherwise only a warning.
+    Rejected(ParseWarning),
+    /// Writing with `reject_invalid` refused a model; this is the first error
+    /// [`KV6Format::validate`](crate::KV6Format::validate) found.
+    Invalid(ValidationIssue),
+    /// A lower level read or write failed.
+    Scroll(scroll::Error),
+    /// Reading or writing a file or stream failed.
+    #[cfg(feature = "std")]
+    Io(io::Error),
+    /// Something went wrong while working on the file at `path`.
+    #[cfg(feature = "std")]
+    Path {
+        path: PathBuf,
+        source: Box<Kv6Error>,
+    },
+}
+
+impl Kv6Error {
+    /// The file this error relates to, if it came from one of the path helpers.
+    #[cfg(feature = "std")]
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Kv6Error::Path { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn with_path(self, path: &Path) -> Self {
+        Kv6Error::Path {
+            path: path.to_path_buf(),
+            source: Box::new(self),
+        }
+    }
+
+    /// A fixed description for when scroll can't carry a formatted message.
+    #[cfg(not(feature = "std"))]
+    fn summary(&self) -> &'static str {
+        match self {
+            Kv6Error::BadMagic { .. } => "bad magic",
+            Kv6Error::Truncated { .. } => "input is truncated",
+            Kv6Error::LimitExceeded { .. } => "header field exceeds its limit",
+            Kv6Error::CacheOverflow { .. } => "caches overflow the address space",
+            Kv6Error::GridOverflow { .. } => "grid overflows the address space",
+            Kv6Error::GridTooLarge { .. } => "grid exceeds its cell limit",
+            Kv6Error::OutOfBounds { .. } => "position is outside the model",
+            Kv6Error::Occupied { .. } => "position is already occupied",
+            Kv6Error::ColumnCount { .. } => "wrong number of columns",
+            Kv6Error::TooLarge { .. } => "count doesn't fit in a u32",
+            Kv6Error::InconsistentSizes(_) => "inconsistent sizes",
+            Kv6Error::ColumnOrder { .. } => "columns out of order",
+            Kv6Error::BufferTooSmall { .. } => "buffer too small",
+            Kv6Error::Rejected(_) => "rejected in strict mode",
+            Kv6Error::Invalid(_) => "model failed validation",
+            Kv6Error::Scroll(_) => "scroll error",
+        }
+    }
+}
+
+impl fmt::Display for Kv6Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Kv6Error::BadMagic { found, detected } => {
+                write!(
+                    f,
+                    "bad magic {:02x?} at offset 0, expected \"Kvxl\"",
+                    found.to_be_bytes()
+                )?;
+                match detected {
+                    Some(kind) => write!(f, " (this looks like a {} file)", kind),
+                    None => Ok(()),
+                }
+            }
+            Kv6Error::Truncated { section, offset } => {
+                write!(f, "input ends in the {} at offset {}", section, offset)
+            }
+            Kv6Error::LimitExceeded {
+                field,
+                value,
+                limit,
+            } => write!(f, "{} of {} exceeds the limit of {}", field, value, limit),
+            Kv6Error::CacheOverflow { x_size, y_size } => write!(
+                f,
+                "caches for {}x{} columns overflow the address space",
+                x_size, y_size
+            ),
+            Kv6Error::GridOverflow {
+                x_size,
+                y_size,
+                z_size,
+            } => write!(
+                f,
+                "a {}x{}x{} grid overflows the address space",
+                x_size, y_size, z_size
+            ),
+            Kv6Error::GridTooLarge { cells, limit } => write!(
+                f,
+                "a grid of {} cells exceeds the limit of {}",
+                cells, limit
+            ),
+            Kv6Error::OutOfBounds { x, y, z } => {
+                write!(f, "{:?} is outside the model", (x, y, z))
+            }
+            Kv6Error::Occupied { x, y, z } => {
+                write!(f, "there's already a voxel at {:?}", (x, y, z))
+            }
+            Kv6Error::ColumnCount { columns, voxels } => {
+                write!(f, "{} columns were given for {} voxels", columns, voxels)
+            }
+            Kv6Error::TooLarge { field, value } => {
+                write!(f, "{} has {} entries, more than fit in a u32", field, value)
+            }
+            Kv6Error::InconsistentSizes(mismatch) => write!(f, "inconsistent sizes: {}", mismatch),
+            Kv6Error::ColumnOrder { expected, found } => match (expected, found) {
+                (Some(expected), Some(found)) => {
+                    write!(f, "expected column {:?}, got {:?}", expected, found)
+                }
+                (None, Some(found)) => {
+                    write!(f, "got column {:?} after the last column", found)
+                }
+                (Some(expected), None) => {
+                    write!(f, "finished before column {:?}", expected)
+                }
+                (None, None) => write!(f, "columns out of order"),
+            },
+            Kv6Error::BufferTooSmall {
+                required,
+                available,
+            } => write!(
+                f,
+                "model needs {} bytes but the buffer only has {}",
+                required, available
+            ),
+            Kv6Error::Rejected(warning) => write!(f, "strict mode: {}", warning),
+            Kv6Error::Invalid(issue) => write!(f, "invalid model: {}", issue),
+            Kv6Error::Scroll(err) => write!(f, "{}", err),
+            #[cfg(feature = "std")]
+            Kv6Error::Io(err) => write!(f, "{}", err),
+            #[cfg(feature = "std")]
+            Kv6Error::Path { path, source } => write!(f, "{}: {}", path.display(), source),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Kv6Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Kv6Error::InconsistentSizes(mismatch) => Some(mismatch),
+            Kv6Error::Scroll(err) => Some(err),
+            #[cfg(feature = "std")]
+            Kv6Error::Io(err) => Some(err),
+            #[cfg(feature = "std")]
+            Kv6Error::Path { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<scroll::Error> for Kv6Error {
+    fn from(err: scroll::Error) -> Self {
+        Kv6Error::Scroll(err)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for Kv6Error {
+    fn from(err: io::Error) -> Self {
+        Kv6Error::Io(err)
+    }
+}
+
+/// Lets the scroll trait impls keep returning `scroll::Error`.
+impl From<Kv6Error> for scroll::Error {
+    fn from(err: Kv6Error) -> Self {
+        match err {
+            Kv6Error::Scroll(err) => err,
+            Kv6Error::BufferTooSmall {
+                required,
+                available,
+            } => scroll::Error::TooBig {
+                size: required,
+                len: available,
+            },
+            #[cfg(feature = "std")]
+            Kv6Error::Io(err) => scroll::Error::IO(err),
+            #[cfg(feature = "std")]
+            err => scroll::Error::Custom(err.to_string()),
+            #[cfg(not(feature = "std"))]
+            err => scroll::Error::BadInput {
+                size: 0,
+                msg: err.summary(),
+            },
+        }
+    }
+}
@@ -1,7 +1,120 @@
-use crate::{try_gread_vec_with, try_gwrite_vec_with};
-use scroll::{ctx, Endian, Pread, Pwrite, BE, LE};
+use crate::{
+    utils::{read_nested, read_vec, write_nested, write_vec},
+    Kv6Error,
+};
+use alloc::{vec, vec::Vec};
+use core::fmt;
+use normals::NormalTable;
+use scroll::{ctx, Endian, Pread, Pwrite, SizeWith, BE, LE};
 
-#[derive(Debug)]
+mod adjust;
+mod alpha;
+mod ao;
+#[cfg(feature = "arbitrary")]
+mod arbitrary_support;
+#[cfg(feature = "tokio")]
+mod async_io;
+mod bounds;
+mod builder;
+mod canvas;
+mod color;
+mod components;
+mod coords;
+mod dense;
+mod detect;
+mod diff;
+mod display;
+#[cfg(feature = "std")]
+mod document;
+mod edit;
+#[cfg(feature = "std")]
+mod file;
+mod fingerprint;
+#[cfg(feature = "glam")]
+mod glam_support;
+mod gradient;
+mod mass;
+mod merge;
+#[cfg(feature = "mmap")]
+mod mmap;
+mod morph;
+mod multi;
+#[cfg(feature = "ndarray")]
+mod ndarray_support;
+pub mod normals;
+mod occupancy;
+mod paint;
+mod pivot;
+mod projection;
+mod quantize;
+#[cfg(feature = "std")]
+mod reader;
+mod recover;
+mod scale;
+#[cfg(feature = "serde")]
+mod serde_support;
+mod shell;
+mod soa;
+mod split;
+mod stats;
+#[cfg(feature = "std")]
+mod stream;
+mod symmetry;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+mod transform;
+mod validate;
+mod view;
+mod visibility;
+#[cfg(feature = "std")]
+mod writer;
+
+pub use adjust::ColorAdjust;
+pub use alpha::AlphaMode;
+pub use ao::AoOptions;
+pub use bounds::Bounds;
+pub use builder::KV6Builder;
+pub use canvas::{Anchor, CropInfo};
+pub use color::Rgb;
+pub use components::Component;
+pub use coords::VoxelCoords;
+pub use dense::DenseVoxelGrid;
+pub use detect::{detect_format, FormatKind};
+pub use diff::{Kv6Diff, PatchError, PatchOptions};
+#[cfg(feature = "std")]
+pub use document::Kv6Document;
+pub use edit::SetResult;
+#[cfg(feature = "glam")]
+pub use glam_support::ResampleOptions;
+pub use gradient::{BlendMode, GradientError};
+pub use mass::MassProperties;
+pub use merge::ConflictPolicy;
+#[cfg(feature = "mmap")]
+pub use mmap::Kv6Mmap;
+pub use morph::DilateOptions;
+pub use multi::{parse_all, Kv6Stream};
+pub use occupancy::OccupancyGrid;
+pub use paint::{Connectivity, FloodOptions};
+pub use pivot::Centering;
+pub use projection::{Axis, ColumnProfile, Projection};
+pub use quantize::QuantizeMethod;
+#[cfg(feature = "std")]
+pub use reader::{Column, Kv6Reader};
+pub use scale::{DownsampleOptions, UpscaleOptions};
+pub use shell::{FillColor, StripOptions};
+pub use soa::Kv6Soa;
+pub use split::ChunkCoord;
+pub use stats::Kv6Stats;
+pub use symmetry::{HalfSpace, SymmetryPlane};
+pub use transform::AxisMapping;
+pub use validate::{Severity, ValidationIssue, ValidationReport};
+pub use view::Kv6Ref;
+pub use visibility::{Face, Visibility};
+#[cfg(feature = "std")]
+pub use writer::Kv6Writer;
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct KV6Format {
     pub magic: u32, // big endian
     pub x_size: u32,
@@ -11,34 +124,509 @@ pub struct KV6Format {
     pub x_pivot: f32,
     pub y_pivot: f32,
     pub z_pivot: f32,
-    pub voxels: Vec<VoxelData>, // length = num_voxels
-    pub xlen: Vec<u32>,         // cached data for speed in Build engine, length = x_size
+    pub voxels: Vec<VoxelData>,   // length = num_voxels
+    pub xlen: Vec<u32>,           // cached data for speed in Build engine, length = x_size
     pub ylen: Vec<Vec<u16>>, // more cached data for speed in Build engine, length[1] = x_size, length[2] = y_size
+    pub palette: Option<Palette>, // optional "SPal" suffix written by slab6
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub alpha_mode: AlphaMode, // not stored in the file, see `AlphaMode`
 }
 
-#[derive(Debug, Default, Pwrite)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, SizeWith)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VoxelData {
-    pub red: u8,   // 0..255
+    pub blue: u8,  // 0..255, the color is stored as a little endian 0x80RRGGBB
     pub green: u8, // 0..255
-    pub blue: u8,  // 0..255
+    pub red: u8,   // 0..255
     pub dummy: u8, // always 128, was probably once an alpha value
 
-    pub height: u16,     // little endian,
-    pub visibility: u8,  // low 6 bits are hidden surface removal info
-    pub normalindex: u8, // should probably ignore
+    pub height: u16,     // little endian
+    pub visibility: u8,  // low 6 bits are hidden surface removal info, see `Visibility`
+    pub normalindex: u8, // index into a normals table, see `normals`
+}
+
+impl VoxelData {
+    /// The value every writer is expected to put in `dummy`.
+    pub const DUMMY: u8 = 128;
+
+    /// Decodes one 8 byte record as stored on disk.
+    pub fn from_bytes(bytes: &[u8; 8]) -> Self {
+        let [blue, green, red, dummy, height_lo, height_hi, visibility, normalindex] = *bytes;
+        Self {
+            blue,
+            green,
+            red,
+            dummy,
+            height: u16::from_le_bytes([height_lo, height_hi]),
+            visibility,
+            normalindex,
+        }
+    }
+}
+
+/// The fixed 32 byte header at the start of every KV6 file.
+#[derive(Debug, Clone, Copy, PartialEq, SizeWith)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Kv6Header {
+    pub magic: u32, // big endian
+    pub x_size: u32,
+    pub y_size: u32,
+    pub z_size: u32,
+
+    pub x_pivot: f32,
+    pub y_pivot: f32,
+    pub z_pivot: f32,
+    pub num_voxels: u32,
+}
+
+impl Kv6Header {
+    pub const SIZE: usize = 32;
+
+    /// Bytes needed for the whole model (header, voxels, xlen and ylen),
+    /// not counting an optional palette suffix. `None` if it doesn't fit in
+    /// a `usize`.
+    pub fn required_len(&self) -> Option<usize> {
+        (self.num_voxels as usize)
+            .checked_mul(8)?
+            .checked_add(cache_len(self.x_size, self.y_size)?)?
+            .checked_add(Self::SIZE)
+    }
+}
+
+/// The 256 color palette slab6 appends after the caches, tagged with `"SPal"`.
+/// Components are stored as 6-bit VGA values (0..63).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Palette {
+    #[cfg_attr(feature = "serde", serde(with = "serde_support"))]
+    pub entries: [[u8; 3]; 256],
+}
+
+impl Palette {
+    pub const MAGIC: [u8; 4] = *b"SPal";
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            entries: [[0; 3]; 256],
+        }
+    }
+}
+
+/// Knobs for [`KV6Format::parse_with`].
+#[derive(Debug, Clone, Copy)]
+pub struct ParseOptions {
+    /// Largest voxel count accepted before anything is allocated.
+    pub max_voxels: u32,
+    /// Largest accepted value for each of `x_size`, `y_size` and `z_size`.
+    pub max_dimension: u32,
+    /// Reject files whose first four bytes aren't `"Kvxl"`.
+    pub check_magic: bool,
+    /// Turn every [`ParseWarning`] into an error instead of tolerating it.
+    pub strict: bool,
+    /// How to read the dummy byte. In [`AlphaMode::DummyByte`] values other
+    /// than 128 are expected and don't produce a warning.
+    pub alpha_mode: AlphaMode,
+}
+
+/// Recoverable oddities noticed while parsing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseWarning {
+    /// The magic was stored byte-swapped (`"lxvK"`) and has been normalized.
+    SwappedMagic,
+    /// The xlen/ylen caches disagree with each other or with the voxel count.
+    CacheMismatch(CacheMismatch),
+    /// The input ended partway through `section`, at byte `offset`.
+    Truncated { section: Section, offset: usize },
+    /// The caches were missing or incomplete and have been rebuilt from the
+    /// recovered voxels.
+    CachesRegenerated,
+    /// `count` voxels have a dummy byte other than 128, the first being voxel
+    /// `index` with `value`.
+    NonstandardDummy {
+        index: usize,
+        value: u8,
+        count: usize,
+    },
+    /// The model ends at `offset` but `count` more bytes follow it.
+    TrailingBytes { offset: usize, count: usize },
+}
+
+impl fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseWarning::SwappedMagic => write!(f, "magic is stored byte-swapped (\"lxvK\")"),
+            ParseWarning::CacheMismatch(mismatch) => write!(f, "{}", mismatch),
+            ParseWarning::Truncated { section, offset } => {
+                write!(f, "input ends in the {} at offset {}", section, offset)
+            }
+            ParseWarning::CachesRegenerated => write!(f, "caches were rebuilt from the voxels"),
+            ParseWarning::NonstandardDummy {
+                index,
+                value,
+                count,
+            } => write!(
+                f,
+                "{} voxels have a dummy byte other than 128, starting with voxel {} ({})",
+                count, index, value
+            ),
+            ParseWarning::TrailingBytes { offset, count } => write!(
+                f,
+                "{} trailing bytes after the model at offset {}",
+                count, offset
+            ),
+        }
+    }
+}
+
+/// The parts of a KV6 file, in the order they're stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    Header,
+    Voxels,
+    Xlen,
+    Ylen,
+    Palette,
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Section::Header => "header",
+            Section::Voxels => "voxels",
+            Section::Xlen => "xlen",
+            Section::Ylen => "ylen",
+            Section::Palette => "palette",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The first inconsistency found by [`KV6Format::check_caches`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CacheMismatch {
+    /// `xlen` doesn't have `x_size` entries.
+    XlenLength { expected: usize, actual: usize },
+    /// `ylen` doesn't have `x_size` rows.
+    YlenRows { expected: usize, actual: usize },
+    /// Row `x` of `ylen` doesn't have `y_size` entries.
+    YlenRowLength {
+        x: usize,
+        expected: usize,
+        actual: usize,
+    },
+    /// `xlen[x]` isn't the sum of `ylen[x][..]`.
+    Column {
+        x: usize,
+        expected: u64,
+        actual: u64,
+    },
+    /// The caches don't add up to the number of voxels.
+    Total { expected: u64, actual: u64 },
+}
+
+impl fmt::Display for CacheMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CacheMismatch::XlenLength { expected, actual } => {
+                write!(f, "xlen has {} entries, expected {}", actual, expected)
+            }
+            CacheMismatch::YlenRows { expected, actual } => {
+                write!(f, "ylen has {} rows, expected {}", actual, expected)
+            }
+            CacheMismatch::YlenRowLength {
+                x,
+                expected,
+                actual,
+            } => {
+                write!(
+                    f,
+                    "ylen[{}] has {} entries, expected {}",
+                    x, actual, expected
+                )
+            }
+            CacheMismatch::Column {
+                x,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "xlen[{}] is {} but ylen[{}] sums to {}",
+                x, actual, x, expected
+            ),
+            CacheMismatch::Total { expected, actual } => write!(
+                f,
+                "caches account for {} voxels but there are {}",
+                actual, expected
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CacheMismatch {}
+
+/// Knobs for [`KV6Format::to_bytes_with`].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct WriteOptions {
+    /// Refuse to write a model that [`KV6Format::validate_with`] finds errors
+    /// in. Warnings never stop a write.
+    pub reject_invalid: bool,
+    /// The table normal indices are checked against.
+    pub normal_table: NormalTable,
+}
+
+impl ParseOptions {
+    /// No limits and no extra checks, which is what the plain `Pread` path uses.
+    pub(crate) fn unlimited() -> Self {
+        Self {
+            max_voxels: u32::MAX,
+            max_dimension: u32::MAX,
+            check_magic: true,
+            strict: false,
+            alpha_mode: AlphaMode::Opaque,
+        }
+    }
+
+    fn warn(
+        &self,
+        warnings: &mut Vec<ParseWarning>,
+        warning: ParseWarning,
+    ) -> Result<(), Kv6Error> {
+        if self.strict {
+            return Err(Kv6Error::Rejected(warning));
+        }
+        warnings.push(warning);
+        Ok(())
+    }
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            max_voxels: 1 << 24,
+            max_dimension: 4096,
+            check_magic: true,
+            strict: false,
+            alpha_mode: AlphaMode::Opaque,
+        }
+    }
+}
+
+impl KV6Format {
+    /// The `"Kvxl"` tag every KV6 file starts with, read big endian.
+    pub const MAGIC: u32 = 0x4b76786c;
+
+    /// Parses a model from the start of `src` with the default [`ParseOptions`],
+    /// returning it along with the number of bytes it took up.
+    ///
+    /// Anything after the model is left alone; use strict options with
+    /// [`KV6Format::parse_with`] to reject it instead.
+    pub fn parse(src: &[u8]) -> Result<(Self, usize), Kv6Error> {
+        Self::read(src, &ParseOptions::default(), &mut Vec::new())
+    }
+
+    /// Parses a model from the start of `src` using the given options.
+    pub fn parse_with(src: &[u8], options: &ParseOptions) -> Result<Self, Kv6Error> {
+        Self::read(src, options, &mut Vec::new()).map(|(format, _)| format)
+    }
+
+    /// Like [`KV6Format::parse_with`], but also returns anything odd that was
+    /// tolerated along the way.
+    pub fn parse_with_warnings(
+        src: &[u8],
+        options: &ParseOptions,
+    ) -> Result<(Self, Vec<ParseWarning>), Kv6Error> {
+        let mut warnings = Vec::new();
+        let (format, _) = Self::read(src, options, &mut warnings)?;
+        Ok((format, warnings))
+    }
+
+    /// Reads just the header, validating the magic, without touching the voxels.
+    pub fn peek_header(src: &[u8]) -> Result<Kv6Header, Kv6Error> {
+        Kv6Header::read(src, &mut 0, &ParseOptions::unlimited(), &mut Vec::new())
+    }
+
+    /// Serializes the model into a buffer of exactly the right size.
+    ///
+    /// The output depends only on the model: the same model always produces
+    /// the same bytes, on every platform. `tests/golden/` pins this down.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Kv6Error> {
+        let size = self.serialized_size();
+        let mut bytes = vec![0u8; size];
+        let written = self.write(&mut bytes)?;
+        assert_eq!(
+            written, size,
+            "serialized size doesn't match what was written"
+        );
+
+        Ok(bytes)
+    }
+
+    /// Like [`KV6Format::to_bytes`], but with the checks in `options`.
+    pub fn to_bytes_with(&self, options: &WriteOptions) -> Result<Vec<u8>, Kv6Error> {
+        if options.reject_invalid {
+            let report = self.validate_with(options.normal_table);
+            let first = report.errors().next().cloned();
+            if let Some(issue) = first {
+                return Err(Kv6Error::Invalid(issue));
+            }
+        }
+        self.to_bytes()
+    }
+
+    /// Number of bytes [`KV6Format::to_bytes`] and `try_into_ctx` produce for
+    /// this model, including the palette suffix if there is one.
+    ///
+    /// `KV6Format` doesn't implement `SizeWith` because its size depends on
+    /// the contents; [`VoxelData`] and [`Kv6Header`] do.
+    pub fn serialized_size(&self) -> usize {
+        let ylen_entries: usize = self.ylen.iter().map(Vec::len).sum();
+        let palette = match self.palette {
+            Some(_) => Palette::MAGIC.len() + 256 * 3,
+            None => 0,
+        };
+
+        Kv6Header::SIZE + self.voxels.len() * 8 + self.xlen.len() * 4 + ylen_entries * 2 + palette
+    }
+
+    /// Resets every voxel's dummy byte to [`VoxelData::DUMMY`].
+    pub fn normalize_dummy(&mut self) {
+        for voxel in &mut self.voxels {
+            voxel.dummy = VoxelData::DUMMY;
+        }
+    }
+
+    fn check_dummy(&self) -> Option<ParseWarning> {
+        if self.alpha_mode == AlphaMode::DummyByte {
+            return None;
+        }
+        let mut odd = self
+            .voxels
+            .iter()
+            .enumerate()
+            .filter(|(_, voxel)| voxel.dummy != VoxelData::DUMMY);
+        let (index, first) = odd.next()?;
+        Some(ParseWarning::NonstandardDummy {
+            index,
+            value: first.dummy,
+            count: odd.count() + 1,
+        })
+    }
+
+    /// Checks that `xlen` and `ylen` have the declared shape, that every
+    /// `xlen[x]` equals the sum of `ylen[x][..]`, and that both add up to the
+    /// number of voxels.
+    pub fn check_caches(&self) -> Result<(), CacheMismatch> {
+        let x_size = self.x_size as usize;
+        let y_size = self.y_size as usize;
+
+        if self.xlen.len() != x_size {
+            return Err(CacheMismatch::XlenLength {
+                expected: x_size,
+                actual: self.xlen.len(),
+            });
+        }
+        if self.ylen.len() != x_size {
+            return Err(CacheMismatch::YlenRows {
+                expected: x_size,
+                actual: self.ylen.len(),
+            });
+        }
+
+        let mut total = 0u64;
+        for (x, (row, &xlen)) in self.ylen.iter().zip(&self.xlen).enumerate() {
+            if row.len() != y_size {
+                return Err(CacheMismatch::YlenRowLength {
+                    x,
+                    expected: y_size,
+                    actual: row.len(),
+                });
+            }
+
+            let column: u64 = row.iter().map(|&n| n as u64).sum();
+            if column != xlen as u64 {
+                return Err(CacheMismatch::Column {
+                    x,
+                    expected: column,
+                    actual: xlen as u64,
+                });
+            }
+            total += column;
+        }
+
+        if total != self.voxels.len() as u64 {
+            return Err(CacheMismatch::Total {
+                expected: self.voxels.len() as u64,
+                actual: total,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Whether both models describe the same grid with the same voxels in
+    /// the same places and colors.
+    ///
+    /// Unlike `==`, this ignores the order of voxels within a column, the
+    /// `dummy`, `visibility` and `normalindex` bytes, pivots and the palette.
+    /// Voxel positions come from `ylen`, so a model whose caches don't match
+    /// its voxels is never equivalent to anything.
+    pub fn equivalent(&self, other: &Self) -> bool {
+        if (self.x_size, self.y_size, self.z_size) != (other.x_size, other.y_size, other.z_size) {
+            return false;
+        }
+        match (self.occupied(), other.occupied()) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    /// Every voxel as `(x, y, height, [red, green, blue])`, sorted.
+    fn occupied(&self) -> Option<Vec<Occupied>> {
+        let mut occupied: Vec<Occupied> = self
+            .iter_with_coords()
+            .ok()?
+            .map(|(x, y, z, voxel)| (x, y, z, [voxel.red, voxel.green, voxel.blue]))
+            .collect();
+        occupied.sort_unstable();
+        Some(occupied)
+    }
 }
 
+/// A voxel's position and color, as compared by [`KV6Format::equivalent`].
+type Occupied = (u32, u32, u16, [u8; 3]);
 
 impl Default for KV6Format {
     fn default() -> Self {
-        Self { magic: 0x4b76786c, x_size: Default::default(), y_size: Default::default(), z_size: Default::default(), x_pivot: Default::default(), y_pivot: Default::default(), z_pivot: Default::default(), voxels: Default::default(), xlen: Default::default(), ylen: Default::default() }
+        Self {
+            magic: KV6Format::MAGIC,
+            x_size: Default::default(),
+            y_size: Default::default(),
+            z_size: Default::default(),
+            x_pivot: Default::default(),
+            y_pivot: Default::default(),
+            z_pivot: Default::default(),
+            voxels: Default::default(),
+            xlen: Default::default(),
+            ylen: Default::default(),
+            palette: Default::default(),
+            alpha_mode: Default::default(),
+        }
     }
 }
 
+/// Sets the sizes and pivot of an otherwise empty model, without building
+/// caches for it.
+#[deprecated(note = "use `KV6Builder`, which also places voxels and builds caches to match")]
 pub struct KV6FormatBuilder {
-    format: KV6Format
+    format: KV6Format,
 }
 
+#[allow(deprecated)]
 impl KV6FormatBuilder {
     pub fn new() -> KV6FormatBuilder {
         Self {
@@ -67,16 +655,66 @@ impl KV6FormatBuilder {
     }
 }
 
+#[allow(deprecated)]
 impl Default for KV6FormatBuilder {
     fn default() -> KV6FormatBuilder {
         Self::new()
     }
 }
 
+/// Like the read side, the endianness is ignored.
+impl ctx::TryIntoCtx<Endian> for &KV6Format {
+    type Error = scroll::Error;
+
+    fn try_into_ctx(self, bytes: &mut [u8], _: Endian) -> Result<usize, Self::Error> {
+        Ok(self.write(bytes)?)
+    }
+}
+
+impl ctx::TryIntoCtx<Endian> for KV6Format {
+    type Error = scroll::Error;
+
+    fn try_into_ctx(self, bytes: &mut [u8], ctx: Endian) -> Result<usize, Self::Error> {
+        (&self).try_into_ctx(bytes, ctx)
+    }
+}
+
+impl ctx::TryIntoCtx<Endian, Vec<u8>> for &KV6Format {
+    type Error = scroll::Error;
+
+    fn try_into_ctx(self, bytes: &mut Vec<u8>, ctx: Endian) -> Result<usize, Self::Error> {
+        self.try_into_ctx(&mut bytes[..], ctx)
+    }
+}
+
 impl ctx::TryIntoCtx<Endian, Vec<u8>> for KV6Format {
     type Error = scroll::Error;
 
     fn try_into_ctx(self, bytes: &mut Vec<u8>, ctx: Endian) -> Result<usize, Self::Error> {
+        (&self).try_into_ctx(&mut bytes[..], ctx)
+    }
+}
+
+impl KV6Format {
+    fn write(&self, bytes: &mut [u8]) -> Result<usize, Kv6Error> {
+        let ctx = LE;
+
+        let num_voxels = u32::try_from(self.voxels.len()).map_err(|_| Kv6Error::TooLarge {
+            field: "voxels",
+            value: self.voxels.len(),
+        })?;
+        // Readers rely on the caches to find columns, so don't write a model
+        // they would disagree about.
+        self.check_caches().map_err(Kv6Error::InconsistentSizes)?;
+        // Check the room up front so a short buffer is left untouched.
+        let required = self.serialized_size();
+        if bytes.len() < required {
+            return Err(Kv6Error::BufferTooSmall {
+                required,
+                available: bytes.len(),
+            });
+        }
+
         let offset = &mut 0;
         bytes.gwrite_with(self.magic, offset, BE)?;
         bytes.gwrite_with(self.x_size, offset, ctx)?;
@@ -87,74 +725,303 @@ impl ctx::TryIntoCtx<Endian, Vec<u8>> for KV6Format {
         bytes.gwrite_with(self.y_pivot, offset, ctx)?;
         bytes.gwrite_with(self.z_pivot, offset, ctx)?;
 
-        bytes.gwrite_with(self.voxels.len() as u32, offset, ctx)?;
-        try_gwrite_vec_with!(bytes, offset, self.voxels, ctx);
+        bytes.gwrite_with(num_voxels, offset, ctx)?;
+        write_vec(bytes, offset, &self.voxels, ctx)?;
+
+        write_vec(bytes, offset, &self.xlen, ctx)?;
+        write_nested(bytes, offset, &self.ylen, ctx)?;
 
-        try_gwrite_vec_with!(bytes, offset, self.xlen, ctx);
-        // TODO: Writing for Vec<Vec<u16>>
-        // try_gwrite_vec_with!(bytes, offset, self.ylen, ctx);
+        if let Some(palette) = self.palette {
+            bytes.gwrite_with(&Palette::MAGIC[..], offset, ())?;
+            bytes.gwrite_with(palette, offset, ctx)?;
+        }
 
         Ok(*offset)
     }
 }
 
-
+/// The layout is fixed, so the endianness is ignored and the model is always
+/// read as little endian with a big endian magic.
 impl<'a> ctx::TryFromCtx<'a, Endian> for KV6Format {
+    type Error = scroll::Error;
+    fn try_from_ctx(src: &'a [u8], _: Endian) -> Result<(Self, usize), Self::Error> {
+        Ok(KV6Format::read(
+            src,
+            &ParseOptions::unlimited(),
+            &mut Vec::new(),
+        )?)
+    }
+}
+
+impl KV6Format {
+    fn read(
+        src: &[u8],
+        options: &ParseOptions,
+        warnings: &mut Vec<ParseWarning>,
+    ) -> Result<(Self, usize), Kv6Error> {
+        // Everything but the magic is little endian.
+        let endian = LE;
+        let offset = &mut 0;
+
+        let Kv6Header {
+            magic,
+            x_size,
+            y_size,
+            z_size,
+            x_pivot,
+            y_pivot,
+            z_pivot,
+            num_voxels,
+        } = Kv6Header::read(src, offset, options, warnings)?;
+
+        // Don't trust the count for the allocation until the bytes are known to exist.
+        let remaining = src.len() - *offset;
+        if num_voxels as u64 * 8 > remaining as u64 {
+            return Err(Kv6Error::Truncated {
+                section: Section::Voxels,
+                offset: *offset + remaining / 8 * 8,
+            });
+        }
+        // Records are fixed size, so decode them straight from the bytes
+        // instead of field by field.
+        let end = *offset + num_voxels as usize * 8;
+        let voxels: Vec<VoxelData> = src[*offset..end]
+            .chunks_exact(8)
+            .map(|chunk| VoxelData::from_bytes(chunk.try_into().unwrap()))
+            .collect();
+        *offset = end;
+
+        // xlen is x_size u32s, ylen is x_size * y_size u16s. Both sizes come
+        // straight from the header, so make sure they fit before allocating.
+        let remaining = src.len() - *offset;
+        match cache_len(x_size, y_size) {
+            Some(needed) if needed <= remaining => {}
+            Some(_) if remaining < x_size as usize * 4 => {
+                return Err(Kv6Error::Truncated {
+                    section: Section::Xlen,
+                    offset: *offset + remaining / 4 * 4,
+                })
+            }
+            Some(_) => {
+                let xlen_bytes = x_size as usize * 4;
+                return Err(Kv6Error::Truncated {
+                    section: Section::Ylen,
+                    offset: *offset + xlen_bytes + (remaining - xlen_bytes) / 2 * 2,
+                });
+            }
+            None => return Err(Kv6Error::CacheOverflow { x_size, y_size }),
+        }
+        let xlen: Vec<u32> = read_vec(src, offset, x_size, endian)?;
+        let ylen: Vec<Vec<u16>> = read_nested(src, offset, x_size, y_size, endian)?;
+
+        let palette = if src.get(*offset..*offset + 4) == Some(&Palette::MAGIC[..]) {
+            *offset += 4;
+            Some(gread(src, offset, endian, Section::Palette)?)
+        } else {
+            None
+        };
+
+        let format = KV6Format {
+            magic,
+            x_size,
+            y_size,
+            z_size,
+            x_pivot,
+            y_pivot,
+            z_pivot,
+            voxels,
+            xlen,
+            ylen,
+            palette,
+            alpha_mode: options.alpha_mode,
+        };
+        if let Err(mismatch) = format.check_caches() {
+            options.warn(warnings, ParseWarning::CacheMismatch(mismatch))?;
+        }
+        if let Some(warning) = format.check_dummy() {
+            options.warn(warnings, warning)?;
+        }
+        if *offset < src.len() {
+            let warning = ParseWarning::TrailingBytes {
+                offset: *offset,
+                count: src.len() - *offset,
+            };
+            options.warn(warnings, warning)?;
+        }
+
+        Ok((format, *offset))
+    }
+}
+
+/// The endianness is ignored, as for [`KV6Format`].
+impl<'a> ctx::TryFromCtx<'a, Endian> for Kv6Header {
+    type Error = scroll::Error;
+    fn try_from_ctx(src: &'a [u8], _: Endian) -> Result<(Self, usize), Self::Error> {
+        let offset = &mut 0;
+        let header = Kv6Header::read(src, offset, &ParseOptions::unlimited(), &mut Vec::new())?;
+        Ok((header, *offset))
+    }
+}
+
+impl ctx::TryIntoCtx<Endian> for Kv6Header {
+    type Error = scroll::Error;
+
+    fn try_into_ctx(self, bytes: &mut [u8], _: Endian) -> Result<usize, Self::Error> {
+        let offset = &mut 0;
+        bytes.gwrite_with(self.magic, offset, BE)?;
+        bytes.gwrite_with(self.x_size, offset, LE)?;
+        bytes.gwrite_with(self.y_size, offset, LE)?;
+        bytes.gwrite_with(self.z_size, offset, LE)?;
+
+        bytes.gwrite_with(self.x_pivot, offset, LE)?;
+        bytes.gwrite_with(self.y_pivot, offset, LE)?;
+        bytes.gwrite_with(self.z_pivot, offset, LE)?;
+
+        bytes.gwrite_with(self.num_voxels, offset, LE)?;
+        Ok(*offset)
+    }
+}
+
+impl Kv6Header {
+    fn read(
+        src: &[u8],
+        offset: &mut usize,
+        options: &ParseOptions,
+        warnings: &mut Vec<ParseWarning>,
+    ) -> Result<Self, Kv6Error> {
+        let section = Section::Header;
+        let endian = LE;
+
+        let mut magic: u32 = gread(src, offset, BE, section)?;
+        if magic == KV6Format::MAGIC.swap_bytes() {
+            magic = KV6Format::MAGIC;
+            options.warn(warnings, ParseWarning::SwappedMagic)?;
+        }
+        if options.check_magic && magic != KV6Format::MAGIC {
+            let detected = detect_format(src)
+                .filter(|kind| !matches!(kind, FormatKind::Kv6 | FormatKind::Unknown));
+            return Err(Kv6Error::BadMagic {
+                found: magic,
+                detected,
+            });
+        }
+        let x_size: u32 = gread(src, offset, endian, section)?;
+        let y_size: u32 = gread(src, offset, endian, section)?;
+        let z_size: u32 = gread(src, offset, endian, section)?;
+        for (field, value) in [("x_size", x_size), ("y_size", y_size), ("z_size", z_size)] {
+            if value > options.max_dimension {
+                return Err(Kv6Error::LimitExceeded {
+                    field,
+                    value,
+                    limit: options.max_dimension,
+                });
+            }
+        }
+
+        let x_pivot: f32 = gread(src, offset, endian, section)?;
+        let y_pivot: f32 = gread(src, offset, endian, section)?;
+        let z_pivot: f32 = gread(src, offset, endian, section)?;
+
+        let num_voxels: u32 = gread(src, offset, endian, section)?;
+        if num_voxels > options.max_voxels {
+            return Err(Kv6Error::LimitExceeded {
+                field: "num_voxels",
+                value: num_voxels,
+                limit: options.max_voxels,
+            });
+        }
+
+        Ok(Kv6Header {
+            magic,
+            x_size,
+            y_size,
+            z_size,
+            x_pivot,
+            y_pivot,
+            z_pivot,
+            num_voxels,
+        })
+    }
+}
+
+/// Reads one value, reporting a short buffer as the end of `section`.
+fn gread<'a, T>(
+    src: &'a [u8],
+    offset: &mut usize,
+    ctx: Endian,
+    section: Section,
+) -> Result<T, Kv6Error>
+where
+    T: ctx::TryFromCtx<'a, Endian, Error = scroll::Error>,
+{
+    let start = *offset;
+    src.gread_with(offset, ctx).map_err(|err| match err {
+        scroll::Error::TooBig { .. } | scroll::Error::BadOffset(_) => Kv6Error::Truncated {
+            section,
+            offset: start,
+        },
+        err => Kv6Error::Scroll(err),
+    })
+}
+
+/// Bytes taken by xlen (`x_size` u32s) and ylen (`x_size * y_size` u16s),
+/// or `None` if that doesn't fit in a `usize`.
+fn cache_len(x_size: u32, y_size: u32) -> Option<usize> {
+    let xlen_bytes = (x_size as usize).checked_mul(4)?;
+    let ylen_bytes = (x_size as usize)
+        .checked_mul(y_size as usize)?
+        .checked_mul(2)?;
+    xlen_bytes.checked_add(ylen_bytes)
+}
+
+impl<'a> ctx::TryFromCtx<'a, Endian> for Palette {
     type Error = scroll::Error;
     fn try_from_ctx(src: &'a [u8], endian: Endian) -> Result<(Self, usize), Self::Error> {
         let offset = &mut 0;
 
-        let magic: u32 = src.gread_with(offset, BE)?;
-        let x_size: u32 = src.gread_with(offset, endian)?;
-        let y_size: u32 = src.gread_with(offset, endian)?;
-        let z_size: u32 = src.gread_with(offset, endian)?;
+        let mut palette = Palette::default();
+        for entry in palette.entries.iter_mut() {
+            src.gread_inout_with(offset, entry, endian)?;
+        }
 
-        let x_pivot: f32 = src.gread_with(offset, endian)?;
-        let y_pivot: f32 = src.gread_with(offset, endian)?;
-        let z_pivot: f32 = src.gread_with(offset, endian)?;
+        Ok((palette, *offset))
+    }
+}
 
-        let num_voxels: u32 = src.gread_with(offset, endian)?;
-        // let data: Vec<u8> = try_gread_vec_with!(src, offset, size, endian);
-        let voxels: Vec<VoxelData> = try_gread_vec_with!(src, offset, num_voxels, endian);
-        let xlen: Vec<u32> = try_gread_vec_with!(src, offset, x_size, endian);
+impl ctx::TryIntoCtx<Endian> for Palette {
+    type Error = scroll::Error;
 
-        Ok((
-            KV6Format {
-                magic,
-                x_size,
-                y_size,
-                z_size,
-                x_pivot,
-                y_pivot,
-                z_pivot,
-                voxels,
-                xlen,
-                ylen: Default::default(),
-            },
-            *offset,
-        ))
+    fn try_into_ctx(self, bytes: &mut [u8], ctx: Endian) -> Result<usize, Self::Error> {
+        let offset = &mut 0;
+        for entry in self.entries {
+            write_vec(bytes, offset, entry, ctx)?;
+        }
+
+        Ok(*offset)
     }
 }
 
+/// The record layout is fixed: `height` is the only multi-byte field and is
+/// always little endian, so the endianness passed in is ignored.
 impl<'a> ctx::TryFromCtx<'a, Endian> for VoxelData {
     type Error = scroll::Error;
-    fn try_from_ctx(src: &'a [u8], endian: Endian) -> Result<(Self, usize), Self::Error> {
+    fn try_from_ctx(src: &'a [u8], _: Endian) -> Result<(Self, usize), Self::Error> {
         let offset = &mut 0;
 
-        let red: u8 = src.gread_with(offset, BE)?;
-        let green: u8 = src.gread_with(offset, endian)?;
-        let blue: u8 = src.gread_with(offset, endian)?;
-        let dummy: u8 = src.gread_with(offset, endian)?;
+        let blue: u8 = src.gread(offset)?;
+        let green: u8 = src.gread(offset)?;
+        let red: u8 = src.gread(offset)?;
+        let dummy: u8 = src.gread(offset)?;
 
         let height: u16 = src.gread_with(offset, LE)?;
-        let visibility: u8 = src.gread_with(offset, endian)?;
-        let normalindex: u8 = src.gread_with(offset, endian)?;
+        let visibility: u8 = src.gread(offset)?;
+        let normalindex: u8 = src.gread(offset)?;
 
         Ok((
             VoxelData {
-                red,
-                green,
                 blue,
+                green,
+                red,
                 dummy,
                 height,
                 visibility,
@@ -165,35 +1032,182 @@ impl<'a> ctx::TryFromCtx<'a, Endian> for VoxelData {
     }
 }
 
-#[cfg(test)]
+impl ctx::TryIntoCtx<Endian> for &VoxelData {
+    type Error = scroll::Error;
+
+    fn try_into_ctx(self, bytes: &mut [u8], _: Endian) -> Result<usize, Self::Error> {
+        let offset = &mut 0;
+
+        bytes.gwrite(self.blue, offset)?;
+        bytes.gwrite(self.green, offset)?;
+        bytes.gwrite(self.red, offset)?;
+        bytes.gwrite(self.dummy, offset)?;
+
+        bytes.gwrite_with(self.height, offset, LE)?;
+        bytes.gwrite(self.visibility, offset)?;
+        bytes.gwrite(self.normalindex, offset)?;
+
+        Ok(*offset)
+    }
+}
+
+impl ctx::TryIntoCtx<Endian> for VoxelData {
+    type Error = scroll::Error;
+
+    fn try_into_ctx(self, bytes: &mut [u8], ctx: Endian) -> Result<usize, Self::Error> {
+        (&self).try_into_ctx(bytes, ctx)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
 mod tests {
-    use super::KV6Format;
-    use scroll::Pread;
-    use std::{
-        fs::File,
-        io::{BufReader, Read},
+    use super::{
+        CacheMismatch, KV6Format, Kv6Header, Palette, ParseOptions, ParseWarning, Section,
+        VoxelData,
+    };
+    use crate::Kv6Error;
+    use scroll::{
+        ctx::{SizeWith, TryIntoCtx},
+        Pread, Pwrite, BE, LE,
     };
+    use std::fs;
 
     #[test]
     fn test_read_header() {
-        let f = File::open("data/grenade.kv6").unwrap();
-        let mut reader = BufReader::new(f);
-        let mut buffer = Vec::new();
-        reader.read_to_end(&mut buffer).unwrap();
-
-        let data = buffer.pread::<KV6Format>(0).unwrap();
+        let data = KV6Format::from_path("data/grenade.kv6").unwrap();
 
         assert_eq!(data.magic, 0x4b76786c);
     }
 
     #[test]
-    fn test_read_sizes() {
-        let f = File::open("data/grenade.kv6").unwrap();
-        let mut reader = BufReader::new(f);
-        let mut buffer = Vec::new();
-        reader.read_to_end(&mut buffer).unwrap();
+    fn test_read_bad_magic() {
+        let mut buffer = fs::read("data/grenade.kv6").unwrap();
+        buffer[..4].copy_from_slice(b"Kvxx");
 
-        let data = buffer.pread::<KV6Format>(0).unwrap();
+        let err = buffer.pread::<KV6Format>(0).unwrap_err();
+        assert!(err.to_string().contains("bad magic"));
+
+        let options = ParseOptions {
+            check_magic: false,
+            ..Default::default()
+        };
+        let data = KV6Format::parse_with(&buffer, &options).unwrap();
+        assert_eq!(data.magic, u32::from_be_bytes(*b"Kvxx"));
+        assert_eq!(data.voxels.len(), 74);
+    }
+
+    #[test]
+    fn test_read_swapped_magic() {
+        let buffer = fs::read("data/grenade.kv6").unwrap();
+
+        let (_, warnings) =
+            KV6Format::parse_with_warnings(&buffer, &ParseOptions::default()).unwrap();
+        assert!(warnings.is_empty());
+
+        let mut swapped = buffer.clone();
+        swapped[..4].copy_from_slice(b"lxvK");
+
+        let (data, warnings) =
+            KV6Format::parse_with_warnings(&swapped, &ParseOptions::default()).unwrap();
+        assert_eq!(data.magic, KV6Format::MAGIC);
+        assert_eq!(warnings, vec![ParseWarning::SwappedMagic]);
+
+        let mut out = vec![0u8; buffer.len()];
+        data.try_into_ctx(&mut out, LE).unwrap();
+        assert_eq!(out, buffer);
+    }
+
+    #[test]
+    fn test_read_swapped_magic_strict() {
+        let mut buffer = fs::read("data/grenade.kv6").unwrap();
+        buffer[..4].copy_from_slice(b"lxvK");
+
+        let options = ParseOptions {
+            strict: true,
+            ..Default::default()
+        };
+        assert!(KV6Format::parse_with(&buffer, &options).is_err());
+    }
+
+    #[test]
+    fn test_read_hostile_voxel_count() {
+        let mut header = Vec::new();
+        header.extend_from_slice(b"Kvxl");
+        for n in [1u32, 1, 1] {
+            header.extend_from_slice(&n.to_le_bytes());
+        }
+        for p in [0.5f32, 0.5, 0.5] {
+            header.extend_from_slice(&p.to_le_bytes());
+        }
+        header.extend_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(header.len(), 32);
+
+        let err = KV6Format::parse_with(&header, &ParseOptions::default()).unwrap_err();
+        assert!(matches!(
+            err,
+            Kv6Error::LimitExceeded {
+                field: "num_voxels",
+                ..
+            }
+        ));
+
+        let options = ParseOptions {
+            max_voxels: u32::MAX,
+            ..Default::default()
+        };
+        assert!(KV6Format::parse_with(&header, &options).is_err());
+        assert!(header.pread::<KV6Format>(0).is_err());
+    }
+
+    #[test]
+    fn test_read_hostile_dimensions() {
+        let mut buffer = fs::read("data/grenade.kv6").unwrap();
+        buffer[4..8].copy_from_slice(&100_000u32.to_le_bytes());
+
+        let err = KV6Format::parse_with(&buffer, &ParseOptions::default()).unwrap_err();
+        assert!(matches!(
+            err,
+            Kv6Error::LimitExceeded {
+                field: "x_size",
+                value: 100_000,
+                limit: 4096,
+            }
+        ));
+    }
+
+    #[test]
+    fn test_read_hostile_cache_dimensions() {
+        let mut header = Vec::new();
+        header.extend_from_slice(b"Kvxl");
+        for n in [0xFFFF_FFFFu32, 0xFFFF_FFFF, 1] {
+            header.extend_from_slice(&n.to_le_bytes());
+        }
+        for p in [0.5f32, 0.5, 0.5] {
+            header.extend_from_slice(&p.to_le_bytes());
+        }
+        header.extend_from_slice(&0u32.to_le_bytes());
+        header.extend_from_slice(&[0; 64]);
+
+        let err = header.pread::<KV6Format>(0).unwrap_err();
+        assert!(err.to_string().contains("4294967295x4294967295"));
+
+        let options = ParseOptions {
+            max_dimension: u32::MAX,
+            ..Default::default()
+        };
+        assert!(KV6Format::parse_with(&header, &options).is_err());
+    }
+
+    #[test]
+    fn test_read_short_magic() {
+        let buffer = b"Kvx";
+        assert!(buffer.pread::<KV6Format>(0).is_err());
+        assert!(KV6Format::parse_with(buffer, &ParseOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_read_sizes() {
+        let data = KV6Format::from_path("data/grenade.kv6").unwrap();
 
         assert_eq!(data.x_size, 6);
         assert_eq!(data.y_size, 6);
@@ -202,12 +1216,7 @@ mod tests {
 
     #[test]
     fn test_read_pivots() {
-        let f = File::open("data/grenade.kv6").unwrap();
-        let mut reader = BufReader::new(f);
-        let mut buffer = Vec::new();
-        reader.read_to_end(&mut buffer).unwrap();
-
-        let data = buffer.pread::<KV6Format>(0).unwrap();
+        let data = KV6Format::from_path("data/grenade.kv6").unwrap();
 
         assert_eq!(data.x_pivot, 2.5);
         assert_eq!(data.y_pivot, 2.5);
@@ -216,23 +1225,502 @@ mod tests {
 
     #[test]
     fn test_read_voxels() {
-        let f = File::open("data/grenade.kv6").unwrap();
-        let mut reader = BufReader::new(f);
-        let mut buffer = Vec::new();
-        reader.read_to_end(&mut buffer).unwrap();
+        let data = KV6Format::from_path("data/grenade.kv6").unwrap();
+        assert_eq!(data.voxels.len(), 74);
+    }
+
+    #[test]
+    fn test_read_xlen() {
+        let data = KV6Format::from_path("data/grenade.kv6").unwrap();
+        assert_eq!(data.xlen.len() as u32, data.x_size);
+    }
+
+    #[test]
+    fn test_read_ylen() {
+        let data = KV6Format::from_path("data/grenade.kv6").unwrap();
+        assert_eq!(data.ylen.len() as u32, data.x_size);
+        for row in &data.ylen {
+            assert_eq!(row.len() as u32, data.y_size);
+        }
+
+        let total: usize = data.ylen.iter().flatten().map(|&n| n as usize).sum();
+        assert_eq!(total, data.voxels.len());
+    }
+
+    #[test]
+    fn test_write_round_trip() {
+        let buffer = fs::read("data/grenade.kv6").unwrap();
 
         let data = buffer.pread::<KV6Format>(0).unwrap();
+        assert!(data.palette.is_some());
+
+        let mut out = vec![0u8; buffer.len()];
+        let written = data.try_into_ctx(&mut out, LE).unwrap();
+
+        assert_eq!(written, buffer.len());
+        assert_eq!(out, buffer);
+    }
+
+    #[test]
+    fn test_write_without_palette() {
+        let buffer = fs::read("data/grenade.kv6").unwrap();
+
+        let mut data = buffer.pread::<KV6Format>(0).unwrap();
+        data.palette = None;
+
+        let suffix = Palette::MAGIC.len() + 256 * 3;
+        let mut out = vec![0u8; buffer.len()];
+        let written = data.try_into_ctx(&mut out, LE).unwrap();
+
+        assert_eq!(written, buffer.len() - suffix);
+        assert_eq!(out[..written], buffer[..written]);
+        assert!(out[written..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_write_palette_all_or_nothing() {
+        let buffer = fs::read("data/grenade.kv6").unwrap();
+
+        let data = buffer.pread::<KV6Format>(0).unwrap();
+
+        let suffix = Palette::MAGIC.len() + 256 * 3;
+        let mut out = vec![0u8; buffer.len() - 10];
+        assert!(data.try_into_ctx(&mut out, LE).is_err());
+        assert!(out[buffer.len() - suffix..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_write_bad_ylen() {
+        let buffer = fs::read("data/grenade.kv6").unwrap();
+
+        let mut data = buffer.pread::<KV6Format>(0).unwrap();
+        data.ylen[2].pop();
+
+        let mut out = vec![0u8; buffer.len()];
+        assert!(data.try_into_ctx(&mut out, LE).is_err());
+    }
+
+    #[test]
+    fn test_read_palette() {
+        let data = KV6Format::from_path("data/grenade.kv6").unwrap();
+        let palette = data.palette.unwrap();
+        assert_eq!(palette.entries[0], [0x01, 0x01, 0x01]);
+        assert_eq!(palette.entries[1], [0x03, 0x03, 0x03]);
+        assert_eq!(palette.entries[255], [0x3f, 0x20, 0x3f]);
+    }
+
+    #[test]
+    fn test_read_without_palette() {
+        let buffer = fs::read("data/grenade.kv6").unwrap();
+
+        let suffix = Palette::MAGIC.len() + 256 * 3;
+        let data = buffer[..buffer.len() - suffix]
+            .pread::<KV6Format>(0)
+            .unwrap();
+        assert!(data.palette.is_none());
         assert_eq!(data.voxels.len(), 74);
     }
 
     #[test]
-    fn test_read_xlen() {
-        let f = File::open("data/grenade.kv6").unwrap();
-        let mut reader = BufReader::new(f);
-        let mut buffer = Vec::new();
-        reader.read_to_end(&mut buffer).unwrap();
+    fn test_read_truncated_palette() {
+        let buffer = fs::read("data/grenade.kv6").unwrap();
+
+        let result = buffer[..buffer.len() - 100].pread::<KV6Format>(0);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_check_caches() {
+        let mut data = KV6Format::from_path("data/grenade.kv6").unwrap();
+        assert_eq!(data.check_caches(), Ok(()));
+
+        data.xlen[1] += 1;
+        assert_eq!(
+            data.check_caches(),
+            Err(CacheMismatch::Column {
+                x: 1,
+                expected: data.xlen[1] as u64 - 1,
+                actual: data.xlen[1] as u64,
+            })
+        );
+
+        data.xlen[1] -= 1;
+        data.voxels.pop();
+        assert_eq!(
+            data.check_caches(),
+            Err(CacheMismatch::Total {
+                expected: 73,
+                actual: 74,
+            })
+        );
+    }
+
+    #[test]
+    fn test_read_strict_caches() {
+        let mut buffer = fs::read("data/grenade.kv6").unwrap();
+
+        // Bump xlen[0]; the ylen table still sums to the old value.
+        let xlen_offset = 32 + 74 * 8;
+        buffer[xlen_offset] += 1;
+
+        let strict = ParseOptions {
+            strict: true,
+            ..Default::default()
+        };
+        assert!(KV6Format::parse_with(&buffer, &strict).is_err());
+
+        let (_, warnings) =
+            KV6Format::parse_with_warnings(&buffer, &ParseOptions::default()).unwrap();
+        assert!(matches!(
+            warnings[..],
+            [ParseWarning::CacheMismatch(CacheMismatch::Column {
+                x: 0,
+                ..
+            })]
+        ));
+    }
+
+    #[test]
+    fn test_peek_header() {
+        let mut buffer = fs::read("data/grenade.kv6").unwrap();
+
+        let header = KV6Format::peek_header(&buffer[..Kv6Header::SIZE]).unwrap();
+        assert_eq!(header.magic, 0x4b76786c);
+        assert_eq!((header.x_size, header.y_size, header.z_size), (6, 6, 9));
+        assert_eq!(
+            (header.x_pivot, header.y_pivot, header.z_pivot),
+            (2.5, 2.5, 3.5)
+        );
+        assert_eq!(header.num_voxels, 74);
+
+        let suffix = Palette::MAGIC.len() + 256 * 3;
+        assert_eq!(header.required_len(), Some(buffer.len() - suffix));
+        assert_eq!(buffer.pread::<Kv6Header>(0).unwrap(), header);
+
+        buffer[0] = b'X';
+        assert!(KV6Format::peek_header(&buffer).is_err());
+    }
+
+    #[test]
+    fn test_read_nonstandard_dummy() {
+        let original = fs::read("data/grenade.kv6").unwrap();
+
+        let mut buffer = original.clone();
+        for (i, value) in [(3, 0), (10, 255), (11, 0)] {
+            buffer[32 + i * 8 + 3] = value;
+        }
+
+        let (mut data, warnings) =
+            KV6Format::parse_with_warnings(&buffer, &ParseOptions::default()).unwrap();
+        assert_eq!(
+            warnings,
+            vec![ParseWarning::NonstandardDummy {
+                index: 3,
+                value: 0,
+                count: 3,
+            }]
+        );
+
+        let strict = ParseOptions {
+            strict: true,
+            ..Default::default()
+        };
+        assert!(KV6Format::parse_with(&buffer, &strict).is_err());
+
+        data.normalize_dummy();
+        let mut out = vec![0u8; original.len()];
+        data.try_into_ctx(&mut out, LE).unwrap();
+        assert_eq!(out, original);
+    }
+
+    #[test]
+    fn test_to_bytes() {
+        let buffer = fs::read("data/grenade.kv6").unwrap();
 
         let data = buffer.pread::<KV6Format>(0).unwrap();
-        assert_eq!(data.xlen.len() as u32, data.x_size);
+        let bytes = data.to_bytes().unwrap();
+        assert_eq!(bytes, buffer);
+
+        let reparsed = bytes.pread::<KV6Format>(0).unwrap();
+        assert_eq!(reparsed.to_bytes().unwrap(), bytes);
+
+        let empty = KV6Format::default().to_bytes().unwrap();
+        assert_eq!(empty.len(), 32);
+        assert!(empty.pread::<KV6Format>(0).is_ok());
+    }
+
+    #[test]
+    fn test_serialized_size() {
+        let buffer = fs::read("data/grenade.kv6").unwrap();
+
+        let data = buffer.pread::<KV6Format>(0).unwrap();
+        let size = data.serialized_size();
+        assert_eq!(size, buffer.len());
+
+        let mut out = vec![0u8; size];
+        assert_eq!(data.try_into_ctx(&mut out, LE).unwrap(), size);
+
+        assert_eq!(VoxelData::size_with(&LE), 8);
+        assert_eq!(Kv6Header::size_with(&LE), Kv6Header::SIZE);
+    }
+
+    #[test]
+    fn test_write_borrowed() {
+        let buffer = fs::read("data/grenade.kv6").unwrap();
+
+        let data = buffer.pread::<KV6Format>(0).unwrap();
+
+        let mut first = vec![0u8; buffer.len()];
+        let mut second = vec![0u8; buffer.len()];
+        assert_eq!(first.pwrite(&data, 0).unwrap(), buffer.len());
+        assert_eq!(second.pwrite_with(&data, 0, LE).unwrap(), buffer.len());
+
+        assert_eq!(first, buffer);
+        assert_eq!(second, buffer);
+        assert_eq!(data.voxels.len(), 74);
+    }
+
+    #[test]
+    fn test_error_sections() {
+        let mut buffer = fs::read("data/grenade.kv6").unwrap();
+
+        let options = ParseOptions::default();
+        let cases = [
+            (20, Section::Header, 20),
+            (32 + 4 * 8 + 5, Section::Voxels, 32 + 4 * 8),
+            (32 + 74 * 8 + 7, Section::Xlen, 32 + 74 * 8 + 4),
+            (32 + 74 * 8 + 24 + 9, Section::Ylen, 32 + 74 * 8 + 24 + 8),
+            (
+                buffer.len() - 1,
+                Section::Palette,
+                32 + 74 * 8 + 24 + 72 + 4,
+            ),
+        ];
+        for (len, section, offset) in cases {
+            let err = KV6Format::parse_with(&buffer[..len], &options).unwrap_err();
+            match err {
+                Kv6Error::Truncated {
+                    section: s,
+                    offset: o,
+                } => assert_eq!((s, o), (section, offset), "truncated to {}", len),
+                err => panic!("truncated to {}: unexpected {:?}", len, err),
+            }
+        }
+
+        buffer[..4].copy_from_slice(b"Kvxx");
+        assert!(matches!(
+            KV6Format::parse_with(&buffer, &options),
+            Err(Kv6Error::BadMagic { found, detected: None }) if found.to_be_bytes() == *b"Kvxx"
+        ));
+    }
+
+    #[test]
+    fn test_endian_ctx_ignored() {
+        let buffer = fs::read("data/grenade.kv6").unwrap();
+
+        let default = buffer.pread::<KV6Format>(0).unwrap();
+        let le = buffer.pread_with::<KV6Format>(0, LE).unwrap();
+        let be = buffer.pread_with::<KV6Format>(0, BE).unwrap();
+        assert_eq!(buffer.pread_with::<Kv6Header>(0, BE).unwrap().x_size, 6);
+        assert_eq!(be.x_size, 6);
+
+        for data in [default, le, be] {
+            let mut out = vec![0u8; buffer.len()];
+            assert_eq!(out.pwrite_with(&data, 0, BE).unwrap(), buffer.len());
+            assert_eq!(out, buffer);
+        }
+    }
+
+    #[test]
+    fn test_voxel_layout() {
+        let voxel = VoxelData {
+            blue: 0x11,
+            green: 0x22,
+            red: 0x33,
+            dummy: VoxelData::DUMMY,
+            height: 0x0102,
+            visibility: 0x3f,
+            normalindex: 0xfe,
+        };
+        let expected = [0x11, 0x22, 0x33, 0x80, 0x02, 0x01, 0x3f, 0xfe];
+
+        for endian in [LE, BE] {
+            let mut out = [0u8; 8];
+            assert_eq!(out.pwrite_with(&voxel, 0, endian).unwrap(), 8);
+            assert_eq!(out, expected);
+
+            let read: VoxelData = expected.pread_with(0, endian).unwrap();
+            assert_eq!(read.blue, 0x11);
+            assert_eq!(read.red, 0x33);
+            assert_eq!(read.color().to_u32(), 0x332211);
+            assert_eq!(read.height, 0x0102);
+            assert_eq!(read.normalindex, 0xfe);
+        }
+    }
+
+    #[test]
+    fn test_round_trip_be() {
+        let data = KV6Format::from_path("data/grenade.kv6").unwrap();
+
+        let mut out = vec![0u8; data.serialized_size()];
+        out.pwrite_with(&data, 0, BE).unwrap();
+        let reparsed = KV6Format::parse_with(&out, &ParseOptions::default()).unwrap();
+        assert_eq!(reparsed.x_size, 6);
+        assert_eq!(reparsed.voxels.len(), 74);
+        assert_eq!(reparsed.check_caches(), Ok(()));
+        assert_eq!(out, data.to_bytes().unwrap());
+    }
+
+    #[test]
+    fn test_parse_consumed() {
+        let mut buffer = fs::read("data/grenade.kv6").unwrap();
+
+        // The whole file, SPal suffix included, is consumed.
+        let (data, consumed) = KV6Format::parse(&buffer).unwrap();
+        assert_eq!(consumed, buffer.len());
+        assert!(data.palette.is_some());
+
+        let len = buffer.len();
+        buffer.extend_from_slice(&[0xde, 0xad, 0xbe, 0xef, 0x00]);
+        assert_eq!(KV6Format::parse(&buffer).unwrap().1, len);
+
+        let (_, warnings) =
+            KV6Format::parse_with_warnings(&buffer, &ParseOptions::default()).unwrap();
+        let trailing = ParseWarning::TrailingBytes {
+            offset: len,
+            count: 5,
+        };
+        assert_eq!(warnings, vec![trailing.clone()]);
+
+        let strict = ParseOptions {
+            strict: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            KV6Format::parse_with(&buffer, &strict),
+            Err(Kv6Error::Rejected(warning)) if warning == trailing
+        ));
+    }
+
+    #[test]
+    fn test_write_inconsistent() {
+        let data = KV6Format::from_path("data/grenade.kv6").unwrap();
+        let mut out = vec![0u8; data.serialized_size()];
+
+        let mut short_xlen = KV6Format::from_path("data/grenade.kv6").unwrap();
+        short_xlen.xlen.pop();
+        assert!(matches!(
+            (&short_xlen).try_into_ctx(&mut out[..], LE),
+            Err(scroll::Error::Custom(_))
+        ));
+        assert!(matches!(
+            short_xlen.to_bytes(),
+            Err(Kv6Error::InconsistentSizes(CacheMismatch::XlenLength {
+                expected: 6,
+                actual: 5,
+            }))
+        ));
+
+        let mut short_row = KV6Format::from_path("data/grenade.kv6").unwrap();
+        short_row.ylen[2].pop();
+        assert!(matches!(
+            short_row.to_bytes(),
+            Err(Kv6Error::InconsistentSizes(CacheMismatch::YlenRowLength {
+                x: 2,
+                expected: 6,
+                actual: 5,
+            }))
+        ));
+
+        let mut extra_voxel = KV6Format::from_path("data/grenade.kv6").unwrap();
+        extra_voxel.voxels.push(VoxelData::default());
+        assert!(matches!(
+            extra_voxel.to_bytes(),
+            Err(Kv6Error::InconsistentSizes(CacheMismatch::Total {
+                expected: 75,
+                actual: 74,
+            }))
+        ));
+
+        assert_eq!((&data).try_into_ctx(&mut out[..], LE).unwrap(), out.len());
+    }
+
+    #[test]
+    fn test_write_buffer_too_small() {
+        let data = KV6Format::from_path("data/grenade.kv6").unwrap();
+        let required = data.serialized_size();
+
+        let mut out = vec![0u8; required - 1];
+        assert!(matches!(
+            data.write(&mut out),
+            Err(Kv6Error::BufferTooSmall {
+                required: 1492,
+                available: 1491,
+            })
+        ));
+        assert!(out.iter().all(|&b| b == 0));
+        assert!(matches!(
+            out.pwrite_with(&data, 0, LE),
+            Err(scroll::Error::TooBig {
+                size: 1492,
+                len: 1491,
+            })
+        ));
+
+        let mut out = vec![0u8; required];
+        assert_eq!(out.pwrite_with(&data, 0, LE).unwrap(), required);
+    }
+
+    #[test]
+    fn test_bulk_voxels_match_per_field() {
+        let buffer = fs::read("data/grenade.kv6").unwrap();
+
+        let data = buffer.pread::<KV6Format>(0).unwrap();
+        let per_field: Vec<VoxelData> =
+            crate::utils::read_vec(&buffer, &mut { Kv6Header::SIZE }, 74u32, LE).unwrap();
+
+        assert_eq!(data.voxels, per_field);
+    }
+
+    #[test]
+    fn test_equivalent() {
+        let data = KV6Format::from_path("data/grenade.kv6").unwrap();
+        assert_eq!(data.clone(), data);
+        assert!(data.equivalent(&data));
+
+        // Reverse the voxels of the first column holding more than one, and
+        // touch the bytes equivalence doesn't care about.
+        let mut start = 0;
+        let count = data
+            .ylen
+            .iter()
+            .flatten()
+            .map(|&n| n as usize)
+            .find(|&n| {
+                start += n;
+                n > 1
+            })
+            .unwrap();
+        start -= count;
+
+        let mut reordered = data.clone();
+        reordered.voxels[start..start + count].reverse();
+        reordered.voxels[0].dummy = 0;
+        reordered.voxels[0].normalindex ^= 1;
+        assert_ne!(reordered, data);
+        assert!(reordered.equivalent(&data));
+        assert!(data.equivalent(&reordered));
+
+        let mut recolored = data.clone();
+        recolored.voxels[0].red ^= 1;
+        assert!(!recolored.equivalent(&data));
+
+        let mut moved = data.clone();
+        moved.voxels[0].height += 100;
+        assert!(!moved.equivalent(&data));
+
+        let mut broken = data.clone();
+        broken.xlen[0] += 1;
+        assert!(!broken.equivalent(&data));
     }
 }
@@ -0,0 +1,312 @@
+use super::{KV6Format, Rgb};
+
+/// A change to every voxel's color, for [`KV6Format::adjust_colors`].
+///
+/// The steps are applied in the order of the fields, on channels scaled to
+/// `0.0..=1.0` and clamped back into that range after each step. The result
+/// is rounded to the nearest byte once, at the end. The default changes
+/// nothing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorAdjust {
+    /// Multiplies red, green and blue, to tint the model.
+    pub gain: [f32; 3],
+    /// Raises each channel to `1 / gamma`, so values above 1 brighten the
+    /// midtones and below 1 darken them. Anything that isn't a positive
+    /// number is taken as 1.
+    pub gamma: f32,
+    /// Moves each channel away from the color's luma by this factor: 0 is
+    /// gray, 1 leaves it alone and higher is more vivid.
+    pub saturation: f32,
+    /// Added to every channel, in bytes.
+    pub brightness: i16,
+}
+
+impl Default for ColorAdjust {
+    fn default() -> Self {
+        ColorAdjust {
+            gain: [1.0; 3],
+            gamma: 1.0,
+            saturation: 1.0,
+            brightness: 0,
+        }
+    }
+}
+
+impl ColorAdjust {
+    /// `color` with the adjustment applied.
+    pub fn apply(&self, color: Rgb) -> Rgb {
+        let mut channels = [color.r, color.g, color.b].map(|c| c as f64 / 255.0);
+        for (channel, gain) in channels.iter_mut().zip(self.gain) {
+            *channel = (*channel * gain as f64).clamp(0.0, 1.0);
+        }
+        if self.gamma > 0.0 && self.gamma.is_finite() && self.gamma != 1.0 {
+            channels = channels.map(|c| pow(c, 1.0 / self.gamma as f64));
+        }
+        let luma = luma(channels);
+        for channel in &mut channels {
+            *channel = (luma + (*channel - luma) * self.saturation as f64).clamp(0.0, 1.0);
+        }
+        let [r, g, b] = channels.map(|c| {
+            let c = (c + self.brightness as f64 / 255.0).clamp(0.0, 1.0);
+            (c * 255.0 + 0.5) as u8
+        });
+        Rgb::new(r, g, b)
+    }
+}
+
+/// Rec. 601 luma of red, green and blue channels, the weights PAL and
+/// NTSC television used and still the usual choice for 8-bit sRGB.
+pub(crate) fn luma([r, g, b]: [f64; 3]) -> f64 {
+    0.299 * r + 0.587 * g + 0.114 * b
+}
+
+/// `base` raised to `exponent`, for `base` in `0.0..=1.0` and positive
+/// `exponent`. `core` has no `powf`, so this works it out as
+/// `exp(exponent * ln(base))`.
+fn pow(base: f64, exponent: f64) -> f64 {
+    if base <= 0.0 {
+        return 0.0;
+    }
+    // base = m * 2^k with m in 1..2, and ln m = 2 atanh((m - 1) / (m + 1)).
+    let bits = base.to_bits();
+    let k = ((bits >> 52) & 0x7ff) as i64 - 1023;
+    let m = f64::from_bits(bits & !(0x7ff << 52) | 1023 << 52);
+    let s = (m - 1.0) / (m + 1.0);
+    let (mut term, mut atanh) = (s, 0.0);
+    for n in 0..20 {
+        atanh += term / (2 * n + 1) as f64;
+        term *= s * s;
+    }
+    let y = exponent * (k as f64 * core::f64::consts::LN_2 + 2.0 * atanh);
+
+    // exp y = 2^n * exp r, with r small enough for the series to settle.
+    if y < -1000.0 {
+        return 0.0;
+    }
+    let n = (y / core::f64::consts::LN_2) as i64;
+    let r = y - n as f64 * core::f64::consts::LN_2;
+    let (mut term, mut exp) = (1.0, 0.0);
+    for i in 1..25 {
+        exp += term;
+        term *= r / i as f64;
+    }
+    if n < -1022 {
+        return 0.0;
+    }
+    exp * f64::from_bits(((n + 1023) as u64) << 52)
+}
+
+impl KV6Format {
+    /// Applies `adjust` to every voxel's color. Nothing else about the
+    /// voxels changes.
+    pub fn adjust_colors(&mut self, adjust: &ColorAdjust) {
+        for voxel in &mut self.voxels {
+            voxel.set_color(adjust.apply(voxel.color()));
+        }
+    }
+
+    /// Replaces every voxel's color with a gray of the same luma, weighting
+    /// red, green and blue 0.299, 0.587 and 0.114 as Rec. 601 does.
+    pub fn grayscale(&mut self) {
+        self.duotone(Rgb::new(0, 0, 0), Rgb::new(255, 255, 255));
+    }
+
+    /// Replaces every voxel's color with the point on the straight line from
+    /// `dark` to `light` that its Rec. 601 luma gives, black mapping to
+    /// `dark` and white to `light`.
+    pub fn duotone(&mut self, dark: Rgb, light: Rgb) {
+        let ramp = |dark: u8, light: u8, t: f64| {
+            (dark as f64 + (light as f64 - dark as f64) * t + 0.5) as u8
+        };
+        for voxel in &mut self.voxels {
+            let color = voxel.color();
+            let t = luma([color.r, color.g, color.b].map(|c| c as f64 / 255.0)).clamp(0.0, 1.0);
+            voxel.set_color(Rgb::new(
+                ramp(dark.r, light.r, t),
+                ramp(dark.g, light.g, t),
+                ramp(dark.b, light.b, t),
+            ));
+        }
+    }
+
+    /// A copy of the model with [`KV6Format::adjust_colors`] applied.
+    pub fn with_adjusted_colors(&self, adjust: &ColorAdjust) -> KV6Format {
+        let mut data = self.clone();
+        data.adjust_colors(adjust);
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{pow, ColorAdjust};
+    use crate::kv6::testing::grenade;
+    use crate::kv6::{KV6Builder, KV6Format, Rgb};
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_pow() {
+        for i in 0..=255 {
+            let base = i as f64 / 255.0;
+            for exponent in [0.1, 1.0 / 2.2, 1.0, 2.2, 7.5] {
+                let expected = base.powf(exponent);
+                assert!((pow(base, exponent) - expected).abs() < 1e-12);
+            }
+        }
+        assert_eq!(pow(1e-300, 3.0), 0.0);
+    }
+
+    #[test]
+    fn test_identity() {
+        let data = grenade();
+        assert_eq!(data.with_adjusted_colors(&ColorAdjust::default()), data);
+        let no_gamma = ColorAdjust {
+            gamma: f32::NAN,
+            ..Default::default()
+        };
+        assert_eq!(data.with_adjusted_colors(&no_gamma), data);
+
+        for c in 0..=255 {
+            let color = Rgb::new(c, 255 - c, c / 2);
+            assert_eq!(ColorAdjust::default().apply(color), color);
+        }
+    }
+
+    #[test]
+    fn test_clamping() {
+        let bright = ColorAdjust {
+            brightness: 300,
+            ..Default::default()
+        };
+        assert_eq!(bright.apply(Rgb::new(0, 10, 200)), Rgb::new(255, 255, 255));
+        let dark = ColorAdjust {
+            brightness: -20,
+            ..Default::default()
+        };
+        assert_eq!(dark.apply(Rgb::new(0, 10, 200)), Rgb::new(0, 0, 180));
+
+        let tint = ColorAdjust {
+            gain: [2.0, 0.5, -1.0],
+            ..Default::default()
+        };
+        assert_eq!(tint.apply(Rgb::new(200, 200, 200)), Rgb::new(255, 100, 0));
+        assert_eq!(tint.apply(Rgb::new(100, 101, 100)), Rgb::new(200, 51, 0));
+
+        let vivid = ColorAdjust {
+            saturation: 10.0,
+            ..Default::default()
+        };
+        assert_eq!(vivid.apply(Rgb::new(200, 100, 100)), Rgb::new(255, 0, 0));
+        let gray = ColorAdjust {
+            saturation: 0.0,
+            ..Default::default()
+        };
+        assert_eq!(gray.apply(Rgb::new(255, 0, 0)), Rgb::new(76, 76, 76));
+    }
+
+    /// A column of single voxels, one per color.
+    fn swatches(colors: &[Rgb]) -> KV6Format {
+        let mut builder = KV6Builder::new(1, 1, colors.len() as u32);
+        for (z, &color) in (0..).zip(colors) {
+            builder = builder.voxel(0, 0, z, color);
+        }
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn test_grayscale() {
+        let mut data = swatches(&[
+            Rgb::new(255, 0, 0),
+            Rgb::new(0, 255, 0),
+            Rgb::new(0, 0, 255),
+            Rgb::new(255, 255, 255),
+            Rgb::new(0, 0, 0),
+            Rgb::new(100, 150, 200),
+        ]);
+        let before = data.clone();
+        data.grayscale();
+        let gray = |v| Rgb::new(v, v, v);
+        assert_eq!(
+            data.colors().collect::<Vec<_>>(),
+            [gray(76), gray(150), gray(29), gray(255), gray(0), gray(141)]
+        );
+        let mut again = data.clone();
+        again.grayscale();
+        assert_eq!(again, data);
+
+        for (voxel, old) in data.voxels.iter().zip(&before.voxels) {
+            assert_eq!(
+                (voxel.height, voxel.visibility, voxel.normalindex),
+                (old.height, old.visibility, old.normalindex)
+            );
+        }
+        assert_eq!((data.xlen, data.ylen), (before.xlen, before.ylen));
+
+        let mut data = grenade();
+        data.grayscale();
+        assert!(data.colors().all(|c| c.r == c.g && c.g == c.b));
+        assert_eq!(data.voxels.len(), 74);
+        assert!(data.check_caches().is_ok());
+    }
+
+    #[test]
+    fn test_duotone() {
+        let mut data = swatches(&[
+            Rgb::new(0, 0, 0),
+            Rgb::new(255, 255, 255),
+            Rgb::new(128, 128, 128),
+            Rgb::new(255, 0, 0),
+        ]);
+        data.duotone(Rgb::new(20, 0, 40), Rgb::new(255, 200, 100));
+        assert_eq!(
+            data.colors().collect::<Vec<_>>(),
+            [
+                Rgb::new(20, 0, 40),
+                Rgb::new(255, 200, 100),
+                Rgb::new(138, 100, 70),
+                Rgb::new(90, 60, 58),
+            ]
+        );
+
+        // Swapping the ends inverts the ramp.
+        let mut data = swatches(&[Rgb::new(0, 0, 0), Rgb::new(255, 255, 255)]);
+        data.duotone(Rgb::new(255, 255, 255), Rgb::new(0, 0, 0));
+        assert_eq!(
+            data.colors().collect::<Vec<_>>(),
+            [Rgb::new(255, 255, 255), Rgb::new(0, 0, 0)]
+        );
+    }
+
+    #[test]
+    fn test_gamma() {
+        let adjust = ColorAdjust {
+            gamma: 2.2,
+            ..Default::default()
+        };
+        assert_eq!(adjust.apply(Rgb::new(128, 64, 0)), Rgb::new(186, 136, 0));
+        assert_eq!(
+            adjust.apply(Rgb::new(255, 255, 255)),
+            Rgb::new(255, 255, 255)
+        );
+        let darken = ColorAdjust {
+            gamma: 1.0 / 2.2,
+            ..Default::default()
+        };
+        assert_eq!(
+            darken.apply(Rgb::new(186, 136, 255)),
+            Rgb::new(127, 64, 255)
+        );
+
+        // Only colors change.
+        let mut data = grenade();
+        data.adjust_colors(&adjust);
+        for (voxel, old) in data.voxels.iter().zip(&grenade().voxels) {
+            assert_eq!(voxel.color(), adjust.apply(old.color()));
+            assert_eq!(
+                (voxel.height, voxel.visibility, voxel.normalindex),
+                (old.height, old.visibility, old.normalindex)
+            );
+        }
+        assert_eq!((data.xlen, data.ylen), (grenade().xlen, grenade().ylen));
+    }
+}
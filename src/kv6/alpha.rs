@@ -0,0 +1,108 @@
+use super::{KV6Format, VoxelData};
+
+/// What a model's `dummy` bytes mean.
+///
+/// The format says they're always 128, but some tools store per-voxel alpha
+/// in them. The mode isn't recorded in the file, so it has to be chosen when
+/// parsing (through [`ParseOptions::alpha_mode`](super::ParseOptions)) or
+/// set afterwards. It never changes what gets written: the dummy bytes are
+/// serialized as they are in either mode, and only
+/// [`KV6Format::normalize_dummy`] resets them to 128.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AlphaMode {
+    /// The dummy byte is padding and every voxel is opaque. Values other than
+    /// 128 are reported as a [`ParseWarning`](super::ParseWarning).
+    #[default]
+    Opaque,
+    /// The dummy byte is the voxel's alpha, 255 being opaque.
+    DummyByte,
+}
+
+impl VoxelData {
+    /// The dummy byte read as alpha. Only meaningful for models in
+    /// [`AlphaMode::DummyByte`]; see [`KV6Format::alpha`] for a mode-aware
+    /// version.
+    pub fn alpha(&self) -> u8 {
+        self.dummy
+    }
+
+    /// Stores `alpha` in the dummy byte.
+    pub fn set_alpha(&mut self, alpha: u8) {
+        self.dummy = alpha;
+    }
+}
+
+impl KV6Format {
+    /// How the dummy bytes are interpreted.
+    pub fn alpha_mode(&self) -> AlphaMode {
+        self.alpha_mode
+    }
+
+    /// Changes how the dummy bytes are interpreted. The voxels aren't touched.
+    pub fn set_alpha_mode(&mut self, mode: AlphaMode) {
+        self.alpha_mode = mode;
+    }
+
+    /// The alpha of voxel `index`: its dummy byte in [`AlphaMode::DummyByte`],
+    /// fully opaque otherwise. `None` if there's no such voxel.
+    pub fn alpha(&self, index: usize) -> Option<u8> {
+        let voxel = self.voxels.get(index)?;
+        Some(match self.alpha_mode {
+            AlphaMode::Opaque => u8::MAX,
+            AlphaMode::DummyByte => voxel.alpha(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AlphaMode;
+    use crate::kv6::testing::grenade;
+    use crate::kv6::{KV6Format, ParseOptions, ParseWarning, VoxelData};
+
+    fn translucent() -> KV6Format {
+        let mut data = grenade();
+        data.set_alpha_mode(AlphaMode::DummyByte);
+        for (i, voxel) in data.voxels.iter_mut().enumerate() {
+            voxel.set_alpha(i as u8 * 3);
+        }
+        data
+    }
+
+    #[test]
+    fn test_alpha_round_trip() {
+        let data = translucent();
+        let bytes = data.to_bytes().unwrap();
+
+        let options = ParseOptions {
+            alpha_mode: AlphaMode::DummyByte,
+            ..Default::default()
+        };
+        let (parsed, warnings) = KV6Format::parse_with_warnings(&bytes, &options).unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(parsed, data);
+        assert_eq!(parsed.alpha(10), Some(30));
+        assert_eq!(parsed.alpha(74), None);
+    }
+
+    #[test]
+    fn test_opaque_mode() {
+        let bytes = translucent().to_bytes().unwrap();
+
+        let (mut parsed, warnings) =
+            KV6Format::parse_with_warnings(&bytes, &ParseOptions::default()).unwrap();
+        assert!(matches!(
+            warnings[..],
+            [ParseWarning::NonstandardDummy { index: 0, .. }]
+        ));
+        assert_eq!(parsed.alpha_mode(), AlphaMode::Opaque);
+        assert_eq!(parsed.alpha(10), Some(u8::MAX));
+
+        // The bytes survive until they're normalized on purpose.
+        assert_eq!(parsed.voxels[10].alpha(), 30);
+        assert_eq!(parsed.to_bytes().unwrap(), bytes);
+        parsed.normalize_dummy();
+        assert!(parsed.voxels.iter().all(|v| v.dummy == VoxelData::DUMMY));
+    }
+}
@@ -0,0 +1,204 @@
+use super::{Face, KV6Format, Rgb};
+use crate::Kv6Error;
+use alloc::vec::Vec;
+
+/// Knobs for [`KV6Format::bake_ao`].
+#[derive(Debug, Clone, Copy)]
+pub struct AoOptions {
+    /// How many cells out from a voxel to look for occluders, along each
+    /// axis. 0 looks at nothing and changes nothing.
+    pub radius: u32,
+    /// How much a fully occluded voxel is darkened, from 0.0 for not at all
+    /// to 1.0 for black. Values outside that range are clamped.
+    pub strength: f32,
+}
+
+impl Default for AoOptions {
+    fn default() -> Self {
+        AoOptions {
+            radius: 2,
+            strength: 0.5,
+        }
+    }
+}
+
+impl KV6Format {
+    /// Darkens each voxel with a face next to air by how crowded its
+    /// surroundings are, as a cheap stand-in for ambient occlusion.
+    ///
+    /// A voxel's occlusion is the share of the other cells within `radius`
+    /// of it, in a cube, that are solid: stored voxels and the unstored
+    /// interior, but not cells outside the grid. Its color is scaled by
+    /// `1 - strength * occlusion`, so even a voxel on a flat surface darkens
+    /// a little, and one at the bottom of a pit darkens more. Interior
+    /// voxels are left alone. Fails if the caches don't match the voxels or
+    /// the occupancy grid doesn't fit in memory, leaving the model as it
+    /// was.
+    pub fn bake_ao(&mut self, options: &AoOptions) -> Result<(), Kv6Error> {
+        let air = self.occupancy()?.exterior();
+        let strength = options.strength.clamp(0.0, 1.0) as f64;
+        if options.radius == 0 || strength == 0.0 {
+            return Ok(());
+        }
+
+        let (x_size, y_size, z_size) = air.dimensions();
+        let solid = |x: i64, y: i64, z: i64| {
+            (0..x_size as i64).contains(&x)
+                && (0..y_size as i64).contains(&y)
+                && (0..z_size.min(1 << 16) as i64).contains(&z)
+                && !air.contains(x as u32, y as u32, z as u16)
+        };
+        let r = options.radius as i64;
+        let neighbours = ((2 * r + 1).pow(3) - 1) as f64;
+
+        let factors: Vec<_> = self
+            .iter_with_coords()
+            .map_err(Kv6Error::InconsistentSizes)?
+            .map(|(x, y, z, _)| {
+                let surface = Face::ALL.into_iter().any(|face| {
+                    face.neighbour(x, y, z)
+                        .is_none_or(|(x, y, z)| !solid(x as i64, y as i64, z as i64))
+                });
+                if !surface {
+                    return None;
+                }
+                let (x, y, z) = (x as i64, y as i64, z as i64);
+                let mut occluders = 0;
+                for dx in -r..=r {
+                    for dy in -r..=r {
+                        for dz in -r..=r {
+                            let this = dx == 0 && dy == 0 && dz == 0;
+                            occluders += (!this && solid(x + dx, y + dy, z + dz)) as u32;
+                        }
+                    }
+                }
+                Some(1.0 - strength * occluders as f64 / neighbours)
+            })
+            .collect();
+
+        for (voxel, factor) in self.voxels.iter_mut().zip(factors) {
+            if let Some(factor) = factor {
+                let color = voxel.color();
+                let [r, g, b] =
+                    [color.r, color.g, color.b].map(|c| (c as f64 * factor + 0.5) as u8);
+                voxel.set_color(Rgb::new(r, g, b));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AoOptions;
+    use crate::kv6::testing::grenade;
+    use crate::kv6::{KV6Builder, KV6Format, Rgb};
+
+    const WHITE: Rgb = Rgb::new(255, 255, 255);
+
+    /// A 9 by 9 block 4 high with a one-cell pit 2 deep sunk into the
+    /// middle of its top.
+    fn pitted() -> KV6Format {
+        let mut builder = KV6Builder::new(9, 9, 4);
+        for x in 0..9 {
+            for y in 0..9 {
+                for z in 0..4 {
+                    if !((x, y) == (4, 4) && z < 2) {
+                        builder = builder.voxel(x, y, z, WHITE);
+                    }
+                }
+            }
+        }
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn test_pit_darker() {
+        let mut data = pitted();
+        data.bake_ao(&AoOptions::default()).unwrap();
+        let brightness = |x, y, z| data.get_voxel(x, y, z).unwrap().red;
+        let pit = brightness(4, 4, 2);
+        let flat = brightness(1, 6, 0);
+        let rim = brightness(4, 3, 0);
+        assert!(
+            pit < rim && rim < flat && flat < 255,
+            "{:?}",
+            (pit, rim, flat)
+        );
+
+        // Within two cells of (2, 6, 0) are the 23 others in its layer that
+        // aren't the pit, 24 in the next and 25 in the one below that.
+        let mut data = pitted();
+        data.bake_ao(&AoOptions {
+            radius: 2,
+            strength: 1.0,
+        })
+        .unwrap();
+        let expected = (255.0 * (1.0 - 72.0 / 124.0) + 0.5) as u8;
+        assert_eq!(data.get_voxel(2, 6, 0).unwrap().red, expected);
+
+        // Colors only, and the same every time.
+        let mut again = pitted();
+        again
+            .bake_ao(&AoOptions {
+                radius: 2,
+                strength: 1.0,
+            })
+            .unwrap();
+        assert_eq!(again, data);
+        for (voxel, old) in data.voxels.iter().zip(&pitted().voxels) {
+            assert_eq!(
+                (voxel.height, voxel.visibility, voxel.normalindex),
+                (old.height, old.visibility, old.normalindex)
+            );
+        }
+    }
+
+    #[test]
+    fn test_interior_skipped() {
+        let mut builder = KV6Builder::new(3, 3, 3);
+        for x in 0..3 {
+            for y in 0..3 {
+                for z in 0..3 {
+                    builder = builder.voxel(x, y, z, WHITE);
+                }
+            }
+        }
+        let mut data = builder.build().unwrap();
+        data.bake_ao(&AoOptions {
+            radius: 1,
+            strength: 1.0,
+        })
+        .unwrap();
+        assert_eq!(data.get_voxel(1, 1, 1).unwrap().color(), WHITE);
+        // A corner sees 7 of its 26 neighbours.
+        let corner = (255.0 * (1.0 - 7.0 / 26.0) + 0.5) as u8;
+        assert_eq!(data.get_voxel(0, 0, 0).unwrap().red, corner);
+    }
+
+    #[test]
+    fn test_no_op() {
+        for options in [
+            AoOptions {
+                strength: 0.0,
+                ..Default::default()
+            },
+            AoOptions {
+                strength: -3.0,
+                ..Default::default()
+            },
+            AoOptions {
+                radius: 0,
+                strength: 1.0,
+            },
+        ] {
+            let mut data = grenade();
+            data.bake_ao(&options).unwrap();
+            assert_eq!(data, grenade());
+        }
+
+        let mut data = grenade();
+        data.bake_ao(&AoOptions::default()).unwrap();
+        assert!(!data.equivalent(&grenade()));
+    }
+}
@@ -0,0 +1,93 @@
+use super::{KV6Format, VoxelData};
+use alloc::vec::Vec;
+use arbitrary::{Arbitrary, Unstructured};
+
+/// Largest size fuzzed models get along each axis, to keep them cheap.
+const MAX_SIZE: u32 = 16;
+
+fn finite_pivot(u: &mut Unstructured<'_>) -> arbitrary::Result<f32> {
+    let pivot: f32 = u.arbitrary()?;
+    Ok(if pivot.is_finite() { pivot } else { 0.0 })
+}
+
+/// Generates models that are valid in every structural way: the caches match
+/// the voxels and each column's heights increase and stay below `z_size`.
+/// Colors and the remaining voxel bytes are left arbitrary.
+impl<'a> Arbitrary<'a> for KV6Format {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let x_size = u.int_in_range(0..=MAX_SIZE)?;
+        let y_size = u.int_in_range(0..=MAX_SIZE)?;
+        let z_size = u.int_in_range(0..=MAX_SIZE)?;
+
+        let mut voxels = Vec::new();
+        let mut ylen = Vec::with_capacity(x_size as usize);
+        for _ in 0..x_size {
+            let mut row = Vec::with_capacity(y_size as usize);
+            for _ in 0..y_size {
+                let mut count = 0;
+                let mut height = u.int_in_range(0..=z_size)?;
+                while height < z_size && u.arbitrary()? {
+                    voxels.push(VoxelData {
+                        height: height as u16,
+                        ..u.arbitrary()?
+                    });
+                    count += 1;
+                    height += 1 + u.int_in_range(0..=2)?;
+                }
+                row.push(count);
+            }
+            ylen.push(row);
+        }
+        let xlen = ylen
+            .iter()
+            .map(|row: &Vec<u16>| row.iter().map(|&n| n as u32).sum())
+            .collect();
+
+        Ok(KV6Format {
+            magic: KV6Format::MAGIC,
+            x_size,
+            y_size,
+            z_size,
+            x_pivot: finite_pivot(u)?,
+            y_pivot: finite_pivot(u)?,
+            z_pivot: finite_pivot(u)?,
+            voxels,
+            xlen,
+            ylen,
+            palette: u.arbitrary()?,
+            alpha_mode: Default::default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kv6::KV6Format;
+    use arbitrary::{Arbitrary, Unstructured};
+
+    #[test]
+    fn test_arbitrary_models_are_valid() {
+        // Any bytes will do, these just aren't all zero.
+        let seed: Vec<u8> = (0..4096u32)
+            .map(|i| (i.wrapping_mul(2654435761) >> 13) as u8)
+            .collect();
+        let mut u = Unstructured::new(&seed);
+
+        let mut total = 0;
+        for _ in 0..16 {
+            let model = KV6Format::arbitrary(&mut u).unwrap();
+            total += model.voxels.len();
+            assert_eq!(model.check_caches(), Ok(()));
+            assert!(model
+                .voxels
+                .iter()
+                .all(|v| (v.height as u32) < model.z_size));
+
+            let bytes = model.to_bytes().unwrap();
+            let (parsed, consumed) = KV6Format::parse(&bytes).unwrap();
+            assert_eq!(consumed, bytes.len());
+            assert_eq!(parsed.to_bytes().unwrap(), bytes);
+        }
+        assert!(total > 0);
+    }
+}
@@ -0,0 +1,94 @@
+use super::{
+    stream::{body_len, short_model},
+    KV6Format, Kv6Header, ParseOptions,
+};
+use crate::Kv6Error;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+impl KV6Format {
+    /// Reads and checks just the header, consuming exactly its 32 bytes.
+    pub async fn peek_header_async<R: AsyncRead + Unpin>(
+        mut reader: R,
+    ) -> Result<Kv6Header, Kv6Error> {
+        let mut bytes = [0u8; Kv6Header::SIZE];
+        reader.read_exact(&mut bytes).await?;
+        Kv6Header::read(&bytes, &mut 0, &ParseOptions::default(), &mut Vec::new())
+    }
+
+    /// The async counterpart of [`KV6Format::from_reader`], with the same
+    /// guarantee of never reading past the end of ylen.
+    pub async fn from_async_reader<R: AsyncRead + Unpin>(mut reader: R) -> Result<Self, Kv6Error> {
+        let options = ParseOptions::default();
+
+        let mut bytes = vec![0u8; Kv6Header::SIZE];
+        reader.read_exact(&mut bytes).await?;
+        let header = Kv6Header::read(&bytes, &mut 0, &options, &mut Vec::new())?;
+
+        let body = body_len(&header)?;
+        let read = (&mut reader).take(body).read_to_end(&mut bytes).await?;
+        if (read as u64) < body {
+            return Err(short_model(body, bytes.len()));
+        }
+
+        KV6Format::parse_with(&bytes, &options)
+    }
+
+    /// The async counterpart of [`KV6Format::to_writer`]. The writer is
+    /// flushed before returning.
+    pub async fn write_async<W: AsyncWrite + Unpin>(&self, mut writer: W) -> Result<(), Kv6Error> {
+        writer.write_all(&self.to_bytes()?).await?;
+        writer.flush().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kv6::testing::grenade_without_palette;
+    use crate::kv6::KV6Format;
+    use tokio::io::{duplex, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_async_round_trip() {
+        let data = grenade_without_palette();
+        // A small pipe makes both sides wait on each other.
+        let (mut client, mut server) = duplex(64);
+
+        let write = async {
+            data.write_async(&mut client).await.unwrap();
+            data.write_async(&mut client).await.unwrap();
+            drop(client);
+        };
+        let read = async {
+            let first = KV6Format::from_async_reader(&mut server).await.unwrap();
+            let second = KV6Format::from_async_reader(&mut server).await.unwrap();
+            (first, second)
+        };
+        let ((), (first, second)) = tokio::join!(write, read);
+
+        let expected = data.to_bytes().unwrap();
+        assert_eq!(first.to_bytes().unwrap(), expected);
+        assert_eq!(second.to_bytes().unwrap(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_peek_header_async() {
+        let bytes = grenade_without_palette().to_bytes().unwrap();
+        let (mut client, mut server) = duplex(1024);
+
+        // Only the header is ever sent, so anything reading further would hang.
+        client.write_all(&bytes[..32]).await.unwrap();
+        let header = KV6Format::peek_header_async(&mut server).await.unwrap();
+        assert_eq!((header.x_size, header.num_voxels), (6, 74));
+    }
+
+    #[tokio::test]
+    async fn test_async_truncated() {
+        let bytes = grenade_without_palette().to_bytes().unwrap();
+        let (mut client, mut server) = duplex(4096);
+        client.write_all(&bytes[..bytes.len() - 1]).await.unwrap();
+        drop(client);
+
+        assert!(KV6Format::from_async_reader(&mut server).await.is_err());
+    }
+}
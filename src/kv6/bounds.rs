@@ -0,0 +1,112 @@
+use super::KV6Format;
+
+/// An axis-aligned box of cells, with both corners included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Bounds {
+    pub min: (u32, u32, u16),
+    pub max: (u32, u32, u16),
+}
+
+impl Bounds {
+    /// The box holding just `(x, y, z)`.
+    pub(crate) fn point(x: u32, y: u32, z: u16) -> Self {
+        Bounds {
+            min: (x, y, z),
+            max: (x, y, z),
+        }
+    }
+
+    /// Grows the box to include `(x, y, z)`.
+    pub(crate) fn extend(&mut self, x: u32, y: u32, z: u16) {
+        self.min = (self.min.0.min(x), self.min.1.min(y), self.min.2.min(z));
+        self.max = (self.max.0.max(x), self.max.1.max(y), self.max.2.max(z));
+    }
+
+    /// The number of cells the box spans along each axis.
+    pub fn size(&self) -> (u32, u32, u32) {
+        (
+            self.max.0 - self.min.0 + 1,
+            self.max.1 - self.min.1 + 1,
+            (self.max.2 - self.min.2) as u32 + 1,
+        )
+    }
+
+    /// Whether `(x, y, z)` is inside the box.
+    pub fn contains(&self, x: u32, y: u32, z: u16) -> bool {
+        (self.min.0..=self.max.0).contains(&x)
+            && (self.min.1..=self.max.1).contains(&y)
+            && (self.min.2..=self.max.2).contains(&z)
+    }
+
+    /// Number of cells in the box.
+    pub(crate) fn volume(&self) -> u64 {
+        let (x, y, z) = self.size();
+        x as u64 * y as u64 * z as u64
+    }
+}
+
+impl KV6Format {
+    /// The smallest box holding every voxel, found from the voxels'
+    /// coordinates without building a grid.
+    ///
+    /// `None` if there are no voxels, or if the caches don't match the
+    /// voxels so their positions are unknown.
+    pub fn occupied_bounds(&self) -> Option<Bounds> {
+        let mut coords = self.iter_with_coords().ok()?;
+        let (x, y, z, _) = coords.next()?;
+        let mut bounds = Bounds::point(x, y, z);
+        for (x, y, z, _) in coords {
+            bounds.extend(x, y, z);
+        }
+        Some(bounds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Bounds;
+    use crate::kv6::testing::{empty, grenade};
+    use crate::kv6::{KV6Format, VoxelData};
+
+    #[test]
+    fn test_grenade_bounds() {
+        let data = grenade();
+        let bounds = data.occupied_bounds().unwrap();
+        assert_eq!(bounds.min, (0, 0, 0));
+        assert_eq!(bounds.max, (5, 5, 8));
+        assert_eq!(bounds.size(), (6, 6, 9));
+        for (x, y, z, _) in data.iter_with_coords().unwrap() {
+            assert!(bounds.contains(x, y, z));
+        }
+        assert!(!bounds.contains(6, 0, 0));
+        assert!(!bounds.contains(0, 0, 9));
+    }
+
+    #[test]
+    fn test_single_voxel_bounds() {
+        let mut data = empty(4, 5, 6);
+        data.insert_voxel(2, 3, 4, VoxelData::default()).unwrap();
+        let bounds = data.occupied_bounds().unwrap();
+        assert_eq!(
+            bounds,
+            Bounds {
+                min: (2, 3, 4),
+                max: (2, 3, 4)
+            }
+        );
+        assert_eq!(bounds.size(), (1, 1, 1));
+        assert!(bounds.contains(2, 3, 4));
+        assert!(!bounds.contains(2, 3, 3) && !bounds.contains(1, 3, 4));
+
+        data.insert_voxel(0, 4, 5, VoxelData::default()).unwrap();
+        let bounds = data.occupied_bounds().unwrap();
+        assert_eq!((bounds.min, bounds.max), ((0, 3, 4), (2, 4, 5)));
+        assert_eq!(bounds.size(), (3, 2, 2));
+    }
+
+    #[test]
+    fn test_empty_bounds() {
+        assert_eq!(empty(4, 5, 6).occupied_bounds(), None);
+        assert_eq!(KV6Format::default().occupied_bounds(), None);
+    }
+}
@@ -0,0 +1,172 @@
+use super::{
+    normals::{NormalMode, NormalTable},
+    KV6Format, Rgb, VoxelData,
+};
+use crate::Kv6Error;
+use alloc::vec::Vec;
+
+/// Collects voxels in any order and turns them into a valid model.
+///
+/// Voxels are only recorded until [`KV6Builder::build`], which sorts them
+/// once, so adding `n` of them costs `O(n log n)` in total. The setters take
+/// and return the builder, so a model can be built in one expression:
+/// `KV6Builder::new(1, 1, 1).voxel(0, 0, 0, color).build()`.
+#[derive(Debug, Clone)]
+pub struct KV6Builder {
+    size: (u32, u32, u32),
+    pivot: Option<(f32, f32, f32)>,
+    voxels: Vec<(u32, u32, u16, Rgb)>,
+}
+
+impl KV6Builder {
+    /// A builder for a model of `x_size` by `y_size` by `z_size` cells.
+    pub fn new(x_size: u32, y_size: u32, z_size: u32) -> Self {
+        KV6Builder {
+            size: (x_size, y_size, z_size),
+            pivot: None,
+            voxels: Vec::new(),
+        }
+    }
+
+    /// Sets the pivot. Without this it's the center of the grid.
+    pub fn pivot(mut self, x: f32, y: f32, z: f32) -> Self {
+        self.pivot = Some((x, y, z));
+        self
+    }
+
+    /// Puts a voxel of `color` at `(x, y, z)`. Setting the same position
+    /// again replaces its color. Positions are checked by `build`.
+    pub fn voxel(mut self, x: u32, y: u32, z: u16, color: Rgb) -> Self {
+        self.voxels.push((x, y, z, color));
+        self
+    }
+
+    /// Builds the model: voxels in column order, caches, visibility and
+    /// normals filled in as for [`KV6Format::from_dense`].
+    ///
+    /// Fails with [`Kv6Error::OutOfBounds`] for the first voxel outside the
+    /// grid.
+    pub fn build(mut self) -> Result<KV6Format, Kv6Error> {
+        let (x_size, y_size, z_size) = self.size;
+        if let Some(&(x, y, z, _)) = self
+            .voxels
+            .iter()
+            .find(|&&(x, y, z, _)| x >= x_size || y >= y_size || z as u32 >= z_size)
+        {
+            return Err(Kv6Error::OutOfBounds { x, y, z });
+        }
+
+        // The sort is stable, so the last write to a position comes last.
+        self.voxels.sort_by_key(|&(x, y, z, _)| (x, y, z));
+        let mut voxels = self.voxels;
+        voxels.dedup_by(|later, earlier| {
+            let same = (later.0, later.1, later.2) == (earlier.0, earlier.1, earlier.2);
+            if same {
+                earlier.3 = later.3;
+            }
+            same
+        });
+
+        let mut data = KV6Format::from_sorted(self.size, voxels)?;
+        if let Some((x, y, z)) = self.pivot {
+            (data.x_pivot, data.y_pivot, data.z_pivot) = (x, y, z);
+        }
+        Ok(data)
+    }
+}
+
+impl KV6Format {
+    /// A model of `size` holding `cells`, which must be in range, in column
+    /// order and free of duplicates. The pivot is the center of the grid.
+    pub(crate) fn from_sorted(
+        (x_size, y_size, z_size): (u32, u32, u32),
+        cells: impl IntoIterator<Item = (u32, u32, u16, Rgb)>,
+    ) -> Result<KV6Format, Kv6Error> {
+        let mut data = KV6Format {
+            x_pivot: x_size as f32 / 2.0,
+            y_pivot: y_size as f32 / 2.0,
+            z_pivot: z_size as f32 / 2.0,
+            ..Default::default()
+        };
+        data.set_columns(
+            (x_size, y_size, z_size),
+            cells.into_iter().map(|(x, y, z, color)| {
+                let mut voxel = VoxelData {
+                    dummy: VoxelData::DUMMY,
+                    height: z,
+                    ..Default::default()
+                };
+                voxel.set_color(color);
+                (x, y, voxel)
+            }),
+        )?;
+        data.recompute_visibility()?;
+        data.recompute_normals(NormalTable::Slab6, NormalMode::FaceAverage)?;
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KV6Builder;
+    use crate::kv6::testing::grenade;
+    use crate::kv6::{KV6Format, Rgb};
+    use crate::Kv6Error;
+
+    #[test]
+    fn test_builder_any_order() {
+        let red = Rgb::new(255, 0, 0);
+        let mut builder = KV6Builder::new(3, 3, 3).pivot(1.0, 2.0, 0.5);
+        for x in (0..3).rev() {
+            for z in [2, 0, 1] {
+                for y in 0..3 {
+                    builder = builder.voxel(x, y, z, red);
+                }
+            }
+        }
+        builder = builder.voxel(1, 1, 1, Rgb::new(0, 0, 1));
+        let data = builder.build().unwrap();
+
+        assert!(data.validate().is_clean(), "{:?}", data.validate());
+        assert_eq!(data.voxels.len(), 27);
+        assert_eq!((data.x_pivot, data.y_pivot, data.z_pivot), (1.0, 2.0, 0.5));
+        assert_eq!(data.get_voxel(1, 1, 1).unwrap().color(), Rgb::new(0, 0, 1));
+        assert_eq!(data.get_voxel(1, 1, 1).unwrap().visibility, 0);
+        assert_eq!(data.get_voxel(0, 1, 1).unwrap().color(), red);
+
+        let (parsed, _) = KV6Format::parse(&data.to_bytes().unwrap()).unwrap();
+        assert_eq!(parsed, data);
+    }
+
+    #[test]
+    fn test_builder_matches_dense() {
+        let grenade = grenade();
+        let mut builder = KV6Builder::new(6, 6, 9);
+        let mut coords: alloc::vec::Vec<_> = grenade.iter_with_coords().unwrap().collect();
+        coords.reverse();
+        for (x, y, z, voxel) in coords {
+            builder = builder.voxel(x, y, z, voxel.color());
+        }
+        let built = builder.build().unwrap();
+        assert_eq!(
+            built,
+            KV6Format::from_dense(&grenade.to_dense().unwrap()).unwrap()
+        );
+        assert!(built.equivalent(&grenade));
+    }
+
+    #[test]
+    fn test_builder_bounds() {
+        let builder = KV6Builder::new(2, 2, 2)
+            .voxel(0, 0, 0, Rgb::default())
+            .voxel(0, 2, 1, Rgb::default());
+        assert!(matches!(
+            builder.build(),
+            Err(Kv6Error::OutOfBounds { x: 0, y: 2, z: 1 })
+        ));
+
+        let empty = KV6Builder::new(2, 2, 2).build().unwrap();
+        assert!(empty.voxels.is_empty());
+        empty.check_caches().unwrap();
+    }
+}
@@ -0,0 +1,299 @@
+use super::{CacheMismatch, KV6Format};
+use crate::Kv6Error;
+use alloc::vec::Vec;
+
+/// What [`KV6Format::crop_to_fit`] did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CropInfo {
+    /// How far every voxel moved towards the origin along each axis.
+    pub offset: (u32, u32, u16),
+    /// `(x_size, y_size, z_size)` before cropping.
+    pub old_size: (u32, u32, u32),
+    /// `(x_size, y_size, z_size)` after cropping.
+    pub new_size: (u32, u32, u32),
+}
+
+/// Where [`KV6Format::resize_canvas`] puts the existing voxels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Anchor {
+    /// Keep the corner at the origin where it is.
+    MinCorner,
+    /// Keep the far corner, at `(x_size, y_size, z_size)`, on the new far
+    /// corner.
+    MaxCorner,
+    /// Keep the middle of the old grid on the middle of the new one, rounding
+    /// towards the origin.
+    Center,
+    /// Move the pivot as close to the middle of the new grid as whole cells
+    /// allow.
+    Pivot,
+}
+
+impl KV6Format {
+    /// Changes the grid to `new_x` by `new_y` by `new_z`, placing the voxels
+    /// in it as `anchor` says and moving the pivot with them. Returns how
+    /// many voxels fell outside the new grid and were dropped.
+    ///
+    /// Only stored voxels are kept; cutting through a solid this way leaves
+    /// its interior, which the file doesn't store, open. When voxels are
+    /// dropped, visibility is recomputed. Fails if the caches don't match
+    /// the voxels or the new caches wouldn't fit in memory.
+    pub fn resize_canvas(
+        &mut self,
+        new_x: u32,
+        new_y: u32,
+        new_z: u32,
+        anchor: Anchor,
+    ) -> Result<usize, Kv6Error> {
+        let old = [self.x_size, self.y_size, self.z_size];
+        let new = [new_x, new_y, new_z];
+        let pivot = self.pivot();
+        let offset: [i64; 3] = core::array::from_fn(|i| {
+            let growth = new[i] as i64 - old[i] as i64;
+            match anchor {
+                Anchor::MinCorner => 0,
+                Anchor::MaxCorner => growth,
+                Anchor::Center => growth / 2,
+                Anchor::Pivot => round(new[i] as f64 / 2.0 - pivot[i] as f64),
+            }
+        });
+
+        let dropped = self.shift_into((new_x, new_y, new_z), offset)?;
+        if dropped > 0 {
+            self.recompute_visibility()?;
+        }
+        Ok(dropped)
+    }
+
+    /// Shrinks the grid to the box the voxels occupy, moving every voxel and
+    /// the pivot by the same amount so the model stays where it was relative
+    /// to its pivot.
+    ///
+    /// A model that already fits is left untouched. One without voxels
+    /// becomes 0 by 0 by 0. Fails if the caches don't match the voxels.
+    pub fn crop_to_fit(&mut self) -> Result<CropInfo, CacheMismatch> {
+        self.check_caches()?;
+        let old_size = (self.x_size, self.y_size, self.z_size);
+        let (offset, new_size) = match self.occupied_bounds() {
+            Some(bounds) => (bounds.min, bounds.size()),
+            None => ((0, 0, 0), (0, 0, 0)),
+        };
+        let info = CropInfo {
+            offset,
+            old_size,
+            new_size,
+        };
+        if offset == (0, 0, 0) && new_size == old_size {
+            return Ok(info);
+        }
+
+        let shift = [-(offset.0 as i64), -(offset.1 as i64), -(offset.2 as i64)];
+        // The new grid is no larger than the old one and holds every voxel.
+        self.shift_into(new_size, shift)
+            .expect("cropping can't grow the caches or drop voxels");
+        Ok(info)
+    }
+
+    /// Moves every voxel by `offset` into a grid of `size`, dropping those
+    /// that land outside it, and moves the pivot along. Returns how many
+    /// voxels were dropped.
+    pub(crate) fn shift_into(
+        &mut self,
+        (x_size, y_size, z_size): (u32, u32, u32),
+        offset: [i64; 3],
+    ) -> Result<usize, Kv6Error> {
+        let mut cells = Vec::with_capacity(self.voxels.len());
+        let limits = [x_size as i64, y_size as i64, (z_size as i64).min(1 << 16)];
+        for (x, y, z, voxel) in self
+            .iter_with_coords()
+            .map_err(Kv6Error::InconsistentSizes)?
+        {
+            let moved = [
+                x as i64 + offset[0],
+                y as i64 + offset[1],
+                z as i64 + offset[2],
+            ];
+            if (0..3).any(|i| !(0..limits[i]).contains(&moved[i])) {
+                continue;
+            }
+            // Moving a whole column keeps it in order, and columns are
+            // visited in order, so nothing needs sorting.
+            let mut voxel = *voxel;
+            voxel.height = moved[2] as u16;
+            cells.push((moved[0] as u32, moved[1] as u32, voxel));
+        }
+
+        let dropped = self.voxels.len() - cells.len();
+        self.set_columns((x_size, y_size, z_size), cells)?;
+        self.translate_pivot(offset[0] as f32, offset[1] as f32, offset[2] as f32);
+        Ok(dropped)
+    }
+}
+
+/// `value` rounded to the nearest integer, halves away from zero, without
+/// needing `std`. NaN gives 0.
+pub(crate) fn round(value: f64) -> i64 {
+    let whole = value as i64;
+    let fraction = value - whole as f64;
+    if fraction >= 0.5 {
+        whole + 1
+    } else if fraction <= -0.5 {
+        whole - 1
+    } else {
+        whole
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Anchor, CropInfo};
+    use crate::kv6::testing::{empty, grenade};
+    use crate::kv6::{Bounds, KV6Format, VoxelData};
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_crop_tight() {
+        let mut data = grenade();
+        let bytes = data.to_bytes().unwrap();
+        assert_eq!(
+            data.crop_to_fit().unwrap(),
+            CropInfo {
+                offset: (0, 0, 0),
+                old_size: (6, 6, 9),
+                new_size: (6, 6, 9),
+            }
+        );
+        assert_eq!(data.to_bytes().unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_crop_margins() {
+        let mut data = empty(10, 8, 12);
+        data.set_pivot(5.0, 4.0, 6.0);
+        for (x, y, z) in [(2, 3, 4), (2, 3, 7), (5, 6, 5)] {
+            data.insert_voxel(x, y, z, VoxelData::default()).unwrap();
+        }
+        let before: Vec<_> = data
+            .iter_with_coords()
+            .unwrap()
+            .map(|(x, y, z, _)| (x, y, z))
+            .collect();
+
+        let info = data.crop_to_fit().unwrap();
+        assert_eq!(info.offset, (2, 3, 4));
+        assert_eq!((info.old_size, info.new_size), ((10, 8, 12), (4, 4, 4)));
+        assert_eq!((data.x_size, data.y_size, data.z_size), (4, 4, 4));
+        assert_eq!(data.pivot(), [3.0, 1.0, 2.0]);
+        assert!(!data.validate().has_errors(), "{:?}", data.validate());
+        assert_eq!(
+            data.occupied_bounds(),
+            Some(Bounds {
+                min: (0, 0, 0),
+                max: (3, 3, 3)
+            })
+        );
+        let after: Vec<_> = data
+            .iter_with_coords()
+            .unwrap()
+            .map(|(x, y, z, _)| (x + 2, y + 3, z + 4))
+            .collect();
+        assert_eq!(after, before);
+
+        // Cropping again does nothing.
+        assert_eq!(data.crop_to_fit().unwrap().offset, (0, 0, 0));
+    }
+
+    #[test]
+    fn test_crop_empty() {
+        let mut data = empty(3, 2, 5);
+        let info = data.crop_to_fit().unwrap();
+        assert_eq!(info.new_size, (0, 0, 0));
+        assert_eq!((data.x_size, data.y_size, data.z_size), (0, 0, 0));
+        assert!(data.xlen.is_empty() && data.ylen.is_empty());
+        let (parsed, _) = KV6Format::parse(&data.to_bytes().unwrap()).unwrap();
+        assert_eq!(parsed, data);
+
+        let mut broken = grenade();
+        broken.voxels.pop();
+        assert!(broken.crop_to_fit().is_err());
+    }
+
+    #[test]
+    fn test_grow_then_shrink() {
+        for anchor in [
+            Anchor::MinCorner,
+            Anchor::MaxCorner,
+            Anchor::Center,
+            Anchor::Pivot,
+        ] {
+            let original = grenade();
+            let mut data = original.clone();
+            assert_eq!(data.resize_canvas(9, 10, 16, anchor).unwrap(), 0);
+            assert_eq!((data.x_size, data.y_size, data.z_size), (9, 10, 16));
+            assert!(data.validate().is_clean(), "{:?}", data.validate());
+            assert_eq!(data.voxels.len(), original.voxels.len());
+
+            // Cropping finds the grenade again wherever it was put.
+            let mut cropped = data.clone();
+            cropped.crop_to_fit().unwrap();
+            assert!(cropped.equivalent(&original));
+            assert_eq!(cropped.pivot(), original.pivot());
+
+            if anchor != Anchor::Pivot {
+                assert_eq!(data.resize_canvas(6, 6, 9, anchor).unwrap(), 0);
+                assert!(data.equivalent(&original), "{:?}", anchor);
+                assert_eq!(data.pivot(), original.pivot());
+            }
+        }
+    }
+
+    #[test]
+    fn test_round() {
+        let cases = [(1.5, 2), (-1.5, -2), (2.49, 2), (-0.2, 0), (f64::NAN, 0)];
+        for (value, rounded) in cases {
+            assert_eq!(super::round(value), rounded);
+        }
+    }
+
+    #[test]
+    fn test_anchors() {
+        let mut data = grenade();
+        data.resize_canvas(8, 6, 9, Anchor::MaxCorner).unwrap();
+        assert_eq!(data.occupied_bounds().unwrap().min, (2, 0, 0));
+        assert_eq!(data.x_pivot, 4.5);
+
+        let mut data = grenade();
+        data.resize_canvas(9, 6, 9, Anchor::Center).unwrap();
+        assert_eq!(data.occupied_bounds().unwrap().min, (1, 0, 0));
+
+        // The grenade's pivot is at (2.5, 2.5, 3.5); 10 / 2 - 2.5 rounds to 3.
+        let mut data = grenade();
+        data.resize_canvas(10, 10, 10, Anchor::Pivot).unwrap();
+        assert_eq!(data.occupied_bounds().unwrap().min, (3, 3, 2));
+        assert_eq!(data.pivot(), [5.5, 5.5, 5.5]);
+    }
+
+    #[test]
+    fn test_shrink_clips() {
+        let original = grenade();
+        let mut data = original.clone();
+        let dropped = data.resize_canvas(6, 6, 4, Anchor::MinCorner).unwrap();
+        let below = original.voxels.iter().filter(|v| v.height >= 4).count();
+        assert_eq!(dropped, below);
+        assert_eq!(data.voxels.len(), original.voxels.len() - below);
+        assert!(data.validate().is_clean(), "{:?}", data.validate());
+        assert!(data.voxels.iter().all(|voxel| voxel.height < 4));
+
+        // Voxels that lost a neighbour to the cut show that face now.
+        let mut visible = data.clone();
+        visible.recompute_visibility().unwrap();
+        assert_eq!(visible, data);
+
+        let mut data = original.clone();
+        assert_eq!(
+            data.resize_canvas(0, 0, 0, Anchor::Center).unwrap(),
+            original.voxels.len()
+        );
+        assert!(data.voxels.is_empty() && data.xlen.is_empty());
+    }
+}
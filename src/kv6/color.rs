@@ -0,0 +1,145 @@
+use super::{KV6Format, Palette, VoxelData};
+
+/// A voxel's color.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb {
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Rgb { r, g, b }
+    }
+
+    /// Packs the color as `0x00RRGGBB`, the way Voxlap stores colors.
+    pub const fn to_u32(self) -> u32 {
+        (self.r as u32) << 16 | (self.g as u32) << 8 | self.b as u32
+    }
+
+    /// Unpacks a `0x00RRGGBB` color. The top byte is ignored.
+    pub const fn from_u32(packed: u32) -> Self {
+        Rgb {
+            r: (packed >> 16) as u8,
+            g: (packed >> 8) as u8,
+            b: packed as u8,
+        }
+    }
+}
+
+impl From<[u8; 3]> for Rgb {
+    fn from([r, g, b]: [u8; 3]) -> Self {
+        Rgb { r, g, b }
+    }
+}
+
+impl From<Rgb> for [u8; 3] {
+    fn from(color: Rgb) -> Self {
+        [color.r, color.g, color.b]
+    }
+}
+
+impl VoxelData {
+    /// The `red`, `green` and `blue` fields together.
+    pub fn color(&self) -> Rgb {
+        Rgb::new(self.red, self.green, self.blue)
+    }
+
+    /// Sets `red`, `green` and `blue`, leaving the rest of the voxel alone.
+    pub fn set_color(&mut self, color: Rgb) {
+        self.red = color.r;
+        self.green = color.g;
+        self.blue = color.b;
+    }
+}
+
+impl KV6Format {
+    /// The color of every voxel, in storage order.
+    pub fn colors(&self) -> impl ExactSizeIterator<Item = Rgb> + '_ {
+        self.voxels.iter().map(VoxelData::color)
+    }
+}
+
+impl Palette {
+    /// Entry `index` as an 8-bit color, scaling the 6-bit components so that
+    /// 63 becomes 255.
+    pub fn color(&self, index: u8) -> Rgb {
+        let [r, g, b] = self.entries[index as usize].map(|c| (c & 63) << 2 | (c & 63) >> 4);
+        Rgb::new(r, g, b)
+    }
+
+    /// The index of the entry closest to `color`, by squared distance between
+    /// 8-bit colors. Ties go to the lowest index.
+    pub fn closest(&self, color: Rgb) -> u8 {
+        let distance = |index: u8| {
+            let entry = self.color(index);
+            [(entry.r, color.r), (entry.g, color.g), (entry.b, color.b)]
+                .iter()
+                .map(|&(a, b)| (a as i32 - b as i32).pow(2))
+                .sum::<i32>()
+        };
+        (0..=u8::MAX)
+            .min_by_key(|&index| distance(index))
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Rgb;
+    use crate::kv6::testing::grenade;
+    use crate::kv6::{Palette, VoxelData};
+    use alloc::vec::Vec;
+    use scroll::{Pwrite, LE};
+
+    #[test]
+    fn test_packing() {
+        let color = Rgb::new(0x12, 0x34, 0x56);
+        assert_eq!(color.to_u32(), 0x123456);
+        assert_eq!(Rgb::from_u32(0xff123456), color);
+        assert_eq!(Rgb::from([0x12, 0x34, 0x56]), color);
+        assert_eq!(<[u8; 3]>::from(color), [0x12, 0x34, 0x56]);
+    }
+
+    #[test]
+    fn test_stored_as_bgr() {
+        let mut voxel = VoxelData::default();
+        voxel.set_color(Rgb::new(0x12, 0x34, 0x56));
+        let mut bytes = [0u8; 8];
+        bytes.pwrite_with(voxel, 0, LE).unwrap();
+        assert_eq!(
+            bytes[..3],
+            0x123456u32.to_le_bytes()[..3],
+            "colors are stored as a little endian 0x..RRGGBB"
+        );
+        assert_eq!(
+            VoxelData::from_bytes(&[0x56, 0x34, 0x12, 0x80, 0, 0, 0, 0]).color(),
+            voxel.color()
+        );
+    }
+
+    #[test]
+    fn test_colors() {
+        let data = grenade();
+        let colors: Vec<Rgb> = data.colors().collect();
+        assert_eq!(colors.len(), 74);
+        assert_eq!(colors[0], Rgb::new(36, 92, 36));
+    }
+
+    #[test]
+    fn test_palette_colors() {
+        let mut palette = Palette::default();
+        palette.entries[0] = [63, 0, 32];
+        palette.entries[7] = [10, 20, 30];
+        assert_eq!(palette.color(0), Rgb::new(255, 0, 130));
+        assert_eq!(palette.color(7), Rgb::new(40, 81, 121));
+        assert_eq!(palette.color(1), Rgb::new(0, 0, 0));
+
+        assert_eq!(palette.closest(Rgb::new(250, 5, 128)), 0);
+        assert_eq!(palette.closest(palette.color(7)), 7);
+        // Entries 1 to 255 are all black; the first one wins.
+        assert_eq!(palette.closest(Rgb::new(0, 0, 0)), 1);
+    }
+}
@@ -0,0 +1,255 @@
+use super::{Bounds, CacheMismatch, Connectivity, KV6Format};
+use crate::Kv6Error;
+use alloc::{collections::BTreeMap, vec, vec::Vec};
+
+/// A group of voxels connected to each other but to nothing else, as found
+/// by [`KV6Format::components`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Component {
+    pub voxel_count: usize,
+    pub bounds: Bounds,
+}
+
+impl KV6Format {
+    /// Splits the voxels into groups that touch as `connectivity` says,
+    /// ordered by where each group's first voxel is stored.
+    ///
+    /// Only stored voxels are looked at, so two parts joined through the
+    /// unstored interior of a solid count as joined only if their stored
+    /// surfaces touch too.
+    pub fn components(&self, connectivity: Connectivity) -> Result<Vec<Component>, CacheMismatch> {
+        Ok(self.label_components(connectivity)?.1)
+    }
+
+    /// Removes every voxel not in the largest group of voxels touching as
+    /// `connectivity` says, the first one found winning ties, and returns
+    /// how many were removed.
+    ///
+    /// Only the surface is stored, and neighbouring surface voxels often
+    /// meet at just an edge or corner, so [`Connectivity::TwentySix`] is
+    /// usually what keeps one object in one piece. Visibility is recomputed
+    /// for what's left. Fails if the caches don't match the voxels, leaving
+    /// the model as it was.
+    pub fn keep_largest_component(
+        &mut self,
+        connectivity: Connectivity,
+    ) -> Result<usize, Kv6Error> {
+        let (labels, components) = self
+            .label_components(connectivity)
+            .map_err(Kv6Error::InconsistentSizes)?;
+        let largest = (0..components.len())
+            .rev()
+            .max_by_key(|&i| components[i].voxel_count);
+        self.retain_components(&labels, |label| Some(label) == largest)
+    }
+
+    /// Removes every group of voxels touching as `connectivity` says with
+    /// fewer than `min_voxels` voxels and returns how many voxels were
+    /// removed, as [`KV6Format::keep_largest_component`] does.
+    pub fn remove_components_smaller_than(
+        &mut self,
+        min_voxels: usize,
+        connectivity: Connectivity,
+    ) -> Result<usize, Kv6Error> {
+        let (labels, components) = self
+            .label_components(connectivity)
+            .map_err(Kv6Error::InconsistentSizes)?;
+        self.retain_components(&labels, |label| components[label].voxel_count >= min_voxels)
+    }
+
+    /// Which component each voxel is in, as an index into the components.
+    fn label_components(
+        &self,
+        connectivity: Connectivity,
+    ) -> Result<(Vec<usize>, Vec<Component>), CacheMismatch> {
+        let cells: Vec<_> = self
+            .iter_with_coords()?
+            .map(|(x, y, z, _)| (x, y, z))
+            .collect();
+        let index: BTreeMap<_, _> = (0..).zip(&cells).map(|(i, &cell)| (cell, i)).collect();
+
+        let mut labels = vec![usize::MAX; cells.len()];
+        let mut components = Vec::new();
+        let mut stack = Vec::new();
+        for (start, &(x, y, z)) in cells.iter().enumerate() {
+            if labels[start] != usize::MAX {
+                continue;
+            }
+            let label = components.len();
+            let mut component = Component {
+                voxel_count: 0,
+                bounds: Bounds::point(x, y, z),
+            };
+            labels[start] = label;
+            stack.push(start);
+            while let Some(i) = stack.pop() {
+                let (x, y, z) = cells[i];
+                component.voxel_count += 1;
+                component.bounds.extend(x, y, z);
+                for cell in connectivity.neighbours((x, y, z)) {
+                    if let Some(&j) = index.get(&cell) {
+                        if labels[j] == usize::MAX {
+                            labels[j] = label;
+                            stack.push(j);
+                        }
+                    }
+                }
+            }
+            components.push(component);
+        }
+        Ok((labels, components))
+    }
+
+    /// Keeps the voxels whose label `keep` accepts and returns how many went.
+    fn retain_components(
+        &mut self,
+        labels: &[usize],
+        keep: impl Fn(usize) -> bool,
+    ) -> Result<usize, Kv6Error> {
+        let cells: Vec<_> = self
+            .iter_with_coords()
+            .map_err(Kv6Error::InconsistentSizes)?
+            .zip(labels)
+            .filter(|&(_, &label)| keep(label))
+            .map(|((x, y, _, voxel), _)| (x, y, *voxel))
+            .collect();
+        let removed = self.voxels.len() - cells.len();
+        if removed > 0 {
+            let size = (self.x_size, self.y_size, self.z_size);
+            self.set_columns(size, cells)?;
+            self.recompute_visibility()?;
+        }
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Component;
+    use crate::kv6::testing::grenade;
+    use crate::kv6::{Bounds, Connectivity, KV6Builder, KV6Format, Rgb};
+
+    /// A 4³ blob in a 10³ grid with three single voxels floating around it,
+    /// one of them touching the blob only at a corner.
+    fn specks() -> KV6Format {
+        let mut builder = KV6Builder::new(10, 10, 10);
+        for x in 2..6 {
+            for y in 2..6 {
+                for z in 2..6 {
+                    builder = builder.voxel(x, y, z, Rgb::new(200, 200, 200));
+                }
+            }
+        }
+        builder
+            .voxel(0, 0, 0, Rgb::new(255, 0, 0))
+            .voxel(9, 9, 9, Rgb::new(0, 255, 0))
+            .voxel(6, 6, 6, Rgb::new(0, 0, 255))
+            .build()
+            .unwrap()
+    }
+
+    fn blob() -> KV6Format {
+        let mut data = specks();
+        for (x, y, z) in [(0, 0, 0), (9, 9, 9), (6, 6, 6)] {
+            data.remove_voxel(x, y, z).unwrap();
+        }
+        data
+    }
+
+    #[test]
+    fn test_components() {
+        let data = specks();
+        let speck = |x, y, z| Component {
+            voxel_count: 1,
+            bounds: Bounds::point(x, y, z),
+        };
+        assert_eq!(
+            data.components(Connectivity::Six).unwrap(),
+            [
+                speck(0, 0, 0),
+                Component {
+                    voxel_count: 64,
+                    bounds: Bounds {
+                        min: (2, 2, 2),
+                        max: (5, 5, 5)
+                    }
+                },
+                speck(6, 6, 6),
+                speck(9, 9, 9),
+            ]
+        );
+        let corners = data.components(Connectivity::TwentySix).unwrap();
+        assert_eq!(corners.len(), 3);
+        assert_eq!(corners[1].voxel_count, 65);
+        assert_eq!(corners[1].bounds.max, (6, 6, 6));
+
+        assert!(KV6Format::default()
+            .components(Connectivity::Six)
+            .unwrap()
+            .is_empty());
+        let mut broken = grenade();
+        broken.xlen[0] += 1;
+        assert!(broken.components(Connectivity::Six).is_err());
+    }
+
+    #[test]
+    fn test_keep_largest() {
+        let mut data = specks();
+        assert_eq!(data.keep_largest_component(Connectivity::Six).unwrap(), 3);
+        assert!(data.equivalent(&blob()));
+        assert!(data.validate().is_clean(), "{:?}", data.validate());
+
+        let mut visible = data.clone();
+        visible.recompute_visibility().unwrap();
+        assert_eq!(visible, data);
+        assert_eq!(data.keep_largest_component(Connectivity::Six).unwrap(), 0);
+
+        // Counting corners, the speck at (6, 6, 6) belongs to the blob.
+        let mut data = specks();
+        assert_eq!(
+            data.keep_largest_component(Connectivity::TwentySix)
+                .unwrap(),
+            2
+        );
+        assert_eq!(data.voxels.len(), 65);
+    }
+
+    #[test]
+    fn test_remove_small() {
+        let mut data = specks();
+        let six = Connectivity::Six;
+        assert_eq!(data.remove_components_smaller_than(1, six).unwrap(), 0);
+        assert_eq!(data, specks());
+        assert_eq!(data.remove_components_smaller_than(2, six).unwrap(), 3);
+        assert!(data.equivalent(&blob()));
+        assert_eq!(data.remove_components_smaller_than(65, six).unwrap(), 64);
+        assert!(data.voxels.is_empty());
+    }
+
+    #[test]
+    fn test_connected_untouched() {
+        let data = grenade();
+        let components = data.components(Connectivity::TwentySix).unwrap();
+        let total: usize = components.iter().map(|c| c.voxel_count).sum();
+        assert_eq!(total, 74);
+        assert_eq!(components.len(), 1);
+
+        // The stored shell only holds together through edges and corners.
+        let corners = Connectivity::TwentySix;
+        let mut data = grenade();
+        assert!(data.components(Connectivity::Six).unwrap().len() > 1);
+        assert_eq!(data.keep_largest_component(corners).unwrap(), 0);
+        assert_eq!(data.remove_components_smaller_than(74, corners).unwrap(), 0);
+        assert_eq!(data, grenade());
+
+        let mut blob = blob();
+        assert_eq!(blob.components(Connectivity::Six).unwrap().len(), 1);
+        assert_eq!(blob.keep_largest_component(Connectivity::Six).unwrap(), 0);
+        assert_eq!(
+            blob.remove_components_smaller_than(64, Connectivity::Six)
+                .unwrap(),
+            0
+        );
+        assert_eq!(blob, self::blob());
+    }
+}
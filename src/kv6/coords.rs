@@ -0,0 +1,316 @@
+use super::{CacheMismatch, KV6Format, VoxelData};
+use alloc::vec::Vec;
+use core::{iter::FusedIterator, ops::Range, slice};
+
+impl KV6Format {
+    /// Every voxel with the `(x, y, z)` it's at, in storage order.
+    ///
+    /// Positions come from walking `ylen`, so the caches have to match the
+    /// voxels; if they don't, this returns the mismatch [`check_caches`]
+    /// reports instead.
+    ///
+    /// [`check_caches`]: KV6Format::check_caches
+    pub fn iter_with_coords(&self) -> Result<VoxelCoords<'_>, CacheMismatch> {
+        self.check_caches()?;
+        Ok(VoxelCoords {
+            voxels: self.voxels.iter(),
+            rows: &self.ylen,
+            next: (0, 0),
+            current: (0, 0),
+            left: 0,
+        })
+    }
+
+    /// Calls `f` with every voxel and its `(x, y, z)`, in storage order, and
+    /// lets it change the voxel in place.
+    ///
+    /// `f` may change `height`. Any column where it did is re-sorted by height
+    /// afterwards so the model stays valid, which means voxels can end up at
+    /// a different index; moving two voxels onto the same height is left to
+    /// the caller to avoid. Fails like [`KV6Format::iter_with_coords`] if the
+    /// caches don't match the voxels, without calling `f`.
+    pub fn for_each_voxel_mut(
+        &mut self,
+        mut f: impl FnMut(u32, u32, u16, &mut VoxelData),
+    ) -> Result<(), CacheMismatch> {
+        self.check_caches()?;
+
+        let mut start = 0;
+        for (x, row) in (0..).zip(&self.ylen) {
+            for (y, &count) in (0..).zip(row) {
+                let column = &mut self.voxels[start..start + count as usize];
+                let mut moved = false;
+                for voxel in column.iter_mut() {
+                    let z = voxel.height;
+                    f(x, y, z, voxel);
+                    moved |= voxel.height != z;
+                }
+                if moved {
+                    column.sort_by_key(|voxel| voxel.height);
+                }
+                start += count as usize;
+            }
+        }
+        Ok(())
+    }
+
+    /// The voxels of column `(x, y)`, top to bottom. `None` if the column is
+    /// outside the model, `Some(&[])` if it's empty.
+    ///
+    /// This is what the caches are for: `xlen` skips whole rows, so finding a
+    /// column costs `O(x + y)` rather than a walk over every column before
+    /// it. It trusts `xlen` to match `ylen`; if it doesn't, the slice can be
+    /// wrong, but it's never out of bounds.
+    pub fn column(&self, x: u32, y: u32) -> Option<&[VoxelData]> {
+        let range = self.column_range(x, y)?;
+        self.voxels.get(range)
+    }
+
+    /// Like [`KV6Format::column`], but mutable. Changing heights is fine as
+    /// long as they keep increasing.
+    pub fn column_mut(&mut self, x: u32, y: u32) -> Option<&mut [VoxelData]> {
+        let range = self.column_range(x, y)?;
+        self.voxels.get_mut(range)
+    }
+
+    /// The voxel at `(x, y, z)`, if there is one.
+    ///
+    /// Columns are binary searched, relying on heights increasing down each
+    /// column as the format requires. In a column that isn't sorted this may
+    /// miss a voxel, but never panics.
+    pub fn get_voxel(&self, x: u32, y: u32, z: u16) -> Option<&VoxelData> {
+        let column = self.column(x, y)?;
+        let index = column.binary_search_by_key(&z, |voxel| voxel.height).ok()?;
+        Some(&column[index])
+    }
+
+    /// Like [`KV6Format::get_voxel`], but mutable.
+    pub fn get_voxel_mut(&mut self, x: u32, y: u32, z: u16) -> Option<&mut VoxelData> {
+        let column = self.column_mut(x, y)?;
+        let index = column.binary_search_by_key(&z, |voxel| voxel.height).ok()?;
+        Some(&mut column[index])
+    }
+
+    /// Where column `(x, y)` is in `voxels`, according to the caches.
+    pub(crate) fn column_range(&self, x: u32, y: u32) -> Option<Range<usize>> {
+        if x >= self.x_size || y >= self.y_size {
+            return None;
+        }
+        let (x, y) = (x as usize, y as usize);
+        let row = self.ylen.get(x)?;
+        let start = self
+            .xlen
+            .get(..x)?
+            .iter()
+            .map(|&n| n as usize)
+            .sum::<usize>()
+            + row.get(..y)?.iter().map(|&n| n as usize).sum::<usize>();
+        Some(start..start + *row.get(y)? as usize)
+    }
+}
+
+/// The iterator returned by [`KV6Format::iter_with_coords`].
+#[derive(Debug, Clone)]
+pub struct VoxelCoords<'a> {
+    voxels: slice::Iter<'a, VoxelData>,
+    rows: &'a [Vec<u16>],
+    /// The next column to look at, and the one being walked.
+    next: (usize, usize),
+    current: (u32, u32),
+    left: usize,
+}
+
+impl<'a> Iterator for VoxelCoords<'a> {
+    type Item = (u32, u32, u16, &'a VoxelData);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.left == 0 {
+            let (x, y) = self.next;
+            match self.rows.get(x)?.get(y) {
+                Some(&count) => {
+                    self.current = (x as u32, y as u32);
+                    self.left = count as usize;
+                    self.next = (x, y + 1);
+                }
+                None => self.next = (x + 1, 0),
+            }
+        }
+        self.left -= 1;
+
+        let voxel = self.voxels.next()?;
+        Some((self.current.0, self.current.1, voxel.height, voxel))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.voxels.size_hint()
+    }
+}
+
+impl ExactSizeIterator for VoxelCoords<'_> {}
+
+impl FusedIterator for VoxelCoords<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use crate::kv6::testing::grenade;
+    use crate::kv6::{CacheMismatch, KV6Format, VoxelData};
+    use alloc::{collections::BTreeSet, vec, vec::Vec};
+
+    #[test]
+    fn test_grenade_coords() {
+        let data = grenade();
+        let coords: Vec<_> = data.iter_with_coords().unwrap().collect();
+        assert_eq!(coords.len(), 74);
+        assert_eq!(data.iter_with_coords().unwrap().len(), 74);
+
+        let mut unique = BTreeSet::new();
+        for (i, &(x, y, z, voxel)) in coords.iter().enumerate() {
+            assert!(x < data.x_size && y < data.y_size && (z as u32) < data.z_size);
+            assert!(core::ptr::eq(voxel, &data.voxels[i]));
+            assert!(unique.insert((x, y, z)));
+        }
+
+        // Each column's voxels are where ylen says they are.
+        for x in 0..data.x_size {
+            for y in 0..data.y_size {
+                let count = coords.iter().filter(|c| (c.0, c.1) == (x, y)).count();
+                assert_eq!(count, data.ylen[x as usize][y as usize] as usize);
+            }
+        }
+    }
+
+    #[test]
+    fn test_coords_empty_and_inconsistent() {
+        let empty = KV6Format {
+            x_size: 2,
+            y_size: 0,
+            xlen: vec![0, 0],
+            ylen: vec![Vec::new(), Vec::new()],
+            ..Default::default()
+        };
+        assert_eq!(empty.iter_with_coords().unwrap().count(), 0);
+
+        let mut data = grenade();
+        data.voxels.pop();
+        assert!(matches!(
+            data.iter_with_coords(),
+            Err(CacheMismatch::Total { .. })
+        ));
+    }
+
+    #[test]
+    fn test_for_each_voxel_mut() {
+        let mut data = grenade();
+        let original = data.clone();
+        data.for_each_voxel_mut(|_, _, z, voxel| {
+            if z < 3 {
+                voxel.red /= 2;
+                voxel.green /= 2;
+                voxel.blue /= 2;
+            }
+        })
+        .unwrap();
+
+        let before = original.iter_with_coords().unwrap();
+        for ((x, y, z, voxel), (_, _, _, old)) in data.iter_with_coords().unwrap().zip(before) {
+            let expected = if z < 3 { old.green / 2 } else { old.green };
+            assert_eq!(voxel.green, expected, "at {:?}", (x, y, z));
+        }
+        assert!(data
+            .voxels
+            .iter()
+            .zip(&original.voxels)
+            .any(|(a, b)| a != b));
+    }
+
+    #[test]
+    fn test_for_each_voxel_mut_resorts() {
+        let mut data = grenade();
+        let (x, y) = (0..data.x_size as usize)
+            .flat_map(|x| (0..data.y_size as usize).map(move |y| (x, y)))
+            .find(|&(x, y)| data.ylen[x][y] > 1)
+            .unwrap();
+
+        // Move the column's top voxel below everything else in it.
+        let mut first = true;
+        data.for_each_voxel_mut(|vx, vy, _, voxel| {
+            if (vx as usize, vy as usize) == (x, y) && first {
+                voxel.height = u16::MAX;
+                first = false;
+            }
+        })
+        .unwrap();
+
+        let column: Vec<u16> = data
+            .iter_with_coords()
+            .unwrap()
+            .filter(|c| (c.0 as usize, c.1 as usize) == (x, y))
+            .map(|c| c.2)
+            .collect();
+        assert_eq!(column.last(), Some(&u16::MAX));
+        assert!(column.windows(2).all(|w| w[0] < w[1]));
+
+        data.xlen[0] += 1;
+        assert!(data.for_each_voxel_mut(|_, _, _, _| panic!()).is_err());
+    }
+
+    #[test]
+    fn test_columns_concatenate() {
+        let data = grenade();
+        let mut all: Vec<VoxelData> = Vec::new();
+        for x in 0..data.x_size {
+            for y in 0..data.y_size {
+                let column = data.column(x, y).unwrap();
+                assert_eq!(column.len(), data.ylen[x as usize][y as usize] as usize);
+                all.extend_from_slice(column);
+            }
+        }
+        assert_eq!(all, data.voxels);
+
+        assert_eq!(data.column(0, 0), Some(&[][..]));
+        assert_eq!(data.column(data.x_size, 0), None);
+        assert_eq!(data.column(0, data.y_size), None);
+    }
+
+    #[test]
+    fn test_column_mut() {
+        let mut data = grenade();
+        let (x, y, _, _) = data.iter_with_coords().unwrap().last().unwrap();
+        for voxel in data.column_mut(x, y).unwrap() {
+            voxel.red = 1;
+        }
+        assert_eq!(data.voxels.last().unwrap().red, 1);
+
+        // Caches that claim more voxels than there are don't panic.
+        data.xlen[0] += 1000;
+        assert_eq!(data.column(1, 0), None);
+    }
+
+    #[test]
+    fn test_get_voxel() {
+        let mut data = grenade();
+        let coords: BTreeSet<(u32, u32, u16)> = data
+            .iter_with_coords()
+            .unwrap()
+            .map(|(x, y, z, _)| (x, y, z))
+            .collect();
+
+        for x in 0..=data.x_size {
+            for y in 0..=data.y_size {
+                for z in 0..=data.z_size as u16 {
+                    let found = data.get_voxel(x, y, z);
+                    assert_eq!(found.is_some(), coords.contains(&(x, y, z)));
+                    if let Some(voxel) = found {
+                        assert_eq!(voxel.height, z);
+                    }
+                }
+            }
+        }
+        assert_eq!(data.get_voxel(u32::MAX, u32::MAX, u16::MAX), None);
+
+        let &(x, y, z) = coords.iter().next().unwrap();
+        data.get_voxel_mut(x, y, z).unwrap().red = 7;
+        assert_eq!(data.get_voxel(x, y, z).unwrap().red, 7);
+        assert!(data.get_voxel_mut(x, y, data.z_size as u16).is_none());
+    }
+}
@@ -0,0 +1,309 @@
+use super::{KV6Format, Rgb};
+use crate::Kv6Error;
+use alloc::{vec, vec::Vec};
+
+/// A color or nothing for every cell of a box, for algorithms that want
+/// random access rather than columns.
+///
+/// Cells are stored x-major with heights innermost, the same order a KV6
+/// file stores its voxels in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DenseVoxelGrid {
+    cells: Vec<Option<Rgb>>,
+    x_size: u32,
+    y_size: u32,
+    z_size: u32,
+}
+
+impl DenseVoxelGrid {
+    /// The number of cells [`KV6Format::to_dense`] allows, about 64 MiB.
+    pub const DEFAULT_LIMIT: usize = 1 << 24;
+
+    /// An empty grid of `x_size` by `y_size` by `z_size` cells. Heights are
+    /// 16 bits, so `z_size` can be at most 65536.
+    pub fn new(x_size: u32, y_size: u32, z_size: u32) -> Result<Self, Kv6Error> {
+        Self::with_limit(x_size, y_size, z_size, usize::MAX)
+    }
+
+    fn with_limit(x_size: u32, y_size: u32, z_size: u32, limit: usize) -> Result<Self, Kv6Error> {
+        let cells = (x_size as usize)
+            .checked_mul(y_size as usize)
+            .and_then(|n| n.checked_mul(z_size as usize))
+            .ok_or(Kv6Error::GridOverflow {
+                x_size,
+                y_size,
+                z_size,
+            })?;
+        if cells > limit {
+            return Err(Kv6Error::GridTooLarge { cells, limit });
+        }
+        if z_size > 1 << 16 {
+            return Err(Kv6Error::LimitExceeded {
+                field: "z_size",
+                value: z_size,
+                limit: 1 << 16,
+            });
+        }
+
+        Ok(DenseVoxelGrid {
+            cells: vec![None; cells],
+            x_size,
+            y_size,
+            z_size,
+        })
+    }
+
+    /// `(x_size, y_size, z_size)`.
+    pub fn dimensions(&self) -> (u32, u32, u32) {
+        (self.x_size, self.y_size, self.z_size)
+    }
+
+    /// The color at `(x, y, z)`, `None` if the cell is empty or out of range.
+    pub fn get(&self, x: u32, y: u32, z: u16) -> Option<Rgb> {
+        self.cells[self.index(x, y, z)?]
+    }
+
+    /// Fills or clears `(x, y, z)`, returning `false` if it's out of range.
+    pub fn set(&mut self, x: u32, y: u32, z: u16, color: Option<Rgb>) -> bool {
+        match self.index(x, y, z) {
+            Some(i) => {
+                self.cells[i] = color;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Every filled cell with its color, in storage order.
+    pub fn iter(&self) -> impl Iterator<Item = (u32, u32, u16, Rgb)> + '_ {
+        let (y_size, z_size) = (self.y_size as usize, self.z_size as usize);
+        self.cells.iter().enumerate().filter_map(move |(i, cell)| {
+            let column = i / z_size;
+            Some((
+                (column / y_size) as u32,
+                (column % y_size) as u32,
+                (i % z_size) as u16,
+                (*cell)?,
+            ))
+        })
+    }
+
+    /// The cells in storage order.
+    #[cfg(feature = "ndarray")]
+    pub(crate) fn into_cells(self) -> Vec<Option<Rgb>> {
+        self.cells
+    }
+
+    /// A grid over `cells`, which must hold `x_size * y_size * z_size`
+    /// entries in storage order.
+    #[cfg(feature = "ndarray")]
+    pub(crate) fn from_cells(
+        x_size: u32,
+        y_size: u32,
+        z_size: u32,
+        cells: Vec<Option<Rgb>>,
+    ) -> Self {
+        debug_assert_eq!(
+            cells.len(),
+            x_size as usize * y_size as usize * z_size as usize
+        );
+        DenseVoxelGrid {
+            cells,
+            x_size,
+            y_size,
+            z_size,
+        }
+    }
+
+    fn index(&self, x: u32, y: u32, z: u16) -> Option<usize> {
+        if x >= self.x_size || y >= self.y_size || z as u32 >= self.z_size {
+            return None;
+        }
+        let column = x as usize * self.y_size as usize + y as usize;
+        Some(column * self.z_size as usize + z as usize)
+    }
+}
+
+impl KV6Format {
+    /// Copies every voxel's color into a [`DenseVoxelGrid`] the size of the
+    /// model, refusing models of more than [`DenseVoxelGrid::DEFAULT_LIMIT`]
+    /// cells or with a `z_size` past 65536.
+    ///
+    /// Only stored voxels are filled, so a model's unstored interior comes out
+    /// empty. Voxels at a height outside `z_size` are left out.
+    pub fn to_dense(&self) -> Result<DenseVoxelGrid, Kv6Error> {
+        self.to_dense_with_limit(DenseVoxelGrid::DEFAULT_LIMIT)
+    }
+
+    /// Like [`KV6Format::to_dense`], but allowing up to `limit` cells.
+    pub fn to_dense_with_limit(&self, limit: usize) -> Result<DenseVoxelGrid, Kv6Error> {
+        let mut grid = DenseVoxelGrid::with_limit(self.x_size, self.y_size, self.z_size, limit)?;
+        for (x, y, z, voxel) in self
+            .iter_with_coords()
+            .map_err(Kv6Error::InconsistentSizes)?
+        {
+            grid.set(x, y, z, Some(voxel.color()));
+        }
+        Ok(grid)
+    }
+
+    /// Builds a model with a voxel for every filled cell of `grid`.
+    ///
+    /// Voxels get the standard dummy byte, visibility from their neighbours
+    /// as [`KV6Format::recompute_visibility`] works it out, and normals from
+    /// [`NormalMode::FaceAverage`](super::normals::NormalMode::FaceAverage)
+    /// against [`NormalTable::Slab6`](super::normals::NormalTable::Slab6).
+    /// The pivot is the center of the grid; set the pivot fields afterwards
+    /// to move it.
+    pub fn from_dense(grid: &DenseVoxelGrid) -> Result<KV6Format, Kv6Error> {
+        KV6Format::from_sorted(grid.dimensions(), grid.iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DenseVoxelGrid;
+    use crate::kv6::testing::grenade;
+    use crate::kv6::{KV6Format, Rgb, VoxelData};
+    use crate::Kv6Error;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_grenade_dense() {
+        let data = grenade();
+        let grid = data.to_dense().unwrap();
+        assert_eq!(grid.dimensions(), (6, 6, 9));
+        for x in 0..=6 {
+            for y in 0..=6 {
+                for z in 0..=9 {
+                    assert_eq!(
+                        grid.get(x, y, z),
+                        data.get_voxel(x, y, z).map(|voxel| voxel.color())
+                    );
+                }
+            }
+        }
+
+        // Storage order matches the model's.
+        let cells: Vec<_> = grid.iter().collect();
+        let voxels: Vec<_> = data
+            .iter_with_coords()
+            .unwrap()
+            .map(|(x, y, z, voxel)| (x, y, z, voxel.color()))
+            .collect();
+        assert_eq!(cells, voxels);
+    }
+
+    #[test]
+    fn test_dense_set() {
+        let mut grid = DenseVoxelGrid::new(2, 3, 4).unwrap();
+        let color = Rgb::new(1, 2, 3);
+        assert!(grid.set(1, 2, 3, Some(color)));
+        assert!(!grid.set(2, 0, 0, Some(color)));
+        assert!(!grid.set(0, 0, 4, Some(color)));
+        assert_eq!(grid.get(1, 2, 3), Some(color));
+        assert_eq!(grid.iter().collect::<Vec<_>>(), [(1, 2, 3, color)]);
+        assert!(grid.set(1, 2, 3, None));
+        assert_eq!(grid.iter().count(), 0);
+    }
+
+    #[test]
+    fn test_dense_limits() {
+        let data = grenade();
+        assert!(matches!(
+            data.to_dense_with_limit(323),
+            Err(Kv6Error::GridTooLarge {
+                cells: 324,
+                limit: 323
+            })
+        ));
+        assert!(data.to_dense_with_limit(324).is_ok());
+        assert!(matches!(
+            DenseVoxelGrid::new(u32::MAX, u32::MAX, u32::MAX),
+            Err(Kv6Error::GridOverflow { .. })
+        ));
+        assert!(matches!(
+            DenseVoxelGrid::new(1, 1, (1 << 16) + 1),
+            Err(Kv6Error::LimitExceeded {
+                field: "z_size",
+                value: 65537,
+                ..
+            })
+        ));
+        assert!(DenseVoxelGrid::new(1, 1, 1 << 16).is_ok());
+
+        let huge = KV6Format {
+            x_size: 4096,
+            y_size: 4096,
+            z_size: 256,
+            ..Default::default()
+        };
+        assert!(matches!(
+            huge.to_dense(),
+            Err(Kv6Error::GridTooLarge { .. })
+        ));
+    }
+
+    /// Serializes, re-parses and validates the model built from `grid`.
+    fn round_trip(grid: &DenseVoxelGrid) -> KV6Format {
+        let data = KV6Format::from_dense(grid).unwrap();
+        let (parsed, _) = KV6Format::parse(&data.to_bytes().unwrap()).unwrap();
+        assert_eq!(parsed, data);
+        assert!(parsed.validate().is_clean(), "{:?}", parsed.validate());
+        assert_eq!(&parsed.to_dense().unwrap(), grid);
+        parsed
+    }
+
+    #[test]
+    fn test_from_grenade() {
+        let grid = grenade().to_dense().unwrap();
+        let data = round_trip(&grid);
+        assert_eq!((data.xlen, data.ylen), (grenade().xlen, grenade().ylen));
+        assert_eq!((data.x_pivot, data.y_pivot, data.z_pivot), (3.0, 3.0, 4.5));
+
+        // The visibility bits come out as slab6 wrote them.
+        let visibility = |data: &KV6Format| -> Vec<u8> {
+            data.voxels.iter().map(|voxel| voxel.visibility).collect()
+        };
+        assert_eq!(
+            visibility(&KV6Format::from_dense(&grid).unwrap()),
+            visibility(&grenade())
+        );
+    }
+
+    #[test]
+    fn test_from_dense_edge_cases() {
+        let empty = round_trip(&DenseVoxelGrid::new(3, 2, 5).unwrap());
+        assert!(empty.voxels.is_empty());
+        assert_eq!(empty.ylen, [[0, 0], [0, 0], [0, 0]]);
+
+        let mut full = DenseVoxelGrid::new(3, 3, 3).unwrap();
+        for x in 0..3 {
+            for y in 0..3 {
+                for z in 0..3 {
+                    full.set(x, y, z, Some(Rgb::new(x as u8, y as u8, z as u8)));
+                }
+            }
+        }
+        let data = round_trip(&full);
+        assert_eq!(data.voxels.len(), 27);
+        assert_eq!(data.get_voxel(1, 1, 1).unwrap().visibility, 0);
+        assert_eq!(data.get_voxel(0, 0, 0).unwrap().visible_faces().count(), 3);
+
+        let mut single = DenseVoxelGrid::new(1, 1, 1).unwrap();
+        single.set(0, 0, 0, Some(Rgb::new(9, 8, 7)));
+        let data = round_trip(&single);
+        assert_eq!(
+            data.voxels,
+            [VoxelData {
+                blue: 7,
+                green: 8,
+                red: 9,
+                dummy: VoxelData::DUMMY,
+                height: 0,
+                visibility: 0x3f,
+                normalindex: 255,
+            }]
+        );
+    }
+}
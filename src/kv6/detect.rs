@@ -0,0 +1,175 @@
+use super::KV6Format;
+use core::fmt;
+
+/// The voxel formats [`detect_format`] can tell apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormatKind {
+    /// Ken Silverman's KV6, as read by this crate.
+    Kv6,
+    /// The Build engine's KVX.
+    Kvx,
+    /// MagicaVoxel's `.vox`.
+    MagicaVoxel,
+    /// A Voxlap world map.
+    Vxl,
+    /// None of the above.
+    Unknown,
+}
+
+impl fmt::Display for FormatKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FormatKind::Kv6 => "KV6",
+            FormatKind::Kvx => "KVX",
+            FormatKind::MagicaVoxel => "MagicaVoxel",
+            FormatKind::Vxl => "VXL",
+            FormatKind::Unknown => "unknown",
+        })
+    }
+}
+
+const VOX_MAGIC: &[u8; 4] = b"VOX ";
+/// Voxlap's `fileid`, stored little endian.
+const VXL_MAGIC: u32 = 0x0907_2000;
+/// Build keeps every KVX dimension within this.
+const KVX_MAX_SIZE: u32 = 256;
+
+/// Guesses the format of a voxel file from its first few bytes.
+///
+/// Returns `None` if there are fewer than 4 bytes to look at. KV6
+/// (`"Kvxl"`), MagicaVoxel (`"VOX "`) and Voxlap maps (`0x09072000`) have
+/// magic numbers. KVX doesn't, so it's recognized by a plausible header
+/// instead: sensible dimensions, and offset tables that agree with them.
+pub fn detect_format(src: &[u8]) -> Option<FormatKind> {
+    let magic: [u8; 4] = src.get(..4)?.try_into().ok()?;
+
+    let kind = if u32::from_be_bytes(magic) == KV6Format::MAGIC {
+        FormatKind::Kv6
+    } else if &magic == VOX_MAGIC {
+        FormatKind::MagicaVoxel
+    } else if u32::from_le_bytes(magic) == VXL_MAGIC {
+        FormatKind::Vxl
+    } else if looks_like_kvx(src) {
+        FormatKind::Kvx
+    } else {
+        FormatKind::Unknown
+    };
+    Some(kind)
+}
+
+/// A KVX file starts with the byte count of its first mip level, followed by
+/// `xsiz`, `ysiz` and `zsiz`, three pivots, then `xoffset[xsiz + 1]` (u32)
+/// and `xyoffset[xsiz][ysiz + 1]` (u16).
+///
+/// This accepts the header if every size is between 1 and 256, the byte
+/// count covers at least the header and both offset tables, and, when it's
+/// there to read, `xoffset[0]` points just past the offset tables, which is
+/// where the first column's voxels always start.
+fn looks_like_kvx(src: &[u8]) -> bool {
+    let word = |i: usize| -> Option<u32> {
+        let bytes = src.get(i * 4..i * 4 + 4)?;
+        Some(u32::from_le_bytes(bytes.try_into().ok()?))
+    };
+    let (Some(numbytes), Some(xsiz), Some(ysiz), Some(zsiz)) = (word(0), word(1), word(2), word(3))
+    else {
+        return false;
+    };
+    if [xsiz, ysiz, zsiz]
+        .iter()
+        .any(|size| !(1..=KVX_MAX_SIZE).contains(size))
+    {
+        return false;
+    }
+
+    let tables = (xsiz + 1) * 4 + xsiz * (ysiz + 1) * 2;
+    if numbytes < 24 + tables {
+        return false;
+    }
+    match word(7) {
+        Some(first_column) => first_column == tables,
+        None => src.len() >= 28,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{detect_format, FormatKind};
+    use crate::{kv6::KV6Format, Kv6Error};
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_detect_fixtures() {
+        let fixtures: [(&[u8], FormatKind); 4] = [
+            (
+                include_bytes!("../../tests/fixtures/grenade.kv6.head"),
+                FormatKind::Kv6,
+            ),
+            (
+                include_bytes!("../../tests/fixtures/model.kvx.head"),
+                FormatKind::Kvx,
+            ),
+            (
+                include_bytes!("../../tests/fixtures/model.vox.head"),
+                FormatKind::MagicaVoxel,
+            ),
+            (
+                include_bytes!("../../tests/fixtures/map.vxl.head"),
+                FormatKind::Vxl,
+            ),
+        ];
+        for (bytes, kind) in fixtures {
+            assert_eq!(detect_format(bytes), Some(kind));
+        }
+    }
+
+    #[test]
+    fn test_detect_unknown() {
+        assert_eq!(detect_format(b""), None);
+        assert_eq!(detect_format(b"Kvx"), None);
+        assert_eq!(detect_format(&[0; 64]), Some(FormatKind::Unknown));
+        assert_eq!(
+            detect_format(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\0\x10\0\0\0\x10"),
+            Some(FormatKind::Unknown)
+        );
+    }
+
+    #[test]
+    fn test_detect_kvx_heuristics() {
+        let kvx = include_bytes!("../../tests/fixtures/model.kvx.head");
+
+        // Only the sizes and byte count to go on.
+        assert_eq!(detect_format(&kvx[..28]), Some(FormatKind::Kvx));
+        assert_eq!(detect_format(&kvx[..20]), Some(FormatKind::Unknown));
+
+        let mut bad_offset = *kvx;
+        bad_offset[28] += 1;
+        assert_eq!(detect_format(&bad_offset), Some(FormatKind::Unknown));
+
+        let mut too_big = *kvx;
+        too_big[4..8].copy_from_slice(&257u32.to_le_bytes());
+        assert_eq!(detect_format(&too_big), Some(FormatKind::Unknown));
+
+        let mut too_short = *kvx;
+        too_short[..4].copy_from_slice(&24u32.to_le_bytes());
+        assert_eq!(detect_format(&too_short), Some(FormatKind::Unknown));
+    }
+
+    #[test]
+    fn test_bad_magic_names_format() {
+        let vox = include_bytes!("../../tests/fixtures/model.vox.head");
+        let err = KV6Format::parse(vox).unwrap_err();
+        assert!(matches!(
+            err,
+            Kv6Error::BadMagic {
+                detected: Some(FormatKind::MagicaVoxel),
+                ..
+            }
+        ));
+        assert!(err
+            .to_string()
+            .ends_with("(this looks like a MagicaVoxel file)"));
+
+        let err = KV6Format::parse(&[0xff; 64]).unwrap_err();
+        assert!(matches!(err, Kv6Error::BadMagic { detected: None, .. }));
+    }
+}
@@ -0,0 +1,408 @@
+use super::{CacheMismatch, KV6Format, Rgb, VoxelData};
+use alloc::collections::BTreeMap;
+use core::fmt;
+
+/// What changed from one model to another, from [`Kv6Diff::compute`].
+///
+/// Voxels are matched by position, so their order and the caches don't
+/// matter. Only colors are compared; a voxel whose `dummy`, `visibility` or
+/// `normalindex` changed but whose color didn't isn't listed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Kv6Diff {
+    /// Voxels only in the second model, as they are there.
+    pub added: BTreeMap<(u32, u32, u16), VoxelData>,
+    /// Voxels only in the first model, as they were there.
+    pub removed: BTreeMap<(u32, u32, u16), VoxelData>,
+    /// Voxels in both with a different color, as `(before, after)`.
+    pub recolored: BTreeMap<(u32, u32, u16), (Rgb, Rgb)>,
+    /// Whether `x_size`, `y_size` or `z_size` differ.
+    pub dimensions_changed: bool,
+    /// Whether any pivot differs.
+    pub pivot_changed: bool,
+}
+
+impl Kv6Diff {
+    /// The changes that turn `a` into `b`. Fails if either model's caches
+    /// don't match its voxels.
+    pub fn compute(a: &KV6Format, b: &KV6Format) -> Result<Kv6Diff, CacheMismatch> {
+        let positions = |data: &KV6Format| -> Result<BTreeMap<(u32, u32, u16), VoxelData>, _> {
+            Ok(data
+                .iter_with_coords()?
+                .map(|(x, y, z, voxel)| ((x, y, z), *voxel))
+                .collect())
+        };
+        let (before, mut after) = (positions(a)?, positions(b)?);
+
+        let mut diff = Kv6Diff {
+            dimensions_changed: (a.x_size, a.y_size, a.z_size) != (b.x_size, b.y_size, b.z_size),
+            pivot_changed: (a.x_pivot, a.y_pivot, a.z_pivot) != (b.x_pivot, b.y_pivot, b.z_pivot),
+            ..Default::default()
+        };
+        for (position, old) in before {
+            match after.remove(&position) {
+                Some(new) if new.color() != old.color() => {
+                    diff.recolored.insert(position, (old.color(), new.color()));
+                }
+                Some(_) => {}
+                None => {
+                    diff.removed.insert(position, old);
+                }
+            }
+        }
+        diff.added = after;
+        Ok(diff)
+    }
+
+    /// Whether the models have the same voxels in the same colors, grid and
+    /// pivot.
+    pub fn is_empty(&self) -> bool {
+        self.voxel_changes() == 0 && !self.dimensions_changed && !self.pivot_changed
+    }
+
+    /// Number of voxels added, removed or recolored.
+    pub fn voxel_changes(&self) -> usize {
+        self.added.len() + self.removed.len() + self.recolored.len()
+    }
+}
+
+impl fmt::Display for Kv6Diff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "no changes");
+        }
+        write!(
+            f,
+            "{} added, {} removed, {} recolored",
+            self.added.len(),
+            self.removed.len(),
+            self.recolored.len()
+        )?;
+        if self.dimensions_changed {
+            write!(f, ", dimensions changed")?;
+        }
+        if self.pivot_changed {
+            write!(f, ", pivot changed")?;
+        }
+        Ok(())
+    }
+}
+
+/// Why [`KV6Format::apply_diff`] refused a diff.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchError {
+    /// The model's caches don't match its voxels.
+    Caches(CacheMismatch),
+    /// The diff was computed between models of different sizes, so its
+    /// positions don't line up with any one grid.
+    DimensionsChanged,
+    /// A voxel to be removed or recolored at `(x, y, z)` isn't there.
+    Missing { x: u32, y: u32, z: u16 },
+    /// A voxel to be added at `(x, y, z)` is already there.
+    Occupied { x: u32, y: u32, z: u16 },
+    /// A voxel to be added at `(x, y, z)` is outside the model's grid.
+    OutOfBounds { x: u32, y: u32, z: u16 },
+    /// The additions would give column `(x, y)` more than 65535 voxels, or
+    /// its row more than `u32::MAX`.
+    ColumnFull { x: u32, y: u32 },
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchError::Caches(mismatch) => write!(f, "{}", mismatch),
+            PatchError::DimensionsChanged => {
+                write!(f, "the diff is between models of different sizes")
+            }
+            PatchError::Missing { x, y, z } => write!(f, "no voxel at {:?}", (x, y, z)),
+            PatchError::Occupied { x, y, z } => {
+                write!(f, "there's already a voxel at {:?}", (x, y, z))
+            }
+            PatchError::OutOfBounds { x, y, z } => {
+                write!(f, "{:?} is outside the model", (x, y, z))
+            }
+            PatchError::ColumnFull { x, y } => {
+                write!(f, "column {:?} can't hold the added voxels", (x, y))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PatchError {}
+
+/// Knobs for [`KV6Format::apply_diff_with`].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PatchOptions {
+    /// Let additions replace voxels that are already there, and skip
+    /// removals of voxels that aren't.
+    pub force: bool,
+}
+
+impl KV6Format {
+    /// Removes, recolors and adds voxels as `diff` says, turning the model
+    /// `diff` was computed from into the other one.
+    ///
+    /// The whole diff is checked before anything changes, so on error the
+    /// model is left as it was. Pivots aren't part of a diff and are left
+    /// alone. Added voxels get their visibility from their new neighbours,
+    /// as [`KV6Format::insert_voxel`] works it out.
+    pub fn apply_diff(&mut self, diff: &Kv6Diff) -> Result<(), PatchError> {
+        self.apply_diff_with(diff, &PatchOptions::default())
+    }
+
+    /// Like [`KV6Format::apply_diff`], with `options`.
+    pub fn apply_diff_with(
+        &mut self,
+        diff: &Kv6Diff,
+        options: &PatchOptions,
+    ) -> Result<(), PatchError> {
+        self.check_caches().map_err(PatchError::Caches)?;
+        if diff.dimensions_changed {
+            return Err(PatchError::DimensionsChanged);
+        }
+
+        // How many voxels each touched column gains or loses.
+        let mut growth: BTreeMap<(u32, u32), i64> = BTreeMap::new();
+        let present = |&(x, y, z): &(u32, u32, u16)| self.get_voxel(x, y, z).is_some();
+        for &(x, y, z) in diff.removed.keys() {
+            if present(&(x, y, z)) {
+                *growth.entry((x, y)).or_default() -= 1;
+            } else if !options.force {
+                return Err(PatchError::Missing { x, y, z });
+            }
+        }
+        for &(x, y, z) in diff.recolored.keys() {
+            if !present(&(x, y, z)) || diff.removed.contains_key(&(x, y, z)) {
+                return Err(PatchError::Missing { x, y, z });
+            }
+        }
+        for &(x, y, z) in diff.added.keys() {
+            if x >= self.x_size || y >= self.y_size || z as u32 >= self.z_size {
+                return Err(PatchError::OutOfBounds { x, y, z });
+            }
+            let taken = present(&(x, y, z)) && !diff.removed.contains_key(&(x, y, z));
+            if taken && !options.force {
+                return Err(PatchError::Occupied { x, y, z });
+            }
+            if !taken {
+                *growth.entry((x, y)).or_default() += 1;
+            }
+        }
+        let mut rows: BTreeMap<u32, i64> = BTreeMap::new();
+        for (&(x, y), &change) in &growth {
+            let column = self.ylen[x as usize][y as usize] as i64 + change;
+            let row = rows.entry(x).or_insert(self.xlen[x as usize] as i64);
+            *row += change;
+            if column > u16::MAX as i64 || *row > u32::MAX as i64 {
+                return Err(PatchError::ColumnFull { x, y });
+            }
+        }
+
+        // Everything has been checked, so the edits below can't fail.
+        for &(x, y, z) in diff.removed.keys() {
+            self.remove_voxel(x, y, z);
+        }
+        for (&(x, y, z), &(_, after)) in &diff.recolored {
+            self.set_voxel(x, y, z, after)
+                .expect("recolored voxels were checked to be there");
+        }
+        for (&(x, y, z), &voxel) in &diff.added {
+            self.remove_voxel(x, y, z);
+            self.insert_voxel(x, y, z, voxel)
+                .expect("additions were checked to fit");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Kv6Diff, PatchError, PatchOptions};
+    use crate::kv6::testing::grenade;
+    use crate::kv6::{KV6Format, Rgb, VoxelData};
+    use alloc::{string::ToString, vec, vec::Vec};
+
+    #[test]
+    fn test_diff_self() {
+        let data = grenade();
+        let diff = Kv6Diff::compute(&data, &data).unwrap();
+        assert!(diff.is_empty());
+        assert_eq!(diff.to_string(), "no changes");
+
+        // Neither the caches nor the order within a column count.
+        let mut shuffled = data.clone();
+        let range = shuffled.column_range(2, 2).unwrap();
+        shuffled.voxels[range].reverse();
+        shuffled.voxels[0].visibility = 0;
+        assert!(Kv6Diff::compute(&data, &shuffled).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_diff_edits() {
+        let data = grenade();
+        let mut edited = data.clone();
+        let removed = edited.remove_voxel(2, 2, 1).unwrap();
+        edited.set_voxel(0, 0, 4, Rgb::new(1, 2, 3)).unwrap();
+        let before = edited.get_voxel(5, 2, 4).unwrap().color();
+        edited.set_voxel(5, 2, 4, Rgb::new(4, 5, 6)).unwrap();
+        // Setting a voxel to its own color isn't a change.
+        let (x, y, z, voxel) = edited.iter_with_coords().unwrap().nth(3).unwrap();
+        let same = voxel.color();
+        edited.set_voxel(x, y, z, same).unwrap();
+
+        let diff = Kv6Diff::compute(&data, &edited).unwrap();
+        assert_eq!(
+            diff.removed
+                .iter()
+                .map(|(&k, &v)| (k, v))
+                .collect::<Vec<_>>(),
+            [((2, 2, 1), removed)]
+        );
+        assert_eq!(diff.added.keys().collect::<Vec<_>>(), [&(0, 0, 4)]);
+        assert_eq!(diff.added[&(0, 0, 4)].color(), Rgb::new(1, 2, 3));
+        assert_eq!(diff.recolored.len(), 1);
+        assert_eq!(diff.recolored[&(5, 2, 4)], (before, Rgb::new(4, 5, 6)));
+
+        assert_eq!(diff.voxel_changes(), 3);
+        assert_eq!(diff.to_string(), "1 added, 1 removed, 1 recolored");
+
+        // The other way round swaps additions and removals.
+        let back = Kv6Diff::compute(&edited, &data).unwrap();
+        assert_eq!((back.added.len(), back.removed.len()), (1, 1));
+        assert!(back.added.contains_key(&(2, 2, 1)));
+        assert_eq!(back.recolored[&(5, 2, 4)], (Rgb::new(4, 5, 6), before));
+    }
+
+    #[test]
+    fn test_diff_header() {
+        let data = grenade();
+        let mut moved = data.clone();
+        moved.x_pivot += 1.0;
+        let diff = Kv6Diff::compute(&data, &moved).unwrap();
+        assert!(!diff.is_empty() && diff.pivot_changed && !diff.dimensions_changed);
+        assert_eq!(
+            diff.to_string(),
+            "0 added, 0 removed, 0 recolored, pivot changed"
+        );
+
+        moved.z_size += 1;
+        let diff = Kv6Diff::compute(&data, &moved).unwrap();
+        assert!(diff.dimensions_changed);
+
+        let mut broken = data.clone();
+        broken.voxels.pop();
+        assert!(Kv6Diff::compute(&data, &broken).is_err());
+    }
+
+    /// The grenade with a few voxels removed, added and recolored.
+    fn edited() -> KV6Format {
+        let mut edited = grenade();
+        edited.remove_voxel(2, 2, 1).unwrap();
+        edited.remove_voxel(3, 3, 8).unwrap();
+        edited.set_voxel(0, 0, 4, Rgb::new(1, 2, 3)).unwrap();
+        edited.set_voxel(0, 0, 5, Rgb::new(1, 2, 3)).unwrap();
+        edited.set_voxel(5, 2, 4, Rgb::new(4, 5, 6)).unwrap();
+        edited
+    }
+
+    #[test]
+    fn test_apply_round_trip() {
+        let (a, b) = (grenade(), edited());
+        let diff = Kv6Diff::compute(&a, &b).unwrap();
+        let mut patched = a.clone();
+        patched.apply_diff(&diff).unwrap();
+        assert!(patched.equivalent(&b));
+        assert!(patched.check_caches().is_ok());
+        assert!(Kv6Diff::compute(&patched, &b).unwrap().is_empty());
+
+        // And back again.
+        patched
+            .apply_diff(&Kv6Diff::compute(&b, &a).unwrap())
+            .unwrap();
+        assert!(patched.equivalent(&a));
+    }
+
+    #[test]
+    fn test_apply_conflicts() {
+        let (a, b) = (grenade(), edited());
+        let diff = Kv6Diff::compute(&a, &b).unwrap();
+
+        // Applying twice: the removals are gone and the additions are there.
+        let mut twice = b.clone();
+        assert_eq!(
+            twice.apply_diff(&diff),
+            Err(PatchError::Missing { x: 2, y: 2, z: 1 })
+        );
+        assert_eq!(twice, b);
+        let mut removals_done = diff.clone();
+        removals_done.removed.clear();
+        assert_eq!(
+            twice.apply_diff(&removals_done),
+            Err(PatchError::Occupied { x: 0, y: 0, z: 4 })
+        );
+
+        // Forcing overwrites the additions and skips missing removals.
+        let force = PatchOptions { force: true };
+        twice.apply_diff_with(&diff, &force).unwrap();
+        assert!(twice.equivalent(&b));
+
+        let mut recolor_missing = Kv6Diff::default();
+        recolor_missing
+            .recolored
+            .insert((0, 0, 0), (Rgb::default(), Rgb::default()));
+        let mut data = a.clone();
+        assert_eq!(
+            data.apply_diff_with(&recolor_missing, &force),
+            Err(PatchError::Missing { x: 0, y: 0, z: 0 })
+        );
+
+        let mut outside = Kv6Diff::default();
+        outside.added.insert((6, 0, 0), Default::default());
+        assert_eq!(
+            data.apply_diff(&outside),
+            Err(PatchError::OutOfBounds { x: 6, y: 0, z: 0 })
+        );
+
+        let mut bigger = a.clone();
+        bigger.z_size += 1;
+        assert_eq!(
+            data.apply_diff(&Kv6Diff::compute(&a, &bigger).unwrap()),
+            Err(PatchError::DimensionsChanged)
+        );
+        assert_eq!(data, a);
+    }
+
+    #[test]
+    fn test_apply_full_column() {
+        let mut full = KV6Format {
+            x_size: 1,
+            y_size: 1,
+            z_size: 1 << 16,
+            xlen: vec![u16::MAX as u32],
+            ylen: vec![vec![u16::MAX]],
+            ..Default::default()
+        };
+        full.voxels = (0..u16::MAX)
+            .map(|height| VoxelData {
+                height,
+                ..Default::default()
+            })
+            .collect();
+
+        let mut add = Kv6Diff::default();
+        add.added.insert((0, 0, u16::MAX), Default::default());
+        let mut data = full.clone();
+        assert_eq!(
+            data.apply_diff(&add),
+            Err(PatchError::ColumnFull { x: 0, y: 0 })
+        );
+        assert_eq!(data, full);
+
+        // Making room first lets the same addition through.
+        add.removed.insert((0, 0, 0), Default::default());
+        data.apply_diff(&add).unwrap();
+        assert_eq!(data.ylen, [[u16::MAX]]);
+        assert!(data.get_voxel(0, 0, u16::MAX).is_some());
+        assert!(data.get_voxel(0, 0, 0).is_none());
+    }
+}
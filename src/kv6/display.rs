@@ -0,0 +1,108 @@
+use super::KV6Format;
+use alloc::{string::String, vec};
+use core::fmt;
+
+/// A one-line summary; use `{:?}` to see every voxel.
+impl fmt::Display for KV6Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}x{}x{} KV6 model with {} voxels, pivot ({}, {}, {}), {}",
+            self.x_size,
+            self.y_size,
+            self.z_size,
+            self.voxels.len(),
+            self.x_pivot,
+            self.y_pivot,
+            self.z_pivot,
+            match self.palette {
+                Some(_) => "with a palette",
+                None => "no palette",
+            }
+        )
+    }
+}
+
+impl KV6Format {
+    /// Draws layer `z` as text: a line per y, a character per x, `#` where a
+    /// voxel is stored and `.` elsewhere. The solid interior KV6 leaves out
+    /// shows up as `.` too.
+    ///
+    /// Columns are found by walking `ylen`, so with caches that don't match
+    /// the voxels the picture is wrong, but this never panics.
+    pub fn dump_slice(&self, z: u16) -> String {
+        let (width, height) = (self.x_size as usize, self.y_size as usize);
+        let mut cells = vec![false; width * height];
+
+        let mut voxels = self.voxels.iter();
+        for (x, row) in self.ylen.iter().enumerate().take(width) {
+            for (y, &count) in row.iter().enumerate() {
+                for voxel in voxels.by_ref().take(count as usize) {
+                    if voxel.height == z && y < height {
+                        cells[y * width + x] = true;
+                    }
+                }
+            }
+        }
+
+        let mut out = String::with_capacity((width + 1) * height);
+        for y in 0..height {
+            let row = &cells[y * width..(y + 1) * width];
+            out.extend(row.iter().map(|&set| if set { '#' } else { '.' }));
+            out.push('\n');
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kv6::testing::grenade;
+    use crate::kv6::KV6Format;
+    use alloc::{format, string::ToString};
+
+    #[test]
+    fn test_display() {
+        let mut data = grenade();
+        assert_eq!(
+            data.to_string(),
+            "6x6x9 KV6 model with 74 voxels, pivot (2.5, 2.5, 3.5), with a palette"
+        );
+        data.palette = None;
+        data.x_pivot = -0.25;
+        assert_eq!(
+            format!("{}", data),
+            "6x6x9 KV6 model with 74 voxels, pivot (-0.25, 2.5, 3.5), no palette"
+        );
+    }
+
+    #[test]
+    fn test_dump_slice() {
+        let data = grenade();
+        assert_eq!(
+            data.dump_slice(0),
+            "......\n\
+             ......\n\
+             ...#..\n\
+             ...#..\n\
+             ......\n\
+             ......\n"
+        );
+        assert_eq!(
+            data.dump_slice(3),
+            "..##..\n\
+             .#..#.\n\
+             #....#\n\
+             #....#\n\
+             .#..#.\n\
+             ..##..\n"
+        );
+        assert_eq!(data.dump_slice(9), "......\n".repeat(6));
+
+        let mut broken = data;
+        broken.ylen[5][5] = 1000;
+        broken.ylen.pop();
+        assert_eq!(broken.dump_slice(3).lines().count(), 6);
+        assert_eq!(KV6Format::default().dump_slice(0), "");
+    }
+}
@@ -0,0 +1,146 @@
+use super::{KV6Format, Palette, ParseOptions, ParseWarning};
+use crate::Kv6Error;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// A model together with its palette and where it came from.
+///
+/// The palette lives here rather than in `model.palette`, which
+/// [`Kv6Document::open`] leaves as `None`; [`Kv6Document::save`] writes this
+/// one. Everything on [`KV6Format`] still works on `model` directly.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Kv6Document {
+    pub model: KV6Format,
+    pub palette: Option<Palette>,
+    /// What the parser tolerated while reading the file.
+    pub warnings: Vec<ParseWarning>,
+    /// The file the document was opened from or last saved to.
+    pub path: Option<PathBuf>,
+}
+
+impl Kv6Document {
+    /// Wraps a model, moving its palette into the document.
+    pub fn new(mut model: KV6Format) -> Self {
+        Kv6Document {
+            palette: model.palette.take(),
+            model,
+            warnings: Vec::new(),
+            path: None,
+        }
+    }
+
+    /// Reads a model with the default [`ParseOptions`], keeping the warnings.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Kv6Error> {
+        let path = path.as_ref();
+        let read = || -> Result<Self, Kv6Error> {
+            let bytes = fs::read(path)?;
+            let (model, warnings) =
+                KV6Format::parse_with_warnings(&bytes, &ParseOptions::default())?;
+            Ok(Kv6Document {
+                warnings,
+                path: Some(path.to_path_buf()),
+                ..Kv6Document::new(model)
+            })
+        };
+
+        read().map_err(|err| err.with_path(path))
+    }
+
+    /// Writes the model with the document's palette to `path`, which becomes
+    /// the document's path.
+    pub fn save<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Kv6Error> {
+        let path = path.as_ref();
+        self.to_kv6().save_to_path(path)?;
+        self.path = Some(path.to_path_buf());
+        Ok(())
+    }
+
+    /// The model with the palette put back, as it would be written.
+    pub fn to_kv6(&self) -> KV6Format {
+        KV6Format {
+            palette: self.palette,
+            ..self.model.clone()
+        }
+    }
+
+    /// The palette index closest to each voxel's color, in storage order.
+    /// `None` without a palette.
+    pub fn quantize_to_palette(&self) -> Option<Vec<u8>> {
+        let palette = self.palette.as_ref()?;
+        Some(
+            self.model
+                .colors()
+                .map(|color| palette.closest(color))
+                .collect(),
+        )
+    }
+
+    /// Colors each voxel with the palette entry `indices` gives for it, the
+    /// reverse of [`Kv6Document::quantize_to_palette`]. Voxels past the end of
+    /// `indices` are left alone, as is everything without a palette.
+    pub fn expand_palette_colors(&mut self, indices: &[u8]) {
+        let Some(palette) = &self.palette else {
+            return;
+        };
+        for (voxel, &index) in self.model.voxels.iter_mut().zip(indices) {
+            voxel.set_color(palette.color(index));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Kv6Document;
+    use crate::kv6::{KV6Format, Rgb};
+    use std::{env, fs, process};
+
+    #[test]
+    fn test_palette_round_trip() {
+        let document = Kv6Document::open("data/grenade.kv6").unwrap();
+        assert!(document.palette.is_some());
+        assert!(document.model.palette.is_none());
+        assert!(document.warnings.is_empty());
+        assert_eq!(document.path.as_deref(), Some("data/grenade.kv6".as_ref()));
+        assert_eq!(
+            document.to_kv6(),
+            KV6Format::from_path("data/grenade.kv6").unwrap()
+        );
+
+        let path = env::temp_dir().join(format!("kv6-document-{}.kv6", process::id()));
+        let mut copy = document.clone();
+        copy.palette.as_mut().unwrap().entries[3] = [1, 2, 3];
+        copy.save(&path).unwrap();
+        assert_eq!(copy.path.as_deref(), Some(path.as_ref()));
+
+        let reopened = Kv6Document::open(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(reopened.palette, copy.palette);
+        assert_eq!(reopened.model, document.model);
+
+        let err = Kv6Document::open("data/missing.kv6").unwrap_err();
+        assert_eq!(err.path(), Some("data/missing.kv6".as_ref()));
+    }
+
+    #[test]
+    fn test_palette_indices() {
+        let mut document = Kv6Document::open("data/grenade.kv6").unwrap();
+        let indices = document.quantize_to_palette().unwrap();
+        assert_eq!(indices.len(), 74);
+
+        document.expand_palette_colors(&indices);
+        let palette = document.palette.unwrap();
+        for (voxel, &index) in document.model.voxels.iter().zip(&indices) {
+            assert_eq!(voxel.color(), palette.color(index));
+        }
+        // Snapping is idempotent.
+        assert_eq!(document.quantize_to_palette().unwrap(), indices);
+
+        let mut bare = Kv6Document::new(KV6Format::default());
+        assert_eq!(bare.quantize_to_palette(), None);
+        bare.model.voxels.push(Default::default());
+        bare.expand_palette_colors(&[5]);
+        assert_eq!(bare.model.voxels[0].color(), Rgb::default());
+    }
+}
@@ -0,0 +1,564 @@
+use super::{
+    normals::{closest_index, NormalTable},
+    CacheMismatch, Face, KV6Format, Rgb, VoxelData,
+};
+use crate::Kv6Error;
+use alloc::{vec, vec::Vec};
+use core::ops::Range;
+
+/// What [`KV6Format::set_voxel`] did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetResult {
+    /// There was no voxel at the position, so one was inserted.
+    Created,
+    /// The existing voxel was recolored.
+    Updated,
+}
+
+impl KV6Format {
+    /// Adds a voxel at `(x, y, z)`, keeping its column sorted by height and
+    /// `xlen`/`ylen` in step with `voxels`.
+    ///
+    /// `data.height` is replaced by `z`. Visibility is fixed up locally rather
+    /// than left stale: the new voxel's face bits are set from its six
+    /// neighbours, and each neighbour loses the face that now touches it.
+    /// Only stored voxels count as neighbours, so a face next to an interior
+    /// cell the file doesn't store is marked visible. The two unused bits of
+    /// `data.visibility` are kept.
+    ///
+    /// Fails if the position is outside the grid or already has a voxel. Like
+    /// [`KV6Format::column`] this trusts the caches rather than checking them
+    /// in full on every call; caches that don't even cover `voxels` are
+    /// reported as [`Kv6Error::InconsistentSizes`].
+    pub fn insert_voxel(
+        &mut self,
+        x: u32,
+        y: u32,
+        z: u16,
+        data: VoxelData,
+    ) -> Result<(), Kv6Error> {
+        let range = self.edit_range(x, y, z)?;
+        let column = &self.voxels[range.clone()];
+        let offset = match column.binary_search_by_key(&z, |voxel| voxel.height) {
+            Ok(_) => return Err(Kv6Error::Occupied { x, y, z }),
+            Err(offset) => offset,
+        };
+
+        let (xi, yi) = (x as usize, y as usize);
+        let count = self.ylen[xi][yi].checked_add(1).ok_or(Kv6Error::TooLarge {
+            field: "column",
+            value: column.len() + 1,
+        })?;
+        let row = self.xlen[xi].checked_add(1).ok_or(Kv6Error::TooLarge {
+            field: "xlen",
+            value: self.xlen[xi] as usize + 1,
+        })?;
+
+        let mut voxel = VoxelData { height: z, ..data };
+        for face in Face::ALL {
+            let neighbour = face
+                .neighbour(x, y, z)
+                .and_then(|(nx, ny, nz)| self.get_voxel_mut(nx, ny, nz));
+            match neighbour {
+                Some(neighbour) => {
+                    neighbour.hide_face(face.opposite());
+                    voxel.hide_face(face);
+                }
+                None => voxel.show_face(face),
+            }
+        }
+
+        self.voxels.insert(range.start + offset, voxel);
+        self.ylen[xi][yi] = count;
+        self.xlen[xi] = row;
+        Ok(())
+    }
+
+    /// Removes the voxel at `(x, y, z)` and returns it, or `None` if there's
+    /// no voxel there.
+    ///
+    /// The counterpart of [`KV6Format::insert_voxel`]: the caches are updated
+    /// in place, and each stored neighbour gets back the face that touched
+    /// the removed voxel. Caches whose counts for the column can't go down
+    /// also give `None`, leaving the model as it was.
+    pub fn remove_voxel(&mut self, x: u32, y: u32, z: u16) -> Option<VoxelData> {
+        let range = self.edit_range(x, y, z).ok()?;
+        let offset = self.voxels[range.clone()]
+            .binary_search_by_key(&z, |voxel| voxel.height)
+            .ok()?;
+
+        let (xi, yi) = (x as usize, y as usize);
+        let count = self.ylen[xi][yi].checked_sub(1)?;
+        let row = self.xlen[xi].checked_sub(1)?;
+
+        let removed = self.voxels.remove(range.start + offset);
+        self.ylen[xi][yi] = count;
+        self.xlen[xi] = row;
+
+        for face in Face::ALL {
+            if let Some(neighbour) = face
+                .neighbour(x, y, z)
+                .and_then(|(nx, ny, nz)| self.get_voxel_mut(nx, ny, nz))
+            {
+                neighbour.show_face(face.opposite());
+            }
+        }
+        Some(removed)
+    }
+
+    /// Makes `(x, y, z)` a voxel of `color`, creating it if needed.
+    ///
+    /// An existing voxel only has its color changed. A new one gets the
+    /// standard dummy byte, a normal facing up (quantized against
+    /// [`NormalTable::Slab6`]) and starts with every face visible before
+    /// [`KV6Format::insert_voxel`] hides the ones with neighbours. Each call
+    /// costs a column lookup plus six neighbour lookups, never a cache
+    /// rebuild, so it's fine to call once per voxel of a brush stroke.
+    pub fn set_voxel(&mut self, x: u32, y: u32, z: u16, color: Rgb) -> Result<SetResult, Kv6Error> {
+        if let Some(voxel) = self.get_voxel_mut(x, y, z) {
+            voxel.set_color(color);
+            return Ok(SetResult::Updated);
+        }
+
+        let mut voxel = VoxelData {
+            dummy: VoxelData::DUMMY,
+            visibility: 0x3f,
+            // slab6's normals point into the solid, so "up" is +z.
+            normalindex: closest_index([0.0, 0.0, 1.0], NormalTable::Slab6),
+            ..Default::default()
+        };
+        voxel.set_color(color);
+        self.insert_voxel(x, y, z, voxel)?;
+        Ok(SetResult::Created)
+    }
+
+    /// Rebuilds `xlen` and `ylen` from `voxels`, leaving everything else
+    /// alone, given the `(x, y)` column of each voxel in order.
+    ///
+    /// Voxels don't record their column, so it has to come from somewhere,
+    /// such as [`KV6Format::iter_with_coords`] before the voxels were edited.
+    /// Voxels must be grouped by column, with columns in storage order, x
+    /// then y; columns with no voxels are simply skipped. Fails if there
+    /// isn't exactly one column per voxel, a column is outside the grid or
+    /// comes before the one ahead of it, or a column would pass 65535
+    /// voxels. On error the caches are unchanged.
+    pub fn recompute_caches(
+        &mut self,
+        columns: impl IntoIterator<Item = (u32, u32)>,
+    ) -> Result<(), Kv6Error> {
+        (self.x_size as usize)
+            .checked_mul(self.y_size as usize)
+            .ok_or(Kv6Error::CacheOverflow {
+                x_size: self.x_size,
+                y_size: self.y_size,
+            })?;
+
+        let mut ylen = vec![vec![0u16; self.y_size as usize]; self.x_size as usize];
+        let mut given = 0;
+        let mut previous: Option<(u32, u32)> = None;
+        for (x, y) in columns {
+            given += 1;
+            // Surplus columns are only counted, for the error below.
+            let Some(voxel) = self.voxels.get(given - 1) else {
+                continue;
+            };
+            if x >= self.x_size || y >= self.y_size {
+                return Err(Kv6Error::OutOfBounds {
+                    x,
+                    y,
+                    z: voxel.height,
+                });
+            }
+            if previous.is_some_and(|previous| (x, y) < previous) {
+                return Err(Kv6Error::ColumnOrder {
+                    expected: previous,
+                    found: Some((x, y)),
+                });
+            }
+            previous = Some((x, y));
+            let count = &mut ylen[x as usize][y as usize];
+            *count = count.checked_add(1).ok_or(Kv6Error::TooLarge {
+                field: "column",
+                value: *count as usize + 1,
+            })?;
+        }
+        if given != self.voxels.len() {
+            return Err(Kv6Error::ColumnCount {
+                columns: given,
+                voxels: self.voxels.len(),
+            });
+        }
+
+        self.xlen = ylen
+            .iter()
+            .map(|row| row.iter().map(|&n| n as u32).sum())
+            .collect();
+        self.ylen = ylen;
+        Ok(())
+    }
+
+    /// Sorts every column by height, the order Voxlap expects, using the
+    /// caches to find where columns start.
+    ///
+    /// Voxels at the same height stay in the order they were in, and a model
+    /// that's already sorted is left exactly as it was. Fails without
+    /// changing anything if the caches don't match the voxels.
+    pub fn canonicalize(&mut self) -> Result<(), CacheMismatch> {
+        self.check_caches()?;
+        let mut start = 0;
+        for &count in self.ylen.iter().flatten() {
+            let end = start + count as usize;
+            self.voxels[start..end].sort_by_key(|voxel| voxel.height);
+            start = end;
+        }
+        Ok(())
+    }
+
+    /// Replaces the grid with one of `size` holding `cells`, which must be in
+    /// range and in column order, and builds caches to match. Pivots and the
+    /// rest are left alone. On error nothing is changed.
+    pub(crate) fn set_columns(
+        &mut self,
+        (x_size, y_size, z_size): (u32, u32, u32),
+        cells: impl IntoIterator<Item = (u32, u32, VoxelData)>,
+    ) -> Result<(), Kv6Error> {
+        (x_size as usize)
+            .checked_mul(y_size as usize)
+            .ok_or(Kv6Error::CacheOverflow { x_size, y_size })?;
+        let mut ylen = vec![vec![0u16; y_size as usize]; x_size as usize];
+        let mut voxels = Vec::new();
+        for (x, y, voxel) in cells {
+            let count = &mut ylen[x as usize][y as usize];
+            *count = count.checked_add(1).ok_or(Kv6Error::TooLarge {
+                field: "column",
+                value: *count as usize + 1,
+            })?;
+            voxels.push(voxel);
+        }
+
+        (self.x_size, self.y_size, self.z_size) = (x_size, y_size, z_size);
+        self.xlen = ylen
+            .iter()
+            .map(|row| row.iter().map(|&n| n as u32).sum())
+            .collect();
+        self.ylen = ylen;
+        self.voxels = voxels;
+        Ok(())
+    }
+
+    /// The range of column `(x, y)` for an edit at `(x, y, z)`, after
+    /// checking the position is in the grid and the caches cover `voxels`.
+    fn edit_range(&self, x: u32, y: u32, z: u16) -> Result<Range<usize>, Kv6Error> {
+        if x >= self.x_size || y >= self.y_size || z as u32 >= self.z_size {
+            return Err(Kv6Error::OutOfBounds { x, y, z });
+        }
+        match self.column_range(x, y) {
+            Some(range) if range.end <= self.voxels.len() => Ok(range),
+            _ => Err(Kv6Error::InconsistentSizes(self.check_caches().expect_err(
+                "caches that don't cover the voxels are inconsistent",
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SetResult;
+    use crate::kv6::testing::{empty, grenade};
+    use crate::kv6::{Face, KV6Format, Rgb, VoxelData};
+    use crate::Kv6Error;
+    use alloc::{vec, vec::Vec};
+
+    #[test]
+    fn test_insert_round_trip() {
+        let mut data = grenade();
+        assert!(data.column(0, 0).unwrap().is_empty());
+
+        let voxel = VoxelData {
+            red: 200,
+            dummy: VoxelData::DUMMY,
+            visibility: 0xc0,
+            ..Default::default()
+        };
+        for z in [4, 0, 8, 2] {
+            data.insert_voxel(0, 0, z, voxel).unwrap();
+        }
+        data.insert_voxel(5, 5, 0, voxel).unwrap();
+        data.check_caches().unwrap();
+
+        let heights: Vec<u16> = data
+            .column(0, 0)
+            .unwrap()
+            .iter()
+            .map(|v| v.height)
+            .collect();
+        assert_eq!(heights, [0, 2, 4, 8]);
+        assert_eq!(data.xlen[0], grenade().xlen[0] + 4);
+        assert_eq!(data.ylen[0][0], 4);
+        assert_eq!(data.voxels.len(), 79);
+        assert_eq!(data.get_voxel(0, 0, 2).unwrap().visibility, 0xc0 | 0x3f);
+
+        let (parsed, _) = KV6Format::parse(&data.to_bytes().unwrap()).unwrap();
+        parsed.check_caches().unwrap();
+        assert_eq!(parsed, data);
+    }
+
+    #[test]
+    fn test_insert_visibility() {
+        let mut data = empty(3, 3, 3);
+        data.insert_voxel(1, 1, 1, VoxelData::default()).unwrap();
+        assert_eq!(data.get_voxel(1, 1, 1).unwrap().visibility, 0x3f);
+
+        data.insert_voxel(1, 1, 0, VoxelData::default()).unwrap();
+        data.insert_voxel(2, 1, 1, VoxelData::default()).unwrap();
+        let center = data.get_voxel(1, 1, 1).unwrap();
+        assert!(!center.is_face_visible(Face::Top));
+        assert!(!center.is_face_visible(Face::Right));
+        assert_eq!(center.visible_faces().count(), 4);
+        assert!(!data
+            .get_voxel(1, 1, 0)
+            .unwrap()
+            .is_face_visible(Face::Bottom));
+        assert!(!data.get_voxel(2, 1, 1).unwrap().is_face_visible(Face::Left));
+        assert_eq!(data.get_voxel(2, 1, 1).unwrap().visible_faces().count(), 5);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut data = empty(3, 3, 3);
+        assert_eq!(data.remove_voxel(0, 0, 0), None);
+        assert_eq!(data.remove_voxel(3, 0, 0), None);
+
+        let voxel = VoxelData {
+            red: 7,
+            ..Default::default()
+        };
+        data.insert_voxel(1, 1, 1, voxel).unwrap();
+        data.insert_voxel(1, 1, 2, VoxelData::default()).unwrap();
+        data.insert_voxel(0, 1, 1, VoxelData::default()).unwrap();
+
+        let removed = data.remove_voxel(1, 1, 1).unwrap();
+        assert_eq!((removed.red, removed.height), (7, 1));
+        assert_eq!(data.remove_voxel(1, 1, 1), None);
+        assert!(data.get_voxel(1, 1, 2).unwrap().is_face_visible(Face::Top));
+        assert_eq!(data.get_voxel(0, 1, 1).unwrap().visibility, 0x3f);
+        data.check_caches().unwrap();
+
+        // Emptying the model column by column.
+        data.remove_voxel(1, 1, 2).unwrap();
+        assert_eq!(data.column(1, 1), Some(&[][..]));
+        data.remove_voxel(0, 1, 1).unwrap();
+        assert_eq!(data, empty(3, 3, 3));
+        assert_eq!(data.remove_voxel(0, 1, 1), None);
+    }
+
+    #[test]
+    fn test_remove_inconsistent_caches() {
+        // The column says it has a voxel but the row total doesn't.
+        let mut data = empty(2, 2, 2);
+        data.insert_voxel(0, 1, 0, VoxelData::default()).unwrap();
+        data.xlen[0] = 0;
+        let before = data.clone();
+        assert_eq!(data.remove_voxel(0, 1, 0), None);
+        assert_eq!(data, before);
+    }
+
+    #[test]
+    fn test_remove_batch_round_trip() {
+        let mut data = grenade();
+        let positions: Vec<(u32, u32, u16)> = data
+            .iter_with_coords()
+            .unwrap()
+            .map(|(x, y, z, _)| (x, y, z))
+            .step_by(3)
+            .collect();
+        for &(x, y, z) in &positions {
+            assert_eq!(data.remove_voxel(x, y, z).unwrap().height, z);
+        }
+        data.check_caches().unwrap();
+        assert_eq!(data.voxels.len(), 74 - positions.len());
+
+        let (parsed, _) = KV6Format::parse(&data.to_bytes().unwrap()).unwrap();
+        assert_eq!(parsed, data);
+        for (x, y, z) in positions {
+            assert!(parsed.get_voxel(x, y, z).is_none());
+        }
+    }
+
+    #[test]
+    fn test_paint_cube() {
+        let mut data = empty(4, 4, 4);
+        let red = Rgb::new(255, 0, 0);
+        for x in 0..3 {
+            for y in 0..3 {
+                for z in 0..3 {
+                    assert_eq!(data.set_voxel(x, y, z, red).unwrap(), SetResult::Created);
+                }
+            }
+        }
+        let blue = Rgb::new(0, 0, 255);
+        assert_eq!(data.set_voxel(1, 1, 1, blue).unwrap(), SetResult::Updated);
+        assert!(matches!(
+            data.set_voxel(4, 0, 0, blue),
+            Err(Kv6Error::OutOfBounds { .. })
+        ));
+
+        let (parsed, warnings) =
+            KV6Format::parse_with_warnings(&data.to_bytes().unwrap(), &Default::default()).unwrap();
+        assert!(warnings.is_empty());
+        parsed.check_caches().unwrap();
+        assert_eq!(parsed, data);
+        assert_eq!(parsed.voxels.len(), 27);
+
+        let center = parsed.get_voxel(1, 1, 1).unwrap();
+        assert_eq!((center.color(), center.visibility), (blue, 0));
+        let corner = parsed.get_voxel(0, 0, 0).unwrap();
+        assert_eq!(corner.color(), red);
+        assert_eq!(corner.visible_faces().count(), 3);
+        assert!(corner.normal()[2] > 0.99);
+        for x in 0..3 {
+            for y in 0..3 {
+                assert_eq!(parsed.ylen[x][y], 3);
+            }
+        }
+    }
+
+    #[test]
+    fn test_recompute_caches() {
+        // The grenade has empty columns, so the layout can't be guessed from
+        // the heights alone.
+        let original = grenade();
+        let columns: Vec<_> = original
+            .iter_with_coords()
+            .unwrap()
+            .map(|(x, y, _, _)| (x, y))
+            .collect();
+        let mut zeroed = original.clone();
+        zeroed.xlen.fill(0);
+        zeroed.ylen.iter_mut().for_each(|row| row.fill(0));
+        zeroed.recompute_caches(columns.iter().copied()).unwrap();
+        assert_eq!(zeroed.xlen, original.xlen);
+        assert_eq!(zeroed.ylen, original.ylen);
+        assert_eq!(zeroed, original);
+        assert!(original.ylen.iter().flatten().any(|&n| n == 0));
+
+        // Neighbouring columns whose heights run on from each other.
+        let mut data = empty(1, 2, 4);
+        data.set_voxel(0, 0, 0, Rgb::new(1, 0, 0)).unwrap();
+        data.set_voxel(0, 1, 1, Rgb::new(2, 0, 0)).unwrap();
+        let mut zeroed = data.clone();
+        zeroed.ylen = vec![vec![0; 2]];
+        zeroed.xlen = vec![0];
+        zeroed.recompute_caches([(0, 0), (0, 1)]).unwrap();
+        assert_eq!(zeroed, data);
+
+        let mut cleared = empty(2, 0, 1);
+        cleared.xlen.clear();
+        cleared.recompute_caches([]).unwrap();
+        assert_eq!(cleared, empty(2, 0, 1));
+    }
+
+    #[test]
+    fn test_recompute_caches_errors() {
+        let columns: Vec<_> = grenade()
+            .iter_with_coords()
+            .unwrap()
+            .map(|(x, y, _, _)| (x, y))
+            .collect();
+        let mut data = grenade();
+        data.xlen.fill(0);
+        assert!(matches!(
+            data.recompute_caches(columns[1..].iter().copied()),
+            Err(Kv6Error::ColumnCount {
+                columns: 73,
+                voxels: 74
+            })
+        ));
+        assert!(matches!(
+            data.recompute_caches(columns.iter().copied().chain([(5, 5)])),
+            Err(Kv6Error::ColumnCount {
+                columns: 75,
+                voxels: 74
+            })
+        ));
+        assert!(matches!(
+            data.recompute_caches(columns.iter().rev().copied()),
+            Err(Kv6Error::ColumnOrder { .. })
+        ));
+        assert!(matches!(
+            data.recompute_caches(columns.iter().map(|&(x, y)| (x + 6, y))),
+            Err(Kv6Error::OutOfBounds { x: 6, .. })
+        ));
+        assert!(data.xlen.iter().all(|&n| n == 0));
+        assert_eq!(data.ylen, grenade().ylen);
+
+        let mut crowded = empty(1, 1, 4);
+        crowded.voxels = vec![VoxelData::default(); 3];
+        crowded.recompute_caches([(0, 0); 3]).unwrap();
+        assert_eq!(crowded.ylen, [[3]]);
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        let bytes = include_bytes!("../../data/grenade.kv6");
+        let mut data = grenade();
+        data.canonicalize().unwrap();
+        assert_eq!(data.to_bytes().unwrap(), bytes);
+
+        // Reverse every column, and give one voxel a twin at the same height
+        // to check ties keep their order.
+        let mut shuffled = grenade();
+        let mut start = 0;
+        for &count in shuffled.ylen.iter().flatten() {
+            shuffled.voxels[start..start + count as usize].reverse();
+            start += count as usize;
+        }
+        assert_ne!(shuffled, data);
+        shuffled.canonicalize().unwrap();
+        assert_eq!(shuffled, data);
+
+        let mut tied = empty(1, 1, 4);
+        let voxel = |height, red| VoxelData {
+            height,
+            red,
+            ..Default::default()
+        };
+        tied.voxels = vec![voxel(3, 0), voxel(1, 1), voxel(3, 2), voxel(1, 3)];
+        tied.ylen[0][0] = 4;
+        tied.xlen[0] = 4;
+        tied.canonicalize().unwrap();
+        let order: Vec<u8> = tied.voxels.iter().map(|v| v.red).collect();
+        assert_eq!(order, [1, 3, 0, 2]);
+
+        tied.xlen[0] = 3;
+        assert!(tied.canonicalize().is_err());
+    }
+
+    #[test]
+    fn test_insert_rejects() {
+        let mut data = grenade();
+        let (x, y, z) = data
+            .iter_with_coords()
+            .unwrap()
+            .map(|c| (c.0, c.1, c.2))
+            .next()
+            .unwrap();
+        assert!(matches!(
+            data.insert_voxel(x, y, z, VoxelData::default()),
+            Err(Kv6Error::Occupied { .. })
+        ));
+        for (x, y, z) in [(6, 0, 0), (0, 6, 0), (0, 0, 9)] {
+            assert!(matches!(
+                data.insert_voxel(x, y, z, VoxelData::default()),
+                Err(Kv6Error::OutOfBounds { .. })
+            ));
+        }
+        assert_eq!(data, grenade());
+
+        data.voxels.clear();
+        assert!(matches!(
+            data.insert_voxel(5, 5, 0, VoxelData::default()),
+            Err(Kv6Error::InconsistentSizes(_))
+        ));
+    }
+}
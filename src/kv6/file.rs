@@ -0,0 +1,55 @@
+use super::{KV6Format, ParseOptions};
+use crate::Kv6Error;
+use std::{fs, path::Path};
+
+impl KV6Format {
+    /// Reads and parses a model from a file, using the default [`ParseOptions`].
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Kv6Error> {
+        let path = path.as_ref();
+        let read = || -> Result<Self, Kv6Error> {
+            let bytes = fs::read(path)?;
+            KV6Format::parse_with(&bytes, &ParseOptions::default())
+        };
+
+        read().map_err(|err| err.with_path(path))
+    }
+
+    /// Serializes the model and writes it to a file, replacing its contents.
+    pub fn save_to_path<P: AsRef<Path>>(&self, path: P) -> Result<(), Kv6Error> {
+        let path = path.as_ref();
+        let write = || -> Result<(), Kv6Error> {
+            fs::write(path, self.to_bytes()?)?;
+            Ok(())
+        };
+
+        write().map_err(|err| err.with_path(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{kv6::KV6Format, Kv6Error};
+    use std::{env, fs, path::Path, process};
+
+    #[test]
+    fn test_save_to_path() {
+        let data = KV6Format::from_path("data/grenade.kv6").unwrap();
+
+        let path = env::temp_dir().join(format!("kv6-save-{}.kv6", process::id()));
+        data.save_to_path(&path).unwrap();
+        let written = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(written, fs::read("data/grenade.kv6").unwrap());
+    }
+
+    #[test]
+    fn test_from_missing_path() {
+        let err = KV6Format::from_path("data/missing.kv6").unwrap_err();
+        assert_eq!(err.path(), Some(Path::new("data/missing.kv6")));
+        match err {
+            Kv6Error::Path { source, .. } => assert!(matches!(*source, Kv6Error::Io(_))),
+            _ => panic!("expected a path error, got {:?}", err),
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use super::{CacheMismatch, KV6Format};
+use alloc::vec::Vec;
+
+/// 64-bit FNV-1a, which is simple enough to pin down in documentation.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+}
+
+impl KV6Format {
+    /// A hash of what the model looks like, for spotting duplicates.
+    ///
+    /// This is the 64-bit FNV-1a hash of, all little endian:
+    ///
+    /// 1. `x_size`, `y_size` and `z_size` as `u32`s,
+    /// 2. the bit patterns of `x_pivot`, `y_pivot` and `z_pivot` as `u32`s,
+    /// 3. for every voxel, sorted by `(x, y, z)`: `x` and `y` as `u32`s, `z`
+    ///    as a `u16`, then red, green and blue.
+    ///
+    /// The caches, the order of voxels within a column, the `dummy`,
+    /// `visibility` and `normalindex` bytes and the palette don't count.
+    /// This won't change between versions. Fails if the caches don't match
+    /// the voxels, since their positions are unknown.
+    pub fn fingerprint(&self) -> Result<u64, CacheMismatch> {
+        let mut voxels: Vec<_> = self
+            .iter_with_coords()?
+            .map(|(x, y, z, voxel)| (x, y, z, [voxel.red, voxel.green, voxel.blue]))
+            .collect();
+        voxels.sort_unstable();
+
+        let mut hash = Fnv1a::new();
+        for size in [self.x_size, self.y_size, self.z_size] {
+            hash.write(&size.to_le_bytes());
+        }
+        for pivot in [self.x_pivot, self.y_pivot, self.z_pivot] {
+            hash.write(&pivot.to_bits().to_le_bytes());
+        }
+        for (x, y, z, color) in voxels {
+            hash.write(&x.to_le_bytes());
+            hash.write(&y.to_le_bytes());
+            hash.write(&z.to_le_bytes());
+            hash.write(&color);
+        }
+        Ok(hash.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Fnv1a;
+    use crate::kv6::testing::grenade;
+    use crate::kv6::{KV6Builder, Rgb};
+    use alloc::vec;
+
+    #[test]
+    fn test_fnv() {
+        // The published test vectors.
+        let hash = |bytes: &[u8]| {
+            let mut hash = Fnv1a::new();
+            hash.write(bytes);
+            hash.0
+        };
+        assert_eq!(hash(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(hash(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+
+    #[test]
+    fn test_grenade_fingerprint() {
+        let data = grenade();
+        let fingerprint = data.fingerprint().unwrap();
+        // Pinned so an accidental change to the algorithm shows up.
+        assert_eq!(fingerprint, 0x19d8_22d5_4bbe_585f);
+
+        let mut cosmetic = data.clone();
+        let range = cosmetic.column_range(2, 2).unwrap();
+        cosmetic.voxels[range].reverse();
+        for voxel in &mut cosmetic.voxels {
+            voxel.visibility = 0;
+            voxel.normalindex = 0;
+            voxel.dummy = 0;
+        }
+        cosmetic.palette = None;
+        assert_eq!(cosmetic.fingerprint().unwrap(), fingerprint);
+
+        let mut recolored = data.clone();
+        recolored.voxels[10].red ^= 1;
+        assert_ne!(recolored.fingerprint().unwrap(), fingerprint);
+
+        let mut moved = data.clone();
+        moved.z_pivot = -moved.z_pivot;
+        assert_ne!(moved.fingerprint().unwrap(), fingerprint);
+
+        moved.voxels.pop();
+        assert!(moved.fingerprint().is_err());
+    }
+
+    #[test]
+    fn test_recomputed_caches() {
+        let mut builder = KV6Builder::new(2, 2, 3);
+        for (x, y) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+            builder = builder.voxel(x, y, 2 - (x + y) as u16, Rgb::new(x as u8, y as u8, 0));
+        }
+        let data = builder.build().unwrap();
+        let mut rebuilt = data.clone();
+        rebuilt.xlen.fill(0);
+        rebuilt.ylen = vec![vec![0; 2]; 2];
+        assert!(rebuilt.fingerprint().is_err());
+        rebuilt
+            .recompute_caches([(0, 0), (0, 1), (1, 0), (1, 1)])
+            .unwrap();
+        assert_eq!(rebuilt.fingerprint(), data.fingerprint());
+    }
+}
@@ -0,0 +1,303 @@
+use super::{DenseVoxelGrid, Face, FillColor, KV6Format, Rgb};
+use crate::Kv6Error;
+use alloc::vec::Vec;
+use glam::{Mat4, Vec3};
+
+/// Knobs for [`KV6Format::transformed`].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ResampleOptions {
+    /// Sample eight points spread through each new cell instead of just its
+    /// middle. The cell is solid if most of them land in solid cells, the
+    /// middle breaking a tie, and takes the average of their colors. Smooths
+    /// edges at angles other than right angles, at eight times the cost.
+    pub supersample: bool,
+}
+
+/// Where the eight supersamples sit, as offsets from a cell's middle.
+const SUPERSAMPLES: [Vec3; 8] = {
+    let (a, b) = (-0.25, 0.25);
+    [
+        Vec3::new(a, a, a),
+        Vec3::new(a, a, b),
+        Vec3::new(a, b, a),
+        Vec3::new(a, b, b),
+        Vec3::new(b, a, a),
+        Vec3::new(b, a, b),
+        Vec3::new(b, b, a),
+        Vec3::new(b, b, b),
+    ]
+};
+
+/// How far a corner of the transformed grid can be off a whole number and
+/// still count as on it, so rounding errors don't add a layer of cells.
+const SNAP: f32 = 1e-3;
+
+impl KV6Format {
+    /// The model with `matrix` applied, for turns and scales that aren't a
+    /// whole number of right angles.
+    ///
+    /// Points are in grid coordinates, in which cell `(x, y, z)` spans
+    /// `x..x + 1` and so on, with z pointing down. The new grid is the box
+    /// around the transformed grid, moved to start at the origin, and the
+    /// pivot goes through `matrix` and that move too. Each new cell is
+    /// filled by mapping its middle back through the inverse of `matrix` and
+    /// taking the cell there, so nearest neighbour, or as `options` says.
+    /// The unstored interior counts as solid; new cells that land in it are
+    /// only stored if they end up next to air, with the color of the nearest
+    /// stored voxel. Visibility and normals are worked out afresh as for
+    /// [`KV6Format::from_dense`]. A matrix that can't be inverted flattens
+    /// the model into nothing and gives an empty 0 by 0 by 0 model.
+    ///
+    /// Fails if the caches don't match the voxels, either grid would have
+    /// more than [`DenseVoxelGrid::DEFAULT_LIMIT`] cells, or the new
+    /// `z_size` would be larger than heights can go.
+    pub fn transformed(
+        &self,
+        matrix: Mat4,
+        options: &ResampleOptions,
+    ) -> Result<KV6Format, Kv6Error> {
+        let inverse = matrix.inverse();
+        if !matrix.is_finite() || matrix.determinant() == 0.0 || !inverse.is_finite() {
+            self.check_caches().map_err(Kv6Error::InconsistentSizes)?;
+            return KV6Format::from_sorted((0, 0, 0), []);
+        }
+
+        let size = Vec3::new(self.x_size as f32, self.y_size as f32, self.z_size as f32);
+        let (mut min, mut max) = (Vec3::INFINITY, Vec3::NEG_INFINITY);
+        for corner in 0..8 {
+            let select = |bit: u32, extent: f32| if corner & bit != 0 { extent } else { 0.0 };
+            let corner = Vec3::new(select(1, size.x), select(2, size.y), select(4, size.z));
+            let corner = matrix.transform_point3(corner);
+            (min, max) = (min.min(corner), max.max(corner));
+        }
+        let origin = (min + SNAP).floor();
+        let extent = ((max - SNAP).ceil() - origin).max(Vec3::ZERO);
+        let cells = extent.x as f64 * extent.y as f64 * extent.z as f64;
+        let limit = DenseVoxelGrid::DEFAULT_LIMIT;
+        if cells > limit as f64 {
+            return Err(Kv6Error::GridTooLarge {
+                cells: cells.min(usize::MAX as f64) as usize,
+                limit,
+            });
+        }
+        let (x_size, y_size, z_size) = (extent.x as u32, extent.y as u32, extent.z as u32);
+        if z_size > 1 << 16 {
+            return Err(Kv6Error::LimitExceeded {
+                field: "z_size",
+                value: z_size,
+                limit: 1 << 16,
+            });
+        }
+
+        // The stored voxels, and every solid cell with the interior colored
+        // from the nearest of them.
+        let stored = self.to_dense()?;
+        let mut filled = self.clone();
+        filled.fill_interior(FillColor::NearestNeighbor)?;
+        let solid = filled.to_dense()?;
+        let sample = |point: Vec3| {
+            let cell = inverse.transform_point3(point).floor();
+            if cell.cmplt(Vec3::ZERO).any() || cell.cmpge(size).any() {
+                return None;
+            }
+            let (x, y, z) = (cell.x as u32, cell.y as u32, cell.z as u16);
+            Some((solid.get(x, y, z)?, stored.get(x, y, z).is_some()))
+        };
+
+        // The new grid, x-major with heights innermost, holding each solid
+        // cell's color and whether it came from a stored voxel.
+        let mut grid = Vec::with_capacity(cells as usize);
+        for x in 0..x_size {
+            for y in 0..y_size {
+                for z in 0..z_size {
+                    let middle = origin + Vec3::new(x as f32, y as f32, z as f32) + 0.5;
+                    if !options.supersample {
+                        grid.push(sample(middle));
+                        continue;
+                    }
+                    let hits: Vec<_> = SUPERSAMPLES
+                        .iter()
+                        .filter_map(|&offset| sample(middle + offset))
+                        .collect();
+                    if hits.len() < 4 || (hits.len() == 4 && sample(middle).is_none()) {
+                        grid.push(None);
+                        continue;
+                    }
+                    let mut sum = [0u32; 3];
+                    for (color, _) in &hits {
+                        sum[0] += color.r as u32;
+                        sum[1] += color.g as u32;
+                        sum[2] += color.b as u32;
+                    }
+                    let n = hits.len() as u32;
+                    let [r, g, b] = sum.map(|sum| ((sum + n / 2) / n) as u8);
+                    let any_stored = hits.iter().any(|&(_, stored)| stored);
+                    grid.push(Some((Rgb::new(r, g, b), any_stored)));
+                }
+            }
+        }
+
+        let index = |x: u32, y: u32, z: u32| {
+            (x < x_size && y < y_size && z < z_size)
+                .then(|| (x as usize * y_size as usize + y as usize) * z_size as usize + z as usize)
+        };
+        let empty = |x: u32, y: u32, z: u32| index(x, y, z).is_none_or(|i| grid[i].is_none());
+        let mut cells = Vec::new();
+        for x in 0..x_size {
+            for y in 0..y_size {
+                for z in 0..z_size {
+                    let Some((color, stored)) = grid[index(x, y, z).expect("in range")] else {
+                        continue;
+                    };
+                    let exposed = || {
+                        Face::ALL.into_iter().any(|face| {
+                            face.neighbour(x, y, z as u16)
+                                .is_none_or(|(x, y, z)| empty(x, y, z as u32))
+                        })
+                    };
+                    if stored || exposed() {
+                        cells.push((x, y, z as u16, color));
+                    }
+                }
+            }
+        }
+
+        let mut data = KV6Format::from_sorted((x_size, y_size, z_size), cells)?;
+        let pivot = matrix.transform_point3(Vec3::from(self.pivot())) - origin;
+        data.set_pivot(pivot.x, pivot.y, pivot.z);
+        data.palette = self.palette;
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ResampleOptions;
+    use crate::kv6::testing::grenade;
+    use crate::kv6::{Axis, FillColor, KV6Builder, KV6Format, Rgb};
+    use alloc::vec::Vec;
+    use glam::{Mat4, Vec3};
+
+    fn visibility(data: &KV6Format) -> Vec<u8> {
+        data.voxels.iter().map(|voxel| voxel.visibility).collect()
+    }
+
+    #[test]
+    fn test_identity() {
+        for supersample in [false, true] {
+            let options = ResampleOptions { supersample };
+            let data = grenade().transformed(Mat4::IDENTITY, &options).unwrap();
+            assert!(data.equivalent(&grenade()));
+            assert_eq!(data.pivot(), grenade().pivot());
+            assert!(data.validate().is_clean(), "{:?}", data.validate());
+        }
+
+        // Moving by whole cells only moves the pivot, since the grid moves
+        // along with it.
+        let moved = grenade()
+            .transformed(
+                Mat4::from_translation(Vec3::new(3.0, -2.0, 0.0)),
+                &Default::default(),
+            )
+            .unwrap();
+        assert!(moved.equivalent(&grenade()));
+        assert_eq!(moved.pivot(), grenade().pivot());
+    }
+
+    #[test]
+    fn test_quarter_turns() {
+        let mut stretched = KV6Builder::new(3, 5, 2);
+        for (i, (x, y, z)) in [(0, 0, 0), (2, 4, 1), (1, 3, 0), (0, 4, 1)]
+            .into_iter()
+            .enumerate()
+        {
+            stretched = stretched.voxel(x, y, z, Rgb::new(i as u8 * 60, 0, 0));
+        }
+        let stretched = stretched.build().unwrap();
+
+        // rotate90 takes +x to +y about z, +y to +z about x and +z to +x
+        // about y, which are right-handed turns in glam's terms.
+        for original in [grenade(), stretched] {
+            for (axis, turn) in [
+                (Axis::X, Mat4::from_rotation_x as fn(f32) -> Mat4),
+                (Axis::Y, Mat4::from_rotation_y),
+                (Axis::Z, Mat4::from_rotation_z),
+            ] {
+                for quarter_turns in 1..4 {
+                    let angle = quarter_turns as f32 * core::f32::consts::FRAC_PI_2;
+                    let data = original
+                        .transformed(turn(angle), &Default::default())
+                        .unwrap();
+                    let mut expected = original.clone();
+                    expected.rotate90(axis, quarter_turns).unwrap();
+                    assert!(data.equivalent(&expected), "{:?}", (axis, quarter_turns));
+                    assert_eq!(visibility(&data), visibility(&expected));
+                    let pivots = data.pivot().into_iter().zip(expected.pivot());
+                    assert!(pivots.into_iter().all(|(a, b)| (a - b).abs() < 1e-4));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_scaled() {
+        let data = grenade();
+        let doubled = data
+            .transformed(Mat4::from_scale(Vec3::splat(2.0)), &Default::default())
+            .unwrap();
+        assert!(doubled.equivalent(&data.upscale(2).unwrap()));
+        assert_eq!(doubled.pivot(), [5.0, 5.0, 7.0]);
+
+        // A turn that isn't a right angle leaves no holes in the surface.
+        let mut builder = KV6Builder::new(8, 8, 8);
+        for x in 0..8 {
+            for y in 0..8 {
+                for z in 0..8 {
+                    builder = builder.voxel(x, y, z, Rgb::new(255, 255, 255));
+                }
+            }
+        }
+        let mut cube = builder.build().unwrap();
+        cube.strip_hidden().unwrap();
+        for supersample in [false, true] {
+            let turned = cube
+                .transformed(
+                    Mat4::from_rotation_z(22.5f32.to_radians()),
+                    &ResampleOptions { supersample },
+                )
+                .unwrap();
+            assert_eq!((turned.x_size, turned.y_size, turned.z_size), (12, 11, 8));
+            assert!(turned.validate().is_clean(), "{:?}", turned.validate());
+            // A hole would let the outside in, leaving nothing enclosed. A
+            // turn keeps the volume, give or take the cells along the edges.
+            let mut filled = turned.clone();
+            let interior = filled
+                .fill_interior(FillColor::Constant(Rgb::new(0, 0, 0)))
+                .unwrap();
+            assert!(interior > 0);
+            assert!(
+                filled.voxels.len().abs_diff(512) < 64,
+                "{}",
+                filled.voxels.len()
+            );
+        }
+    }
+
+    #[test]
+    fn test_degenerate() {
+        let flat = grenade()
+            .transformed(
+                Mat4::from_scale(Vec3::new(1.0, 0.0, 1.0)),
+                &Default::default(),
+            )
+            .unwrap();
+        assert_eq!((flat.x_size, flat.y_size, flat.z_size), (0, 0, 0));
+        assert!(flat.voxels.is_empty());
+
+        let mut broken = grenade();
+        broken.xlen[0] += 1;
+        assert!(broken
+            .transformed(Mat4::IDENTITY, &Default::default())
+            .is_err());
+    }
+}
@@ -0,0 +1,232 @@
+use super::{KV6Format, Rgb};
+use core::fmt;
+
+/// How [`KV6Format::apply_height_gradient`] combines the gradient with the
+/// colors already there.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    /// Use the gradient's color.
+    #[default]
+    Replace,
+    /// Multiply each channel by the gradient's, so white leaves the color
+    /// alone and black makes it black.
+    Multiply,
+    /// Multiply dark channels and screen light ones, which tints while
+    /// keeping the existing contrast.
+    Overlay,
+}
+
+/// Why [`KV6Format::apply_height_gradient`] refused its stops.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GradientError {
+    /// There were no stops.
+    NoStops,
+    /// Stop `index` has a NaN or infinite position.
+    NotFinite { index: usize },
+    /// Stop `index` comes before the stop ahead of it.
+    Unsorted { index: usize },
+}
+
+impl fmt::Display for GradientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GradientError::NoStops => write!(f, "the gradient has no stops"),
+            GradientError::NotFinite { index } => {
+                write!(f, "stop {} has a position that isn't finite", index)
+            }
+            GradientError::Unsorted { index } => {
+                write!(f, "stop {} is positioned before the one ahead of it", index)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for GradientError {}
+
+/// The gradient's color at `t`. `stops` must be checked already.
+fn sample(stops: &[(f32, Rgb)], t: f32) -> Rgb {
+    let next = stops.iter().position(|&(position, _)| position > t);
+    let (before, after) = match next {
+        Some(0) => return stops[0].1,
+        Some(i) => (stops[i - 1], stops[i]),
+        None => return stops[stops.len() - 1].1,
+    };
+    let f = (t - before.0) / (after.0 - before.0);
+    let lerp = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * f + 0.5) as u8;
+    Rgb::new(
+        lerp(before.1.r, after.1.r),
+        lerp(before.1.g, after.1.g),
+        lerp(before.1.b, after.1.b),
+    )
+}
+
+/// `base` and `blend` mixed per `mode`, channel by channel.
+fn blend(mode: BlendMode, base: u8, blend: u8) -> u8 {
+    let (base, blend) = (base as u32, blend as u32);
+    let value = match mode {
+        BlendMode::Replace => blend,
+        BlendMode::Multiply => (base * blend + 127) / 255,
+        BlendMode::Overlay if base < 128 => (2 * base * blend + 127) / 255,
+        BlendMode::Overlay => 255 - (2 * (255 - base) * (255 - blend) + 127) / 255,
+    };
+    value as u8
+}
+
+impl KV6Format {
+    /// Colors every voxel from a gradient running down the model, blending
+    /// it with the voxel's color as `mode` says.
+    ///
+    /// A voxel at height `z` takes the gradient at `z / (z_size - 1)`, so 0.0
+    /// is the top of the grid (z points down) and 1.0 the bottom; a model
+    /// one voxel high uses 0.0 throughout. `stops` are `(position, color)`
+    /// pairs in order. Between two stops the color is interpolated linearly
+    /// in RGB, and past either end it's the end stop's. Where two stops share
+    /// a position the later one wins, which gives a hard edge. Fails without
+    /// changing anything if there are no stops, a position isn't finite or a
+    /// stop comes before the one ahead of it.
+    pub fn apply_height_gradient(
+        &mut self,
+        stops: &[(f32, Rgb)],
+        mode: BlendMode,
+    ) -> Result<(), GradientError> {
+        if stops.is_empty() {
+            return Err(GradientError::NoStops);
+        }
+        for (index, &(position, _)) in stops.iter().enumerate() {
+            if !position.is_finite() {
+                return Err(GradientError::NotFinite { index });
+            }
+            if index > 0 && position < stops[index - 1].0 {
+                return Err(GradientError::Unsorted { index });
+            }
+        }
+
+        let bottom = self.z_size.saturating_sub(1).max(1) as f32;
+        for voxel in &mut self.voxels {
+            let color = voxel.color();
+            let gradient = sample(stops, voxel.height as f32 / bottom);
+            voxel.set_color(Rgb::new(
+                blend(mode, color.r, gradient.r),
+                blend(mode, color.g, gradient.g),
+                blend(mode, color.b, gradient.b),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BlendMode, GradientError};
+    use crate::kv6::{KV6Builder, KV6Format, Rgb};
+    use alloc::vec::Vec;
+
+    const RED: Rgb = Rgb::new(255, 0, 0);
+    const BLUE: Rgb = Rgb::new(0, 0, 255);
+    const WHITE: Rgb = Rgb::new(255, 255, 255);
+
+    /// A single column 11 voxels high, all `color`.
+    fn pillar(color: Rgb) -> KV6Format {
+        let mut builder = KV6Builder::new(1, 1, 11);
+        for z in 0..11 {
+            builder = builder.voxel(0, 0, z, color);
+        }
+        builder.build().unwrap()
+    }
+
+    fn all_colors(data: &KV6Format) -> Vec<Rgb> {
+        data.colors().collect()
+    }
+
+    #[test]
+    fn test_gradient_ends() {
+        let mut data = pillar(WHITE);
+        let stops = [(0.0, RED), (1.0, BLUE)];
+        data.apply_height_gradient(&stops, BlendMode::Replace)
+            .unwrap();
+        let colors = all_colors(&data);
+        assert_eq!(colors[0], RED);
+        assert_eq!(colors[10], BLUE);
+        assert_eq!(colors[5], Rgb::new(128, 0, 128));
+        assert_eq!(colors[2], Rgb::new(204, 0, 51));
+
+        // Stops inside the range hold the ends flat past them.
+        let mut data = pillar(WHITE);
+        let stops = [(0.2, RED), (0.5, WHITE), (0.8, BLUE)];
+        data.apply_height_gradient(&stops, BlendMode::Replace)
+            .unwrap();
+        let colors = all_colors(&data);
+        assert_eq!(colors[..3], [RED; 3]);
+        assert_eq!(colors[5], WHITE);
+        assert_eq!(colors[8..], [BLUE; 3]);
+        assert_eq!(colors[4], Rgb::new(255, 170, 170));
+    }
+
+    #[test]
+    fn test_gradient_hard_edge() {
+        let mut data = pillar(WHITE);
+        let stops = [(0.0, RED), (0.5, RED), (0.5, BLUE), (1.0, BLUE)];
+        data.apply_height_gradient(&stops, BlendMode::Replace)
+            .unwrap();
+        let colors = all_colors(&data);
+        assert_eq!(colors[..5], [RED; 5]);
+        assert_eq!(colors[5..], [BLUE; 6]);
+
+        let mut data = pillar(WHITE);
+        data.apply_height_gradient(&[(0.3, RED)], BlendMode::Replace)
+            .unwrap();
+        assert!(data.colors().all(|color| color == RED));
+    }
+
+    #[test]
+    fn test_blend_modes() {
+        let gray = Rgb::new(100, 200, 50);
+        let stops = [(0.0, Rgb::new(255, 128, 0)), (1.0, Rgb::new(255, 128, 0))];
+        let blended = |mode| {
+            let mut data = pillar(gray);
+            data.apply_height_gradient(&stops, mode).unwrap();
+            data.voxels[0].color()
+        };
+        assert_eq!(blended(BlendMode::Replace), Rgb::new(255, 128, 0));
+        assert_eq!(blended(BlendMode::Multiply), Rgb::new(100, 100, 0));
+        assert_eq!(blended(BlendMode::Overlay), Rgb::new(200, 200, 0));
+
+        // Multiplying by white changes nothing; replacing does.
+        let mut data = pillar(gray);
+        data.apply_height_gradient(&[(0.0, WHITE)], BlendMode::Multiply)
+            .unwrap();
+        assert_eq!(data, pillar(gray));
+        data.apply_height_gradient(&[(0.0, WHITE)], BlendMode::Replace)
+            .unwrap();
+        assert_eq!(data, pillar(WHITE));
+    }
+
+    #[test]
+    fn test_gradient_errors() {
+        let mut data = pillar(WHITE);
+        assert_eq!(
+            data.apply_height_gradient(&[], BlendMode::Replace),
+            Err(GradientError::NoStops)
+        );
+        assert_eq!(
+            data.apply_height_gradient(&[(0.0, RED), (f32::NAN, BLUE)], BlendMode::Replace),
+            Err(GradientError::NotFinite { index: 1 })
+        );
+        assert_eq!(
+            data.apply_height_gradient(&[(0.0, RED), (0.6, BLUE), (0.5, RED)], BlendMode::Replace),
+            Err(GradientError::Unsorted { index: 2 })
+        );
+        assert_eq!(data, pillar(WHITE));
+
+        // A flat model sits at the top of the gradient.
+        let mut flat = KV6Builder::new(2, 1, 1)
+            .voxel(0, 0, 0, WHITE)
+            .voxel(1, 0, 0, WHITE)
+            .build()
+            .unwrap();
+        flat.apply_height_gradient(&[(0.0, RED), (1.0, BLUE)], BlendMode::Replace)
+            .unwrap();
+        assert!(flat.colors().all(|color| color == RED));
+    }
+}
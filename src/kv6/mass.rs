@@ -0,0 +1,211 @@
+use super::{KV6Format, OccupancyGrid};
+use crate::Kv6Error;
+
+/// The mass distribution of a model of uniform density, one unit of mass
+/// per cell, from [`KV6Format::mass_properties`].
+///
+/// Cell `(x, y, z)` spans `x..x + 1` and so on, the units pivots are in.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MassProperties {
+    /// Solid cells, counting the unstored interior.
+    pub voxel_count: u64,
+    /// The centroid in model coordinates. The pivot if there are no voxels.
+    pub center_of_mass: (f64, f64, f64),
+    /// The centroid minus the pivot.
+    pub pivot_relative: (f64, f64, f64),
+    /// The inertia tensor about the centroid, treating every cell as a unit
+    /// cube.
+    pub inertia: [[f64; 3]; 3],
+}
+
+impl KV6Format {
+    /// Works out the model's [`MassProperties`].
+    ///
+    /// The solid is every stored voxel plus the cells they enclose, which
+    /// KV6 files leave out, so this builds an [`OccupancyGrid`]
+    /// and fails when [`KV6Format::occupancy`] does.
+    pub fn mass_properties(&self) -> Result<MassProperties, Kv6Error> {
+        let air = self.occupancy()?.exterior();
+        let solid = || solid_cells(self, &air);
+        let center = |x: u32, y: u32, z: u16| [x as f64 + 0.5, y as f64 + 0.5, z as f64 + 0.5];
+        let pivot = [
+            self.x_pivot as f64,
+            self.y_pivot as f64,
+            self.z_pivot as f64,
+        ];
+
+        let mut count = 0u64;
+        let mut sum = [0.0; 3];
+        for (x, y, z) in solid() {
+            count += 1;
+            for (sum, p) in sum.iter_mut().zip(center(x, y, z)) {
+                *sum += p;
+            }
+        }
+        if count == 0 {
+            return Ok(MassProperties {
+                center_of_mass: (pivot[0], pivot[1], pivot[2]),
+                ..Default::default()
+            });
+        }
+        let com = sum.map(|sum| sum / count as f64);
+
+        // Second moments about the centroid, taken in a second pass so they
+        // don't lose precision to large coordinates. A unit cube adds 1/12
+        // along each axis on top of its center.
+        let mut moments = [[0.0; 3]; 3];
+        for (x, y, z) in solid() {
+            let p = center(x, y, z);
+            let d = [p[0] - com[0], p[1] - com[1], p[2] - com[2]];
+            for i in 0..3 {
+                for j in 0..3 {
+                    moments[i][j] += d[i] * d[j];
+                }
+            }
+        }
+        for (i, row) in moments.iter_mut().enumerate() {
+            row[i] += count as f64 / 12.0;
+        }
+        let trace = moments[0][0] + moments[1][1] + moments[2][2];
+        let mut inertia = [[0.0; 3]; 3];
+        for i in 0..3 {
+            for j in 0..3 {
+                inertia[i][j] = if i == j { trace } else { 0.0 } - moments[i][j];
+            }
+        }
+
+        Ok(MassProperties {
+            voxel_count: count,
+            center_of_mass: (com[0], com[1], com[2]),
+            pivot_relative: (com[0] - pivot[0], com[1] - pivot[1], com[2] - pivot[2]),
+            inertia,
+        })
+    }
+
+    /// Moves the pivot to the centroid found by
+    /// [`KV6Format::mass_properties`]. Empty models keep their pivot.
+    pub fn recenter_pivot_to_mass(&mut self) -> Result<(), Kv6Error> {
+        let (x, y, z) = self.mass_properties()?.center_of_mass;
+        (self.x_pivot, self.y_pivot, self.z_pivot) = (x as f32, y as f32, z as f32);
+        Ok(())
+    }
+}
+
+/// Every cell of `data` that isn't in `air`, x-major.
+fn solid_cells<'a>(
+    data: &KV6Format,
+    air: &'a OccupancyGrid,
+) -> impl Iterator<Item = (u32, u32, u16)> + 'a {
+    let (x_size, y_size) = (data.x_size, data.y_size);
+    let z_size = data.z_size.min(1 << 16);
+    (0..x_size).flat_map(move |x| {
+        (0..y_size).flat_map(move |y| {
+            (0..z_size)
+                .map(move |z| (x, y, z as u16))
+                .filter(|&(x, y, z)| !air.contains(x, y, z))
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MassProperties;
+    use crate::kv6::{KV6Builder, KV6Format, Rgb};
+
+    fn block(x_size: u32, y_size: u32, z_size: u16) -> KV6Format {
+        let mut builder = KV6Builder::new(x_size + 2, y_size + 2, z_size as u32 + 2);
+        for x in 1..=x_size {
+            for y in 1..=y_size {
+                for z in 1..=z_size {
+                    builder = builder.voxel(x, y, z, Rgb::new(1, 2, 3));
+                }
+            }
+        }
+        builder.build().unwrap()
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_single_voxel() {
+        let mut data = block(1, 1, 1);
+        data.x_pivot = 0.0;
+        let mass = data.mass_properties().unwrap();
+        assert_eq!(mass.voxel_count, 1);
+        assert_eq!(mass.center_of_mass, (1.5, 1.5, 1.5));
+        assert_eq!(mass.pivot_relative, (1.5, 0.0, 0.0));
+        for i in 0..3 {
+            for j in 0..3 {
+                assert_close(mass.inertia[i][j], if i == j { 1.0 / 6.0 } else { 0.0 });
+            }
+        }
+    }
+
+    #[test]
+    fn test_cube() {
+        // A solid cube of side 2 and mass 8 has m * (2² + 2²) / 12 = 16 / 3
+        // about each axis.
+        let mass = block(2, 2, 2).mass_properties().unwrap();
+        assert_eq!(mass.voxel_count, 8);
+        assert_eq!(mass.center_of_mass, (2.0, 2.0, 2.0));
+        for i in 0..3 {
+            for j in 0..3 {
+                assert_close(mass.inertia[i][j], if i == j { 16.0 / 3.0 } else { 0.0 });
+            }
+        }
+
+        // The pivot of a model that doesn't fill its grid is off the centroid.
+        let mut data = block(2, 2, 2);
+        data.x_size += 3;
+        data.xlen.extend([0; 3]);
+        data.ylen.extend(core::iter::repeat_n(alloc::vec![0; 4], 3));
+        data.x_pivot = 0.5;
+        assert_eq!(
+            data.mass_properties().unwrap().pivot_relative,
+            (1.5, 0.0, 0.0)
+        );
+        data.recenter_pivot_to_mass().unwrap();
+        assert_eq!((data.x_pivot, data.y_pivot, data.z_pivot), (2.0, 2.0, 2.0));
+    }
+
+    #[test]
+    fn test_interior_counts() {
+        // Taking out the middle of a 3x3x3 cube leaves it enclosed, which a
+        // KV6 file reads as solid.
+        let mut data = block(3, 3, 3);
+        let full = data.mass_properties().unwrap();
+        data.remove_voxel(2, 2, 2).unwrap();
+        assert_eq!(data.voxels.len(), 26);
+        assert_eq!(data.mass_properties().unwrap(), full);
+        assert_eq!(full.voxel_count, 27);
+        // Side 3 and mass 27: 27 * (9 + 9) / 12.
+        assert_close(full.inertia[1][1], 40.5);
+
+        // An asymmetric shape has products of inertia.
+        let mut data = block(2, 1, 1);
+        data.insert_voxel(1, 2, 1, Default::default()).unwrap();
+        let mass = data.mass_properties().unwrap();
+        assert_eq!(mass.voxel_count, 3);
+        assert!(mass.inertia[0][1] > 0.0);
+        assert_eq!(mass.inertia[0][1], mass.inertia[1][0]);
+    }
+
+    #[test]
+    fn test_empty() {
+        let mut data = KV6Format {
+            x_pivot: 1.0,
+            ..Default::default()
+        };
+        assert_eq!(
+            data.mass_properties().unwrap(),
+            MassProperties {
+                center_of_mass: (1.0, 0.0, 0.0),
+                ..Default::default()
+            }
+        );
+        data.recenter_pivot_to_mass().unwrap();
+        assert_eq!(data.x_pivot, 1.0);
+    }
+}
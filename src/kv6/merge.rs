@@ -0,0 +1,200 @@
+use super::{KV6Format, VoxelData};
+use crate::Kv6Error;
+use alloc::collections::{btree_map::Entry, BTreeMap};
+
+/// What [`KV6Format::merge`] does where both models have a voxel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Keep the voxel already there.
+    KeepSelf,
+    /// Replace it with the other model's.
+    KeepOther,
+    /// Keep the voxel already there, colored halfway between the two.
+    Blend,
+}
+
+impl KV6Format {
+    /// Copies `other`'s voxels into this model, with `other`'s origin at
+    /// `offset`, resolving voxels both have by `conflict`.
+    ///
+    /// The grid grows to hold both models' grids. When `offset` is negative
+    /// along an axis, this model's voxels and pivot move up by that much so
+    /// nothing ends up before the origin. The pivot is otherwise this
+    /// model's. Visibility is recomputed; normals are kept, so call
+    /// [`KV6Format::recompute_normals`] afterwards to fix up the seams.
+    ///
+    /// Fails if either model's caches don't match its voxels, a voxel lies
+    /// outside its model's grid, or the combined grid is too big for the
+    /// format.
+    pub fn merge(
+        &mut self,
+        other: &KV6Format,
+        offset: (i32, i32, i32),
+        conflict: ConflictPolicy,
+    ) -> Result<(), Kv6Error> {
+        let sizes = [self.x_size, self.y_size, self.z_size].map(|n| n as i64);
+        let other_sizes = [other.x_size, other.y_size, other.z_size].map(|n| n as i64);
+        let offset = [offset.0 as i64, offset.1 as i64, offset.2 as i64];
+        let min: [i64; 3] = core::array::from_fn(|i| offset[i].min(0));
+        let max: [i64; 3] = core::array::from_fn(|i| sizes[i].max(offset[i] + other_sizes[i]));
+
+        let fields = ["x_size", "y_size", "z_size"];
+        let mut size = [0; 3];
+        for i in 0..3 {
+            let value = max[i] - min[i];
+            size[i] = u32::try_from(value).map_err(|_| Kv6Error::TooLarge {
+                field: fields[i],
+                value: value as usize,
+            })?;
+        }
+        if size[2] > 1 << 16 && !(self.voxels.is_empty() && other.voxels.is_empty()) {
+            return Err(Kv6Error::LimitExceeded {
+                field: "z_size",
+                value: size[2],
+                limit: 1 << 16,
+            });
+        }
+
+        let mut cells = BTreeMap::new();
+        let mut place = |data: &KV6Format, at: [i64; 3], keep_new: bool| {
+            for (x, y, z, voxel) in data
+                .iter_with_coords()
+                .map_err(Kv6Error::InconsistentSizes)?
+            {
+                if z as u32 >= data.z_size {
+                    return Err(Kv6Error::OutOfBounds { x, y, z });
+                }
+                let position = (
+                    (x as i64 + at[0]) as u32,
+                    (y as i64 + at[1]) as u32,
+                    (z as i64 + at[2]) as u16,
+                );
+                match cells.entry(position) {
+                    Entry::Vacant(entry) => {
+                        entry.insert(*voxel);
+                    }
+                    Entry::Occupied(mut entry) if keep_new => {
+                        resolve(entry.get_mut(), voxel, conflict);
+                    }
+                    Entry::Occupied(_) => {}
+                }
+            }
+            Ok(())
+        };
+        place(self, min.map(|n| -n), false)?;
+        place(other, core::array::from_fn(|i| offset[i] - min[i]), true)?;
+
+        self.set_columns(
+            (size[0], size[1], size[2]),
+            cells.into_iter().map(|((x, y, z), mut voxel)| {
+                voxel.height = z;
+                (x, y, voxel)
+            }),
+        )?;
+        self.translate_pivot(-min[0] as f32, -min[1] as f32, -min[2] as f32);
+        self.recompute_visibility()
+    }
+}
+
+/// Settles a voxel `existing` shares with `incoming` by `conflict`.
+fn resolve(existing: &mut VoxelData, incoming: &VoxelData, conflict: ConflictPolicy) {
+    match conflict {
+        ConflictPolicy::KeepSelf => {}
+        ConflictPolicy::KeepOther => *existing = *incoming,
+        ConflictPolicy::Blend => {
+            let mix = |a: u8, b: u8| ((a as u16 + b as u16).div_ceil(2)) as u8;
+            existing.red = mix(existing.red, incoming.red);
+            existing.green = mix(existing.green, incoming.green);
+            existing.blue = mix(existing.blue, incoming.blue);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConflictPolicy;
+    use crate::kv6::testing::grenade;
+    use crate::kv6::{KV6Builder, KV6Format, Rgb};
+    use crate::Kv6Error;
+
+    fn single(color: Rgb) -> KV6Format {
+        KV6Builder::new(1, 1, 1)
+            .voxel(0, 0, 0, color)
+            .build()
+            .unwrap()
+    }
+
+    const RED: Rgb = Rgb::new(200, 0, 0);
+    const BLUE: Rgb = Rgb::new(0, 0, 101);
+
+    #[test]
+    fn test_merge_apart() {
+        let mut data = single(RED);
+        data.merge(&single(BLUE), (2, 0, 0), ConflictPolicy::KeepSelf)
+            .unwrap();
+        assert_eq!((data.x_size, data.y_size, data.z_size), (3, 1, 1));
+        assert_eq!(data.voxels.len(), 2);
+        assert_eq!(data.get_voxel(0, 0, 0).unwrap().color(), RED);
+        assert_eq!(data.get_voxel(2, 0, 0).unwrap().color(), BLUE);
+        assert_eq!(data.pivot(), [0.5, 0.5, 0.5]);
+        assert!(data.validate().is_clean(), "{:?}", data.validate());
+
+        // Side by side, the touching faces are hidden.
+        let mut data = single(RED);
+        data.merge(&single(BLUE), (0, 1, 0), ConflictPolicy::KeepSelf)
+            .unwrap();
+        let mut visible = data.clone();
+        visible.recompute_visibility().unwrap();
+        assert_eq!(visible, data);
+        assert_eq!(data.voxels[0].visible_faces().count(), 5);
+    }
+
+    #[test]
+    fn test_merge_overlapping() {
+        for (conflict, color) in [
+            (ConflictPolicy::KeepSelf, RED),
+            (ConflictPolicy::KeepOther, BLUE),
+            (ConflictPolicy::Blend, Rgb::new(100, 0, 51)),
+        ] {
+            let mut data = single(RED);
+            data.merge(&single(BLUE), (0, 0, 0), conflict).unwrap();
+            assert_eq!(data.voxels.len(), 1);
+            assert_eq!(data.voxels[0].color(), color);
+            assert!(data.validate().is_clean(), "{:?}", data.validate());
+        }
+    }
+
+    #[test]
+    fn test_merge_negative() {
+        let mut data = single(RED);
+        data.set_pivot(0.5, 0.5, 1.0);
+        data.merge(&single(BLUE), (-2, 0, -1), ConflictPolicy::KeepSelf)
+            .unwrap();
+        assert_eq!((data.x_size, data.y_size, data.z_size), (3, 1, 2));
+        assert_eq!(data.get_voxel(2, 0, 1).unwrap().color(), RED);
+        assert_eq!(data.get_voxel(0, 0, 0).unwrap().color(), BLUE);
+        assert_eq!(data.pivot(), [2.5, 0.5, 2.0]);
+        assert!(data.validate().is_clean(), "{:?}", data.validate());
+    }
+
+    #[test]
+    fn test_merge_grenades() {
+        let grenade = grenade();
+        let mut data = grenade.clone();
+        data.merge(&grenade, (3, 0, 0), ConflictPolicy::KeepOther)
+            .unwrap();
+        assert_eq!((data.x_size, data.y_size, data.z_size), (9, 6, 9));
+        assert!(data.validate().is_clean(), "{:?}", data.validate());
+        // The overlapping half of each grenade is stored once.
+        assert!(data.voxels.len() < grenade.voxels.len() * 2);
+
+        let mut broken = grenade.clone();
+        broken.voxels.pop();
+        let before = data.clone();
+        assert!(matches!(
+            data.merge(&broken, (0, 0, 0), ConflictPolicy::KeepSelf),
+            Err(Kv6Error::InconsistentSizes(_))
+        ));
+        assert_eq!(data, before);
+    }
+}
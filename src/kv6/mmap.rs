@@ -0,0 +1,81 @@
+use super::{KV6Format, Kv6Ref, ParseOptions};
+use crate::Kv6Error;
+use memmap2::Mmap;
+use std::{fs::File, path::Path};
+
+/// Maps a file read-only.
+///
+/// The mapping is only sound as long as nothing truncates or rewrites the file
+/// while it's alive, which is the usual caveat with memory-mapped files.
+fn map(path: &Path) -> Result<Mmap, Kv6Error> {
+    let file = File::open(path)?;
+    // SAFETY: the map is read-only, see above for the remaining caveat.
+    Ok(unsafe { Mmap::map(&file)? })
+}
+
+impl KV6Format {
+    /// Like [`KV6Format::from_path`], but parses out of a memory mapping
+    /// instead of reading the file into a buffer first.
+    pub fn from_path_mmap<P: AsRef<Path>>(path: P) -> Result<Self, Kv6Error> {
+        let path = path.as_ref();
+        let read = || -> Result<Self, Kv6Error> {
+            KV6Format::parse_with(&map(path)?, &ParseOptions::default())
+        };
+
+        read().map_err(|err| err.with_path(path))
+    }
+}
+
+/// A memory-mapped model file, checked once when it's opened.
+///
+/// Nothing is copied out of the mapping until it's accessed through
+/// [`Kv6Mmap::view`].
+#[derive(Debug)]
+pub struct Kv6Mmap {
+    map: Mmap,
+}
+
+impl Kv6Mmap {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Kv6Error> {
+        let path = path.as_ref();
+        let open = || -> Result<Self, Kv6Error> {
+            let map = map(path)?;
+            Kv6Ref::new(&map)?;
+            Ok(Self { map })
+        };
+
+        open().map_err(|err| err.with_path(path))
+    }
+
+    pub fn view(&self) -> Kv6Ref<'_> {
+        Kv6Ref::new(&self.map).expect("mapping was checked when it was opened")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Kv6Mmap;
+    use crate::{kv6::KV6Format, Kv6Error};
+
+    #[test]
+    fn test_from_path_mmap() {
+        let mapped = KV6Format::from_path_mmap("data/grenade.kv6").unwrap();
+        let buffered = KV6Format::from_path("data/grenade.kv6").unwrap();
+        assert_eq!(mapped.to_bytes().unwrap(), buffered.to_bytes().unwrap());
+
+        let file = Kv6Mmap::open("data/grenade.kv6").unwrap();
+        assert_eq!(
+            file.view().to_owned().to_bytes().unwrap(),
+            buffered.to_bytes().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_mmap_missing_path() {
+        let err = Kv6Mmap::open("data/missing.kv6").unwrap_err();
+        match err {
+            Kv6Error::Path { source, .. } => assert!(matches!(*source, Kv6Error::Io(_))),
+            _ => panic!("expected a path error, got {:?}", err),
+        }
+    }
+}
@@ -0,0 +1,329 @@
+use super::{normals, Face, FillColor, KV6Format, VoxelData};
+use crate::Kv6Error;
+use alloc::{collections::BTreeMap, vec::Vec};
+
+/// Knobs for [`KV6Format::dilate_with`].
+#[derive(Debug, Clone, Copy)]
+pub struct DilateOptions {
+    /// The color of the added voxels.
+    pub color: FillColor,
+    /// Grow the grid by `iterations` cells on every side first, moving the
+    /// voxels and pivot along, so nothing is cut off at the edges.
+    pub grow_canvas: bool,
+}
+
+impl Default for DilateOptions {
+    fn default() -> Self {
+        DilateOptions {
+            color: FillColor::NearestNeighbor,
+            grow_canvas: false,
+        }
+    }
+}
+
+/// Every solid cell of a model, with its voxel and whether the file stores
+/// it rather than it being part of the unstored interior.
+type Solid = BTreeMap<(u32, u32, u16), (VoxelData, bool)>;
+
+impl KV6Format {
+    /// Thickens the model by `iterations` layers, each filling every empty
+    /// cell with a face on a solid one, within the grid. See
+    /// [`KV6Format::dilate_with`].
+    pub fn dilate(&mut self, iterations: u32, color: FillColor) -> Result<(), Kv6Error> {
+        self.dilate_with(
+            iterations,
+            &DilateOptions {
+                color,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Like [`KV6Format::dilate`], with `options`.
+    ///
+    /// With [`FillColor::NearestNeighbor`] each new voxel takes the color of
+    /// the one it grew from. New cells that end up with no face on air are
+    /// left as unstored interior. New voxels get the standard dummy byte and
+    /// the default normal, and visibility is recomputed for everything.
+    /// Fails if the caches don't match the voxels, the occupancy grid doesn't
+    /// fit in memory, the grown grid would be larger than the format can
+    /// store or a column would pass 65535 voxels, leaving the model as it
+    /// was.
+    pub fn dilate_with(
+        &mut self,
+        iterations: u32,
+        options: &DilateOptions,
+    ) -> Result<(), Kv6Error> {
+        let mut solid = self.solid_cells()?;
+        if iterations == 0 {
+            return Ok(());
+        }
+        let mut size = (self.x_size, self.y_size, self.z_size);
+        if options.grow_canvas {
+            let grow = |field, size: u32| {
+                size.checked_add(iterations)
+                    .and_then(|size| size.checked_add(iterations))
+                    .ok_or(Kv6Error::TooLarge {
+                        field,
+                        value: (size as u64 + 2 * iterations as u64)
+                            .try_into()
+                            .unwrap_or(usize::MAX),
+                    })
+            };
+            size = (
+                grow("x_size", size.0)?,
+                grow("y_size", size.1)?,
+                grow("z_size", size.2)?,
+            );
+            if size.2 > 1 << 16 {
+                return Err(Kv6Error::LimitExceeded {
+                    field: "z_size",
+                    value: size.2,
+                    limit: 1 << 16,
+                });
+            }
+            let shift = iterations;
+            solid = solid
+                .into_iter()
+                .map(|((x, y, z), (mut voxel, stored))| {
+                    voxel.height = z + shift as u16;
+                    ((x + shift, y + shift, voxel.height), (voxel, stored))
+                })
+                .collect();
+        }
+
+        let inside = |(x, y, z): (u32, u32, u16)| x < size.0 && y < size.1 && (z as u32) < size.2;
+        for _ in 0..iterations {
+            let mut layer = BTreeMap::new();
+            for (&(x, y, z), (voxel, _)) in &solid {
+                for cell in Face::ALL
+                    .into_iter()
+                    .filter_map(|face| face.neighbour(x, y, z))
+                {
+                    if !inside(cell) || solid.contains_key(&cell) || layer.contains_key(&cell) {
+                        continue;
+                    }
+                    let mut new = VoxelData {
+                        dummy: VoxelData::DUMMY,
+                        height: cell.2,
+                        visibility: 0,
+                        normalindex: normals::DEFAULT_INDEX,
+                        ..Default::default()
+                    };
+                    new.set_color(match options.color {
+                        FillColor::Constant(color) => color,
+                        FillColor::NearestNeighbor => voxel.color(),
+                    });
+                    layer.insert(cell, (new, false));
+                }
+            }
+            if layer.is_empty() {
+                break;
+            }
+            solid.extend(layer);
+        }
+
+        self.store_solid(size, &solid)?;
+        if options.grow_canvas {
+            self.translate_pivot(iterations as f32, iterations as f32, iterations as f32);
+        }
+        Ok(())
+    }
+
+    /// Thins the model by `iterations` layers, each removing every solid
+    /// cell with a face on air or the edge of the grid.
+    ///
+    /// The unstored interior counts as solid, so interior cells that end up
+    /// on the surface become voxels, with the color of the nearest voxel and
+    /// the default normal. Visibility is recomputed for everything. Fails if
+    /// the caches don't match the voxels or the occupancy grid doesn't fit
+    /// in memory, leaving the model as it was.
+    pub fn erode(&mut self, iterations: u32) -> Result<(), Kv6Error> {
+        let mut solid = self.solid_cells()?;
+        if iterations == 0 {
+            return Ok(());
+        }
+        let size = (self.x_size, self.y_size, self.z_size);
+        for _ in 0..iterations {
+            let exposed: Vec<_> = solid
+                .keys()
+                .copied()
+                .filter(|&(x, y, z)| {
+                    Face::ALL.into_iter().any(|face| {
+                        face.neighbour(x, y, z)
+                            .is_none_or(|cell| !solid.contains_key(&cell))
+                    })
+                })
+                .collect();
+            if exposed.is_empty() {
+                break;
+            }
+            for cell in exposed {
+                solid.remove(&cell);
+            }
+        }
+        self.store_solid(size, &solid)
+    }
+
+    /// Every solid cell, the interior taking the color of the nearest voxel.
+    fn solid_cells(&self) -> Result<Solid, Kv6Error> {
+        let mut solid: Solid = self
+            .iter_with_coords()
+            .map_err(Kv6Error::InconsistentSizes)?
+            .map(|(x, y, z, voxel)| ((x, y, z), (*voxel, true)))
+            .collect();
+        let mut filled = self.clone();
+        filled.fill_interior(FillColor::NearestNeighbor)?;
+        for (x, y, z, voxel) in filled
+            .iter_with_coords()
+            .map_err(Kv6Error::InconsistentSizes)?
+        {
+            solid.entry((x, y, z)).or_insert((*voxel, false));
+        }
+        Ok(solid)
+    }
+
+    /// Replaces the grid with one of `size` storing the cells of `solid` that
+    /// were stored already or have a face on air or the edge of the grid,
+    /// and recomputes visibility.
+    fn store_solid(&mut self, size: (u32, u32, u32), solid: &Solid) -> Result<(), Kv6Error> {
+        let inside = |(x, y, z): (u32, u32, u16)| x < size.0 && y < size.1 && (z as u32) < size.2;
+        let cells: Vec<_> = solid
+            .iter()
+            .filter(|&(&(x, y, z), &(_, stored))| {
+                stored
+                    || Face::ALL.into_iter().any(|face| {
+                        face.neighbour(x, y, z)
+                            .is_none_or(|cell| !inside(cell) || !solid.contains_key(&cell))
+                    })
+            })
+            .map(|(&(x, y, _), &(voxel, _))| (x, y, voxel))
+            .collect();
+        self.set_columns(size, cells)?;
+        self.recompute_visibility()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DilateOptions;
+    use crate::kv6::testing::grenade;
+    use crate::kv6::{FillColor, KV6Builder, KV6Format, Rgb};
+
+    const WHITE: Rgb = Rgb::new(255, 255, 255);
+    const RED: Rgb = Rgb::new(255, 0, 0);
+
+    /// A `size`³ grid with every cell for which `keep` holds filled white.
+    fn cells(size: u32, keep: impl Fn(u32, u32, u32) -> bool) -> KV6Format {
+        let mut builder = KV6Builder::new(size, size, size);
+        for x in 0..size {
+            for y in 0..size {
+                for z in 0..size {
+                    if keep(x, y, z) {
+                        builder = builder.voxel(x, y, z as u16, WHITE);
+                    }
+                }
+            }
+        }
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn test_dilate_single() {
+        let mut data = cells(5, |x, y, z| (x, y, z) == (2, 2, 2));
+        data.dilate(1, FillColor::Constant(RED)).unwrap();
+        assert_eq!(data.voxels.len(), 7);
+        assert!(data.validate().is_clean(), "{:?}", data.validate());
+        assert_eq!(data.get_voxel(2, 2, 2).unwrap().color(), WHITE);
+        assert_eq!(data.get_voxel(2, 1, 2).unwrap().color(), RED);
+        assert_eq!(data.get_voxel(2, 2, 3).unwrap().color(), RED);
+
+        // Twice reaches every cell within two steps. The six next to the
+        // middle end up inside and aren't stored.
+        let mut data = cells(5, |x, y, z| (x, y, z) == (2, 2, 2));
+        data.dilate(2, FillColor::NearestNeighbor).unwrap();
+        assert_eq!(data.voxels.len(), 19);
+        assert!(data.get_voxel(2, 2, 1).is_none());
+        assert!(data.colors().all(|color| color == WHITE));
+        let mut solid = data.clone();
+        solid.fill_interior(FillColor::Constant(RED)).unwrap();
+        assert_eq!(solid.voxels.len(), 25);
+    }
+
+    #[test]
+    fn test_dilate_canvas() {
+        // In a corner half the neighbours are off the grid.
+        let mut data = cells(3, |x, y, z| (x, y, z) == (0, 0, 0));
+        data.dilate(1, FillColor::NearestNeighbor).unwrap();
+        assert_eq!(data.voxels.len(), 4);
+        assert_eq!((data.x_size, data.y_size, data.z_size), (3, 3, 3));
+
+        let mut data = cells(3, |x, y, z| (x, y, z) == (0, 0, 0));
+        let pivot = data.pivot();
+        data.dilate_with(
+            2,
+            &DilateOptions {
+                grow_canvas: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!((data.x_size, data.y_size, data.z_size), (7, 7, 7));
+        assert_eq!(data.voxels.len(), 19);
+        assert_eq!(data.get_voxel(2, 2, 2).unwrap().color(), WHITE);
+        assert_eq!(data.pivot(), pivot.map(|c| c + 2.0));
+        assert!(data.validate().is_clean(), "{:?}", data.validate());
+    }
+
+    #[test]
+    fn test_erode_then_dilate() {
+        let inner = |c: u32| (1..6).contains(&c);
+        let mut data = cells(7, |x, y, z| inner(x) && inner(y) && inner(z));
+        data.erode(1).unwrap();
+        assert!(data.equivalent(&cells(7, |x, y, z| {
+            [x, y, z].iter().all(|c| (2..5).contains(c))
+        })));
+
+        // Growing back fills the faces but not the edges and corners, which
+        // are two steps away.
+        data.dilate(1, FillColor::NearestNeighbor).unwrap();
+        let expected = cells(7, |x, y, z| {
+            let on_rim = [x, y, z].iter().filter(|&&c| c == 1 || c == 5).count();
+            inner(x) && inner(y) && inner(z) && on_rim <= 1
+        });
+        assert!(data.equivalent(&expected));
+        assert_eq!(data.voxels.len(), 81);
+        assert!(data.validate().is_clean(), "{:?}", data.validate());
+    }
+
+    #[test]
+    fn test_erode_interior() {
+        // Only the shell is stored, so eroding has to bring the interior out.
+        let mut data = cells(5, |_, _, _| true);
+        data.strip_hidden().unwrap();
+        assert_eq!(data.voxels.len(), 98);
+        data.erode(1).unwrap();
+        assert_eq!(data.voxels.len(), 26);
+        assert_eq!(data.surface_voxel_count().unwrap(), 26);
+        assert!(data.colors().all(|color| color == WHITE));
+        assert!(data.get_voxel(0, 2, 2).is_none());
+        assert!(data.get_voxel(1, 2, 2).is_some());
+
+        data.erode(5).unwrap();
+        assert!(data.voxels.is_empty());
+        assert_eq!((data.x_size, data.y_size, data.z_size), (5, 5, 5));
+    }
+
+    #[test]
+    fn test_no_op() {
+        let mut data = grenade();
+        data.erode(0).unwrap();
+        data.dilate(0, FillColor::Constant(RED)).unwrap();
+        assert_eq!(data, grenade());
+
+        let mut broken = grenade();
+        broken.xlen[0] += 1;
+        assert!(broken.erode(1).is_err());
+        assert!(broken.dilate(1, FillColor::NearestNeighbor).is_err());
+    }
+}
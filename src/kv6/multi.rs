@@ -0,0 +1,110 @@
+use super::KV6Format;
+use crate::Kv6Error;
+use alloc::vec::Vec;
+
+/// Iterates over models stored back to back in one buffer.
+///
+/// Each model is parsed with the default [`ParseOptions`](super::ParseOptions)
+/// and the next one starts right where it ended. Iteration stops at the end
+/// of the buffer, or after the first error, such as a partial model at the
+/// end. Offsets in errors are relative to the start of the whole buffer.
+pub struct Kv6Stream<'a> {
+    src: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Kv6Stream<'a> {
+    pub fn new(src: &'a [u8]) -> Self {
+        Self { src, offset: 0 }
+    }
+
+    /// Where the next model starts.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl Iterator for Kv6Stream<'_> {
+    type Item = Result<KV6Format, Kv6Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.offset >= self.src.len() {
+            return None;
+        }
+
+        match KV6Format::parse(&self.src[self.offset..]) {
+            Ok((format, consumed)) => {
+                self.offset += consumed;
+                Some(Ok(format))
+            }
+            Err(err) => {
+                let err = match err {
+                    Kv6Error::Truncated { section, offset } => Kv6Error::Truncated {
+                        section,
+                        offset: self.offset + offset,
+                    },
+                    err => err,
+                };
+                self.offset = self.src.len();
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// Parses every model stored back to back in `src`.
+pub fn parse_all(src: &[u8]) -> Result<Vec<KV6Format>, Kv6Error> {
+    Kv6Stream::new(src).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_all, Kv6Stream};
+    use crate::{
+        kv6::{KV6Format, Section},
+        Kv6Error,
+    };
+
+    const GRENADE: &[u8] = include_bytes!("../../data/grenade.kv6");
+
+    #[test]
+    fn test_parse_all() {
+        let grenade = KV6Format::parse(GRENADE).unwrap().0;
+        let mut bare = KV6Format::parse(GRENADE).unwrap().0;
+        bare.palette = None;
+        let empty = KV6Format::default();
+
+        let originals = [grenade, bare, empty];
+        let mut blob = alloc::vec::Vec::new();
+        for model in &originals {
+            blob.extend(model.to_bytes().unwrap());
+        }
+
+        let models = parse_all(&blob).unwrap();
+        assert_eq!(models.len(), 3);
+        for (model, original) in models.iter().zip(&originals) {
+            assert_eq!(model.to_bytes().unwrap(), original.to_bytes().unwrap());
+        }
+        assert!(parse_all(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_all_partial() {
+        let model = KV6Format::parse(GRENADE).unwrap().0.to_bytes().unwrap();
+        let mut blob = model.clone();
+        blob.extend_from_slice(&model[..100]);
+
+        let mut stream = Kv6Stream::new(&blob);
+        assert!(stream.next().unwrap().is_ok());
+        assert_eq!(stream.offset(), model.len());
+        assert!(matches!(
+            stream.next(),
+            Some(Err(Kv6Error::Truncated {
+                section: Section::Voxels,
+                offset,
+            })) if offset == model.len() + 96
+        ));
+        assert!(stream.next().is_none());
+        assert!(parse_all(&blob).is_err());
+    }
+}
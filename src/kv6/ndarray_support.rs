@@ -0,0 +1,94 @@
+use super::{DenseVoxelGrid, KV6Format, Rgb};
+use crate::Kv6Error;
+use ndarray::Array3;
+
+impl KV6Format {
+    /// The model as an array indexed `[[x, y, z]]`, holding each stored
+    /// voxel's color.
+    ///
+    /// The axes are the file's: x to the right, y to the front, and z
+    /// *down*, so `[[x, y, 0]]` is the top of column `(x, y)`. Built through
+    /// [`KV6Format::to_dense`], with the same size limit.
+    pub fn to_array3(&self) -> Result<Array3<Option<Rgb>>, Kv6Error> {
+        let grid = self.to_dense()?;
+        let (x, y, z) = grid.dimensions();
+        let shape = (x as usize, y as usize, z as usize);
+        Ok(Array3::from_shape_vec(shape, grid.into_cells())
+            .expect("grids are x-major with z innermost, like a standard layout array"))
+    }
+
+    /// Builds a model from an array indexed `[[x, y, z]]`, with the axes
+    /// described in [`KV6Format::to_array3`], and sets the pivot to `pivot`.
+    ///
+    /// Goes through [`KV6Format::from_dense`], so visibility and normals are
+    /// worked out the same way. Fails if an axis is longer than the format
+    /// can store: heights are 16 bits, so z has at most 65536 cells.
+    pub fn from_array3(
+        array: &Array3<Option<Rgb>>,
+        pivot: (f32, f32, f32),
+    ) -> Result<KV6Format, Kv6Error> {
+        let (x, y, z) = array.dim();
+        let size = |field, value: usize, limit: u32| {
+            u32::try_from(value)
+                .ok()
+                .filter(|&value| value <= limit)
+                .ok_or(Kv6Error::LimitExceeded {
+                    field,
+                    value: value.min(u32::MAX as usize) as u32,
+                    limit,
+                })
+        };
+        let (x, y, z) = (
+            size("x_size", x, u32::MAX)?,
+            size("y_size", y, u32::MAX)?,
+            size("z_size", z, 1 << 16)?,
+        );
+
+        // `iter` walks the array in logical order whatever its memory layout.
+        let grid = DenseVoxelGrid::from_cells(x, y, z, array.iter().copied().collect());
+        let mut data = KV6Format::from_dense(&grid)?;
+        (data.x_pivot, data.y_pivot, data.z_pivot) = pivot;
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kv6::testing::grenade;
+    use crate::kv6::{KV6Format, Rgb};
+    use ndarray::{Array3, ShapeBuilder};
+
+    #[test]
+    fn test_axes_match_get_voxel() {
+        let data = grenade();
+        let array = data.to_array3().unwrap();
+        assert_eq!(array.dim(), (6, 6, 9));
+        for ((x, y, z), &cell) in array.indexed_iter() {
+            let voxel = data.get_voxel(x as u32, y as u32, z as u16);
+            assert_eq!(cell, voxel.map(|voxel| voxel.color()));
+        }
+        // Column (3, 2) starts at the top of the model.
+        assert!(array[[3, 2, 0]].is_some());
+        assert!(data.column(3, 2).unwrap()[0].height == 0);
+    }
+
+    #[test]
+    fn test_array_round_trip() {
+        let data = grenade();
+        let array = data.to_array3().unwrap();
+        let rebuilt = KV6Format::from_array3(&array, (2.5, 2.5, 3.5)).unwrap();
+        assert!(rebuilt.equivalent(&data));
+        assert_eq!(rebuilt.to_array3().unwrap(), array);
+        assert_eq!(
+            (rebuilt.x_pivot, rebuilt.y_pivot, rebuilt.z_pivot),
+            (2.5, 2.5, 3.5)
+        );
+
+        // A Fortran-ordered array means the same thing.
+        let mut fortran = Array3::from_elem((2, 3, 4).f(), None);
+        fortran[[1, 2, 3]] = Some(Rgb::new(1, 2, 3));
+        let model = KV6Format::from_array3(&fortran, (0.0, 0.0, 0.0)).unwrap();
+        assert_eq!(model.voxels.len(), 1);
+        assert_eq!(model.get_voxel(1, 2, 3).unwrap().color(), Rgb::new(1, 2, 3));
+    }
+}
@@ -0,0 +1,896 @@
+//! The unit vectors `normalindex` refers to.
+
+use super::{Face, KV6Format, VoxelData};
+use crate::Kv6Error;
+
+/// slab6's table of 256 directions, spread evenly over the sphere along a
+/// golden-angle spiral. Entry `i` has `z = (i + 0.5) / 128 - 1`, and lies at
+/// `i * 2π * 0.381966…` around the z axis, with `x = cos` and `y = sin` of
+/// that angle.
+///
+/// This uses Voxlap's axes: x to the right, y to the front and z down, so
+/// index 0 points almost straight up (towards lower heights) and index 255
+/// almost straight down. The values are what slab6 computes in single
+/// precision.
+///
+/// slab6 stores normals pointing into the solid rather than out of it: a
+/// voxel whose top face is exposed gets an index near 255.
+pub const NORMAL_TABLE_SLAB6: [[f32; 3]; 256] = [
+    [0.08830199, 0.0, -0.99609375],
+    [-0.112554885, 0.1031095, -0.98828125],
+    [0.01719445, -0.19592188, -0.98046875],
+    [0.1413096, 0.18431336, -0.97265625],
+    [-0.2588065, -0.045779224, -0.96484375],
+    [0.2446762, -0.15564297, -0.95703125],
+    [-0.081675895, 0.30383024, -0.94921875],
+    [-0.1554523, -0.29931396, -0.94140625],
+    [0.33659002, 0.12292214, -0.93359375],
+    [-0.34945774, 0.14425108, -0.92578125],
+    [0.16811967, -0.35926196, -0.91796875],
+    [0.12398287, 0.39527693, -0.91015625],
+    [-0.37292084, -0.21611525, -0.90234375],
+    [0.43657935, -0.09598075, -0.89453125],
+    [-0.26588768, 0.37819785, -0.88671875],
+    [-0.061298896, -0.47303936, -0.87890625],
+    [0.37553194, 0.3164987, -0.87109375],
+    [-0.5042922, 0.020854065, -0.86328125],
+    [0.36707047, -0.36528414, -0.85546875],
+    [-0.024506679, 0.5299795, -0.84765625],
+    [-0.347795, -0.41677466, -0.83984375],
+    [0.5497747, 0.073971406, -0.83203125],
+    [-0.46482873, 0.3234157, -0.82421875],
+    [0.12674566, -0.56339717, -0.81640625],
+    [0.292525, 0.5104951, -0.80859375],
+    [-0.57062197, -0.18204397, -0.80078125],
+    [0.5530829, -0.25553834, -0.79296875],
+    [-0.23908675, 0.57128555, -0.78515625],
+    [-0.21291223, -0.59195024, -0.77734375],
+    [0.5652901, 0.29710063, -0.76953125],
+    [-0.6265074, 0.16514535, -0.76171875],
+    [0.3553207, -0.5526053, -0.75390625],
+    [0.11277759, 0.6562205, -0.74609375],
+    [-0.5332698, -0.41299412, -0.73828125],
+    [0.68061435, -0.056387506, -0.73046875],
+    [-0.4693848, 0.5073912, -0.72265625],
+    [0.0034113019, -0.69927585, -0.71484375],
+    [0.4751453, 0.5237784, -0.70703125],
+    [-0.7118571, -0.06597475, -0.69921875],
+    [0.57548726, -0.43677428, -0.69140625],
+    [-0.13063344, 0.71807694, -0.68359375],
+    [-0.39258477, -0.6238564, -0.67578125],
+    [0.71772385, 0.19669834, -0.66796875],
+    [-0.66826856, 0.3429444, -0.66015625],
+    [0.26346686, -0.71065664, -0.65234375],
+    [0.28827852, 0.708149, -0.64453125],
+    [-0.69680554, -0.33022913, -0.63671875],
+    [0.7429709, -0.22906588, -0.62890625],
+    [-0.39627457, 0.6761723, -0.62109375],
+    [-0.16583395, -0.7722598, -0.61328125],
+    [0.6488299, 0.46089837, -0.60546875],
+    [-0.79559755, 0.099153794, -0.59765625],
+    [0.5234081, -0.6149214, -0.58984375],
+    [0.029634405, 0.81262624, -0.58203125],
+    [-0.57465804, -0.5831303, -0.57421875],
+    [0.82305104, 0.04208315, -0.56640625],
+    [-0.63941646, 0.5283177, -0.55859375],
+    [0.11533198, -0.82664293, -0.55078125],
+    [0.47624123, 0.6916496, -0.54296875],
+    [-0.82324094, -0.18942581, -0.53515625],
+    [0.73924994, -0.41882947, -0.52734375],
+    [-0.26366565, 0.8127532, -0.51953125],
+    [-0.35653913, -0.7816801, -0.51171875],
+    [0.79515773, 0.3373465, -0.50390625],
+    [-0.8184507, 0.28987843, -0.49609375],
+    [0.40976414, -0.7705029, -0.48828125],
+    [0.21940207, 0.84912455, -0.48046875],
+    [-0.7389066, -0.48022187, -0.47265625],
+    [0.8733213, -0.14570586, -0.46484375],
+    [-0.54803735, 0.70055515, -0.45703125],
+    [-0.0694211, -0.89072067, -0.44921875],
+    [0.65570146, 0.6125489, -0.44140625],
+    [-0.9010655, -0.008791609, -0.43359375],
+    [0.673122, -0.6046627, -0.42578125],
+    [-0.08824853, 0.90416497, -0.41796875],
+    [-0.54781765, -0.7291554, -0.41015625],
+    [0.8998953, 0.16824938, -0.40234375],
+    [-0.7800869, 0.4856023, -0.39453125],
+    [0.24808398, -0.8882021, -0.38671875],
+    [0.4185065, 0.8253983, -0.37890625],
+    [-0.8691001, -0.327039, -0.37109375],
+    [0.86462116, -0.3470692, -0.36328125],
+    [-0.40440458, 0.84267366, -0.35546875],
+    [-0.27187327, -0.89734054, -0.34765625],
+    [0.80907595, 0.47948128, -0.33984375],
+    [-0.92319953, 0.19354011, -0.33203125],
+    [0.55158657, -0.7685275, -0.32421875],
+    [0.11272405, 0.9419025, -0.31640625],
+    [-0.72131425, -0.62006104, -0.30859375],
+    [0.9532178, -0.030105948, -0.30078125],
+    [-0.6842752, 0.667785, -0.29296875],
+    [0.0536129, -0.9569805, -0.28515625],
+    [0.6083483, 0.7436348, -0.27734375],
+    [-0.9530933, -0.13771738, -0.26953125],
+    [0.79758686, -0.5434688, -0.26171875],
+    [-0.22148521, 0.9415285, -0.25390625],
+    [-0.47366285, -0.8456248, -0.24609375],
+    [0.92232764, 0.30419362, -0.23828125],
+    [-0.88729304, 0.39949375, -0.23046875],
+    [0.385126, -0.8956016, -0.22265625],
+    [0.32156688, 0.9221914, -0.21484375],
+    [-0.86152935, -0.46357873, -0.20703125],
+    [0.94997895, -0.24052396, -0.19921875],
+    [-0.53886753, 0.82035685, -0.19140625],
+    [-0.1570372, -0.97037756, -0.18359375],
+    [0.77239466, 0.61033386, -0.17578125],
+    [-0.98317385, 0.071803175, -0.16796875],
+    [0.67735106, -0.71801496, -0.16015625],
+    [-0.014463549, 0.9882217, -0.15234375],
+    [-0.65764856, -0.7393301, -0.14453125],
+    [0.98544383, 0.10103695, -0.13671875],
+    [-0.7957251, 0.59178096, -0.12890625],
+    [0.18718626, -0.9748321, -0.12109375],
+    [0.52094764, 0.8460383, -0.11328125],
+    [-0.9564481, -0.27218255, -0.10546875],
+    [0.8898248, -0.4457297, -0.09765625],
+    [-0.35530555, 0.9304225, -0.08984375],
+    [-0.36674815, -0.9266967, -0.08203125],
+    [0.8969539, 0.43585005, -0.07421875],
+    [-0.9563262, 0.2846585, -0.06640625],
+    [0.5131326, -0.85630697, -0.05859375],
+    [0.20014484, 0.9784494, -0.05078125],
+    [-0.8088105, -0.5864975, -0.04296875],
+    [0.9928685, -0.11391345, -0.03515625],
+    [-0.655323, 0.75485367, -0.02734375],
+    [-0.026686441, -0.99945307, -0.01953125],
+    [0.6948834, 0.7190269, -0.01171875],
+    [-0.998142, -0.06080478, -0.00390625],
+    [0.77707195, -0.62939966, 0.00390625],
+    [-0.14782624, 0.98894393, 0.01171875],
+    [-0.5589513, -0.82897043, 0.01953125],
+    [0.9719367, 0.233648, 0.02734375],
+    [-0.87428916, 0.4841307, 0.03515625],
+    [0.31755093, -0.94726723, 0.04296875],
+    [0.40556872, 0.91265285, 0.05078125],
+    [-0.9151496, -0.39883316, 0.05859375],
+    [0.94374806, -0.32392874, 0.06640625],
+    [-0.47681642, 0.87586397, 0.07421875],
+    [-0.23990065, -0.9673255, 0.08203125],
+    [0.82975286, 0.5508524, 0.08984375],
+    [-0.9832025, 0.15419455, 0.09765625],
+    [0.6203285, -0.77721864, 0.10546875],
+    [0.067534305, 0.99126506, 0.11328125],
+    [-0.7187202, -0.68467337, 0.12109375],
+    [0.991468, 0.019349154, 0.12890625],
+    [-0.74336225, 0.6547676, 0.13671875],
+    [0.105724454, -0.9838359, 0.14453125],
+    [0.5859185, 0.79592144, 0.15234375],
+    [-0.96846265, -0.19086649, 0.16015625],
+    [0.84193295, -0.51277226, 0.16796875],
+    [-0.274063, 0.9455107, 0.17578125],
+    [-0.43596485, -0.881038, 0.18359375],
+    [0.91520894, 0.35462117, 0.19140625],
+    [-0.9129403, 0.3561627, 0.19921875],
+    [0.43187386, -0.8778513, 0.20703125],
+    [0.2740566, 0.93740875, 0.21484375],
+    [-0.8337934, -0.50518584, 0.22265625],
+    [0.9542793, -0.19035551, 0.23046875],
+    [-0.57395935, 0.7834492, 0.23828125],
+    [-0.1057797, -0.9634566, 0.24609375],
+    [0.7272874, 0.6376399, 0.25390625],
+    [-0.9649145, 0.021054305, 0.26171875],
+    [0.69572085, -0.66582686, 0.26953125],
+    [-0.06309744, 0.95869654, 0.27734375],
+    [-0.5996317, -0.7477485, 0.28515625],
+    [0.94491524, 0.14596091, 0.29296875],
+    [-0.7933258, 0.52930593, 0.30078125],
+    [0.22683671, -0.9237505, 0.30859375],
+    [0.45548812, 0.83211637, 0.31640625],
+    [-0.8954488, -0.3050471, 0.32421875],
+    [0.86384714, -0.37884477, 0.33203125],
+    [-0.3799423, 0.86031973, 0.33984375],
+    [-0.30006477, -0.8883109, 0.34765625],
+    [0.8187339, 0.4509066, 0.35546875],
+    [-0.90536827, 0.21985233, 0.36328125],
+    [0.51736397, -0.77111864, 0.37109375],
+    [0.13892066, 0.91494864, 0.37890625],
+    [-0.71795434, -0.57878333, 0.38671875],
+    [0.91705114, -0.0579852, 0.39453125],
+    [-0.63468367, 0.6597698, 0.40234375],
+    [0.022243198, -0.91174394, 0.41015625],
+    [0.59713674, 0.68463844, 0.41796875],
+    [-0.89916414, -0.10106491, 0.42578125],
+    [0.7282794, -0.5306652, 0.43359375],
+    [-0.17779821, 0.879516, 0.44140625],
+    [-0.46099675, -0.76530033, 0.44921875],
+    [0.8530688, 0.25178567, 0.45703125],
+    [-0.7954594, 0.38879886, 0.46484375],
+    [0.32240045, -0.82015485, 0.47265625],
+    [0.31475866, 0.8185822, 0.48046875],
+    [-0.78116566, -0.38905224, 0.48828125],
+    [0.83456236, -0.23957595, 0.49609375],
+    [-0.45119292, 0.7365483, 0.50390625],
+    [-0.16395678, -0.8433635, 0.51171875],
+    [0.6868015, 0.5083219, 0.51953125],
+    [-0.8450192, 0.0886065, 0.52734375],
+    [0.5599905, -0.6324701, 0.53515625],
+    [0.014223146, 0.83963245, 0.54296875],
+    [-0.5741408, -0.6058072, 0.55078125],
+    [0.8273751, 0.058509402, 0.55859375],
+    [-0.64544046, 0.5124359, 0.56640625],
+    [0.128928, -0.8084865, 0.57421875],
+    [0.44800755, 0.6786228, 0.58203125],
+    [-0.78327054, -0.19639668, 0.58984375],
+    [0.705153, -0.38153145, 0.59765625],
+    [-0.26031274, 0.7520936, 0.60546875],
+    [-0.3137007, -0.7248986, 0.61328125],
+    [0.71538126, 0.32011276, 0.62109375],
+    [-0.73779714, 0.245219, 0.62890625],
+    [0.37527806, -0.6736138, 0.63671875],
+    [0.17679404, 0.7438571, 0.64453125],
+    [-0.6273227, -0.42533982, 0.65234375],
+    [0.7431583, -0.10913076, 0.66015625],
+    [-0.4698837, 0.57708496, 0.66796875],
+    [-0.04292495, -0.7358513, 0.67578125],
+    [0.52351916, 0.5085541, 0.68359375],
+    [-0.7221567, -0.021143405, 0.69140625],
+    [0.5410577, -0.46727905, 0.69921875],
+    [-0.08241631, 0.7023634, 0.70703125],
+    [-0.40904844, -0.56716645, 0.71484375],
+    [0.6768264, 0.1402637, 0.72265625],
+    [-0.5867201, 0.34953538, 0.73046875],
+    [0.19408897, -0.6459646, 0.73828125],
+    [0.2894663, 0.59962773, 0.74609375],
+    [-0.61025727, -0.24333398, 0.75390625],
+    [0.605869, -0.22958058, 0.76171875],
+    [-0.2874835, 0.5702411, 0.76953125],
+    [-0.17062522, -0.6054946, 0.77734375],
+    [0.52650625, 0.3260688, 0.78515625],
+    [-0.59862536, 0.11334987, 0.79296875],
+    [0.35866988, -0.47969297, 0.80078125],
+    [0.05850296, 0.58545154, 0.80859375],
+    [-0.4304875, -0.3849173, 0.81640625],
+    [0.56623036, -0.006828963, 0.82421875],
+    [-0.4044911, 0.37961948, 0.83203125],
+    [0.040932514, -0.5412827, 0.83984375],
+    [0.32785878, 0.4171181, 0.84765625],
+    [-0.51098895, -0.08404473, 0.85546875],
+    [0.42256498, -0.2760151, 0.86328125],
+    [-0.12176718, 0.47578192, 0.87109375],
+    [-0.22493903, -0.42062598, 0.87890625],
+    [0.43613788, 0.15334149, 0.88671875],
+    [-0.4111006, 0.17552821, 0.89453125],
+    [0.17796372, -0.39256167, 0.90234375],
+    [0.12874071, 0.393753, 0.91015625],
+    [-0.34556186, -0.19473152, 0.91796875],
+    [0.3682359, -0.08562372, 0.92578125],
+    [-0.20253788, 0.29560295, 0.93359375],
+    [-0.047373906, -0.33393112, 0.94140625],
+    [0.24299908, 0.19983797, 0.94921875],
+    [-0.2895716, 0.015474761, 0.95703125],
+    [0.1840482, -0.18762405, 0.96484375],
+    [-0.007929332, 0.23211408, 0.97265625],
+    [-0.12782331, -0.14947319, 0.98046875],
+    [0.1515152, 0.018528756, 0.98828125],
+    [-0.07186996, 0.051302526, 0.99609375],
+];
+
+/// Voxlap's table of 255 directions. It's built the same way as
+/// [`NORMAL_TABLE_SLAB6`], with `z = (i + 0.5) / 127.5 - 1`, so every entry
+/// is slightly off from slab6's. Index 255 has no entry; Voxlap treats it as
+/// "no normal".
+pub const NORMAL_TABLE_VOXLAP: [[f32; 3]; 255] = [
+    [0.088474624, 0.0, -0.99607843],
+    [-0.11277408, 0.103310294, -0.9882353],
+    [0.0172278, -0.1963019, -0.98039216],
+    [0.14158253, 0.18466933, -0.972549],
+    [-0.25930434, -0.04586729, -0.9647059],
+    [0.24514498, -0.15594116, -0.95686275],
+    [-0.08183169, 0.30440983, -0.9490196],
+    [-0.15574759, -0.2998825, -0.9411765],
+    [0.3372267, 0.12315465, -0.93333334],
+    [-0.3501158, 0.14452273, -0.9254902],
+    [0.16843487, -0.35993555, -0.91764706],
+    [0.12421428, 0.39601472, -0.9098039],
+    [-0.37361372, -0.2165168, -0.9019608],
+    [0.43738675, -0.09615825, -0.89411765],
+    [-0.26637712, 0.37889403, -0.8862745],
+    [-0.06141121, -0.4739061, -0.8784314],
+    [0.37621668, 0.31707582, -0.87058824],
+    [-0.5052073, 0.020891909, -0.8627451],
+    [0.36773333, -0.3659438, -0.85490197],
+    [-0.024550714, 0.53093183, -0.84705883],
+    [-0.3484168, -0.41751978, -0.8392157],
+    [0.5507526, 0.07410298, -0.83137256],
+    [-0.46565124, 0.323988, -0.8235294],
+    [0.12696876, -0.5643889, -0.8156863],
+    [0.29303724, 0.511389, -0.8078431],
+    [-0.57161576, -0.18236102, -0.79999995],
+    [0.55404097, -0.25598097, -0.7921568],
+    [-0.23949856, 0.57226956, -0.7843137],
+    [-0.21327691, -0.5929641, -0.77647054],
+    [0.5662528, 0.29760662, -0.7686274],
+    [-0.62756824, 0.16542499, -0.76078427],
+    [0.3559188, -0.55353546, -0.75294113],
+    [0.11296629, 0.6573185, -0.745098],
+    [-0.5341567, -0.41368097, -0.73725486],
+    [0.68173933, -0.05648071, -0.7294117],
+    [-0.4701558, 0.5082246, -0.7215686],
+    [0.0034168698, -0.7004172, -0.71372545],
+    [0.47591585, 0.5246278, -0.7058823],
+    [-0.71300393, -0.06608105, -0.6980392],
+    [0.57640827, -0.43747333, -0.69019604],
+    [-0.13084109, 0.7192184, -0.6823529],
+    [-0.39320457, -0.6248414, -0.67450976],
+    [0.71884906, 0.19700672, -0.6666666],
+    [-0.6693088, 0.34347823, -0.6588235],
+    [0.26387405, -0.7117549, -0.65098035],
+    [0.28872076, 0.7092354, -0.6431372],
+    [-0.6978666, -0.33073202, -0.6352941],
+    [0.74409366, -0.22941203, -0.62745094],
+    [-0.3968688, 0.67718625, -0.6196078],
+    [-0.1660807, -0.77340883, -0.61176467],
+    [0.6497876, 0.46157867, -0.60392153],
+    [-0.79676235, 0.099298954, -0.5960784],
+    [0.5241681, -0.6158142, -0.58823526],
+    [0.029677069, 0.81379616, -0.5803921],
+    [-0.5754783, -0.5839627, -0.572549],
+    [0.82421553, 0.042142693, -0.56470585],
+    [-0.64031315, 0.5290586, -0.5568627],
+    [0.11549225, -0.8277917, -0.5490196],
+    [0.4768969, 0.69260186, -0.54117644],
+    [-0.8243637, -0.18968415, -0.5333333],
+    [0.74024844, -0.41939518, -0.52549016],
+    [-0.2640183, 0.8138402, -0.517647],
+    [-0.35701123, -0.78271514, -0.5098039],
+    [0.7961999, 0.33778864, -0.50196075],
+    [-0.8195122, 0.29025438, -0.49411762],
+    [0.4102899, -0.7714916, -0.48627448],
+    [0.21968053, 0.85020226, -0.47843134],
+    [-0.739834, -0.48082462, -0.4705882],
+    [0.87440497, -0.14588666, -0.46274507],
+    [-0.5487095, 0.7014144, -0.45490193],
+    [-0.06950523, -0.8918001, -0.4470588],
+    [0.65648645, 0.61328226, -0.43921566],
+    [-0.9021309, -0.008802004, -0.43137252],
+    [0.67390776, -0.6053686, -0.4235294],
+    [-0.0883502, 0.9052067, -0.41568625],
+    [-0.5484404, -0.72998434, -0.4078431],
+    [0.90090436, 0.16843803, -0.39999998],
+    [-0.78094923, 0.4861391, -0.39215684],
+    [0.2483543, -0.88916993, -0.3843137],
+    [0.4189558, 0.82628447, -0.37647057],
+    [-0.870019, -0.32738477, -0.36862743],
+    [0.8655211, -0.34743044, -0.3607843],
+    [-0.4048188, 0.8435368, -0.35294116],
+    [-0.27214715, -0.8982445, -0.34509802],
+    [0.8098773, 0.47995618, -0.33725488],
+    [-0.9240981, 0.19372848, -0.32941175],
+    [0.5521137, -0.769262, -0.3215686],
+    [0.1128298, 0.94278616, -0.31372547],
+    [-0.721978, -0.62063164, -0.30588233],
+    [0.95407784, -0.030133111, -0.2980392],
+    [-0.6848801, 0.6683753, -0.29019606],
+    [0.053659298, -0.95780873, -0.28235292],
+    [0.6088635, 0.7442645, -0.2745098],
+    [-0.9538823, -0.13783139, -0.26666665],
+    [0.79823184, -0.5439083, -0.2588235],
+    [-0.22166002, 0.9422716, -0.25098038],
+    [-0.47402737, -0.84627557, -0.24313718],
+    [0.92301905, 0.30442163, -0.23529404],
+    [-0.8879403, 0.3997852, -0.2274509],
+    [0.38539907, -0.8962366, -0.21960777],
+    [0.32178822, 0.9228261, -0.21176463],
+    [-0.86210436, -0.46388814, -0.2039215],
+    [0.9505928, -0.24067938, -0.19607836],
+    [-0.5392041, 0.82086927, -0.18823522],
+    [-0.15713187, -0.9709626, -0.18039209],
+    [0.77284324, 0.6106883, -0.17254895],
+    [-0.9837228, 0.07184326, -0.16470581],
+    [0.67771393, -0.7183996, -0.15686268],
+    [-0.014470964, 0.98872834, -0.14901954],
+    [-0.6579704, -0.7396919, -0.1411764],
+    [0.9859028, 0.10108401, -0.13333327],
+    [-0.79607666, 0.5920424, -0.12549013],
+    [0.1872644, -0.97523904, -0.11764699],
+    [0.5211523, 0.8463707, -0.109803855],
+    [-0.9567999, -0.2722827, -0.10196072],
+    [0.8901296, -0.44588235, -0.09411758],
+    [-0.35541812, 0.93071723, -0.086274445],
+    [-0.36685476, -0.92696613, -0.07843131],
+    [0.897191, 0.43596524, -0.07058817],
+    [-0.9565533, 0.28472608, -0.062745035],
+    [0.51324046, -0.85648704, -0.054901898],
+    [0.20018138, 0.97862804, -0.04705876],
+    [-0.80893546, -0.5865881, -0.039215624],
+    [0.99299353, -0.1139278, -0.031372488],
+    [-0.6553866, 0.7549269, -0.02352935],
+    [-0.026688248, -0.9995208, -0.015686214],
+    [0.69490975, 0.71905416, -0.007843077],
+    [-0.99814963, -0.060805246, 5.9604645e-8],
+    [0.77705395, -0.6293851, 0.007843196],
+    [-0.1478182, 0.9888902, 0.015686333],
+    [-0.55890316, -0.828899, 0.02352947],
+    [0.9718216, 0.23362033, 0.031372607],
+    [-0.874157, 0.48405755, 0.039215744],
+    [0.31749234, -0.9470925, 0.04705888],
+    [0.40548018, 0.9124536, 0.054902017],
+    [-0.91491836, -0.39873233, 0.062745154],
+    [0.9434765, -0.32383552, 0.07058829],
+    [-0.47666225, 0.8755807, 0.07843143],
+    [-0.23981437, -0.9669776, 0.086274564],
+    [0.8294239, 0.5506341, 0.0941177],
+    [-0.982776, 0.15412764, 0.10196084],
+    [0.62003577, -0.77685183, 0.109803975],
+    [0.06749981, 0.9907587, 0.11764711],
+    [-0.71832466, -0.6842966, 0.12549025],
+    [0.9908826, 0.01933773, 0.13333338],
+    [-0.7428929, 0.6543542, 0.14117652],
+    [0.1056533, -0.9831737, 0.14901966],
+    [0.5854992, 0.795352, 0.1568628],
+    [-0.96772784, -0.19072168, 0.16470593],
+    [0.84125704, -0.51236063, 0.17254907],
+    [-0.27383068, 0.9447092, 0.1803922],
+    [-0.43557537, -0.8802509, 0.18823534],
+    [0.9143486, 0.3542878, 0.19607848],
+    [-0.9120387, 0.35581094, 0.20392162],
+    [0.4314264, -0.8769418, 0.21176475],
+    [0.2737591, 0.93639106, 0.21960789],
+    [-0.83284616, -0.50461197, 0.22745103],
+    [0.9531461, -0.19012946, 0.23529416],
+    [-0.5732476, 0.7824777, 0.2431373],
+    [-0.105642855, -0.9622102, 0.25098044],
+    [0.72630674, 0.6367801, 0.25882357],
+    [-0.9635595, 0.02102474, 0.2666667],
+    [0.69470406, -0.6648538, 0.27450985],
+    [-0.06300155, 0.95723957, 0.28235298],
+    [-0.5986846, -0.7465674, 0.29019612],
+    [0.94336516, 0.14572148, 0.29803926],
+    [-0.79197496, 0.5284047, 0.3058824],
+    [0.22643602, -0.9221188, 0.31372553],
+    [0.45465383, 0.8305923, 0.32156867],
+    [-0.8937489, -0.304468, 0.3294118],
+    [0.8621483, -0.37809974, 0.33725494],
+    [-0.37916857, 0.8585677, 0.34509808],
+    [-0.29943222, -0.8864383, 0.35294122],
+    [0.81694794, 0.449923, 0.36078435],
+    [-0.9033253, 0.21935624, 0.3686275],
+    [0.5161567, -0.76931924, 0.37647063],
+    [0.13858552, 0.9127414, 0.38431376],
+    [-0.71616423, -0.5773402, 0.3921569],
+    [0.91468847, -0.057835806, 0.40000004],
+    [-0.6329944, 0.6580137, 0.40784317],
+    [0.02218205, -0.9092375, 0.4156863],
+    [0.5954415, 0.6826948, 0.42352945],
+    [-0.8965285, -0.10076867, 0.43137258],
+    [0.7260756, -0.5290594, 0.43921572],
+    [-0.17724283, 0.8767687, 0.44705886],
+    [-0.45951054, -0.76283306, 0.454902],
+    [0.8502306, 0.25094795, 0.46274513],
+    [-0.7927284, 0.38746402, 0.47058827],
+    [0.3212583, -0.81724936, 0.4784314],
+    [0.3136081, 0.81558996, 0.48627454],
+    [-0.7782195, -0.38758492, 0.49411768],
+    [0.8313149, -0.23864372, 0.5019608],
+    [-0.44938147, 0.7335912, 0.50980407],
+    [-0.16327761, -0.8398701, 0.5176472],
+    [0.6838661, 0.5061493, 0.52549034],
+    [-0.8412927, 0.08821574, 0.5333335],
+    [0.55744225, -0.62959194, 0.5411766],
+    [0.014156353, 0.8356894, 0.54901975],
+    [-0.5713582, -0.6028711, 0.5568629],
+    [0.8232362, 0.05821671, 0.564706],
+    [-0.6421074, 0.5097897, 0.57254916],
+    [0.12824063, -0.8041761, 0.5803923],
+    [0.44554114, 0.67488676, 0.58823544],
+    [-0.7788171, -0.19528003, 0.5960786],
+    [0.7010116, -0.37929067, 0.6039217],
+    [-0.25873312, 0.74752986, 0.61176485],
+    [-0.31173354, -0.7203528, 0.619608],
+    [0.710744, 0.31803775, 0.6274511],
+    [-0.7328521, 0.24357545, 0.63529426],
+    [0.3726766, -0.6689443, 0.6431374],
+    [0.17552607, 0.7385221, 0.65098053],
+    [-0.62266624, -0.42218265, 0.65882367],
+    [0.737447, -0.10829209, 0.6666668],
+    [-0.46614355, 0.5724915, 0.67450994],
+    [-0.04257092, -0.72978216, 0.6823531],
+    [0.51904297, 0.5042059, 0.6901962],
+    [-0.7157527, -0.020955909, 0.69803935],
+    [0.53607875, -0.462979, 0.7058825],
+    [-0.081628844, 0.69565254, 0.7137256],
+    [-0.40498802, -0.5615365, 0.72156876],
+    [0.669842, 0.13881627, 0.7294119],
+    [-0.5804215, 0.34578303, 0.73725504],
+    [0.1919198, -0.63874525, 0.7450982],
+    [0.28609565, 0.59264547, 0.7529413],
+    [-0.6028473, -0.24037932, 0.76078445],
+    [0.59819055, -0.226671, 0.7686276],
+    [-0.28367695, 0.5626905, 0.7764707],
+    [-0.16826229, -0.5971093, 0.78431386],
+    [0.5188715, 0.3213405, 0.792157],
+    [-0.58952457, 0.11162663, 0.80000013],
+    [0.3529454, -0.4720369, 0.80784327],
+    [0.057521265, 0.5756275, 0.8156864],
+    [-0.42288038, -0.37811542, 0.82352954],
+    [0.55567485, -0.0067016594, 0.8313727],
+    [-0.39652112, 0.37213954, 0.8392158],
+    [0.040078223, -0.5299857, 0.84705895],
+    [0.32059368, 0.4078751, 0.8549021],
+    [-0.49893537, -0.08206222, 0.8627452],
+    [0.411923, -0.2690639, 0.87058836],
+    [-0.11848236, 0.46294713, 0.8784315],
+    [-0.21841507, -0.40842643, 0.88627464],
+    [0.42248008, 0.14853954, 0.8941178],
+    [-0.39713278, 0.16956437, 0.9019609],
+    [0.17136608, -0.37800825, 0.90980405],
+    [0.123498335, 0.37771922, 0.9176472],
+    [-0.32998362, -0.18595286, 0.9254903],
+    [0.3496819, -0.08130947, 0.93333346],
+    [-0.19099712, 0.2787593, 0.9411766],
+    [-0.044275604, -0.31209168, 0.94901973],
+    [0.22440292, 0.18454482, 0.95686287],
+    [-0.2629541, 0.014052316, 0.964706],
+    [0.16295148, -0.16611744, 0.97254914],
+    [-0.0067277695, 0.19694093, 0.9803923],
+    [-0.09939928, -0.11623489, 0.9882354],
+    [0.087819055, 0.01073937, 0.99607855],
+];
+
+/// Which table a model's `normalindex` values were written against.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NormalTable {
+    /// [`NORMAL_TABLE_SLAB6`], used by slab6 and most existing files.
+    #[default]
+    Slab6,
+    /// [`NORMAL_TABLE_VOXLAP`].
+    Voxlap,
+}
+
+impl NormalTable {
+    /// The vector returned for an index the table doesn't have.
+    pub const MISSING: [f32; 3] = [0.0; 3];
+
+    /// Every entry of the table, in index order.
+    pub fn entries(self) -> &'static [[f32; 3]] {
+        match self {
+            NormalTable::Slab6 => &NORMAL_TABLE_SLAB6,
+            NormalTable::Voxlap => &NORMAL_TABLE_VOXLAP,
+        }
+    }
+
+    /// Looks `index` up, returning [`NormalTable::MISSING`] if it's past the
+    /// end of the table.
+    pub fn get(self, index: u8) -> [f32; 3] {
+        self.entries()
+            .get(index as usize)
+            .copied()
+            .unwrap_or(Self::MISSING)
+    }
+}
+
+/// How [`KV6Format::recompute_normals`] estimates a voxel's normal.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NormalMode {
+    /// The average direction of the voxel's faces that border air. Cheap, but
+    /// flat surfaces and edges only ever get axis or diagonal directions.
+    #[default]
+    FaceAverage,
+    /// The direction towards the solid cells in the 3×3×3 neighbourhood
+    /// around the voxel, which follows slopes and curves more smoothly.
+    Gradient,
+}
+
+/// What [`closest_index`] returns for a direction it can't quantize. In the
+/// Voxlap table this is the "no normal" index.
+pub const DEFAULT_INDEX: u8 = 255;
+
+/// The index of the entry in `table` closest to `dir`, i.e. with the largest
+/// dot product. `dir` doesn't need to be normalized, but if it has zero length
+/// or isn't finite this returns [`DEFAULT_INDEX`].
+pub fn closest_index(dir: [f32; 3], table: NormalTable) -> u8 {
+    let length = dir.iter().map(|&c| c as f64 * c as f64).sum::<f64>();
+    if length == 0.0 || !length.is_finite() {
+        return DEFAULT_INDEX;
+    }
+
+    let mut best = (f64::NEG_INFINITY, DEFAULT_INDEX);
+    for (i, entry) in (0..=u8::MAX).zip(table.entries()) {
+        let dot: f64 = dir
+            .iter()
+            .zip(entry)
+            .map(|(&a, &b)| a as f64 * b as f64)
+            .sum();
+        if dot > best.0 {
+            best = (dot, i);
+        }
+    }
+    best.1
+}
+
+impl VoxelData {
+    /// The surface normal `normalindex` refers to in [`NORMAL_TABLE_SLAB6`].
+    pub fn normal(&self) -> [f32; 3] {
+        self.normal_with(NormalTable::Slab6)
+    }
+
+    /// The surface normal `normalindex` refers to in `table`.
+    pub fn normal_with(&self, table: NormalTable) -> [f32; 3] {
+        table.get(self.normalindex)
+    }
+}
+
+impl KV6Format {
+    /// Estimates every voxel's normal from the model's geometry and stores
+    /// the closest entry of `table` in `normalindex`.
+    ///
+    /// Normals point into the solid, the way slab6 writes them. Air is worked
+    /// out as in [`KV6Format::recompute_visibility`], so interior cells the
+    /// file doesn't store count as solid. Voxels with no face next to air
+    /// keep their index; ones whose estimate cancels out, like a lone voxel,
+    /// get [`DEFAULT_INDEX`].
+    pub fn recompute_normals(
+        &mut self,
+        table: NormalTable,
+        mode: NormalMode,
+    ) -> Result<(), Kv6Error> {
+        let air = self.occupancy()?.exterior();
+        let (x_size, y_size, z_size) = air.dimensions();
+        let is_air = |x: i64, y: i64, z: i64| {
+            let inside = (0..x_size as i64).contains(&x)
+                && (0..y_size as i64).contains(&y)
+                && (0..z_size as i64).contains(&z);
+            !inside || air.contains(x as u32, y as u32, z as u16)
+        };
+
+        self.for_each_voxel_mut(|x, y, z, voxel| {
+            let (x, y, z) = (x as i64, y as i64, z as i64);
+            let open = Face::ALL.map(|face| {
+                let (dx, dy, dz) = face.normal();
+                is_air(x + dx as i64, y + dy as i64, z + dz as i64)
+            });
+            if !open.contains(&true) {
+                return;
+            }
+
+            let mut dir = [0i64; 3];
+            match mode {
+                NormalMode::FaceAverage => {
+                    for (face, _) in Face::ALL.into_iter().zip(open).filter(|&(_, open)| open) {
+                        let (dx, dy, dz) = face.normal();
+                        dir = [dir[0] - dx as i64, dir[1] - dy as i64, dir[2] - dz as i64];
+                    }
+                }
+                NormalMode::Gradient => {
+                    for dx in -1..=1 {
+                        for dy in -1..=1 {
+                            for dz in -1..=1 {
+                                if !is_air(x + dx, y + dy, z + dz) {
+                                    dir = [dir[0] + dx, dir[1] + dy, dir[2] + dz];
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            voxel.normalindex = closest_index(dir.map(|c| c as f32), table);
+        })
+        .map_err(Kv6Error::InconsistentSizes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        closest_index, NormalMode, NormalTable, DEFAULT_INDEX, NORMAL_TABLE_SLAB6,
+        NORMAL_TABLE_VOXLAP,
+    };
+    use crate::kv6::testing::grenade;
+    use crate::kv6::{KV6Format, VoxelData};
+    use alloc::vec;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_slab6_entries() {
+        assert_eq!(NORMAL_TABLE_SLAB6[0], [0.08830199, 0.0, -0.99609375]);
+        assert_eq!(
+            NORMAL_TABLE_SLAB6[128],
+            [0.77707195, -0.62939966, 0.00390625]
+        );
+        assert_eq!(
+            NORMAL_TABLE_SLAB6[255],
+            [-0.07186996, 0.051302526, 0.99609375]
+        );
+    }
+
+    #[test]
+    fn test_unit_length() {
+        for table in [NormalTable::Slab6, NormalTable::Voxlap] {
+            for (i, &[x, y, z]) in table.entries().iter().enumerate() {
+                let length = x * x + y * y + z * z;
+                assert!(
+                    (length - 1.0).abs() < 1e-5,
+                    "{:?} entry {} has length {}",
+                    table,
+                    i,
+                    length
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_voxlap_entries() {
+        assert_eq!(NORMAL_TABLE_VOXLAP.len(), 255);
+        assert_eq!(NORMAL_TABLE_VOXLAP[0], [0.088474624, 0.0, -0.99607843]);
+        assert_eq!(
+            NORMAL_TABLE_VOXLAP[254],
+            [0.087819055, 0.01073937, 0.99607855]
+        );
+        assert_ne!(NORMAL_TABLE_VOXLAP[100], NORMAL_TABLE_SLAB6[100]);
+    }
+
+    #[test]
+    fn test_missing_index() {
+        let voxel = VoxelData {
+            normalindex: 255,
+            ..Default::default()
+        };
+        assert_eq!(voxel.normal_with(NormalTable::Voxlap), NormalTable::MISSING);
+        assert_eq!(
+            voxel.normal_with(NormalTable::Slab6),
+            NORMAL_TABLE_SLAB6[255]
+        );
+        assert_eq!(NormalTable::default(), NormalTable::Slab6);
+    }
+
+    #[test]
+    fn test_voxel_normal() {
+        let voxel = VoxelData {
+            normalindex: 255,
+            ..Default::default()
+        };
+        assert_eq!(voxel.normal(), NORMAL_TABLE_SLAB6[255]);
+        assert!(voxel.normal()[2] > 0.99);
+    }
+
+    #[test]
+    fn test_grenade_normals_point_inwards() {
+        let data = grenade();
+        for voxel in &data.voxels {
+            let outwards = voxel.visible_faces().fold([0; 3], |[x, y, z], face| {
+                let (dx, dy, dz) = face.normal();
+                [x + dx, y + dy, z + dz]
+            });
+            let [x, y, z] = voxel.normal();
+            let dot = x * outwards[0] as f32 + y * outwards[1] as f32 + z * outwards[2] as f32;
+            assert!(dot < 0.0, "{:?}", voxel);
+        }
+    }
+
+    #[test]
+    fn test_closest_degenerate() {
+        for table in [NormalTable::Slab6, NormalTable::Voxlap] {
+            assert_eq!(closest_index([0.0; 3], table), DEFAULT_INDEX);
+            assert_eq!(closest_index([f32::NAN, 0.0, 1.0], table), DEFAULT_INDEX);
+            assert_eq!(
+                closest_index([f32::INFINITY, 0.0, 0.0], table),
+                DEFAULT_INDEX
+            );
+        }
+        assert_eq!(closest_index([0.0, 0.0, -1.0], NormalTable::Slab6), 0);
+        assert_eq!(closest_index([0.0, 0.0, 1.0], NormalTable::Voxlap), 254);
+    }
+
+    /// A solid 3×3×3 cube in the middle of a 5×5×5 grid.
+    fn inset_cube() -> KV6Format {
+        let mut data = KV6Format {
+            x_size: 5,
+            y_size: 5,
+            z_size: 5,
+            xlen: vec![0; 5],
+            ylen: vec![vec![0; 5]; 5],
+            ..Default::default()
+        };
+        for x in 1..4 {
+            for y in 1..4 {
+                for z in 1..4 {
+                    data.insert_voxel(x, y, z, VoxelData::default()).unwrap();
+                }
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn test_recompute_cube() {
+        for mode in [NormalMode::FaceAverage, NormalMode::Gradient] {
+            for table in [NormalTable::Slab6, NormalTable::Voxlap] {
+                let mut data = inset_cube();
+                data.get_voxel_mut(2, 2, 2).unwrap().normalindex = 77;
+                data.recompute_normals(table, mode).unwrap();
+                assert_eq!(data.get_voxel(2, 2, 2).unwrap().normalindex, 77);
+
+                for (x, y, z, voxel) in data.iter_with_coords().unwrap() {
+                    if (x, y, z) == (2, 2, 2) {
+                        continue;
+                    }
+                    assert!((voxel.normalindex as usize) < table.entries().len());
+
+                    // Corners, edges and faces all point at the center.
+                    let inwards = [2 - x as i32, 2 - y as i32, 2 - z as i32].map(|c| c as f32);
+                    let length = inwards.iter().map(|c| c * c).sum::<f32>().sqrt();
+                    let normal = voxel.normal_with(table);
+                    let dot: f32 = normal.iter().zip(inwards).map(|(a, b)| a * b).sum();
+                    assert!(
+                        dot / length > 0.95,
+                        "{:?} {:?} at {:?}",
+                        mode,
+                        table,
+                        (x, y, z)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_recompute_lone_voxel() {
+        let mut data = KV6Format {
+            x_size: 1,
+            y_size: 1,
+            z_size: 1,
+            xlen: vec![0],
+            ylen: vec![vec![0]],
+            ..Default::default()
+        };
+        data.insert_voxel(0, 0, 0, VoxelData::default()).unwrap();
+        for mode in [NormalMode::FaceAverage, NormalMode::Gradient] {
+            data.recompute_normals(NormalTable::Voxlap, mode).unwrap();
+            assert_eq!(data.voxels[0].normalindex, DEFAULT_INDEX);
+        }
+    }
+
+    #[test]
+    fn test_recompute_grenade() {
+        let mut data = grenade();
+        let original = data.clone();
+        data.recompute_normals(NormalTable::Slab6, NormalMode::Gradient)
+            .unwrap();
+        let close = data
+            .voxels
+            .iter()
+            .zip(&original.voxels)
+            .filter(|(a, b)| {
+                let dot: f32 = a.normal().iter().zip(b.normal()).map(|(a, b)| a * b).sum();
+                dot > 0.5
+            })
+            .count();
+        assert!(close * 10 > data.voxels.len() * 9, "{} of 74", close);
+    }
+
+    fn table() -> impl Strategy<Value = NormalTable> {
+        prop_oneof![Just(NormalTable::Slab6), Just(NormalTable::Voxlap)]
+    }
+
+    proptest! {
+        #[test]
+        fn test_closest_entry_is_itself(table in table(), index in 0..=u8::MAX) {
+            let index = index.min((table.entries().len() - 1) as u8);
+            prop_assert_eq!(closest_index(table.get(index), table), index);
+        }
+
+        #[test]
+        fn test_closest_scale_invariant(
+            table in table(),
+            dir in [-1.0..1.0f32, -1.0..1.0f32, -1.0..1.0f32],
+            scale in 0.01..100.0f32,
+        ) {
+            let length = dir.iter().map(|c| c * c).sum::<f32>().sqrt();
+            prop_assume!(length > 1e-3);
+
+            let index = closest_index(dir, table);
+            prop_assert_eq!(closest_index(dir.map(|c| c / length), table), index);
+            prop_assert_eq!(closest_index(dir.map(|c| c * scale), table), index);
+        }
+    }
+}
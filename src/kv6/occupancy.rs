@@ -0,0 +1,189 @@
+use super::{Face, KV6Format};
+use crate::Kv6Error;
+use alloc::{vec, vec::Vec};
+
+/// One bit per cell of a model, set where there's a voxel.
+///
+/// Built once by [`KV6Format::occupancy`], after which every lookup is a
+/// single bit test, unlike [`KV6Format::get_voxel`] which searches a column.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OccupancyGrid {
+    bits: Vec<u64>,
+    x_size: u32,
+    y_size: u32,
+    z_size: u32,
+    len: usize,
+}
+
+impl OccupancyGrid {
+    /// An empty grid of `x_size` by `y_size` by `z_size` cells.
+    pub fn new(x_size: u32, y_size: u32, z_size: u32) -> Result<Self, Kv6Error> {
+        let overflow = Kv6Error::GridOverflow {
+            x_size,
+            y_size,
+            z_size,
+        };
+        let cells = (x_size as usize)
+            .checked_mul(y_size as usize)
+            .and_then(|n| n.checked_mul(z_size as usize))
+            .ok_or(overflow)?;
+
+        Ok(OccupancyGrid {
+            bits: vec![0; cells.div_ceil(64)],
+            x_size,
+            y_size,
+            z_size,
+            len: 0,
+        })
+    }
+
+    /// `(x_size, y_size, z_size)`.
+    pub fn dimensions(&self) -> (u32, u32, u32) {
+        (self.x_size, self.y_size, self.z_size)
+    }
+
+    /// Number of occupied cells.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether there's a voxel at `(x, y, z)`. Out of range is `false`.
+    pub fn contains(&self, x: u32, y: u32, z: u16) -> bool {
+        match self.index(x, y, z) {
+            Some(i) => self.bits[i / 64] & 1 << (i % 64) != 0,
+            None => false,
+        }
+    }
+
+    /// Marks `(x, y, z)` as occupied, returning `false` if it's out of range.
+    pub fn insert(&mut self, x: u32, y: u32, z: u16) -> bool {
+        let Some(i) = self.index(x, y, z) else {
+            return false;
+        };
+        let word = &mut self.bits[i / 64];
+        if *word & 1 << (i % 64) == 0 {
+            *word |= 1 << (i % 64);
+            self.len += 1;
+        }
+        true
+    }
+
+    /// The empty cells that can be reached from outside the grid by stepping
+    /// between face-adjacent empty cells. Empty cells that aren't in it are
+    /// enclosed, which in a KV6 file means they're solid but not stored.
+    pub(crate) fn exterior(&self) -> OccupancyGrid {
+        let mut air = OccupancyGrid {
+            bits: vec![0; self.bits.len()],
+            len: 0,
+            ..*self
+        };
+        // Heights past u16::MAX can't hold voxels, so they're never reached.
+        let (x_size, y_size, z_size) = (self.x_size, self.y_size, self.z_size.min(1 << 16));
+
+        let mut stack = Vec::new();
+        for x in 0..x_size {
+            for y in 0..y_size {
+                for z in 0..z_size {
+                    let edge = x == 0
+                        || y == 0
+                        || z == 0
+                        || x == x_size - 1
+                        || y == y_size - 1
+                        || z == z_size - 1;
+                    if edge {
+                        stack.push((x, y, z as u16));
+                    }
+                }
+            }
+        }
+        while let Some((x, y, z)) = stack.pop() {
+            if self.contains(x, y, z) || air.contains(x, y, z) || !air.insert(x, y, z) {
+                continue;
+            }
+            for face in Face::ALL {
+                stack.extend(face.neighbour(x, y, z));
+            }
+        }
+        air
+    }
+
+    fn index(&self, x: u32, y: u32, z: u16) -> Option<usize> {
+        if x >= self.x_size || y >= self.y_size || z as u32 >= self.z_size {
+            return None;
+        }
+        let column = x as usize * self.y_size as usize + y as usize;
+        Some(column * self.z_size as usize + z as usize)
+    }
+}
+
+impl KV6Format {
+    /// Builds an [`OccupancyGrid`] for the model in one pass over the voxels.
+    ///
+    /// Voxels at a height outside `z_size` aren't in the grid. Fails if the
+    /// caches don't match the voxels, or if the grid wouldn't fit in memory.
+    pub fn occupancy(&self) -> Result<OccupancyGrid, Kv6Error> {
+        let mut grid = OccupancyGrid::new(self.x_size, self.y_size, self.z_size)?;
+        for (x, y, z, _) in self
+            .iter_with_coords()
+            .map_err(Kv6Error::InconsistentSizes)?
+        {
+            grid.insert(x, y, z);
+        }
+        Ok(grid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OccupancyGrid;
+    use crate::kv6::testing::grenade;
+    use crate::Kv6Error;
+
+    #[test]
+    fn test_grenade_occupancy() {
+        let data = grenade();
+        let grid = data.occupancy().unwrap();
+        assert_eq!(grid.len(), 74);
+        assert_eq!(grid.dimensions(), (6, 6, 9));
+
+        for x in 0..=data.x_size {
+            for y in 0..=data.y_size {
+                for z in 0..=data.z_size as u16 {
+                    assert_eq!(
+                        grid.contains(x, y, z),
+                        data.get_voxel(x, y, z).is_some(),
+                        "at {:?}",
+                        (x, y, z)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_occupancy_sizes() {
+        let mut grid = OccupancyGrid::new(1, 1, 65535).unwrap();
+        assert!(grid.is_empty());
+        assert!(grid.insert(0, 0, 65534));
+        assert!(grid.insert(0, 0, 65534));
+        assert!(!grid.insert(0, 0, 65535));
+        assert!(grid.contains(0, 0, 65534));
+        assert_eq!(grid.len(), 1);
+
+        assert!(matches!(
+            OccupancyGrid::new(u32::MAX, u32::MAX, u32::MAX),
+            Err(Kv6Error::GridOverflow { .. })
+        ));
+
+        let mut data = grenade();
+        data.voxels.pop();
+        assert!(matches!(
+            data.occupancy(),
+            Err(Kv6Error::InconsistentSizes(_))
+        ));
+    }
+}
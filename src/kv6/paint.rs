@@ -0,0 +1,321 @@
+use super::{CacheMismatch, KV6Format, Rgb};
+use alloc::{collections::BTreeMap, vec, vec::Vec};
+
+/// Which cells count as touching.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Connectivity {
+    /// Cells sharing a face.
+    #[default]
+    Six,
+    /// Cells sharing a face, an edge or a corner.
+    TwentySix,
+}
+
+impl Connectivity {
+    /// Every cell touching `(x, y, z)` that has non-negative coordinates.
+    pub(crate) fn neighbours(
+        self,
+        (x, y, z): (u32, u32, u16),
+    ) -> impl Iterator<Item = (u32, u32, u16)> {
+        let face = self == Connectivity::Six;
+        (-1..=1)
+            .flat_map(|x| (-1..=1).flat_map(move |y| (-1..=1).map(move |z| (x, y, z))))
+            .filter(move |&(dx, dy, dz): &(i64, i64, i64)| {
+                let steps = dx.abs() + dy.abs() + dz.abs();
+                steps == 1 || (!face && steps > 1)
+            })
+            .filter_map(move |(dx, dy, dz)| {
+                Some((
+                    u32::try_from(x as i64 + dx).ok()?,
+                    u32::try_from(y as i64 + dy).ok()?,
+                    u16::try_from(z as i64 + dz).ok()?,
+                ))
+            })
+    }
+}
+
+/// Knobs for [`KV6Format::flood_fill`].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FloodOptions {
+    /// How far each channel may be from the seed's color for a voxel to be
+    /// part of the region.
+    pub tolerance: u8,
+    pub connectivity: Connectivity,
+}
+
+/// Whether every channel of `a` is within `tolerance` of `b`'s.
+fn within(a: Rgb, b: Rgb, tolerance: u8) -> bool {
+    [(a.r, b.r), (a.g, b.g), (a.b, b.b)]
+        .iter()
+        .all(|&(a, b)| a.abs_diff(b) <= tolerance)
+}
+
+impl KV6Format {
+    /// Recolors the voxels reachable from `seed` through touching voxels
+    /// whose color matches the seed's, returning how many changed color.
+    ///
+    /// Matching is against the seed's color, not the neighbour's, so the
+    /// region can't creep across a gradient. An empty `seed` changes nothing.
+    pub fn flood_fill(
+        &mut self,
+        seed: (u32, u32, u16),
+        new_color: Rgb,
+        options: &FloodOptions,
+    ) -> Result<usize, CacheMismatch> {
+        let index: BTreeMap<_, _> = self
+            .iter_with_coords()?
+            .enumerate()
+            .map(|(i, (x, y, z, _))| ((x, y, z), i))
+            .collect();
+        let Some(&start) = index.get(&seed) else {
+            return Ok(0);
+        };
+        let target = self.voxels[start].color();
+
+        let mut seen = vec![false; self.voxels.len()];
+        seen[start] = true;
+        let mut stack = vec![seed];
+        let mut changed = 0;
+        while let Some((x, y, z)) = stack.pop() {
+            let voxel = &mut self.voxels[index[&(x, y, z)]];
+            if voxel.color() != new_color {
+                voxel.set_color(new_color);
+                changed += 1;
+            }
+            for cell in options.connectivity.neighbours((x, y, z)) {
+                let Some(&i) = index.get(&cell) else {
+                    continue;
+                };
+                if !seen[i] && within(self.voxels[i].color(), target, options.tolerance) {
+                    seen[i] = true;
+                    stack.push(cell);
+                }
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Recolors every voxel whose channels are each within `tolerance` of
+    /// `from`'s to `to`, returning how many changed color.
+    pub fn replace_color(&mut self, from: Rgb, to: Rgb, tolerance: u8) -> usize {
+        let mut changed = 0;
+        for voxel in &mut self.voxels {
+            let color = voxel.color();
+            if color != to && within(color, from, tolerance) {
+                voxel.set_color(to);
+                changed += 1;
+            }
+        }
+        changed
+    }
+
+    /// Calls `recolor` with every voxel's position and color, and gives the
+    /// voxel whatever color it returns. Returns how many changed color.
+    ///
+    /// Only the color is touched; heights, visibility and normals stay as
+    /// they were.
+    pub fn recolor_where(
+        &mut self,
+        recolor: impl Fn(u32, u32, u16, Rgb) -> Option<Rgb>,
+    ) -> Result<usize, CacheMismatch> {
+        let colors: Vec<_> = self
+            .iter_with_coords()?
+            .map(|(x, y, z, voxel)| recolor(x, y, z, voxel.color()))
+            .collect();
+        let mut changed = 0;
+        for (voxel, color) in self.voxels.iter_mut().zip(colors) {
+            if let Some(color) = color.filter(|&color| color != voxel.color()) {
+                voxel.set_color(color);
+                changed += 1;
+            }
+        }
+        Ok(changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Connectivity, FloodOptions};
+    use crate::kv6::testing::grenade;
+    use crate::kv6::{KV6Builder, KV6Format, Rgb};
+
+    const RED: Rgb = Rgb::new(255, 0, 0);
+    const BLUE: Rgb = Rgb::new(0, 0, 255);
+    const GREEN: Rgb = Rgb::new(0, 255, 0);
+
+    /// A 4³ cube, red on the `x = 0` face and blue everywhere else.
+    fn two_tone() -> KV6Format {
+        let mut builder = KV6Builder::new(4, 4, 4);
+        for x in 0..4 {
+            for y in 0..4 {
+                for z in 0..4 {
+                    builder = builder.voxel(x, y, z, if x == 0 { RED } else { BLUE });
+                }
+            }
+        }
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn test_flood_face() {
+        let mut data = two_tone();
+        let options = FloodOptions::default();
+        assert_eq!(data.flood_fill((0, 1, 2), GREEN, &options).unwrap(), 16);
+        for (x, y, z, voxel) in data.iter_with_coords().unwrap() {
+            let expected = if x == 0 { GREEN } else { BLUE };
+            assert_eq!(voxel.color(), expected, "at {:?}", (x, y, z));
+        }
+        let mut unchanged = two_tone();
+        for voxel in unchanged
+            .voxels
+            .iter_mut()
+            .filter(|voxel| voxel.color() == RED)
+        {
+            voxel.set_color(GREEN);
+        }
+        assert_eq!(data, unchanged);
+
+        // Filling again with the same color changes nothing.
+        assert_eq!(data.flood_fill((0, 0, 0), GREEN, &options).unwrap(), 0);
+        assert_eq!(data.flood_fill((3, 3, 3), GREEN, &options).unwrap(), 48);
+        assert!(data.colors().all(|color| color == GREEN));
+    }
+
+    #[test]
+    fn test_flood_tolerance() {
+        let mut data = two_tone();
+        data.get_voxel_mut(0, 3, 3)
+            .unwrap()
+            .set_color(Rgb::new(250, 4, 0));
+        let exact = FloodOptions::default();
+        assert_eq!(
+            data.clone().flood_fill((0, 0, 0), GREEN, &exact).unwrap(),
+            15
+        );
+        let loose = FloodOptions {
+            tolerance: 5,
+            ..Default::default()
+        };
+        assert_eq!(
+            data.clone().flood_fill((0, 0, 0), GREEN, &loose).unwrap(),
+            16
+        );
+        // The tolerance is measured from the seed, so seeding on the odd
+        // voxel with none picks up only that one.
+        assert_eq!(data.flood_fill((0, 3, 3), GREEN, &exact).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_flood_connectivity() {
+        let mut builder = KV6Builder::new(4, 4, 4);
+        for i in 0..4 {
+            builder = builder.voxel(i, i, i as u16, RED);
+        }
+        let data = builder.build().unwrap();
+        let six = FloodOptions::default();
+        assert_eq!(data.clone().flood_fill((1, 1, 1), GREEN, &six).unwrap(), 1);
+        let corners = FloodOptions {
+            connectivity: Connectivity::TwentySix,
+            ..Default::default()
+        };
+        let mut filled = data.clone();
+        assert_eq!(filled.flood_fill((1, 1, 1), GREEN, &corners).unwrap(), 4);
+        assert!(filled.colors().all(|color| color == GREEN));
+
+        // Empty cells and cells outside the model do nothing.
+        let mut empty = data.clone();
+        assert_eq!(empty.flood_fill((1, 0, 0), GREEN, &corners).unwrap(), 0);
+        assert_eq!(empty.flood_fill((9, 9, 9), GREEN, &corners).unwrap(), 0);
+        assert_eq!(empty, data);
+    }
+
+    #[test]
+    fn test_replace_color() {
+        let mut data = two_tone();
+        assert_eq!(data.replace_color(RED, GREEN, 0), 16);
+        assert_eq!(data.replace_color(RED, GREEN, 0), 0);
+        assert_eq!(data.replace_color(GREEN, GREEN, 0), 0);
+        let mut expected = two_tone();
+        expected
+            .recolor_where(|x, _, _, _| (x == 0).then_some(GREEN))
+            .unwrap();
+        assert_eq!(data, expected);
+
+        let mut data = grenade();
+        let before = data.clone();
+        let color = data.voxels[0].color();
+        let exact = data.colors().filter(|&c| c == color).count();
+        assert_eq!(
+            data.replace_color(color, RED, 0),
+            exact - (color == RED) as usize
+        );
+        for (voxel, old) in data.voxels.iter().zip(&before.voxels) {
+            let (mut voxel, mut old) = (*voxel, *old);
+            assert_eq!(
+                voxel.color() == RED,
+                old.color() == color || old.color() == RED
+            );
+            voxel.set_color(Rgb::default());
+            old.set_color(Rgb::default());
+            assert_eq!(voxel, old);
+        }
+    }
+
+    #[test]
+    fn test_replace_tolerance() {
+        let mut data = two_tone();
+        data.get_voxel_mut(3, 3, 3)
+            .unwrap()
+            .set_color(Rgb::new(3, 1, 250));
+        data.get_voxel_mut(3, 3, 2)
+            .unwrap()
+            .set_color(Rgb::new(0, 0, 245));
+        assert_eq!(data.clone().replace_color(BLUE, GREEN, 0), 46);
+        assert_eq!(data.clone().replace_color(BLUE, GREEN, 5), 47);
+        assert_eq!(data.clone().replace_color(BLUE, GREEN, 10), 48);
+        assert_eq!(data.replace_color(Rgb::new(128, 0, 128), GREEN, 124), 0);
+    }
+
+    #[test]
+    fn test_recolor_where() {
+        let mut data = grenade();
+        let changed = data
+            .recolor_where(|_, _, z, color| (z > 4 && color != RED).then_some(RED))
+            .unwrap();
+        let below = grenade()
+            .iter_with_coords()
+            .unwrap()
+            .filter(|&(_, _, z, voxel)| z > 4 && voxel.color() != RED)
+            .count();
+        assert_eq!(changed, below);
+        assert!(changed > 0);
+        for (x, y, z, voxel) in data.iter_with_coords().unwrap() {
+            let old = grenade().get_voxel(x, y, z).copied().unwrap();
+            if z > 4 {
+                assert_eq!(voxel.color(), RED);
+            } else {
+                assert_eq!(*voxel, old);
+            }
+            assert_eq!(
+                (voxel.height, voxel.visibility, voxel.normalindex),
+                (old.height, old.visibility, old.normalindex)
+            );
+        }
+
+        assert_eq!(data.recolor_where(|_, _, _, _| None).unwrap(), 0);
+        data.xlen[0] += 1;
+        assert!(data.recolor_where(|_, _, _, _| Some(RED)).is_err());
+    }
+
+    #[test]
+    fn test_flood_large_region() {
+        // Deep enough that a recursive fill would risk the stack.
+        let mut builder = KV6Builder::new(1, 1, 60000);
+        for z in 0..60000 {
+            builder = builder.voxel(0, 0, z, RED);
+        }
+        let mut data = builder.build().unwrap();
+        let options = FloodOptions::default();
+        assert_eq!(data.flood_fill((0, 0, 0), BLUE, &options).unwrap(), 60000);
+    }
+}
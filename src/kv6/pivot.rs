@@ -0,0 +1,133 @@
+use super::KV6Format;
+
+/// Where [`KV6Format::recenter_pivot`] puts the pivot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Centering {
+    /// The middle of the box the voxels occupy.
+    BoundingBoxCenter,
+    /// The average position of the stored voxels. For the center of the
+    /// solid, interior included, see [`KV6Format::recenter_pivot_to_mass`].
+    OccupiedCenter,
+    /// The middle of the bottom face of the occupied box, for models that
+    /// stand on the ground. z points down, so that's its highest z.
+    BottomCenter,
+}
+
+impl KV6Format {
+    /// `[x_pivot, y_pivot, z_pivot]`.
+    pub fn pivot(&self) -> [f32; 3] {
+        [self.x_pivot, self.y_pivot, self.z_pivot]
+    }
+
+    pub fn set_pivot(&mut self, x: f32, y: f32, z: f32) {
+        (self.x_pivot, self.y_pivot, self.z_pivot) = (x, y, z);
+    }
+
+    /// Moves the pivot by `(dx, dy, dz)`.
+    pub fn translate_pivot(&mut self, dx: f32, dy: f32, dz: f32) {
+        self.x_pivot += dx;
+        self.y_pivot += dy;
+        self.z_pivot += dz;
+    }
+
+    /// Moves the pivot as `centering` says, measuring from the voxels rather
+    /// than from the grid, in which cell `(x, y, z)` spans `x..x + 1` and so
+    /// on.
+    ///
+    /// Returns `false`, leaving the pivot alone, if there are no voxels or
+    /// the caches don't match them.
+    pub fn recenter_pivot(&mut self, centering: Centering) -> bool {
+        let Some(bounds) = self.occupied_bounds() else {
+            return false;
+        };
+        let middle = |min: u32, max: u32| (min as f64 + max as f64 + 1.0) / 2.0;
+        let x = middle(bounds.min.0, bounds.max.0);
+        let y = middle(bounds.min.1, bounds.max.1);
+        let z = middle(bounds.min.2 as u32, bounds.max.2 as u32);
+        let [x, y, z] = match centering {
+            Centering::BoundingBoxCenter => [x, y, z],
+            Centering::BottomCenter => [x, y, bounds.max.2 as f64 + 1.0],
+            Centering::OccupiedCenter => {
+                let mut sum = [0.0f64; 3];
+                let mut count = 0u64;
+                // occupied_bounds has already checked the caches.
+                for (x, y, z, _) in self.iter_with_coords().into_iter().flatten() {
+                    sum[0] += x as f64 + 0.5;
+                    sum[1] += y as f64 + 0.5;
+                    sum[2] += z as f64 + 0.5;
+                    count += 1;
+                }
+                sum.map(|sum| sum / count as f64)
+            }
+        };
+        self.set_pivot(x as f32, y as f32, z as f32);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Centering;
+    use crate::kv6::testing::{empty, grenade};
+    use crate::kv6::{KV6Format, VoxelData};
+
+    /// Voxels at (1, 2, 3), (3, 2, 3) and (3, 2, 6) in a grid of 8 by 8 by
+    /// 8, well away from its center.
+    fn off_center() -> KV6Format {
+        let mut data = empty(8, 8, 8);
+        for (x, y, z) in [(1, 2, 3), (3, 2, 3), (3, 2, 6)] {
+            data.insert_voxel(x, y, z, VoxelData::default()).unwrap();
+        }
+        data
+    }
+
+    #[test]
+    fn test_pivot_accessors() {
+        let mut data = off_center();
+        data.set_pivot(1.0, 2.0, 3.0);
+        assert_eq!(data.pivot(), [1.0, 2.0, 3.0]);
+        data.translate_pivot(0.5, -2.0, 0.0);
+        assert_eq!(data.pivot(), [1.5, 0.0, 3.0]);
+        assert_eq!(data.z_pivot, 3.0);
+    }
+
+    #[test]
+    fn test_recenter() {
+        let mut data = off_center();
+        assert!(data.recenter_pivot(Centering::BoundingBoxCenter));
+        assert_eq!(data.pivot(), [2.5, 2.5, 5.0]);
+        assert!(data.recenter_pivot(Centering::BottomCenter));
+        assert_eq!(data.pivot(), [2.5, 2.5, 7.0]);
+        assert!(data.recenter_pivot(Centering::OccupiedCenter));
+        let [x, y, z] = data.pivot();
+        assert!((x - 17.0 / 6.0).abs() < 1e-6);
+        assert_eq!((y, z), (2.5, 4.5));
+    }
+
+    #[test]
+    fn test_recenter_grenade() {
+        let mut data = grenade();
+        assert!(data.recenter_pivot(Centering::BoundingBoxCenter));
+        assert_eq!(data.pivot(), [3.0, 3.0, 4.5]);
+        assert!(data.recenter_pivot(Centering::BottomCenter));
+        assert_eq!(data.pivot(), [3.0, 3.0, 9.0]);
+    }
+
+    #[test]
+    fn test_recenter_empty() {
+        let mut data = empty(4, 4, 4);
+        data.set_pivot(1.0, 1.0, 1.0);
+        for centering in [
+            Centering::BoundingBoxCenter,
+            Centering::OccupiedCenter,
+            Centering::BottomCenter,
+        ] {
+            assert!(!data.recenter_pivot(centering));
+            assert_eq!(data.pivot(), [1.0, 1.0, 1.0]);
+        }
+
+        let mut broken = off_center();
+        broken.voxels.pop();
+        assert!(!broken.recenter_pivot(Centering::BoundingBoxCenter));
+    }
+}
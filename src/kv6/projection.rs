@@ -0,0 +1,326 @@
+use super::{CacheMismatch, KV6Format};
+use alloc::{vec, vec::Vec};
+
+/// One of the model's axes, in Voxlap's convention: x to the right, y to
+/// the front and z down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    /// Every axis, in order.
+    pub const ALL: [Axis; 3] = [Axis::X, Axis::Y, Axis::Z];
+
+    /// 0 for x, 1 for y and 2 for z.
+    pub(crate) const fn index(self) -> usize {
+        self as usize
+    }
+
+    /// The two axes left when looking along this one, in the order they
+    /// index a projected grid: `(row, column)`, so cell `(a, b)` is at
+    /// `a * size_of_b + b`.
+    ///
+    /// Along x that's `(y, z)`, along y `(x, z)` and along z `(x, y)`.
+    pub const fn plane(self) -> (Axis, Axis) {
+        match self {
+            Axis::X => (Axis::Y, Axis::Z),
+            Axis::Y => (Axis::X, Axis::Z),
+            Axis::Z => (Axis::X, Axis::Y),
+        }
+    }
+}
+
+/// Voxel counts over the plane across an axis, from [`KV6Format::project`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Projection {
+    pub axis: Axis,
+    /// Cells along the first axis of [`Axis::plane`].
+    pub rows: usize,
+    /// Cells along the second axis of [`Axis::plane`].
+    pub columns: usize,
+    /// How many voxels lie behind each cell, row-major.
+    pub counts: Vec<u32>,
+}
+
+impl Projection {
+    /// The count at `(row, column)`, `None` if it's out of range.
+    pub fn get(&self, row: usize, column: usize) -> Option<u32> {
+        if row >= self.rows || column >= self.columns {
+            return None;
+        }
+        Some(self.counts[row * self.columns + column])
+    }
+
+    /// Number of cells with at least one voxel behind them.
+    pub fn occupied_area(&self) -> usize {
+        self.counts.iter().filter(|&&n| n > 0).count()
+    }
+
+    /// The most voxels behind any one cell.
+    pub fn max_depth(&self) -> u32 {
+        self.counts.iter().copied().max().unwrap_or(0)
+    }
+}
+
+/// The voxels of one column, from [`KV6Format::column_profile`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ColumnProfile {
+    /// The highest voxel, which has the lowest z.
+    pub min_z: Option<u16>,
+    /// The lowest voxel, which has the highest z.
+    pub max_z: Option<u16>,
+    pub count: u16,
+    /// Whether the stored voxels fill `min_z..=max_z` without a gap. Since
+    /// KV6 files leave out a solid interior, a gap can be solid rather than
+    /// air. Empty columns count as contiguous.
+    pub contiguous: bool,
+}
+
+impl KV6Format {
+    /// A [`ColumnProfile`] for every column, x-major: column `(x, y)` is at
+    /// `x * y_size + y`.
+    ///
+    /// Each column is sorted by height in a valid model, so only its first
+    /// and last voxel are looked at for the extent. Fails if the caches
+    /// don't match the voxels.
+    pub fn column_profile(&self) -> Result<Vec<ColumnProfile>, CacheMismatch> {
+        self.check_caches()?;
+        let mut start = 0;
+        let profiles = self
+            .ylen
+            .iter()
+            .flatten()
+            .map(|&count| {
+                let column = &self.voxels[start..start + count as usize];
+                start += count as usize;
+                let (min_z, max_z) = match column {
+                    [] => (None, None),
+                    [first, .., last] => (Some(first.height), Some(last.height)),
+                    [only] => (Some(only.height), Some(only.height)),
+                };
+                let contiguous = column.windows(2).all(|w| w[1].height == w[0].height + 1);
+                ColumnProfile {
+                    min_z,
+                    max_z,
+                    count,
+                    contiguous,
+                }
+            })
+            .collect();
+        Ok(profiles)
+    }
+
+    /// Counts the voxels behind every cell of the plane across `axis`.
+    /// Voxels at a height outside `z_size` are left out.
+    pub fn project(&self, axis: Axis) -> Result<Projection, CacheMismatch> {
+        let (rows, columns) = axis.plane();
+        let (rows, columns) = (self.axis_len(rows), self.axis_len(columns));
+        let mut counts = vec![0; rows * columns];
+        for (x, y, z, _) in self.iter_with_coords()? {
+            if let Some(i) = self.plane_index(axis, x, y, z) {
+                counts[i] += 1;
+            }
+        }
+        Ok(Projection {
+            axis,
+            rows,
+            columns,
+            counts,
+        })
+    }
+
+    /// Which cells of the plane across `axis` have a voxel behind them, laid
+    /// out as [`Axis::plane`] describes. Voxels at a height outside `z_size`
+    /// are left out.
+    pub fn silhouette(&self, axis: Axis) -> Result<Vec<bool>, CacheMismatch> {
+        Ok(self.project(axis)?.counts.iter().map(|&n| n > 0).collect())
+    }
+
+    fn axis_len(&self, axis: Axis) -> usize {
+        match axis {
+            Axis::X => self.x_size as usize,
+            Axis::Y => self.y_size as usize,
+            Axis::Z => self.z_size as usize,
+        }
+    }
+
+    /// Where `(x, y, z)` lands in a grid projected along `axis`, or `None` if
+    /// it's outside the model.
+    pub(crate) fn plane_index(&self, axis: Axis, x: u32, y: u32, z: u16) -> Option<usize> {
+        if x >= self.x_size || y >= self.y_size || z as u32 >= self.z_size {
+            return None;
+        }
+        let (x, y, z) = (x as usize, y as usize, z as usize);
+        Some(match axis {
+            Axis::X => y * self.z_size as usize + z,
+            Axis::Y => x * self.z_size as usize + z,
+            Axis::Z => x * self.y_size as usize + y,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Axis, ColumnProfile, Projection};
+    use crate::kv6::testing::{grenade, lopsided};
+    use crate::kv6::{KV6Format, VoxelData};
+    use alloc::{vec, vec::Vec};
+
+    #[test]
+    fn test_grenade_profile() {
+        let data = grenade();
+        let profiles = data.column_profile().unwrap();
+        assert_eq!(profiles.len(), 36);
+        assert_eq!(
+            profiles[0],
+            ColumnProfile {
+                contiguous: true,
+                ..Default::default()
+            }
+        );
+        for (i, profile) in profiles.iter().enumerate() {
+            let column = data.column(i as u32 / 6, i as u32 % 6).unwrap();
+            assert_eq!(profile.count as usize, column.len());
+            assert_eq!(profile.min_z, column.first().map(|v| v.height));
+            assert_eq!(profile.max_z, column.last().map(|v| v.height));
+        }
+        // The middle of the grenade is hollow, so its columns only store
+        // their two ends.
+        let middle = profiles[2 * 6 + 2];
+        assert_eq!(
+            (middle.min_z, middle.max_z, middle.count),
+            (Some(1), Some(8), 2)
+        );
+        assert!(!middle.contiguous);
+
+        let mut broken = data;
+        broken.voxels.pop();
+        assert!(broken.column_profile().is_err());
+    }
+
+    #[test]
+    fn test_gap_profile() {
+        let mut data = KV6Format {
+            x_size: 2,
+            y_size: 1,
+            z_size: 8,
+            xlen: vec![0; 2],
+            ylen: vec![vec![0]; 2],
+            ..Default::default()
+        };
+        for z in [0, 1, 2] {
+            data.insert_voxel(0, 0, z, VoxelData::default()).unwrap();
+        }
+        for z in [3, 4, 7] {
+            data.insert_voxel(1, 0, z, VoxelData::default()).unwrap();
+        }
+        let profiles = data.column_profile().unwrap();
+        assert_eq!(
+            profiles,
+            [
+                ColumnProfile {
+                    min_z: Some(0),
+                    max_z: Some(2),
+                    count: 3,
+                    contiguous: true
+                },
+                ColumnProfile {
+                    min_z: Some(3),
+                    max_z: Some(7),
+                    count: 3,
+                    contiguous: false
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_silhouettes() {
+        let data = grenade();
+        let top = data.silhouette(Axis::Z).unwrap();
+        assert_eq!(top.len(), 36);
+        let profiles = data.column_profile().unwrap();
+        let occupied: Vec<bool> = profiles.iter().map(|p| p.count > 0).collect();
+        assert_eq!(top, occupied);
+
+        // Seen from the side, a cell is set when any voxel lies on its line.
+        let side = data.silhouette(Axis::X).unwrap();
+        let front = data.silhouette(Axis::Y).unwrap();
+        assert_eq!((side.len(), front.len()), (6 * 9, 6 * 9));
+        for a in 0..6 {
+            for z in 0..9 {
+                let line = |f: &dyn Fn(u32) -> (u32, u32)| {
+                    (0..6).any(|b| {
+                        let (x, y) = f(b);
+                        data.get_voxel(x, y, z).is_some()
+                    })
+                };
+                assert_eq!(side[a as usize * 9 + z as usize], line(&|x| (x, a)));
+                assert_eq!(front[a as usize * 9 + z as usize], line(&|y| (a, y)));
+            }
+        }
+        assert!(!side[0] && side[2 * 9]);
+    }
+
+    #[test]
+    fn test_projections() {
+        let data = lopsided();
+
+        let top = data.project(Axis::Z).unwrap();
+        assert_eq!((top.rows, top.columns), (2, 3));
+        assert_eq!(top.counts, [2, 0, 0, 1, 0, 1]);
+        assert_eq!((top.occupied_area(), top.max_depth()), (3, 2));
+        assert_eq!(top.get(0, 0), Some(2));
+        assert_eq!((top.get(2, 0), top.get(0, 3)), (None, None));
+
+        let side = data.project(Axis::X).unwrap();
+        assert_eq!((side.rows, side.columns), (3, 4));
+        #[rustfmt::skip]
+        assert_eq!(side.counts, [
+            1, 0, 0, 2,
+            0, 0, 0, 0,
+            0, 1, 0, 0,
+        ]);
+        assert_eq!(side.get(0, 3), Some(2));
+        assert_eq!((side.occupied_area(), side.max_depth()), (3, 2));
+
+        let front = data.project(Axis::Y).unwrap();
+        assert_eq!((front.rows, front.columns), (2, 4));
+        #[rustfmt::skip]
+        assert_eq!(front.counts, [
+            1, 0, 0, 1,
+            0, 1, 0, 1,
+        ]);
+        assert_eq!((front.occupied_area(), front.max_depth()), (4, 1));
+
+        for projection in [top, side, front] {
+            let total: u32 = projection.counts.iter().sum();
+            assert_eq!(total, 4);
+        }
+    }
+
+    #[test]
+    fn test_empty_projection() {
+        let projection = KV6Format::default().project(Axis::Y).unwrap();
+        assert_eq!(
+            projection,
+            Projection {
+                axis: Axis::Y,
+                rows: 0,
+                columns: 0,
+                counts: vec![],
+            }
+        );
+        assert_eq!((projection.occupied_area(), projection.max_depth()), (0, 0));
+    }
+
+    #[test]
+    fn test_planes() {
+        assert_eq!(Axis::X.plane(), (Axis::Y, Axis::Z));
+        assert_eq!(Axis::Y.plane(), (Axis::X, Axis::Z));
+        assert_eq!(Axis::Z.plane(), (Axis::X, Axis::Y));
+    }
+}
@@ -0,0 +1,288 @@
+use super::{KV6Format, Rgb};
+use alloc::{collections::BTreeMap, vec::Vec};
+
+/// How [`KV6Format::quantize_colors`] picks its palette.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum QuantizeMethod {
+    /// Split the colors in two along their widest channel, at the voxel
+    /// count's median, until there are enough boxes, then use each box's
+    /// average. Good for gradients and shading.
+    #[default]
+    MedianCut,
+    /// The colors used by the most voxels. Keeps a few flat colors exact,
+    /// but loses anything rare.
+    Popularity,
+}
+
+/// Squared distance between two colors.
+fn distance(a: Rgb, b: Rgb) -> u32 {
+    [(a.r, b.r), (a.g, b.g), (a.b, b.b)]
+        .iter()
+        .map(|&(a, b)| (a.abs_diff(b) as u32).pow(2))
+        .sum()
+}
+
+/// Every color the voxels use, with how many use it, sorted by color.
+fn histogram(data: &KV6Format) -> Vec<(Rgb, u64)> {
+    let mut counts = BTreeMap::new();
+    for color in data.colors() {
+        *counts.entry(color.to_u32()).or_insert(0) += 1;
+    }
+    counts
+        .into_iter()
+        .map(|(color, count)| (Rgb::from_u32(color), count))
+        .collect()
+}
+
+/// The channel `colors` spread furthest along, as an index into `[r, g, b]`,
+/// and how far.
+fn widest(colors: &[(Rgb, u64)]) -> (usize, u8) {
+    (0..3)
+        .map(|channel| {
+            let values = colors.iter().map(|(color, _)| channel_of(*color, channel));
+            let spread = values.clone().max().unwrap_or(0) - values.min().unwrap_or(0);
+            (channel, spread)
+        })
+        .max_by_key(|&(channel, spread)| (spread, core::cmp::Reverse(channel)))
+        .unwrap_or((0, 0))
+}
+
+fn channel_of(color: Rgb, channel: usize) -> u8 {
+    [color.r, color.g, color.b][channel]
+}
+
+/// The average of `colors`, weighted by their counts and rounded.
+fn average(colors: &[(Rgb, u64)]) -> Rgb {
+    let total: u64 = colors.iter().map(|(_, count)| count).sum();
+    let mean = |channel| {
+        let sum: u64 = colors
+            .iter()
+            .map(|&(color, count)| channel_of(color, channel) as u64 * count)
+            .sum();
+        ((sum + total / 2) / total) as u8
+    };
+    Rgb::new(mean(0), mean(1), mean(2))
+}
+
+fn median_cut(mut colors: Vec<(Rgb, u64)>, max_colors: usize) -> Vec<Rgb> {
+    let mut boxes = Vec::new();
+    boxes.push(colors.as_mut_slice());
+    while boxes.len() < max_colors {
+        // The box with the widest spread that still has colors to split.
+        let Some((index, channel)) = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, colors)| colors.len() > 1)
+            .map(|(index, colors)| (index, widest(colors)))
+            .max_by_key(|&(index, (_, spread))| (spread, core::cmp::Reverse(index)))
+            .map(|(index, (channel, _))| (index, channel))
+        else {
+            break;
+        };
+        let colors = boxes.swap_remove(index);
+        colors.sort_by_key(|&(color, _)| (channel_of(color, channel), color.to_u32()));
+
+        // Split where half the voxels are on each side, keeping both halves
+        // non-empty.
+        let total: u64 = colors.iter().map(|(_, count)| count).sum();
+        let mut below = 0;
+        let mut split = 1;
+        for (i, (_, count)) in colors.iter().enumerate() {
+            below += count;
+            split = i + 1;
+            if below * 2 >= total {
+                break;
+            }
+        }
+        let (low, high) = colors.split_at_mut(split.min(colors.len() - 1));
+        boxes.push(low);
+        boxes.push(high);
+    }
+    boxes.iter().map(|colors| average(colors)).collect()
+}
+
+impl KV6Format {
+    /// Reduces the model to at most `max_colors` colors, chosen by `method`,
+    /// and returns them sorted by their `0x00RRGGBB` value. Every voxel gets
+    /// the closest of them.
+    ///
+    /// A model already within `max_colors` keeps its colors exactly, and the
+    /// palette is just the colors it uses. A `max_colors` of 0 is taken as 1.
+    pub fn quantize_colors(&mut self, max_colors: usize, method: QuantizeMethod) -> Vec<Rgb> {
+        let max_colors = max_colors.max(1);
+        let colors = histogram(self);
+        let mut palette = if colors.len() <= max_colors {
+            colors.into_iter().map(|(color, _)| color).collect()
+        } else {
+            match method {
+                QuantizeMethod::MedianCut => median_cut(colors, max_colors),
+                QuantizeMethod::Popularity => {
+                    let mut colors = colors;
+                    colors
+                        .sort_by_key(|&(color, count)| (core::cmp::Reverse(count), color.to_u32()));
+                    colors.truncate(max_colors);
+                    colors.into_iter().map(|(color, _)| color).collect()
+                }
+            }
+        };
+        palette.sort_by_key(|color| color.to_u32());
+        palette.dedup();
+        self.quantize_to_palette(&palette);
+        palette
+    }
+
+    /// Gives every voxel the closest color in `palette`, by squared distance
+    /// with ties going to the earliest entry, and returns how many changed.
+    /// An empty palette changes nothing.
+    ///
+    /// For a [`Palette`](super::Palette), such as Build's `PALETTE.DAT`, pass
+    /// its colors with `(0..=255).map(|i| palette.color(i))`.
+    pub fn quantize_to_palette(&mut self, palette: &[Rgb]) -> usize {
+        if palette.is_empty() {
+            return 0;
+        }
+        let mut closest = BTreeMap::new();
+        let mut changed = 0;
+        for voxel in &mut self.voxels {
+            let color = voxel.color();
+            let new = *closest.entry(color.to_u32()).or_insert_with(|| {
+                palette
+                    .iter()
+                    .copied()
+                    .min_by_key(|&entry| distance(entry, color))
+                    .unwrap_or(color)
+            });
+            if new != color {
+                voxel.set_color(new);
+                changed += 1;
+            }
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::QuantizeMethod;
+    use crate::kv6::testing::grenade;
+    use crate::kv6::{KV6Builder, KV6Format, Palette, Rgb};
+    use alloc::{collections::BTreeSet, vec::Vec};
+
+    /// A 16 by 16 slab running from black to red along x and to green
+    /// along y, with 256 colors.
+    fn gradient() -> KV6Format {
+        let mut builder = KV6Builder::new(16, 16, 1);
+        for x in 0..16 {
+            for y in 0..16 {
+                builder = builder.voxel(x, y, 0, Rgb::new(x as u8 * 17, y as u8 * 17, 0));
+            }
+        }
+        builder.build().unwrap()
+    }
+
+    fn unique(data: &KV6Format) -> BTreeSet<u32> {
+        data.colors().map(Rgb::to_u32).collect()
+    }
+
+    #[test]
+    fn test_few_colors_unchanged() {
+        let used = unique(&grenade()).len();
+        for method in [QuantizeMethod::MedianCut, QuantizeMethod::Popularity] {
+            for max_colors in [used, used + 1, 256] {
+                let mut data = grenade();
+                let palette = data.quantize_colors(max_colors, method);
+                assert_eq!(data, grenade());
+                assert_eq!(palette.len(), used);
+                let colors: BTreeSet<_> = palette.iter().map(|color| color.to_u32()).collect();
+                assert_eq!(colors, unique(&grenade()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_median_cut_gradient() {
+        let mut data = gradient();
+        let before = data.clone();
+        let palette = data.quantize_colors(16, QuantizeMethod::MedianCut);
+        assert_eq!(palette.len(), 16);
+        assert!(palette
+            .windows(2)
+            .all(|pair| pair[0].to_u32() < pair[1].to_u32()));
+        assert!(unique(&data).len() <= 16);
+        for (voxel, old) in data.voxels.iter().zip(&before.voxels) {
+            assert!(palette.contains(&voxel.color()));
+            let (new, was) = (voxel.color(), old.color());
+            assert!(new.r.abs_diff(was.r) <= 34 && new.g.abs_diff(was.g) <= 34);
+            assert_eq!(new.b, 0);
+            assert_eq!(
+                (voxel.height, voxel.visibility),
+                (old.height, old.visibility)
+            );
+        }
+
+        // Quantizing again to the same count leaves it alone.
+        let again = data.clone().quantize_colors(16, QuantizeMethod::MedianCut);
+        assert_eq!(again, palette);
+    }
+
+    #[test]
+    fn test_popularity() {
+        let mut builder = KV6Builder::new(1, 1, 10);
+        let colors = [(0, 5), (255, 3), (128, 2)];
+        let mut z = 0;
+        for (value, count) in colors {
+            for _ in 0..count {
+                builder = builder.voxel(0, 0, z, Rgb::new(value, 0, 0));
+                z += 1;
+            }
+        }
+        let mut data = builder.build().unwrap();
+        let palette = data.quantize_colors(2, QuantizeMethod::Popularity);
+        assert_eq!(palette, [Rgb::new(0, 0, 0), Rgb::new(255, 0, 0)]);
+        let reds: Vec<u8> = data.colors().map(|color| color.r).collect();
+        assert_eq!(reds, [0, 0, 0, 0, 0, 255, 255, 255, 255, 255]);
+
+        let mut single = data.clone();
+        assert_eq!(
+            single.quantize_colors(0, QuantizeMethod::MedianCut).len(),
+            1
+        );
+        assert_eq!(unique(&single).len(), 1);
+    }
+
+    #[test]
+    fn test_fixed_palette() {
+        let mut data = gradient();
+        let palette = [Rgb::new(0, 0, 0), Rgb::new(255, 255, 255)];
+        let changed = data.quantize_to_palette(&palette);
+        let mut whites = 0;
+        for (x, y, _, voxel) in data.iter_with_coords().unwrap() {
+            // Blue is 0, so white is always 255² further off there.
+            let (r, g) = (x * 17, y * 17);
+            let black = r * r + g * g;
+            let white = (255 - r).pow(2) + (255 - g).pow(2) + 255 * 255;
+            whites += (white < black) as usize;
+            let expected = if black <= white {
+                palette[0]
+            } else {
+                palette[1]
+            };
+            assert_eq!(voxel.color(), expected, "at {:?}", (x, y));
+        }
+        assert!(whites > 0);
+        assert_eq!(changed, 255);
+        assert_eq!(data.quantize_to_palette(&[]), 0);
+
+        // Build palettes come as a 6-bit Palette.
+        let mut vga = Palette::default();
+        vga.entries[1] = [63, 0, 0];
+        let colors: Vec<Rgb> = (0..=255).map(|i| vga.color(i)).collect();
+        let mut data = gradient();
+        data.quantize_to_palette(&colors);
+        assert_eq!(unique(&data).len(), 2);
+        assert_eq!(
+            data.get_voxel(15, 0, 0).unwrap().color(),
+            Rgb::new(255, 0, 0)
+        );
+    }
+}
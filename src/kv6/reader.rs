@@ -0,0 +1,218 @@
+use super::{CacheMismatch, Kv6Header, ParseOptions, VoxelData};
+use crate::{utils::read_vec, Kv6Error};
+use scroll::LE;
+use std::io::{Read, Seek, SeekFrom};
+
+/// The voxels of one `(x, y)` column, top to bottom.
+#[derive(Debug)]
+pub struct Column {
+    pub x: u32,
+    pub y: u32,
+    pub voxels: Vec<VoxelData>,
+}
+
+/// Reads a model one column at a time without holding the whole voxel array.
+///
+/// The caches that say how long each column is are stored after the voxels,
+/// so [`Kv6Reader::new`] seeks past the voxels to read them, then seeks back
+/// and streams the voxels. Only the caches and the current column are kept in
+/// memory. Every column is yielded in x-major, y-minor order, empty ones
+/// included. The optional palette suffix is ignored.
+pub struct Kv6Reader<R> {
+    reader: R,
+    header: Kv6Header,
+    ylen: Vec<u16>,
+    next: usize,
+    read: u64,
+    failed: bool,
+}
+
+impl<R: Read + Seek> Kv6Reader<R> {
+    /// Reads the header and caches of the model starting at the reader's
+    /// current position, checking them with the default [`ParseOptions`].
+    pub fn new(mut reader: R) -> Result<Self, Kv6Error> {
+        let start = reader.stream_position()?;
+        let mut bytes = [0u8; Kv6Header::SIZE];
+        reader.read_exact(&mut bytes)?;
+        let header = Kv6Header::read(&bytes, &mut 0, &ParseOptions::default(), &mut Vec::new())?;
+
+        let x_size = header.x_size as usize;
+        let y_size = header.y_size as usize;
+        let voxels = Kv6Header::SIZE as u64 + header.num_voxels as u64 * 8;
+        reader.seek(SeekFrom::Start(start + voxels))?;
+
+        let mut caches = Vec::new();
+        let cache_len = (x_size * 4 + x_size * y_size * 2) as u64;
+        reader.by_ref().take(cache_len).read_to_end(&mut caches)?;
+        if (caches.len() as u64) < cache_len {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+        let offset = &mut 0;
+        let xlen: Vec<u32> = read_vec(&caches, offset, x_size, LE)?;
+        let ylen: Vec<u16> = read_vec(&caches, offset, x_size * y_size, LE)?;
+
+        let mut total = 0u64;
+        for (x, (row, &xlen)) in ylen.chunks(y_size.max(1)).zip(&xlen).enumerate() {
+            let column: u64 = row.iter().map(|&n| n as u64).sum();
+            if column != xlen as u64 {
+                return Err(Kv6Error::InconsistentSizes(CacheMismatch::Column {
+                    x,
+                    expected: column,
+                    actual: xlen as u64,
+                }));
+            }
+            total += column;
+        }
+        if total != header.num_voxels as u64 {
+            return Err(Kv6Error::InconsistentSizes(CacheMismatch::Total {
+                expected: header.num_voxels as u64,
+                actual: total,
+            }));
+        }
+
+        reader.seek(SeekFrom::Start(start + Kv6Header::SIZE as u64))?;
+        Ok(Self {
+            reader,
+            header,
+            ylen,
+            next: 0,
+            read: 0,
+            failed: false,
+        })
+    }
+
+    pub fn header(&self) -> &Kv6Header {
+        &self.header
+    }
+
+    fn read_column(&mut self) -> Result<Column, Kv6Error> {
+        let y_size = self.header.y_size as usize;
+        let index = self.next;
+        let len = self.ylen[index] as usize;
+
+        let mut bytes = vec![0u8; len * 8];
+        self.reader.read_exact(&mut bytes)?;
+        let voxels = bytes
+            .chunks_exact(8)
+            .map(|chunk| VoxelData::from_bytes(chunk.try_into().unwrap()))
+            .collect();
+
+        self.next += 1;
+        self.read += len as u64;
+        Ok(Column {
+            x: (index / y_size) as u32,
+            y: (index % y_size) as u32,
+            voxels,
+        })
+    }
+}
+
+impl<R: Read + Seek> Iterator for Kv6Reader<R> {
+    type Item = Result<Column, Kv6Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.next >= self.ylen.len() {
+            return None;
+        }
+
+        let column = self.read_column();
+        if column.is_err() {
+            self.failed = true;
+        } else if self.next == self.ylen.len() && self.read != self.header.num_voxels as u64 {
+            // Can't happen once the caches were checked, but the count is
+            // what callers rely on, so make sure.
+            self.failed = true;
+            return Some(Err(Kv6Error::InconsistentSizes(CacheMismatch::Total {
+                expected: self.header.num_voxels as u64,
+                actual: self.read,
+            })));
+        }
+        Some(column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Kv6Reader;
+    use crate::kv6::testing::grenade_without_palette;
+    use crate::{
+        kv6::{CacheMismatch, KV6Format},
+        Kv6Error,
+    };
+    use std::{
+        fs,
+        io::{Cursor, Seek, SeekFrom},
+    };
+
+    #[test]
+    fn test_reader_matches_parser() {
+        let buffer = fs::read("data/grenade.kv6").unwrap();
+        let (parsed, _) = KV6Format::parse(&buffer).unwrap();
+
+        let reader = Kv6Reader::new(Cursor::new(&buffer)).unwrap();
+        let header = *reader.header();
+        assert_eq!(header.num_voxels, 74);
+
+        // Rebuild the model from the stream alone and compare all of it.
+        let mut streamed = KV6Format {
+            magic: header.magic,
+            x_size: header.x_size,
+            y_size: header.y_size,
+            z_size: header.z_size,
+            x_pivot: header.x_pivot,
+            y_pivot: header.y_pivot,
+            z_pivot: header.z_pivot,
+            xlen: vec![0; header.x_size as usize],
+            ylen: vec![vec![0; header.y_size as usize]; header.x_size as usize],
+            ..Default::default()
+        };
+        let mut columns = Vec::new();
+        for column in reader {
+            let column = column.unwrap();
+            columns.push((column.x, column.y));
+            streamed.xlen[column.x as usize] += column.voxels.len() as u32;
+            streamed.ylen[column.x as usize][column.y as usize] = column.voxels.len() as u16;
+            streamed.voxels.extend(column.voxels);
+        }
+        let expected: Vec<_> = (0..6).flat_map(|x| (0..6).map(move |y| (x, y))).collect();
+        assert_eq!(columns, expected);
+        // The palette suffix is the one thing the reader skips.
+        assert!(parsed.palette.is_some());
+        assert_eq!(
+            streamed,
+            KV6Format {
+                palette: None,
+                ..parsed
+            }
+        );
+    }
+
+    #[test]
+    fn test_reader_offset_start() {
+        let mut buffer = vec![0u8; 7];
+        buffer.extend(fs::read("data/grenade.kv6").unwrap());
+        let mut cursor = Cursor::new(&buffer);
+        cursor.seek(SeekFrom::Start(7)).unwrap();
+
+        let reader = Kv6Reader::new(cursor).unwrap();
+        let count: usize = reader.map(|column| column.unwrap().voxels.len()).sum();
+        assert_eq!(count, 74);
+    }
+
+    #[test]
+    fn test_reader_bad_caches() {
+        let data = grenade_without_palette();
+        let mut bytes = data.to_bytes().unwrap();
+        // Bump the first ylen entry without touching xlen.
+        let ylen = 32 + 74 * 8 + 6 * 4;
+        bytes[ylen] += 1;
+
+        assert!(matches!(
+            Kv6Reader::new(Cursor::new(&bytes)),
+            Err(Kv6Error::InconsistentSizes(CacheMismatch::Column {
+                x: 0,
+                ..
+            }))
+        ));
+    }
+}
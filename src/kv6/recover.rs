@@ -0,0 +1,292 @@
+use super::{KV6Format, Kv6Header, Palette, ParseOptions, ParseWarning, Section, VoxelData};
+use crate::Kv6Error;
+use alloc::{vec, vec::Vec};
+use scroll::{Pread, LE};
+
+impl KV6Format {
+    /// Salvages what it can from a damaged or truncated file.
+    ///
+    /// Every fully decoded voxel is kept. If the input ends before the caches
+    /// are complete, they're rebuilt from the recovered voxels: whole ylen rows
+    /// that made it are reused, and the remaining voxels are split into a new
+    /// column wherever the height stops increasing. Empty columns can't be told
+    /// apart this way, so the rebuilt layout is a best guess, but it is always
+    /// consistent with the voxel count.
+    ///
+    /// Complete files come back exactly as the regular parser would return
+    /// them, with no warnings. Only a missing or invalid header is an error.
+    pub fn parse_lossy(src: &[u8]) -> Result<(Self, Vec<ParseWarning>), Kv6Error> {
+        let options = ParseOptions::default();
+        let mut warnings = Vec::new();
+        let offset = &mut 0;
+
+        let header = Kv6Header::read(src, offset, &options, &mut warnings)?;
+        let x_size = header.x_size as usize;
+        let y_size = header.y_size as usize;
+
+        let count = (header.num_voxels as usize).min((src.len() - *offset) / 8);
+        let mut voxels: Vec<VoxelData> = Vec::with_capacity(count);
+        for _ in 0..count {
+            voxels.push(src.gread_with(offset, LE)?);
+        }
+        let mut complete = count == header.num_voxels as usize;
+        if !complete {
+            warnings.push(ParseWarning::Truncated {
+                section: Section::Voxels,
+                offset: *offset,
+            });
+        }
+
+        let mut xlen: Vec<u32> = Vec::with_capacity(x_size);
+        if complete {
+            while xlen.len() < x_size && src.len() - *offset >= 4 {
+                xlen.push(src.gread_with(offset, LE)?);
+            }
+            if xlen.len() < x_size {
+                complete = false;
+                warnings.push(ParseWarning::Truncated {
+                    section: Section::Xlen,
+                    offset: *offset,
+                });
+            }
+        }
+
+        let mut ylen: Vec<Vec<u16>> = Vec::with_capacity(x_size);
+        if complete {
+            while ylen.len() < x_size && src.len() - *offset >= y_size * 2 {
+                let mut row = Vec::with_capacity(y_size);
+                for _ in 0..y_size {
+                    row.push(src.gread_with(offset, LE)?);
+                }
+                ylen.push(row);
+            }
+            if ylen.len() < x_size {
+                complete = false;
+                warnings.push(ParseWarning::Truncated {
+                    section: Section::Ylen,
+                    offset: *offset,
+                });
+            }
+        }
+
+        let mut palette = None;
+        if complete && src.get(*offset..*offset + 4) == Some(&Palette::MAGIC[..]) {
+            match src.pread_with::<Palette>(*offset + 4, LE) {
+                Ok(found) => palette = Some(found),
+                Err(_) => warnings.push(ParseWarning::Truncated {
+                    section: Section::Palette,
+                    offset: *offset,
+                }),
+            }
+        }
+
+        if !complete {
+            ylen = rebuild_ylen(&mut voxels, x_size, y_size, ylen);
+            xlen = ylen
+                .iter()
+                .map(|row| row.iter().map(|&n| n as u32).sum())
+                .collect();
+            warnings.push(ParseWarning::CachesRegenerated);
+        }
+
+        let format = KV6Format {
+            magic: header.magic,
+            x_size: header.x_size,
+            y_size: header.y_size,
+            z_size: header.z_size,
+            x_pivot: header.x_pivot,
+            y_pivot: header.y_pivot,
+            z_pivot: header.z_pivot,
+            voxels,
+            xlen,
+            ylen,
+            palette,
+            alpha_mode: options.alpha_mode,
+        };
+        if complete {
+            if let Err(mismatch) = format.check_caches() {
+                warnings.push(ParseWarning::CacheMismatch(mismatch));
+            }
+        }
+        warnings.extend(format.check_dummy());
+
+        Ok((format, warnings))
+    }
+}
+
+/// Completes a partially read ylen table so that it covers `voxels` exactly,
+/// dropping any voxels that can't be placed in a column.
+fn rebuild_ylen(
+    voxels: &mut Vec<VoxelData>,
+    x_size: usize,
+    y_size: usize,
+    mut rows: Vec<Vec<u16>>,
+) -> Vec<Vec<u16>> {
+    // Rows that were read in full are only trusted while the voxels they
+    // describe were actually recovered.
+    let mut used = 0;
+    let mut kept = 0;
+    for row in &rows {
+        let n: usize = row.iter().map(|&n| n as usize).sum();
+        if used + n > voxels.len() {
+            break;
+        }
+        used += n;
+        kept += 1;
+    }
+    rows.truncate(kept);
+
+    let cells = (x_size - kept) * y_size;
+    let mut columns: Vec<u16> = Vec::with_capacity(cells);
+    let mut previous: Option<u16> = None;
+    for voxel in &voxels[used..] {
+        let starts_column = previous.is_none_or(|height| voxel.height <= height);
+        if starts_column {
+            if columns.len() == cells {
+                break;
+            }
+            columns.push(0);
+        }
+        match columns.last_mut() {
+            Some(n) if *n < u16::MAX => *n += 1,
+            _ => break,
+        }
+        previous = Some(voxel.height);
+    }
+
+    voxels.truncate(used + columns.iter().map(|&n| n as usize).sum::<usize>());
+    columns.resize(cells, 0);
+    if y_size > 0 {
+        rows.extend(columns.chunks(y_size).map(|row| row.to_vec()));
+    }
+    rows.resize(x_size, vec![0; y_size]);
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kv6::{KV6Format, Palette, ParseOptions, ParseWarning, Section, VoxelData};
+    use alloc::{vec, vec::Vec};
+    use scroll::{ctx::TryIntoCtx, Pread, LE};
+
+    fn read_grenade() -> Vec<u8> {
+        include_bytes!("../../data/grenade.kv6").to_vec()
+    }
+
+    #[test]
+    fn test_lossy_complete() {
+        let buffer = read_grenade();
+
+        let (data, warnings) = KV6Format::parse_lossy(&buffer).unwrap();
+        assert!(warnings.is_empty());
+
+        let strict = ParseOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let expected = KV6Format::parse_with(&buffer, &strict).unwrap();
+
+        let mut a = vec![0u8; buffer.len()];
+        let mut b = vec![0u8; buffer.len()];
+        data.try_into_ctx(&mut a, LE).unwrap();
+        expected.try_into_ctx(&mut b, LE).unwrap();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_lossy_truncated_voxels() {
+        let buffer = read_grenade();
+
+        let (data, warnings) = KV6Format::parse_lossy(&buffer[..32 + 10 * 8 + 3]).unwrap();
+        assert_eq!(
+            warnings,
+            vec![
+                ParseWarning::Truncated {
+                    section: Section::Voxels,
+                    offset: 32 + 10 * 8,
+                },
+                ParseWarning::CachesRegenerated,
+            ]
+        );
+        assert_eq!(data.voxels.len(), 10);
+        assert_eq!(data.check_caches(), Ok(()));
+    }
+
+    #[test]
+    fn test_lossy_truncated_ylen() {
+        let buffer = read_grenade();
+        let original = buffer.pread::<KV6Format>(0).unwrap();
+
+        let (data, warnings) = KV6Format::parse_lossy(&buffer[..700]).unwrap();
+        assert!(matches!(
+            warnings[..],
+            [
+                ParseWarning::Truncated {
+                    section: Section::Ylen,
+                    ..
+                },
+                ParseWarning::CachesRegenerated,
+            ]
+        ));
+        assert_eq!(data.voxels.len(), 74);
+        assert_eq!(data.ylen[0], original.ylen[0]);
+        assert_eq!(data.check_caches(), Ok(()));
+    }
+
+    #[test]
+    fn test_lossy_surplus_runs() {
+        // Three runs of heights for two columns, with the caches cut off.
+        let data = KV6Format {
+            x_size: 1,
+            y_size: 2,
+            z_size: 4,
+            voxels: [0, 1, 0, 2, 0]
+                .map(|height| VoxelData {
+                    height,
+                    dummy: VoxelData::DUMMY,
+                    ..Default::default()
+                })
+                .to_vec(),
+            xlen: vec![5],
+            ylen: vec![vec![2, 3]],
+            ..Default::default()
+        };
+        let bytes = data.to_bytes().unwrap();
+
+        let (data, warnings) = KV6Format::parse_lossy(&bytes[..32 + 5 * 8]).unwrap();
+        assert_eq!(warnings.last(), Some(&ParseWarning::CachesRegenerated));
+        assert_eq!(data.ylen, [[2, 2]]);
+        assert_eq!(data.voxels.len(), 4);
+        assert_eq!(data.check_caches(), Ok(()));
+        let heights: Vec<u16> = data
+            .column(0, 1)
+            .unwrap()
+            .iter()
+            .map(|v| v.height)
+            .collect();
+        assert_eq!(heights, [0, 2]);
+    }
+
+    #[test]
+    fn test_lossy_truncated_palette() {
+        let buffer = read_grenade();
+        let suffix = Palette::MAGIC.len() + 256 * 3;
+
+        let (data, warnings) = KV6Format::parse_lossy(&buffer[..buffer.len() - 100]).unwrap();
+        assert_eq!(
+            warnings,
+            vec![ParseWarning::Truncated {
+                section: Section::Palette,
+                offset: buffer.len() - suffix,
+            }]
+        );
+        assert!(data.palette.is_none());
+        assert_eq!(data.check_caches(), Ok(()));
+    }
+
+    #[test]
+    fn test_lossy_bad_header() {
+        let buffer = read_grenade();
+        assert!(KV6Format::parse_lossy(&buffer[..20]).is_err());
+    }
+}
@@ -0,0 +1,413 @@
+use super::{DenseVoxelGrid, KV6Format, Rgb};
+use crate::Kv6Error;
+use alloc::{vec, vec::Vec};
+
+/// Knobs for [`KV6Format::upscale_with`].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct UpscaleOptions {
+    /// Drop the voxels that end up with no face next to air instead of
+    /// storing them hidden, as slab6 does for a model's interior.
+    pub shell_only: bool,
+}
+
+/// Knobs for [`KV6Format::downsample_with`].
+#[derive(Debug, Clone, Copy)]
+pub struct DownsampleOptions {
+    /// How much of a block has to be solid for it to become a voxel, from 0
+    /// to 1. Defaults to a half.
+    pub threshold: f32,
+}
+
+impl Default for DownsampleOptions {
+    fn default() -> Self {
+        DownsampleOptions { threshold: 0.5 }
+    }
+}
+
+impl KV6Format {
+    /// Replaces every voxel with a `factor` by `factor` by `factor` block of
+    /// its color, multiplying the sizes and pivot by `factor`.
+    ///
+    /// Visibility and normals are worked out afresh, as for
+    /// [`KV6Format::from_dense`], and voxels get the standard dummy byte. A
+    /// `factor` of 0 gives an empty 0 by 0 by 0 model.
+    pub fn upscale(&self, factor: u32) -> Result<KV6Format, Kv6Error> {
+        self.upscale_with(factor, &UpscaleOptions::default())
+    }
+
+    /// Like [`KV6Format::upscale`], with `options`.
+    ///
+    /// Fails with [`Kv6Error::TooLarge`] if a size or the number of voxels
+    /// wouldn't fit in a `u32`, with [`Kv6Error::LimitExceeded`] if heights
+    /// would go past `u16::MAX` or there would be more than
+    /// [`DenseVoxelGrid::DEFAULT_LIMIT`] voxels, or if the caches don't match
+    /// the voxels.
+    pub fn upscale_with(
+        &self,
+        factor: u32,
+        options: &UpscaleOptions,
+    ) -> Result<KV6Format, Kv6Error> {
+        let scale = |field, size: u32| {
+            size.checked_mul(factor).ok_or(Kv6Error::TooLarge {
+                field,
+                value: (size as u64 * factor as u64)
+                    .try_into()
+                    .unwrap_or(usize::MAX),
+            })
+        };
+        let size = (
+            scale("x_size", self.x_size)?,
+            scale("y_size", self.y_size)?,
+            scale("z_size", self.z_size)?,
+        );
+        if size.2 > 1 << 16 && !self.voxels.is_empty() {
+            return Err(Kv6Error::LimitExceeded {
+                field: "z_size",
+                value: size.2,
+                limit: 1 << 16,
+            });
+        }
+        let count = self.voxels.len() as u128 * (factor as u128).pow(3);
+        if count > u32::MAX as u128 {
+            return Err(Kv6Error::TooLarge {
+                field: "voxels",
+                value: count.try_into().unwrap_or(usize::MAX),
+            });
+        }
+        // Every block is built before any are dropped, so cap them up front.
+        let limit = DenseVoxelGrid::DEFAULT_LIMIT as u32;
+        if count > limit as u128 {
+            return Err(Kv6Error::LimitExceeded {
+                field: "voxels",
+                value: count as u32,
+                limit,
+            });
+        }
+
+        let mut cells = Vec::with_capacity(count as usize);
+        for (x, y, z, voxel) in self
+            .iter_with_coords()
+            .map_err(Kv6Error::InconsistentSizes)?
+        {
+            if z as u32 >= self.z_size {
+                return Err(Kv6Error::OutOfBounds { x, y, z });
+            }
+            let (x, y, z) = (x * factor, y * factor, z as u32 * factor);
+            for dx in 0..factor {
+                for dy in 0..factor {
+                    for dz in 0..factor {
+                        cells.push((x + dx, y + dy, (z + dz) as u16, voxel.color()));
+                    }
+                }
+            }
+        }
+        cells.sort_unstable_by_key(|&(x, y, z, _)| (x, y, z));
+
+        let mut data = KV6Format::from_sorted(size, cells)?;
+        if options.shell_only {
+            data.drop_hidden().map_err(Kv6Error::InconsistentSizes)?;
+        }
+        data.x_pivot = self.x_pivot * factor as f32;
+        data.y_pivot = self.y_pivot * factor as f32;
+        data.z_pivot = self.z_pivot * factor as f32;
+        data.palette = self.palette;
+        Ok(data)
+    }
+
+    /// Shrinks the model by `factor` along every axis, turning each block of
+    /// `factor` by `factor` by `factor` cells into one voxel if at least half
+    /// of it is solid. See [`KV6Format::downsample_with`].
+    pub fn downsample(&self, factor: u32) -> Result<KV6Format, Kv6Error> {
+        self.downsample_with(factor, &DownsampleOptions::default())
+    }
+
+    /// Like [`KV6Format::downsample`], with `options`.
+    ///
+    /// Sizes are divided by `factor` rounding up; the part of a block past
+    /// the edge of the grid counts as empty. A block's solid cells include
+    /// the interior a file doesn't store, and its color is the average of
+    /// the stored voxels in it. A block that's solid only inside the model,
+    /// with no stored voxel, isn't stored either. The pivot is divided by
+    /// `factor`, and visibility and normals are worked out afresh as for
+    /// [`KV6Format::from_dense`]. A `factor` of 0 is taken as 1.
+    ///
+    /// Fails if the caches don't match the voxels or the model's occupancy
+    /// grid doesn't fit in memory.
+    pub fn downsample_with(
+        &self,
+        factor: u32,
+        options: &DownsampleOptions,
+    ) -> Result<KV6Format, Kv6Error> {
+        let factor = factor.max(1);
+        let air = self.occupancy()?.exterior();
+        let z_limit = self.z_size.min(1 << 16);
+        let size = (
+            self.x_size.div_ceil(factor),
+            self.y_size.div_ceil(factor),
+            self.z_size.div_ceil(factor),
+        );
+        let needed = options.threshold as f64 * (factor as f64 * factor as f64 * factor as f64);
+
+        /// Solid cells, summed color and stored voxels in one block.
+        #[derive(Clone, Copy, Default)]
+        struct Block {
+            solid: u64,
+            color: [u64; 3],
+            stored: u64,
+        }
+
+        let mut cells = Vec::new();
+        let span =
+            |block: u32, size: u32| block * factor..size.min((block + 1).saturating_mul(factor));
+        for bx in 0..size.0 {
+            for by in 0..size.1 {
+                let mut blocks = vec![Block::default(); z_limit.div_ceil(factor) as usize];
+                for x in span(bx, self.x_size) {
+                    for y in span(by, self.y_size) {
+                        for z in 0..z_limit {
+                            if !air.contains(x, y, z as u16) {
+                                blocks[(z / factor) as usize].solid += 1;
+                            }
+                        }
+                        for voxel in self.column(x, y).unwrap_or_default() {
+                            let Some(block) =
+                                blocks.get_mut(voxel.height as usize / factor as usize)
+                            else {
+                                continue;
+                            };
+                            if (voxel.height as u32) < z_limit {
+                                let color = [voxel.red, voxel.green, voxel.blue];
+                                for (sum, c) in block.color.iter_mut().zip(color) {
+                                    *sum += c as u64;
+                                }
+                                block.stored += 1;
+                            }
+                        }
+                    }
+                }
+                for (bz, block) in (0..).zip(blocks) {
+                    if block.stored > 0 && block.solid as f64 >= needed {
+                        let [r, g, b] = block
+                            .color
+                            .map(|sum| ((sum + block.stored / 2) / block.stored) as u8);
+                        cells.push((bx, by, bz, Rgb::new(r, g, b)));
+                    }
+                }
+            }
+        }
+
+        let mut data = KV6Format::from_sorted(size, cells)?;
+        data.x_pivot = self.x_pivot / factor as f32;
+        data.y_pivot = self.y_pivot / factor as f32;
+        data.z_pivot = self.z_pivot / factor as f32;
+        data.palette = self.palette;
+        Ok(data)
+    }
+
+    /// `levels` models, each half the size of the one before, starting from
+    /// half the size of this one, made with [`KV6Format::downsample`].
+    pub fn generate_mips(&self, levels: usize) -> Result<Vec<KV6Format>, Kv6Error> {
+        let mut mips: Vec<KV6Format> = Vec::with_capacity(levels);
+        for _ in 0..levels {
+            let mip = mips.last().unwrap_or(self).downsample(2)?;
+            mips.push(mip);
+        }
+        Ok(mips)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DownsampleOptions, UpscaleOptions};
+    use crate::kv6::testing::{cube, grenade};
+    use crate::kv6::{Bounds, KV6Builder, KV6Format, Rgb};
+    use crate::Kv6Error;
+    use alloc::vec::Vec;
+
+    const SHELL: UpscaleOptions = UpscaleOptions { shell_only: true };
+
+    fn single() -> KV6Format {
+        KV6Builder::new(1, 1, 1)
+            .voxel(0, 0, 0, Rgb::new(10, 20, 30))
+            .pivot(0.5, 0.5, 1.0)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_upscale_single() {
+        let data = single().upscale(2).unwrap();
+        assert_eq!((data.x_size, data.y_size, data.z_size), (2, 2, 2));
+        assert_eq!(data.voxels.len(), 8);
+        assert_eq!(data.pivot(), [1.0, 1.0, 2.0]);
+        assert!(data.validate().is_clean(), "{:?}", data.validate());
+        assert!(data.colors().all(|color| color == Rgb::new(10, 20, 30)));
+        // Every voxel of a 2x2x2 block is on its surface.
+        assert_eq!(single().upscale_with(2, &SHELL).unwrap(), data);
+
+        let data = single().upscale(3).unwrap();
+        assert_eq!(data.voxels.len(), 27);
+        assert_eq!(data.get_voxel(1, 1, 1).unwrap().visibility, 0);
+        let shell = single().upscale_with(3, &SHELL).unwrap();
+        assert_eq!(shell.voxels.len(), 26);
+        assert_eq!(shell.get_voxel(1, 1, 1), None);
+        assert!(shell.validate().is_clean());
+        assert!(shell.equivalent(&{
+            let mut data = data.clone();
+            data.remove_voxel(1, 1, 1);
+            data
+        }));
+    }
+
+    #[test]
+    fn test_upscale_stats() {
+        let data = grenade();
+        let (before, after) = (
+            data.stats().unwrap(),
+            data.upscale(2).unwrap().stats().unwrap(),
+        );
+        assert_eq!(after.voxel_count, before.voxel_count * 8);
+        assert_eq!(after.dimensions, (12, 12, 18));
+        assert_eq!(after.pivot, (5.0, 5.0, 7.0));
+        assert_eq!(after.bounds.unwrap().max, (11, 11, 17));
+        assert_eq!(after.unique_colors, before.unique_colors);
+        assert_eq!(after.non_empty_columns, before.non_empty_columns * 4);
+        assert_eq!(after.max_column_len, before.max_column_len * 2);
+
+        let upscaled = data.upscale(2).unwrap();
+        assert_eq!(upscaled.palette, data.palette);
+        assert!(upscaled.validate().is_clean(), "{:?}", upscaled.validate());
+        let shell = data.upscale_with(2, &SHELL).unwrap();
+        assert!(shell.voxels.len() < upscaled.voxels.len());
+        assert!(shell.voxels.iter().all(|voxel| voxel.visibility != 0));
+
+        assert_eq!(data.upscale(1).unwrap().voxels.len(), data.voxels.len());
+        let empty = data.upscale(0).unwrap();
+        assert_eq!((empty.x_size, empty.voxels.len()), (0, 0));
+    }
+
+    #[test]
+    fn test_upscale_limits() {
+        let mut data = single();
+        data.x_size = u32::MAX / 2 + 1;
+        assert!(matches!(
+            data.upscale(2),
+            Err(Kv6Error::TooLarge {
+                field: "x_size",
+                ..
+            })
+        ));
+
+        // Too many voxels to build, though the count fits in a u32.
+        assert!(matches!(
+            single().upscale(1600),
+            Err(Kv6Error::LimitExceeded {
+                field: "voxels",
+                value: 4_096_000_000,
+                limit: 16_777_216,
+            })
+        ));
+        assert!(matches!(
+            single().upscale(257),
+            Err(Kv6Error::LimitExceeded {
+                value: 16_974_593,
+                ..
+            })
+        ));
+
+        let mut data = single();
+        data.z_size = 40000;
+        assert!(matches!(
+            data.upscale(2),
+            Err(Kv6Error::LimitExceeded {
+                field: "z_size",
+                value: 80000,
+                ..
+            })
+        ));
+
+        assert!(matches!(
+            single().upscale(1700),
+            Err(Kv6Error::TooLarge {
+                field: "voxels",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_mips() {
+        let mips = cube(8).generate_mips(4).unwrap();
+        let counts: Vec<_> = mips.iter().map(|mip| mip.voxels.len()).collect();
+        // The last voxel is only an eighth of the block past it.
+        assert_eq!(counts, [64, 8, 1, 0]);
+        for (mip, side) in mips.iter().zip([4, 2, 1]) {
+            assert_eq!((mip.x_size, mip.y_size, mip.z_size), (side, side, side));
+            assert_eq!(
+                mip.occupied_bounds(),
+                Some(Bounds {
+                    min: (0, 0, 0),
+                    max: (side - 1, side - 1, side as u16 - 1)
+                })
+            );
+            assert!(mip.validate().is_clean(), "{:?}", mip.validate());
+        }
+        assert_eq!(mips[0].pivot(), [2.0, 2.0, 2.0]);
+        assert!(cube(8).generate_mips(0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_downsample_odd() {
+        // 5 cells round up to 3 blocks, the last only half inside the grid.
+        // Blocks hanging over two or three edges are less than half full.
+        let data = cube(5).downsample(2).unwrap();
+        assert_eq!((data.x_size, data.y_size, data.z_size), (3, 3, 3));
+        assert_eq!(data.voxels.len(), 27 - 1 - 6);
+        assert!(data.get_voxel(2, 2, 2).is_none() && data.get_voxel(2, 1, 2).is_none());
+        assert!(data.get_voxel(2, 0, 1).is_some());
+
+        let loose = DownsampleOptions { threshold: 0.1 };
+        assert_eq!(cube(5).downsample_with(2, &loose).unwrap().voxels.len(), 27);
+        let strict = DownsampleOptions { threshold: 1.0 };
+        assert_eq!(cube(5).downsample_with(2, &strict).unwrap().voxels.len(), 8);
+    }
+
+    #[test]
+    fn test_downsample_colors() {
+        let data = KV6Builder::new(2, 2, 2)
+            .voxel(0, 0, 0, Rgb::new(0, 0, 0))
+            .voxel(1, 0, 0, Rgb::new(100, 50, 9))
+            .voxel(0, 1, 0, Rgb::new(100, 50, 0))
+            .voxel(1, 1, 0, Rgb::new(100, 50, 0))
+            .build()
+            .unwrap();
+        let small = data.downsample(2).unwrap();
+        assert_eq!(small.voxels.len(), 1);
+        assert_eq!(small.voxels[0].color(), Rgb::new(75, 38, 2));
+
+        // Half full is enough by default, a quarter isn't.
+        let mut data = data;
+        data.remove_voxel(1, 1, 0);
+        assert!(data.downsample(2).unwrap().voxels.is_empty());
+    }
+
+    #[test]
+    fn test_downsample_hollow() {
+        // A 4x4x4 cube with its middle 2x2x2 left out: the middle is still
+        // solid, so every block is full.
+        let mut data = cube(4);
+        for (x, y, z) in [(1, 1, 1), (1, 1, 2), (1, 2, 1), (1, 2, 2)] {
+            data.remove_voxel(x, y, z);
+            data.remove_voxel(x + 1, y, z);
+        }
+        assert_eq!(data.voxels.len(), 56);
+        let small = data.downsample(2).unwrap();
+        assert_eq!(small.voxels.len(), 8);
+
+        let grenade = grenade();
+        let small = grenade.downsample(2).unwrap();
+        assert_eq!((small.x_size, small.y_size, small.z_size), (3, 3, 5));
+        assert!(small.validate().is_clean(), "{:?}", small.validate());
+        assert_eq!(small.occupied_bounds().unwrap().max, (2, 2, 4));
+    }
+}
@@ -0,0 +1,53 @@
+//! Serde can't derive for arrays longer than 32, so palette entries go
+//! through here as a plain sequence.
+
+use alloc::vec::Vec;
+use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+pub(super) fn serialize<S: Serializer>(entries: &[[u8; 3]; 256], s: S) -> Result<S::Ok, S::Error> {
+    s.collect_seq(entries)
+}
+
+pub(super) fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<[[u8; 3]; 256], D::Error> {
+    let entries = Vec::<[u8; 3]>::deserialize(d)?;
+    entries
+        .try_into()
+        .map_err(|entries: Vec<_>| D::Error::invalid_length(entries.len(), &"256 palette entries"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kv6::KV6Format;
+    use alloc::string::ToString;
+
+    const GRENADE: &[u8] = include_bytes!("../../data/grenade.kv6");
+
+    #[test]
+    fn test_serde_json_round_trip() {
+        let (data, _) = KV6Format::parse(GRENADE).unwrap();
+
+        let json = serde_json::to_string(&data).unwrap();
+        let back: KV6Format = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.to_bytes().unwrap(), GRENADE);
+    }
+
+    #[test]
+    fn test_bincode_round_trip() {
+        let (data, _) = KV6Format::parse(GRENADE).unwrap();
+
+        let bytes = bincode::serialize(&data).unwrap();
+        let back: KV6Format = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(back.to_bytes().unwrap(), GRENADE);
+        assert_eq!(back.palette, data.palette);
+    }
+
+    #[test]
+    fn test_short_palette_rejected() {
+        let (data, _) = KV6Format::parse(GRENADE).unwrap();
+        let mut json: serde_json::Value = serde_json::to_value(&data).unwrap();
+        json["palette"]["entries"].as_array_mut().unwrap().pop();
+
+        let err = serde_json::from_value::<KV6Format>(json).unwrap_err();
+        assert!(err.to_string().contains("256 palette entries"));
+    }
+}
@@ -0,0 +1,513 @@
+use super::{normals, CacheMismatch, Face, KV6Format, OccupancyGrid, Rgb, VoxelData};
+use crate::Kv6Error;
+use alloc::{collections::BTreeMap, vec::Vec};
+
+/// The color [`KV6Format::fill_interior`] and [`KV6Format::dilate`] give the
+/// voxels they add.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FillColor {
+    /// The same color everywhere.
+    Constant(Rgb),
+    /// The color of the closest voxel already in the model, counting steps
+    /// between face-adjacent cells.
+    NearestNeighbor,
+}
+
+/// Knobs for [`KV6Format::strip_hidden_with`].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StripOptions {
+    /// Go by the stored visibility bits instead of recomputing them, which is
+    /// faster but only right if they're up to date.
+    pub trust_visibility: bool,
+}
+
+impl KV6Format {
+    /// Removes every voxel with no face next to air, which can't be seen,
+    /// returning how many were removed. See [`KV6Format::strip_hidden_with`].
+    pub fn strip_hidden(&mut self) -> Result<usize, Kv6Error> {
+        self.strip_hidden_with(&StripOptions::default())
+    }
+
+    /// Like [`KV6Format::strip_hidden`], with `options`.
+    ///
+    /// Unless `trust_visibility` is set, visibility is recomputed first, as
+    /// [`KV6Format::recompute_visibility`] does. Either way the remaining
+    /// voxels keep their bits: a removed voxel becomes part of the unstored
+    /// interior, which still counts as solid. Fails if the caches don't
+    /// match the voxels, leaving the model as it was.
+    pub fn strip_hidden_with(&mut self, options: &StripOptions) -> Result<usize, Kv6Error> {
+        self.check_caches().map_err(Kv6Error::InconsistentSizes)?;
+        if !options.trust_visibility {
+            self.recompute_visibility()?;
+        }
+        self.drop_hidden().map_err(Kv6Error::InconsistentSizes)
+    }
+
+    /// A copy of the model with only the voxels that have a face next to air,
+    /// keeping colors, pivot, dimensions and palette. See
+    /// [`KV6Format::surface_shell_with`].
+    pub fn surface_shell(&self) -> Result<KV6Format, Kv6Error> {
+        self.surface_shell_with(&StripOptions::default())
+    }
+
+    /// Like [`KV6Format::surface_shell`], with `options`, as
+    /// [`KV6Format::strip_hidden_with`] takes them.
+    pub fn surface_shell_with(&self, options: &StripOptions) -> Result<KV6Format, Kv6Error> {
+        let mut shell = self.clone();
+        shell.strip_hidden_with(options)?;
+        Ok(shell)
+    }
+
+    /// How many voxels have a face next to air, worked out from where the
+    /// voxels are rather than their visibility bits.
+    pub fn surface_voxel_count(&self) -> Result<usize, Kv6Error> {
+        let air = self.occupancy()?.exterior();
+        let (x_size, y_size, z_size) = air.dimensions();
+        let open = |(x, y, z): (u32, u32, u16)| {
+            x >= x_size || y >= y_size || z as u32 >= z_size || air.contains(x, y, z)
+        };
+        Ok(self
+            .iter_with_coords()
+            .map_err(Kv6Error::InconsistentSizes)?
+            .filter(|&(x, y, z, _)| {
+                Face::ALL
+                    .into_iter()
+                    .any(|face| face.neighbour(x, y, z).is_none_or(open))
+            })
+            .count())
+    }
+
+    /// Keeps only the voxels within `thickness` steps of the air around the
+    /// model, counting a voxel with a face on the air as one step away and
+    /// moving between face-adjacent cells. Returns how many were removed.
+    ///
+    /// Distances are taken through the solid, which includes the interior
+    /// the file doesn't store, and enclosed cavities count as part of it, so
+    /// the model looks the same afterwards. A `thickness` of 0 removes every
+    /// voxel. Fails if the caches don't match the voxels or the occupancy
+    /// grid doesn't fit in memory, leaving the model as it was.
+    pub fn hollow(&mut self, thickness: u32) -> Result<usize, Kv6Error> {
+        let air = self.occupancy()?.exterior();
+        let (x_size, y_size, z_size) = air.dimensions();
+        let solid = |(x, y, z): (u32, u32, u16)| {
+            x < x_size && y < y_size && (z as u32) < z_size && !air.contains(x, y, z)
+        };
+
+        // Breadth first from the surface, one layer per step of thickness.
+        let mut shell = OccupancyGrid::new(x_size, y_size, z_size)?;
+        let mut layer = Vec::new();
+        if thickness > 0 {
+            for x in 0..x_size {
+                for y in 0..y_size {
+                    for z in 0..z_size.min(1 << 16) {
+                        let cell = (x, y, z as u16);
+                        let surface = Face::ALL
+                            .into_iter()
+                            .any(|face| !face.neighbour(x, y, z as u16).is_some_and(solid));
+                        if solid(cell) && surface {
+                            shell.insert(x, y, z as u16);
+                            layer.push(cell);
+                        }
+                    }
+                }
+            }
+        }
+        for _ in 1..thickness {
+            let mut next = Vec::new();
+            for (x, y, z) in layer {
+                for (x, y, z) in Face::ALL
+                    .into_iter()
+                    .filter_map(|face| face.neighbour(x, y, z))
+                {
+                    if solid((x, y, z)) && !shell.contains(x, y, z) {
+                        shell.insert(x, y, z);
+                        next.push((x, y, z));
+                    }
+                }
+            }
+            if next.is_empty() {
+                break;
+            }
+            layer = next;
+        }
+
+        let cells: Vec<_> = self
+            .iter_with_coords()
+            .map_err(Kv6Error::InconsistentSizes)?
+            .filter(|&(x, y, z, _)| shell.contains(x, y, z))
+            .map(|(x, y, _, voxel)| (x, y, *voxel))
+            .collect();
+        let removed = self.voxels.len() - cells.len();
+        let size = (self.x_size, self.y_size, self.z_size);
+        self.set_columns(size, cells)?;
+        self.recompute_visibility()?;
+        Ok(removed)
+    }
+
+    /// Stores a voxel in every enclosed empty cell, the ones that can't be
+    /// reached from outside the model without passing through a voxel, and
+    /// returns how many were added.
+    ///
+    /// The new voxels have no visible faces and the default normal, and the
+    /// existing ones keep theirs, since the cells were already solid as far
+    /// as visibility goes. A model with nothing enclosed is left unchanged.
+    /// Fails if the caches don't match the voxels, the occupancy grid doesn't
+    /// fit in memory or a column would pass 65535 voxels, leaving the model
+    /// as it was.
+    pub fn fill_interior(&mut self, color: FillColor) -> Result<usize, Kv6Error> {
+        let occupied = self.occupancy()?;
+        let air = occupied.exterior();
+        let (x_size, y_size, z_size) = occupied.dimensions();
+        let enclosed = |(x, y, z): (u32, u32, u16)| {
+            x < x_size
+                && y < y_size
+                && (z as u32) < z_size
+                && !occupied.contains(x, y, z)
+                && !air.contains(x, y, z)
+        };
+        let coords = || self.iter_with_coords().map_err(Kv6Error::InconsistentSizes);
+
+        let mut filled = BTreeMap::new();
+        match color {
+            FillColor::Constant(color) => {
+                for x in 0..x_size {
+                    for y in 0..y_size {
+                        for z in 0..z_size.min(1 << 16) {
+                            if enclosed((x, y, z as u16)) {
+                                filled.insert((x, y, z as u16), color);
+                            }
+                        }
+                    }
+                }
+            }
+            FillColor::NearestNeighbor => {
+                // Breadth first from every voxel at once, so each cell takes
+                // the color of whichever voxel reaches it first.
+                let mut layer: Vec<_> = coords()?
+                    .map(|(x, y, z, voxel)| ((x, y, z), voxel.color()))
+                    .collect();
+                while !layer.is_empty() {
+                    let mut next = Vec::new();
+                    for ((x, y, z), color) in layer {
+                        for cell in Face::ALL
+                            .into_iter()
+                            .filter_map(|face| face.neighbour(x, y, z))
+                        {
+                            if enclosed(cell) && !filled.contains_key(&cell) {
+                                filled.insert(cell, color);
+                                next.push((cell, color));
+                            }
+                        }
+                    }
+                    layer = next;
+                }
+            }
+        }
+        if filled.is_empty() {
+            return Ok(0);
+        }
+
+        let mut cells: Vec<_> = coords()?
+            .map(|(x, y, z, voxel)| ((x, y, z), *voxel))
+            .chain(filled.iter().map(|(&(x, y, z), &color)| {
+                let mut voxel = VoxelData {
+                    dummy: VoxelData::DUMMY,
+                    height: z,
+                    visibility: 0,
+                    normalindex: normals::DEFAULT_INDEX,
+                    ..Default::default()
+                };
+                voxel.set_color(color);
+                ((x, y, z), voxel)
+            }))
+            .collect();
+        cells.sort_by_key(|&(cell, _)| cell);
+        let size = (self.x_size, self.y_size, self.z_size);
+        self.set_columns(
+            size,
+            cells.into_iter().map(|((x, y, _), voxel)| (x, y, voxel)),
+        )?;
+        Ok(filled.len())
+    }
+
+    /// Removes every voxel with no visible face bit, returning how many went.
+    /// Fails if the caches don't match the voxels, leaving the model as it
+    /// was.
+    pub(crate) fn drop_hidden(&mut self) -> Result<usize, CacheMismatch> {
+        self.check_caches()?;
+        let cells: Vec<_> = self
+            .iter_with_coords()?
+            .filter(|(_, _, _, voxel)| voxel.visibility & 0x3f != 0)
+            .map(|(x, y, _, voxel)| (x, y, *voxel))
+            .collect();
+        let removed = self.voxels.len() - cells.len();
+        let size = (self.x_size, self.y_size, self.z_size);
+        self.set_columns(size, cells)
+            .expect("a subset of valid columns is valid");
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FillColor, StripOptions};
+    use crate::kv6::testing::{cube, grenade};
+    use crate::kv6::{CacheMismatch, KV6Builder, KV6Format, Rgb};
+
+    const TRUST: StripOptions = StripOptions {
+        trust_visibility: true,
+    };
+
+    #[test]
+    fn test_strip_grenade() {
+        let mut data = grenade();
+        assert_eq!(data.strip_hidden().unwrap(), 0);
+        assert_eq!(data, grenade());
+        assert_eq!(data.strip_hidden_with(&TRUST).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_strip_cube() {
+        let mut data = cube(5);
+        assert_eq!(data.strip_hidden().unwrap(), 27);
+        assert_eq!(data.voxels.len(), 125 - 27);
+        assert!(data.validate().is_clean(), "{:?}", data.validate());
+        for (x, y, z, _) in data.iter_with_coords().unwrap() {
+            let edge = |c: u32| c == 0 || c == 4;
+            assert!(edge(x) || edge(y) || edge(z as u32));
+        }
+        // What's left is the same model as far as visibility goes.
+        let mut visible = data.clone();
+        visible.recompute_visibility().unwrap();
+        assert_eq!(visible, data);
+        assert_eq!(data.strip_hidden().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_hollow_cube() {
+        for (thickness, kept) in [(1, 512 - 216), (2, 512 - 64), (4, 512), (10, 512)] {
+            let mut data = cube(8);
+            assert_eq!(data.hollow(thickness).unwrap(), 512 - kept);
+            assert_eq!(data.voxels.len(), kept);
+            assert!(data.validate().is_clean(), "{:?}", data.validate());
+            assert!(
+                data.equivalent(&{
+                    let mut solid = cube(8);
+                    solid.strip_hidden().unwrap();
+                    solid
+                }) == (thickness == 1)
+            );
+        }
+
+        let mut data = cube(8);
+        assert_eq!(data.hollow(0).unwrap(), 512);
+        assert!(data.voxels.is_empty());
+    }
+
+    #[test]
+    fn test_hollow_cavity() {
+        // Taking out the middle leaves an enclosed cavity, which is still
+        // part of the solid, so hollowing measures through it.
+        let mut data = cube(8);
+        for x in 3..5 {
+            for y in 3..5 {
+                for z in 3..5 {
+                    data.remove_voxel(x, y, z).unwrap();
+                }
+            }
+        }
+        let mut hollowed = data.clone();
+        assert_eq!(hollowed.hollow(1).unwrap(), 512 - 8 - 296);
+        assert_eq!(hollowed.hollow(1).unwrap(), 0);
+        assert_eq!(data.clone().hollow(3).unwrap(), 0);
+        assert_eq!(data.hollow(2).unwrap(), 64 - 8);
+        assert_eq!(data.voxels.len(), 448);
+
+        // The grenade is already one voxel thick.
+        let mut data = grenade();
+        assert_eq!(data.hollow(1).unwrap(), 0);
+        assert!(data.equivalent(&grenade()));
+    }
+
+    /// A ball of `color` with radius `r`, centered in a box `2r` across.
+    fn sphere(r: u32, color: Rgb) -> KV6Format {
+        let n = 2 * r;
+        let mut builder = KV6Builder::new(n, n, n);
+        let r = r as i64;
+        for x in 0..n {
+            for y in 0..n {
+                for z in 0..n as u16 {
+                    let d = |c: u32| 2 * c as i64 + 1 - 2 * r;
+                    if d(x) * d(x) + d(y) * d(y) + d(z as u32) * d(z as u32) <= 4 * r * r {
+                        builder = builder.voxel(x, y, z, color);
+                    }
+                }
+            }
+        }
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn test_fill_hollow_sphere() {
+        let red = Rgb::new(255, 0, 0);
+        let solid = sphere(6, red);
+        let mut hollow = solid.clone();
+        let removed = hollow.strip_hidden().unwrap();
+        assert!(removed > 0);
+
+        let mut data = hollow.clone();
+        assert_eq!(
+            data.fill_interior(FillColor::NearestNeighbor).unwrap(),
+            removed
+        );
+        assert!(data.equivalent(&solid));
+        assert!(data.validate().is_clean(), "{:?}", data.validate());
+        // Filling changes nothing about what's visible.
+        let mut visible = data.clone();
+        visible.recompute_visibility().unwrap();
+        assert_eq!(visible, data);
+        assert_eq!(data.fill_interior(FillColor::NearestNeighbor).unwrap(), 0);
+
+        let blue = Rgb::new(0, 0, 255);
+        let mut data = hollow.clone();
+        assert_eq!(
+            data.fill_interior(FillColor::Constant(blue)).unwrap(),
+            removed
+        );
+        assert_eq!(data.voxels.len(), solid.voxels.len());
+        for (x, y, z, voxel) in data.iter_with_coords().unwrap() {
+            let kept = hollow.get_voxel(x, y, z);
+            assert_eq!(voxel.color(), if kept.is_some() { red } else { blue });
+            assert_eq!(voxel.visibility == 0, kept.is_none());
+        }
+    }
+
+    #[test]
+    fn test_fill_nearest_colors() {
+        let mut data = cube(5);
+        data.strip_hidden().unwrap();
+        assert_eq!(data.fill_interior(FillColor::NearestNeighbor).unwrap(), 27);
+        // The middle of each inner face has only one voxel next to it.
+        for (x, y, z) in [
+            (1, 2, 2),
+            (3, 2, 2),
+            (2, 1, 2),
+            (2, 3, 2),
+            (2, 2, 1),
+            (2, 2, 3),
+        ] {
+            let (nx, ny, nz) = (x * 2 - 2, y * 2 - 2, z * 2 - 2);
+            assert_eq!(
+                data.get_voxel(x, y, z).unwrap().color(),
+                Rgb::new(nx as u8, ny as u8, nz as u8)
+            );
+        }
+    }
+
+    #[test]
+    fn test_fill_nothing_enclosed() {
+        let mut data = cube(4);
+        assert_eq!(data.fill_interior(FillColor::NearestNeighbor).unwrap(), 0);
+        assert_eq!(data, cube(4));
+
+        let mut data = sphere(3, Rgb::new(1, 2, 3));
+        let original = data.clone();
+        assert_eq!(
+            data.fill_interior(FillColor::Constant(Rgb::new(0, 0, 0)))
+                .unwrap(),
+            0
+        );
+        assert_eq!(data, original);
+
+        // The grenade's interior isn't stored, so it gets filled.
+        let mut data = grenade();
+        let added = data.fill_interior(FillColor::NearestNeighbor).unwrap();
+        assert!(added > 0);
+        assert_eq!(data.voxels.len(), 74 + added);
+        data.strip_hidden().unwrap();
+        assert!(data.equivalent(&grenade()));
+    }
+
+    #[test]
+    fn test_surface_shell() {
+        assert_eq!(cube(1).surface_voxel_count().unwrap(), 1);
+        for n in 2..6 {
+            let data = cube(n);
+            let count = (6 * n * n - 12 * n + 8) as usize;
+            assert_eq!(data.surface_voxel_count().unwrap(), count);
+
+            let shell = data.surface_shell().unwrap();
+            assert_eq!(shell.voxels.len(), count);
+            assert_eq!(
+                (shell.x_pivot, shell.y_pivot, shell.z_pivot),
+                (data.x_pivot, data.y_pivot, data.z_pivot)
+            );
+            assert_eq!(shell.surface_voxel_count().unwrap(), count);
+            assert!(shell.surface_shell().unwrap().equivalent(&shell));
+        }
+
+        // Hollowing keeps the surface, so the shell of a hollow model is itself.
+        let mut data = cube(8);
+        data.hollow(2).unwrap();
+        let shell = data.surface_shell().unwrap();
+        assert_eq!(shell.voxels.len(), 296);
+        assert!(shell.equivalent(&cube(8).surface_shell().unwrap()));
+        assert!(shell.surface_shell().unwrap().equivalent(&shell));
+
+        let shell = grenade().surface_shell().unwrap();
+        assert_eq!(shell, grenade());
+        assert_eq!(grenade().surface_voxel_count().unwrap(), 74);
+    }
+
+    #[test]
+    fn test_surface_stale_bits() {
+        let mut data = cube(4);
+        for voxel in &mut data.voxels {
+            voxel.visibility = 0;
+        }
+        assert_eq!(data.surface_voxel_count().unwrap(), 56);
+        assert_eq!(data.surface_shell().unwrap().voxels.len(), 56);
+        assert!(data.surface_shell_with(&TRUST).unwrap().voxels.is_empty());
+    }
+
+    #[test]
+    fn test_strip_trusting() {
+        // Stale bits are believed when trusted and replaced otherwise.
+        let mut data = grenade();
+        data.voxels[0].visibility = 0;
+        data.voxels[1].visibility = 0x40;
+        let mut trusting = data.clone();
+        assert_eq!(trusting.strip_hidden_with(&TRUST).unwrap(), 2);
+        assert_eq!(trusting.voxels.len(), grenade().voxels.len() - 2);
+        assert!(trusting.check_caches().is_ok());
+        assert_eq!(data.strip_hidden().unwrap(), 0);
+
+        let mut data = cube(3);
+        for voxel in &mut data.voxels {
+            voxel.visibility = 0x3f;
+        }
+        assert_eq!(data.strip_hidden_with(&TRUST).unwrap(), 0);
+        assert_eq!(data.strip_hidden().unwrap(), 1);
+
+        let mut broken = grenade();
+        broken.voxels.pop();
+        assert!(broken.strip_hidden().is_err());
+    }
+
+    #[test]
+    fn test_drop_hidden_mismatch() {
+        // Mismatched caches are reported rather than emptying the model.
+        let mut broken = cube(3);
+        broken.ylen[0][0] += 1;
+        let before = broken.clone();
+        assert!(matches!(
+            broken.drop_hidden(),
+            Err(CacheMismatch::Column { x: 0, .. })
+        ));
+        assert_eq!(broken, before);
+
+        let mut data = cube(3);
+        assert_eq!(data.drop_hidden(), Ok(1));
+        assert_eq!(data.voxels.len(), 26);
+    }
+}
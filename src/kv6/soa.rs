@@ -0,0 +1,145 @@
+use super::{KV6Format, Rgb, VoxelData};
+use alloc::vec::Vec;
+
+/// A model with each voxel field in its own vector, so passes over one
+/// field, such as recoloring or height statistics, don't drag the rest
+/// through the cache.
+///
+/// Entry `i` of every vector belongs to voxel `i` of the model, and `xlen`
+/// and `ylen` delimit columns exactly as they do in [`KV6Format`]. Change
+/// the vectors in step; [`Kv6Soa::to_kv6`] stops at the shortest one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Kv6Soa {
+    pub reds: Vec<u8>,
+    pub greens: Vec<u8>,
+    pub blues: Vec<u8>,
+    pub dummies: Vec<u8>,
+    pub heights: Vec<u16>,
+    pub visibilities: Vec<u8>,
+    pub normal_indices: Vec<u8>,
+    pub xlen: Vec<u32>,
+    pub ylen: Vec<Vec<u16>>,
+    /// Everything else about the model; its voxels and caches are empty.
+    rest: KV6Format,
+}
+
+impl Kv6Soa {
+    pub fn len(&self) -> usize {
+        self.heights.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heights.is_empty()
+    }
+
+    /// Replaces every voxel's color with `f` of it.
+    pub fn map_colors(&mut self, mut f: impl FnMut(Rgb) -> Rgb) {
+        let colors = self
+            .reds
+            .iter_mut()
+            .zip(&mut self.greens)
+            .zip(&mut self.blues);
+        for ((r, g), b) in colors {
+            let color = f(Rgb::new(*r, *g, *b));
+            (*r, *g, *b) = (color.r, color.g, color.b);
+        }
+    }
+
+    /// Reassembles the model.
+    pub fn to_kv6(&self) -> KV6Format {
+        let voxels = self
+            .reds
+            .iter()
+            .zip(&self.greens)
+            .zip(&self.blues)
+            .zip(&self.dummies)
+            .zip(&self.heights)
+            .zip(&self.visibilities)
+            .zip(&self.normal_indices)
+            .map(
+                |((((((&red, &green), &blue), &dummy), &height), &visibility), &normalindex)| {
+                    VoxelData {
+                        blue,
+                        green,
+                        red,
+                        dummy,
+                        height,
+                        visibility,
+                        normalindex,
+                    }
+                },
+            )
+            .collect();
+
+        KV6Format {
+            voxels,
+            xlen: self.xlen.clone(),
+            ylen: self.ylen.clone(),
+            ..self.rest.clone()
+        }
+    }
+}
+
+impl From<&KV6Format> for Kv6Soa {
+    fn from(data: &KV6Format) -> Self {
+        let field = |f: fn(&VoxelData) -> u8| data.voxels.iter().map(f).collect();
+        Kv6Soa {
+            reds: field(|voxel| voxel.red),
+            greens: field(|voxel| voxel.green),
+            blues: field(|voxel| voxel.blue),
+            dummies: field(|voxel| voxel.dummy),
+            heights: data.voxels.iter().map(|voxel| voxel.height).collect(),
+            visibilities: field(|voxel| voxel.visibility),
+            normal_indices: field(|voxel| voxel.normalindex),
+            xlen: data.xlen.clone(),
+            ylen: data.ylen.clone(),
+            rest: KV6Format {
+                voxels: Vec::new(),
+                xlen: Vec::new(),
+                ylen: Vec::new(),
+                ..data.clone()
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Kv6Soa;
+    use crate::kv6::testing::grenade;
+    use crate::kv6::{KV6Format, Rgb};
+
+    #[test]
+    fn test_soa_round_trip() {
+        let data = grenade();
+        let soa = Kv6Soa::from(&data);
+        assert_eq!(soa.len(), 74);
+        assert_eq!(soa.heights[3], data.voxels[3].height);
+        assert_eq!(soa.to_kv6(), data);
+
+        let empty = KV6Format::default();
+        let soa = Kv6Soa::from(&empty);
+        assert!(soa.is_empty());
+        assert_eq!(soa.to_kv6(), empty);
+    }
+
+    #[test]
+    fn test_map_colors() {
+        let data = grenade();
+        let mut soa = Kv6Soa::from(&data);
+        soa.map_colors(|Rgb { r, g, b }| Rgb::new(b, r, g));
+
+        let mapped = soa.to_kv6();
+        for (before, after) in data.voxels.iter().zip(&mapped.voxels) {
+            assert_eq!(
+                after.color(),
+                Rgb::new(before.blue, before.red, before.green)
+            );
+            assert_eq!(
+                (after.height, after.visibility),
+                (before.height, before.visibility)
+            );
+        }
+        assert_eq!((mapped.xlen, mapped.palette), (data.xlen, data.palette));
+    }
+}
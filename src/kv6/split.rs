@@ -0,0 +1,152 @@
+use super::KV6Format;
+use crate::Kv6Error;
+use alloc::{collections::BTreeMap, vec::Vec};
+
+/// Which chunk of a model [`KV6Format::split`] made, counted in chunks from
+/// the origin. Its first cell is at these coordinates times the chunk size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ChunkCoord {
+    pub x: u32,
+    pub y: u32,
+    pub z: u32,
+}
+
+impl KV6Format {
+    /// Cuts the model into chunks of `chunk` cells, or less at the far edges
+    /// of the grid, in `ChunkCoord` order. Chunks without voxels are left
+    /// out. A chunk size of 0 is taken as 1.
+    ///
+    /// Each chunk's pivot is the model's pivot relative to the chunk, so
+    /// [`KV6Format::merge`]-ing the chunks back at `coord * chunk` gives the
+    /// original voxels. Each chunk's visibility is recomputed on its own, so
+    /// faces on the cut are shown, but cutting through a solid leaves the
+    /// interior the file doesn't store open. Fails if the caches don't match
+    /// the voxels or a voxel lies outside the grid.
+    pub fn split(&self, chunk: (u32, u32, u32)) -> Result<Vec<(ChunkCoord, KV6Format)>, Kv6Error> {
+        let (cx, cy, cz) = (chunk.0.max(1), chunk.1.max(1), chunk.2.max(1));
+        let mut chunks: BTreeMap<ChunkCoord, Vec<_>> = BTreeMap::new();
+        for (x, y, z, voxel) in self
+            .iter_with_coords()
+            .map_err(Kv6Error::InconsistentSizes)?
+        {
+            if z as u32 >= self.z_size {
+                return Err(Kv6Error::OutOfBounds { x, y, z });
+            }
+            let coord = ChunkCoord {
+                x: x / cx,
+                y: y / cy,
+                z: z as u32 / cz,
+            };
+            let mut voxel = *voxel;
+            voxel.height = (z as u32 % cz) as u16;
+            // Voxels are visited in column order, so each chunk's are too.
+            chunks
+                .entry(coord)
+                .or_default()
+                .push((x % cx, y % cy, voxel));
+        }
+
+        chunks
+            .into_iter()
+            .map(|(coord, cells)| {
+                let origin = (coord.x * cx, coord.y * cy, coord.z * cz);
+                let size = (
+                    cx.min(self.x_size - origin.0),
+                    cy.min(self.y_size - origin.1),
+                    cz.min(self.z_size - origin.2),
+                );
+                let mut part = KV6Format {
+                    x_pivot: self.x_pivot - origin.0 as f32,
+                    y_pivot: self.y_pivot - origin.1 as f32,
+                    z_pivot: self.z_pivot - origin.2 as f32,
+                    palette: self.palette,
+                    alpha_mode: self.alpha_mode,
+                    ..Default::default()
+                };
+                part.set_columns(size, cells)?;
+                part.recompute_visibility()?;
+                Ok((coord, part))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChunkCoord;
+    use crate::kv6::testing::grenade;
+    use crate::kv6::{ConflictPolicy, KV6Format};
+
+    #[test]
+    fn test_split_and_merge() {
+        let original = grenade();
+        let chunks = original.split((4, 4, 4)).unwrap();
+        assert!(chunks.len() <= 2 * 2 * 3);
+        assert!(chunks.windows(2).all(|w| w[0].0 < w[1].0));
+        let total: usize = chunks.iter().map(|(_, chunk)| chunk.voxels.len()).sum();
+        assert_eq!(total, original.voxels.len());
+
+        let mut whole = KV6Format {
+            x_pivot: original.x_pivot,
+            y_pivot: original.y_pivot,
+            z_pivot: original.z_pivot,
+            ..Default::default()
+        };
+        for (coord, chunk) in &chunks {
+            assert!(chunk.x_size <= 4 && chunk.y_size <= 4 && chunk.z_size <= 4);
+            assert!(!chunk.voxels.is_empty());
+            // The pivot is usually outside a chunk, which is only a warning.
+            assert!(!chunk.validate().has_errors(), "{:?}", chunk.validate());
+            let origin = (coord.x * 4, coord.y * 4, coord.z * 4);
+            assert_eq!(
+                chunk.pivot(),
+                [
+                    original.x_pivot - origin.0 as f32,
+                    original.y_pivot - origin.1 as f32,
+                    original.z_pivot - origin.2 as f32,
+                ]
+            );
+            let offset = (origin.0 as i32, origin.1 as i32, origin.2 as i32);
+            whole
+                .merge(chunk, offset, ConflictPolicy::KeepSelf)
+                .unwrap();
+        }
+        assert!(whole.equivalent(&original));
+        assert_eq!(whole.pivot(), original.pivot());
+        let mut visible = original.clone();
+        visible.recompute_visibility().unwrap();
+        assert_eq!(
+            whole
+                .voxels
+                .iter()
+                .map(|v| v.visibility)
+                .collect::<alloc::vec::Vec<_>>(),
+            visible
+                .voxels
+                .iter()
+                .map(|v| v.visibility)
+                .collect::<alloc::vec::Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_split_edges() {
+        let original = grenade();
+        let chunks = original.split((4, 4, 4)).unwrap();
+        // 9 high in chunks of 4 leaves a last layer one cell high.
+        let (_, last) = chunks.iter().find(|(coord, _)| coord.z == 2).unwrap();
+        assert_eq!(last.z_size, 1);
+
+        let whole = original.split((100, 100, 100)).unwrap();
+        assert_eq!(whole.len(), 1);
+        assert_eq!(whole[0].0, ChunkCoord { x: 0, y: 0, z: 0 });
+        assert!(whole[0].1.equivalent(&original));
+
+        let cells = original.split((0, 0, 0)).unwrap();
+        assert_eq!(cells.len(), original.voxels.len());
+
+        let mut broken = original.clone();
+        broken.voxels.pop();
+        assert!(broken.split((4, 4, 4)).is_err());
+    }
+}
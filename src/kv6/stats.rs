@@ -0,0 +1,107 @@
+use super::{Bounds, CacheMismatch, KV6Format};
+use alloc::collections::BTreeSet;
+
+/// A summary of a model, from [`KV6Format::stats`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Kv6Stats {
+    pub voxel_count: usize,
+    /// `(x_size, y_size, z_size)`.
+    pub dimensions: (u32, u32, u32),
+    /// `(x_pivot, y_pivot, z_pivot)`.
+    pub pivot: (f32, f32, f32),
+    /// The smallest box holding every voxel, `None` for an empty model.
+    pub bounds: Option<Bounds>,
+    /// Voxels per cell of `bounds`, or 0 for an empty model. Since only
+    /// surface voxels are stored, solid models come out well below 1.
+    pub fill_ratio: f64,
+    pub non_empty_columns: usize,
+    pub unique_colors: usize,
+    /// Fewest voxels in a non-empty column, or 0 if there are none.
+    pub min_column_len: u16,
+    /// Most voxels in any column.
+    pub max_column_len: u16,
+}
+
+impl KV6Format {
+    /// Gathers a [`Kv6Stats`] in one pass over the voxels. Fails like
+    /// [`KV6Format::iter_with_coords`] if the caches don't match.
+    pub fn stats(&self) -> Result<Kv6Stats, CacheMismatch> {
+        let mut bounds: Option<Bounds> = None;
+        let mut colors = BTreeSet::new();
+        for (x, y, z, voxel) in self.iter_with_coords()? {
+            match &mut bounds {
+                Some(bounds) => bounds.extend(x, y, z),
+                None => bounds = Some(Bounds::point(x, y, z)),
+            }
+            colors.insert(voxel.color().to_u32());
+        }
+
+        let columns = self.ylen.iter().flatten().filter(|&&n| n > 0);
+        Ok(Kv6Stats {
+            voxel_count: self.voxels.len(),
+            dimensions: (self.x_size, self.y_size, self.z_size),
+            pivot: (self.x_pivot, self.y_pivot, self.z_pivot),
+            bounds,
+            fill_ratio: match bounds {
+                Some(bounds) => self.voxels.len() as f64 / bounds.volume() as f64,
+                None => 0.0,
+            },
+            non_empty_columns: columns.clone().count(),
+            unique_colors: colors.len(),
+            min_column_len: columns.clone().copied().min().unwrap_or(0),
+            max_column_len: columns.copied().max().unwrap_or(0),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Kv6Stats;
+    use crate::kv6::testing::grenade;
+    use crate::kv6::{Bounds, KV6Format};
+    use alloc::vec;
+
+    #[test]
+    fn test_grenade_stats() {
+        let data = grenade();
+        let stats = data.stats().unwrap();
+        assert_eq!(
+            stats,
+            Kv6Stats {
+                voxel_count: 74,
+                dimensions: (6, 6, 9),
+                pivot: (2.5, 2.5, 3.5),
+                bounds: Some(Bounds {
+                    min: (0, 0, 0),
+                    max: (5, 5, 8),
+                }),
+                fill_ratio: 74.0 / 324.0,
+                non_empty_columns: 24,
+                unique_colors: 2,
+                min_column_len: 2,
+                max_column_len: 4,
+            }
+        );
+    }
+
+    #[test]
+    fn test_empty_stats() {
+        let data = KV6Format {
+            x_size: 2,
+            y_size: 2,
+            z_size: 2,
+            xlen: vec![0; 2],
+            ylen: vec![vec![0; 2]; 2],
+            ..Default::default()
+        };
+        let stats = data.stats().unwrap();
+        assert_eq!(stats.bounds, None);
+        assert_eq!(stats.fill_ratio, 0.0);
+        assert_eq!((stats.min_column_len, stats.max_column_len), (0, 0));
+        assert_eq!(stats.unique_colors, 0);
+
+        let mut broken = data;
+        broken.xlen.pop();
+        assert!(broken.stats().is_err());
+    }
+}
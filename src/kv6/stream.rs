@@ -0,0 +1,108 @@
+use super::{KV6Format, Kv6Header, ParseOptions};
+use crate::Kv6Error;
+use std::io::{self, Read, Write};
+
+impl KV6Format {
+    /// Reads one model from a stream, consuming exactly the header, voxels,
+    /// xlen and ylen and nothing after them, so several models can be read
+    /// back to back from the same stream.
+    ///
+    /// The optional `SPal` suffix isn't read: spotting it would mean
+    /// consuming the start of whatever follows the model. Use
+    /// [`KV6Format::from_path`] or [`KV6Format::parse_with`] for files that
+    /// may carry one.
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Self, Kv6Error> {
+        let options = ParseOptions::default();
+
+        let mut bytes = vec![0u8; Kv6Header::SIZE];
+        reader.read_exact(&mut bytes)?;
+        let header = Kv6Header::read(&bytes, &mut 0, &options, &mut Vec::new())?;
+
+        let body = body_len(&header)?;
+        // Let the buffer grow as data actually arrives instead of trusting
+        // the header with one big allocation.
+        let read = reader.by_ref().take(body).read_to_end(&mut bytes)?;
+        if (read as u64) < body {
+            return Err(short_model(body, bytes.len()));
+        }
+
+        KV6Format::parse_with(&bytes, &options)
+    }
+
+    /// Serializes the model, including any palette suffix, into a stream.
+    pub fn to_writer<W: Write>(&self, mut writer: W) -> Result<(), Kv6Error> {
+        writer.write_all(&self.to_bytes()?)?;
+        Ok(())
+    }
+}
+
+/// Bytes that follow the header, up to the end of ylen.
+pub(super) fn body_len(header: &Kv6Header) -> Result<u64, Kv6Error> {
+    let total = header
+        .required_len()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "model size overflows usize"))?;
+    Ok((total - Kv6Header::SIZE) as u64)
+}
+
+/// The error for a stream that ended after `read` of a model's bytes.
+pub(super) fn short_model(body: u64, read: usize) -> Kv6Error {
+    io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        format!(
+            "model needs {} bytes but the stream ended after {}",
+            body + Kv6Header::SIZE as u64,
+            read
+        ),
+    )
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kv6::testing::grenade_without_palette;
+    use crate::kv6::KV6Format;
+    use std::io::{Cursor, Read};
+
+    /// Hands out at most three bytes per call.
+    struct Chunked<R>(R);
+
+    impl<R: Read> Read for Chunked<R> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let len = buf.len().min(3);
+            self.0.read(&mut buf[..len])
+        }
+    }
+
+    #[test]
+    fn test_from_reader_cursor() {
+        let data = grenade_without_palette();
+        let mut stream = Vec::new();
+        data.to_writer(&mut stream).unwrap();
+        data.to_writer(&mut stream).unwrap();
+
+        let mut cursor = Cursor::new(&stream);
+        let first = KV6Format::from_reader(&mut cursor).unwrap();
+        assert_eq!(cursor.position() as usize, stream.len() / 2);
+        let second = KV6Format::from_reader(&mut cursor).unwrap();
+        assert_eq!(cursor.position() as usize, stream.len());
+
+        assert_eq!(first.to_bytes().unwrap(), data.to_bytes().unwrap());
+        assert_eq!(second.to_bytes().unwrap(), data.to_bytes().unwrap());
+    }
+
+    #[test]
+    fn test_from_reader_chunked() {
+        let data = grenade_without_palette();
+        let bytes = data.to_bytes().unwrap();
+
+        let read = KV6Format::from_reader(Chunked(&bytes[..])).unwrap();
+        assert_eq!(read.to_bytes().unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_from_reader_truncated() {
+        let bytes = grenade_without_palette().to_bytes().unwrap();
+        assert!(KV6Format::from_reader(Chunked(&bytes[..bytes.len() - 1])).is_err());
+        assert!(KV6Format::from_reader(&bytes[..16]).is_err());
+    }
+}
@@ -0,0 +1,229 @@
+use super::{
+    canvas::round,
+    normals::{closest_index, NormalTable},
+    Axis, KV6Format,
+};
+use crate::Kv6Error;
+use alloc::vec::Vec;
+
+/// The plane [`KV6Format::symmetrize`] mirrors across, at right angles to
+/// an axis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SymmetryPlane {
+    /// Through the middle of the cells at `index` along the axis, which
+    /// mirror onto themselves. The plane for a model an odd number of cells
+    /// across.
+    Layer(Axis, u32),
+    /// Between the cells at `index - 1` and `index` along the axis. The plane
+    /// for a model an even number of cells across.
+    Between(Axis, u32),
+    /// Through the pivot, rounded to the nearest half cell.
+    Pivot(Axis),
+}
+
+/// Which side of a [`SymmetryPlane`] to keep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HalfSpace {
+    /// The cells nearer the origin than the plane.
+    Below,
+    /// The cells further from the origin than the plane.
+    Above,
+}
+
+impl KV6Format {
+    /// Replaces the `source` side's mirror image, on the other side of
+    /// `plane`, with a copy of the `source` side.
+    ///
+    /// The `source` side and any cells the plane passes through are kept as
+    /// they are, apart from visibility, which is recomputed for everything.
+    /// The copies get mirrored normals. Copies that land outside the grid
+    /// are dropped, so a plane off the middle leaves the far end of the
+    /// larger side empty. Fails if the caches don't match the voxels or a
+    /// column would pass 65535 voxels, leaving the model as it was.
+    pub fn symmetrize(&mut self, plane: SymmetryPlane, source: HalfSpace) -> Result<(), Kv6Error> {
+        let (axis, twice) = match plane {
+            SymmetryPlane::Layer(axis, index) => (axis, 2 * index as i64 + 1),
+            SymmetryPlane::Between(axis, index) => (axis, 2 * index as i64),
+            SymmetryPlane::Pivot(axis) => (axis, round(2.0 * self.pivot()[axis.index()] as f64)),
+        };
+        let i = axis.index();
+        let size = [self.x_size, self.y_size, self.z_size.min(1 << 16)][i] as i64;
+        // Twice the distance from the plane to a cell's middle, positive on
+        // the source side.
+        let side = |c: u32| match source {
+            HalfSpace::Below => twice - (2 * c as i64 + 1),
+            HalfSpace::Above => 2 * c as i64 + 1 - twice,
+        };
+
+        let normals = NormalTable::Slab6;
+        let mut cells = Vec::with_capacity(self.voxels.len());
+        for (x, y, z, voxel) in self
+            .iter_with_coords()
+            .map_err(Kv6Error::InconsistentSizes)?
+        {
+            let mut position = [x, y, z as u32];
+            if side(position[i]) < 0 {
+                continue;
+            }
+            cells.push((position, *voxel));
+            if side(position[i]) == 0 {
+                continue;
+            }
+            let mirrored = twice - 1 - position[i] as i64;
+            if !(0..size).contains(&mirrored) {
+                continue;
+            }
+            position[i] = mirrored as u32;
+            let mut voxel = *voxel;
+            if (voxel.normalindex as usize) < normals.entries().len() {
+                let mut normal = voxel.normal_with(normals);
+                normal[i] = -normal[i];
+                voxel.normalindex = closest_index(normal, normals);
+            }
+            cells.push((position, voxel));
+        }
+        cells.sort_by_key(|&(position, _)| position);
+
+        let size = (self.x_size, self.y_size, self.z_size);
+        self.set_columns(
+            size,
+            cells.into_iter().map(|([x, y, z], mut voxel)| {
+                voxel.height = z as u16;
+                (x, y, voxel)
+            }),
+        )?;
+        self.recompute_visibility()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HalfSpace, SymmetryPlane};
+    use crate::kv6::testing::grenade;
+    use crate::kv6::{
+        normals::{closest_index, NormalTable},
+        Axis, KV6Builder, KV6Format, Rgb,
+    };
+    use alloc::vec::Vec;
+
+    /// A 5x4x3 model with nothing symmetric about it.
+    fn asymmetric() -> KV6Format {
+        let mut builder = KV6Builder::new(5, 4, 3);
+        for (i, (x, y, z)) in [
+            (0, 0, 0),
+            (1, 3, 2),
+            (2, 1, 1),
+            (2, 2, 0),
+            (4, 0, 2),
+            (3, 3, 1),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            builder = builder.voxel(x, y, z, Rgb::new(i as u8 * 40, 0, 0));
+        }
+        builder.build().unwrap()
+    }
+
+    /// The voxels of `data` for which `keep` holds, as `(x, y, z, color)`.
+    fn half(data: &KV6Format, keep: impl Fn(u32, u32, u16) -> bool) -> Vec<(u32, u32, u16, Rgb)> {
+        data.iter_with_coords()
+            .unwrap()
+            .filter(|&(x, y, z, _)| keep(x, y, z))
+            .map(|(x, y, z, voxel)| (x, y, z, voxel.color()))
+            .collect()
+    }
+
+    #[test]
+    fn test_symmetrize_odd() {
+        for source in [HalfSpace::Below, HalfSpace::Above] {
+            let mut data = asymmetric();
+            data.symmetrize(SymmetryPlane::Layer(Axis::X, 2), source)
+                .unwrap();
+            assert!(data.validate().is_clean(), "{:?}", data.validate());
+
+            let mut flipped = data.clone();
+            flipped.flip(Axis::X).unwrap();
+            assert!(flipped.equivalent(&data));
+
+            // The source side and the middle column are as they were.
+            let kept = |x: u32, _, _| match source {
+                HalfSpace::Below => x <= 2,
+                HalfSpace::Above => x >= 2,
+            };
+            assert_eq!(half(&data, kept), half(&asymmetric(), kept));
+        }
+
+        let mut data = asymmetric();
+        data.symmetrize(SymmetryPlane::Layer(Axis::X, 2), HalfSpace::Below)
+            .unwrap();
+        assert_eq!(data.get_voxel(4, 0, 0).unwrap().red, 0);
+        assert_eq!(data.get_voxel(3, 3, 2).unwrap().red, 40);
+        assert!(data.get_voxel(4, 0, 2).is_none());
+        assert_eq!(data.voxels.len(), 6);
+    }
+
+    #[test]
+    fn test_symmetrize_even() {
+        for axis in Axis::ALL {
+            let size = [6, 6, 9][axis.index()];
+            let plane = if size % 2 == 0 {
+                SymmetryPlane::Between(axis, size / 2)
+            } else {
+                SymmetryPlane::Layer(axis, size / 2)
+            };
+            let mut data = grenade();
+            data.symmetrize(plane, HalfSpace::Above).unwrap();
+            assert!(data.validate().is_clean(), "{:?}", data.validate());
+            let mut flipped = data.clone();
+            flipped.flip(axis).unwrap();
+            assert!(flipped.equivalent(&data), "{:?}", axis);
+            assert_eq!(flipped.voxels.len(), data.voxels.len());
+
+            let kept = |x: u32, y: u32, z: u16| [x, y, z as u32][axis.index()] >= size / 2;
+            assert_eq!(half(&data, kept), half(&grenade(), kept));
+        }
+
+        // The grenade's pivot is in the middle of column 2 along x.
+        let mut pivot = grenade();
+        pivot
+            .symmetrize(SymmetryPlane::Pivot(Axis::X), HalfSpace::Below)
+            .unwrap();
+        let mut layer = grenade();
+        layer
+            .symmetrize(SymmetryPlane::Layer(Axis::X, 2), HalfSpace::Below)
+            .unwrap();
+        assert_eq!(pivot, layer);
+    }
+
+    #[test]
+    fn test_symmetrize_normals() {
+        let mut data = grenade();
+        data.symmetrize(SymmetryPlane::Between(Axis::Y, 3), HalfSpace::Below)
+            .unwrap();
+        for (x, y, z, voxel) in data.iter_with_coords().unwrap() {
+            let source = grenade().get_voxel(x, y.min(5 - y), z).copied().unwrap();
+            let [nx, ny, nz] = source.normal();
+            let expected = if y < 3 {
+                source.normalindex
+            } else {
+                closest_index([nx, -ny, nz], NormalTable::Slab6)
+            };
+            assert_eq!(voxel.normalindex, expected, "at {:?}", (x, y, z));
+        }
+        assert_eq!(data.pivot(), grenade().pivot());
+    }
+
+    #[test]
+    fn test_symmetrize_off_center() {
+        // Copies past the end of the grid are dropped, and the far end of
+        // the destination is cleared.
+        let mut data = asymmetric();
+        data.symmetrize(SymmetryPlane::Between(Axis::X, 1), HalfSpace::Below)
+            .unwrap();
+        assert_eq!(
+            half(&data, |_, _, _| true),
+            [(0, 0, 0, Rgb::new(0, 0, 0)), (1, 0, 0, Rgb::new(0, 0, 0))]
+        );
+    }
+}
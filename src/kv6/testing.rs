@@ -0,0 +1,195 @@
+//! Proptest strategies for generating valid models, and a few fixed models
+//! to test against, for this crate's tests and for anyone else's behind the
+//! `testing` feature.
+
+use super::{KV6Builder, KV6Format, Palette, Rgb, VoxelData};
+use alloc::vec::Vec;
+use proptest::{collection, option, prelude::*};
+
+/// A voxel with an arbitrary color and the standard dummy byte. `height` is
+/// left at 0 for [`model`] to fill in.
+pub fn voxel() -> impl Strategy<Value = VoxelData> {
+    (any::<[u8; 3]>(), any::<u8>(), any::<u8>()).prop_map(
+        |([red, green, blue], visibility, normalindex)| VoxelData {
+            red,
+            green,
+            blue,
+            dummy: VoxelData::DUMMY,
+            height: 0,
+            visibility,
+            normalindex,
+        },
+    )
+}
+
+/// A palette of 6-bit VGA components.
+pub fn palette() -> impl Strategy<Value = Palette> {
+    collection::vec([0..64u8, 0..64u8, 0..64u8], 256).prop_map(|entries| Palette {
+        entries: entries.try_into().unwrap(),
+    })
+}
+
+/// The voxels of one column of a model `z_size` tall, heights increasing.
+fn column(z_size: u32) -> impl Strategy<Value = Vec<VoxelData>> {
+    let max = (z_size as usize).min(8);
+    collection::btree_set(0..z_size as u16, 0..=max)
+        .prop_flat_map(|heights| {
+            let count = heights.len();
+            (Just(heights), collection::vec(voxel(), count))
+        })
+        .prop_map(|(heights, voxels)| {
+            voxels
+                .into_iter()
+                .zip(heights)
+                .map(|(voxel, height)| VoxelData { height, ..voxel })
+                .collect()
+        })
+}
+
+/// A small valid model: up to 8 by 8 columns, at most 8 voxels per column
+/// with increasing heights below `z_size`, and caches that match.
+pub fn model() -> impl Strategy<Value = KV6Format> {
+    (0..=8u32, 0..=8u32, 1..=32u32)
+        .prop_flat_map(|(x_size, y_size, z_size)| {
+            (
+                Just((x_size, y_size, z_size)),
+                collection::vec(column(z_size), (x_size * y_size) as usize),
+                [-64.0..64.0f32, -64.0..64.0f32, -64.0..64.0f32],
+                option::of(palette()),
+            )
+        })
+        .prop_map(|((x_size, y_size, z_size), columns, pivots, palette)| {
+            let ylen: Vec<Vec<u16>> = if y_size == 0 {
+                alloc::vec![Vec::new(); x_size as usize]
+            } else {
+                columns
+                    .chunks(y_size as usize)
+                    .map(|row| row.iter().map(|column| column.len() as u16).collect())
+                    .collect()
+            };
+            KV6Format {
+                magic: KV6Format::MAGIC,
+                x_size,
+                y_size,
+                z_size,
+                x_pivot: pivots[0],
+                y_pivot: pivots[1],
+                z_pivot: pivots[2],
+                xlen: ylen
+                    .iter()
+                    .map(|row| row.iter().map(|&n| n as u32).sum())
+                    .collect(),
+                ylen,
+                voxels: columns.into_iter().flatten().collect(),
+                palette,
+                alpha_mode: Default::default(),
+            }
+        })
+}
+
+/// The grenade shipped in `data/grenade.kv6`, 6×6×9 with a palette.
+pub fn grenade() -> KV6Format {
+    KV6Format::parse(include_bytes!("../../data/grenade.kv6"))
+        .unwrap()
+        .0
+}
+
+/// [`grenade`] without its palette, for code that doesn't read or write the
+/// suffix.
+pub fn grenade_without_palette() -> KV6Format {
+    KV6Format {
+        palette: None,
+        ..grenade()
+    }
+}
+
+/// A model of the given size with no voxels.
+pub fn empty(x_size: u32, y_size: u32, z_size: u32) -> KV6Format {
+    KV6Format {
+        x_size,
+        y_size,
+        z_size,
+        xlen: alloc::vec![0; x_size as usize],
+        ylen: alloc::vec![alloc::vec![0; y_size as usize]; x_size as usize],
+        ..Default::default()
+    }
+}
+
+/// A solid cube of side `n`, each voxel colored by its position.
+pub fn cube(n: u32) -> KV6Format {
+    let mut builder = KV6Builder::new(n, n, n);
+    for x in 0..n {
+        for y in 0..n {
+            for z in 0..n as u16 {
+                builder = builder.voxel(x, y, z, Rgb::new(x as u8, y as u8, z as u8));
+            }
+        }
+    }
+    builder.build().unwrap()
+}
+
+/// A 2x3x4 model with no symmetry: voxels at (0, 0, 0), (0, 0, 3),
+/// (1, 0, 3) and (1, 2, 1), each with a different color.
+pub fn lopsided() -> KV6Format {
+    let mut data = KV6Format {
+        x_pivot: 0.5,
+        y_pivot: 1.0,
+        z_pivot: 3.0,
+        ..empty(2, 3, 4)
+    };
+    for (i, (x, y, z)) in [(0, 0, 0), (0, 0, 3), (1, 0, 3), (1, 2, 1)]
+        .into_iter()
+        .enumerate()
+    {
+        let mut voxel = VoxelData {
+            dummy: VoxelData::DUMMY,
+            ..Default::default()
+        };
+        voxel.set_color(Rgb::new(i as u8, 0, 0));
+        data.insert_voxel(x, y, z, voxel).unwrap();
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::model;
+    use crate::kv6::KV6Format;
+    use proptest::prelude::*;
+    use scroll::{Pwrite, LE};
+
+    proptest! {
+        #[test]
+        fn test_round_trip(data in model()) {
+            prop_assert_eq!(data.check_caches(), Ok(()));
+
+            let bytes = data.to_bytes().unwrap();
+            let (parsed, consumed) = KV6Format::parse(&bytes).unwrap();
+            prop_assert_eq!(consumed, bytes.len());
+            prop_assert_eq!(parsed, data);
+        }
+
+        #[test]
+        fn test_serialized_size(data in model()) {
+            let size = data.serialized_size();
+            let mut out = alloc::vec![0u8; size + 16];
+            prop_assert_eq!(out.pwrite_with(&data, 0, LE).unwrap(), size);
+        }
+
+        #[test]
+        fn test_prefixes_fail(data in model()) {
+            let bytes = data.to_bytes().unwrap();
+            let header = KV6Format::peek_header(&bytes).unwrap();
+            let model_len = header.required_len().unwrap();
+
+            // Anything short of the caches is an error...
+            for len in 0..model_len {
+                prop_assert!(KV6Format::parse(&bytes[..len]).is_err());
+            }
+            // ...and cutting into the palette suffix must not panic either.
+            for len in model_len..bytes.len() {
+                let _ = KV6Format::parse(&bytes[..len]);
+            }
+        }
+    }
+}
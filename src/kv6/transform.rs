@@ -0,0 +1,601 @@
+use super::{
+    normals::{closest_index, NormalTable},
+    Axis, Face, KV6Format,
+};
+use crate::Kv6Error;
+use alloc::vec::Vec;
+
+/// Which axis of a model feeds each axis of the remapped model, and whether
+/// it's reversed, for [`KV6Format::remap_axes`]. Any of the 48 ways to
+/// permute and mirror the axes can be expressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AxisMapping {
+    axes: [(Axis, bool); 3],
+}
+
+impl AxisMapping {
+    /// Leaves every axis as it is.
+    pub const IDENTITY: AxisMapping = AxisMapping {
+        axes: [(Axis::X, false), (Axis::Y, false), (Axis::Z, false)],
+    };
+
+    /// From slab6's axes, with z pointing down, to MagicaVoxel's, with z
+    /// pointing up. Only z is reversed, which also turns the left-handed axes
+    /// into right-handed ones.
+    pub const SLAB6_TO_MAGICAVOXEL: AxisMapping = AxisMapping {
+        axes: [(Axis::X, false), (Axis::Y, false), (Axis::Z, true)],
+    };
+
+    /// The inverse of [`AxisMapping::SLAB6_TO_MAGICAVOXEL`], which is the
+    /// same mapping.
+    pub const MAGICAVOXEL_TO_SLAB6: AxisMapping = Self::SLAB6_TO_MAGICAVOXEL;
+
+    /// A mapping whose x, y and z axes are the source axis in each pair,
+    /// reversed if its flag is set. `None` unless every axis is used once.
+    pub fn new(x: (Axis, bool), y: (Axis, bool), z: (Axis, bool)) -> Option<AxisMapping> {
+        let (a, b, c) = (x.0, y.0, z.0);
+        if a == b || b == c || a == c {
+            return None;
+        }
+        Some(AxisMapping { axes: [x, y, z] })
+    }
+
+    /// For the x, y and z axes the source axis and whether it's reversed.
+    pub fn axes(&self) -> [(Axis, bool); 3] {
+        self.axes
+    }
+
+    /// Reverses `axis`.
+    pub fn flip(axis: Axis) -> AxisMapping {
+        let mut mapping = Self::IDENTITY;
+        mapping.axes[axis.index()].1 = true;
+        mapping
+    }
+
+    /// A quarter turn about `axis`, taking the next axis round to the one
+    /// after: about z, +x goes to +y; about x, +y to +z; about y, +z to +x.
+    pub fn quarter_turn(axis: Axis) -> AxisMapping {
+        let mut mapping = Self::IDENTITY;
+        let (from, to) = match axis {
+            Axis::X => (Axis::Y, Axis::Z),
+            Axis::Y => (Axis::Z, Axis::X),
+            Axis::Z => (Axis::X, Axis::Y),
+        };
+        // The new `from` axis is the old `to` axis reversed, and the new `to`
+        // axis is the old `from`.
+        mapping.axes[from.index()] = (to, true);
+        mapping.axes[to.index()] = (from, false);
+        mapping
+    }
+
+    /// This mapping followed by `next`.
+    pub fn then(self, next: AxisMapping) -> AxisMapping {
+        AxisMapping {
+            axes: next.axes.map(|(axis, negated)| {
+                let (source, reversed) = self.axes[axis.index()];
+                (source, reversed != negated)
+            }),
+        }
+    }
+
+    /// The mapping that undoes this one.
+    pub fn inverse(self) -> AxisMapping {
+        let mut inverse = Self::IDENTITY;
+        for (axis, (source, negated)) in Axis::ALL.into_iter().zip(self.axes) {
+            inverse.axes[source.index()] = (axis, negated);
+        }
+        inverse
+    }
+
+    /// The face of a source voxel that ends up as `face`.
+    fn source_face(&self, face: Face) -> Face {
+        let (axis, negated) = self.axes[face as usize / 2];
+        let positive = (face as usize % 2 == 1) != negated;
+        Face::ALL[axis.index() * 2 + positive as usize]
+    }
+}
+
+impl KV6Format {
+    /// Mirrors the model along `axis` within its grid, so a voxel at `x`
+    /// moves to `x_size - 1 - x` and so on.
+    ///
+    /// The pivot is mirrored too, the two visibility bits for the axis trade
+    /// places, and normals are reflected and matched to the closest entry of
+    /// [`NormalTable::Slab6`]; see [`KV6Format::flip_with`] for other tables.
+    /// Fails if the caches don't match the voxels or a voxel lies outside the
+    /// grid, leaving the model as it was.
+    pub fn flip(&mut self, axis: Axis) -> Result<(), Kv6Error> {
+        self.flip_with(axis, NormalTable::default())
+    }
+
+    /// Like [`KV6Format::flip`], for normals written against `table`.
+    pub fn flip_with(&mut self, axis: Axis, table: NormalTable) -> Result<(), Kv6Error> {
+        self.remap_axes_with(AxisMapping::flip(axis), table)
+    }
+
+    /// Turns the model `quarter_turns` times 90° about `axis`, in the
+    /// direction that takes +x to +y about z, +y to +z about x and +z to +x
+    /// about y.
+    ///
+    /// The two sizes across `axis` trade places, and the pivot, visibility
+    /// and normals turn with the voxels, as for [`KV6Format::flip`]. Fails
+    /// the same way, or with [`Kv6Error::LimitExceeded`] if a turn would
+    /// make `z_size` larger than heights can go.
+    pub fn rotate90(&mut self, axis: Axis, quarter_turns: u8) -> Result<(), Kv6Error> {
+        self.rotate90_with(axis, quarter_turns, NormalTable::default())
+    }
+
+    /// Like [`KV6Format::rotate90`], for normals written against `table`.
+    pub fn rotate90_with(
+        &mut self,
+        axis: Axis,
+        quarter_turns: u8,
+        table: NormalTable,
+    ) -> Result<(), Kv6Error> {
+        let turn = AxisMapping::quarter_turn(axis);
+        let mut mapping = AxisMapping::IDENTITY;
+        for _ in 0..quarter_turns % 4 {
+            mapping = mapping.then(turn);
+        }
+        self.remap_axes_with(mapping, table)
+    }
+
+    /// Moves every voxel to where `mapping` sends it: the new x coordinate is
+    /// the source axis of `mapping`'s x, counted from the far end if that's
+    /// reversed, and so on. The sizes and pivot follow the same way.
+    ///
+    /// Caches are rebuilt, visibility bits move to the faces they now
+    /// describe and normals are transformed and matched to the closest entry
+    /// of [`NormalTable::Slab6`], or of the table given to
+    /// [`KV6Format::remap_axes_with`]. Fails as [`KV6Format::rotate90`] does, in
+    /// which case the model is left as it was. The identity mapping leaves
+    /// the model untouched.
+    pub fn remap_axes(&mut self, mapping: AxisMapping) -> Result<(), Kv6Error> {
+        self.remap_axes_with(mapping, NormalTable::default())
+    }
+
+    /// Like [`KV6Format::remap_axes`], for normals written against `table`,
+    /// which they're transformed in and matched back to. Indices past the end
+    /// of `table` are kept as they are.
+    pub fn remap_axes_with(
+        &mut self,
+        mapping: AxisMapping,
+        table: NormalTable,
+    ) -> Result<(), Kv6Error> {
+        if mapping == AxisMapping::IDENTITY {
+            return Ok(());
+        }
+        let sizes = [self.x_size, self.y_size, self.z_size];
+        let pivots = [self.x_pivot, self.y_pivot, self.z_pivot];
+        let [x_size, y_size, z_size] = mapping.axes.map(|(axis, _)| sizes[axis.index()]);
+        let moved = |axis: Axis, negated: bool| (axis, negated) != (Axis::Z, false);
+        if z_size > 1 << 16 && moved(mapping.axes[2].0, mapping.axes[2].1) {
+            return Err(Kv6Error::LimitExceeded {
+                field: "z_size",
+                value: z_size,
+                limit: 1 << 16,
+            });
+        }
+
+        let mut cells = Vec::with_capacity(self.voxels.len());
+        for (x, y, z, voxel) in self
+            .iter_with_coords()
+            .map_err(Kv6Error::InconsistentSizes)?
+        {
+            if z as u32 >= self.z_size {
+                return Err(Kv6Error::OutOfBounds { x, y, z });
+            }
+            let source = [x, y, z as u32];
+            let [x, y, z] = mapping.axes.map(|(axis, negated)| {
+                let i = axis.index();
+                if negated {
+                    sizes[i] - 1 - source[i]
+                } else {
+                    source[i]
+                }
+            });
+
+            let mut voxel = *voxel;
+            let visibility = voxel.visibility;
+            voxel.visibility = visibility & !0x3f;
+            for face in Face::ALL {
+                if visibility & mapping.source_face(face).flag().bits() != 0 {
+                    voxel.visibility |= face.flag().bits();
+                }
+            }
+            if (voxel.normalindex as usize) < table.entries().len() {
+                let normal = voxel.normal_with(table);
+                let normal = mapping.axes.map(|(axis, negated)| {
+                    let c = normal[axis.index()];
+                    if negated {
+                        -c
+                    } else {
+                        c
+                    }
+                });
+                voxel.normalindex = closest_index(normal, table);
+            }
+            voxel.height = z as u16;
+            cells.push(((x, y), voxel));
+        }
+        // Stable, so voxels that were in order along the new z stay so.
+        cells.sort_by_key(|&((x, y), voxel)| (x, y, voxel.height));
+
+        self.set_columns(
+            (x_size, y_size, z_size),
+            cells.into_iter().map(|((x, y), voxel)| (x, y, voxel)),
+        )?;
+        [self.x_pivot, self.y_pivot, self.z_pivot] = mapping.axes.map(|(axis, negated)| {
+            let i = axis.index();
+            if negated {
+                sizes[i] as f32 - pivots[i]
+            } else {
+                pivots[i]
+            }
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AxisMapping;
+    use crate::kv6::testing::{grenade, lopsided};
+    use crate::kv6::{
+        normals::{closest_index, NormalTable, DEFAULT_INDEX},
+        Axis, Face, KV6Format, VoxelData,
+    };
+    use crate::Kv6Error;
+    use alloc::{vec, vec::Vec};
+
+    #[test]
+    fn test_flip_positions() {
+        let mut data = lopsided();
+        data.flip(Axis::X).unwrap();
+        assert!(data.check_caches().is_ok());
+        assert_eq!(data.get_voxel(1, 0, 0).unwrap().red, 0);
+        assert_eq!(data.get_voxel(1, 0, 3).unwrap().red, 1);
+        assert_eq!(data.get_voxel(0, 0, 3).unwrap().red, 2);
+        assert_eq!(data.get_voxel(0, 2, 1).unwrap().red, 3);
+        assert_eq!(data.x_pivot, 1.5);
+        assert_eq!((data.y_pivot, data.z_pivot), (1.0, 3.0));
+
+        // Flipping z has to keep each column sorted by height.
+        let mut data = lopsided();
+        data.flip(Axis::Z).unwrap();
+        assert!(data.validate().is_clean(), "{:?}", data.validate());
+        assert_eq!(data.get_voxel(0, 0, 3).unwrap().red, 0);
+        assert_eq!(data.get_voxel(0, 0, 0).unwrap().red, 1);
+        assert_eq!(data.z_pivot, 1.0);
+    }
+
+    #[test]
+    fn test_flip_faces() {
+        let mut voxel = VoxelData {
+            visibility: (Face::Left.flag() | Face::Top.flag()).bits() | 0x40,
+            ..Default::default()
+        };
+        let mut data = KV6Format {
+            x_size: 1,
+            y_size: 1,
+            z_size: 1,
+            xlen: vec![1],
+            ylen: vec![vec![1]],
+            ..Default::default()
+        };
+        // Pointing right, into the voxel from its left face.
+        voxel.normalindex = (0..=255)
+            .max_by(|&a: &u8, &b| {
+                NormalTable::Slab6.get(a)[0].total_cmp(&NormalTable::Slab6.get(b)[0])
+            })
+            .unwrap();
+        data.voxels.push(voxel);
+
+        data.flip(Axis::X).unwrap();
+        let flipped = data.voxels[0];
+        assert_eq!(
+            flipped.visibility,
+            (Face::Right.flag() | Face::Top.flag()).bits() | 0x40
+        );
+        let [x, y, z] = flipped.normal();
+        let [ox, oy, oz] = voxel.normal();
+        assert!(x < -0.9);
+        assert!((x + ox).abs() < 0.1 && (y - oy).abs() < 0.1 && (z - oz).abs() < 0.1);
+
+        data.flip(Axis::Z).unwrap();
+        assert_eq!(
+            data.voxels[0].visibility,
+            (Face::Right.flag() | Face::Bottom.flag()).bits() | 0x40
+        );
+    }
+
+    #[test]
+    fn test_rotate_positions() {
+        let mut data = lopsided();
+        data.rotate90(Axis::Z, 1).unwrap();
+        assert_eq!((data.x_size, data.y_size, data.z_size), (3, 2, 4));
+        assert!(data.validate().is_clean(), "{:?}", data.validate());
+        // (x, y) goes to (y_size - 1 - y, x).
+        assert_eq!(data.get_voxel(2, 0, 0).unwrap().red, 0);
+        assert_eq!(data.get_voxel(2, 1, 3).unwrap().red, 2);
+        assert_eq!(data.get_voxel(0, 1, 1).unwrap().red, 3);
+        assert_eq!((data.x_pivot, data.y_pivot, data.z_pivot), (2.0, 0.5, 3.0));
+        let top = data.voxels.iter().find(|voxel| voxel.red == 3).unwrap();
+        assert_eq!(top.visibility, 0x3f);
+
+        // About x, (y, z) goes to (z_size - 1 - z, y).
+        let mut data = lopsided();
+        data.rotate90(Axis::X, 1).unwrap();
+        assert_eq!((data.x_size, data.y_size, data.z_size), (2, 4, 3));
+        assert_eq!(data.get_voxel(0, 3, 0).unwrap().red, 0);
+        assert_eq!(data.get_voxel(1, 2, 2).unwrap().red, 3);
+
+        // About y, (x, z) goes to (z, x_size - 1 - x).
+        let mut data = lopsided();
+        data.rotate90(Axis::Y, 3).unwrap();
+        let mut back = data.clone();
+        back.rotate90(Axis::Y, 1).unwrap();
+        assert!(back.equivalent(&lopsided()));
+        data.rotate90(Axis::Y, 2).unwrap();
+        assert_eq!((data.x_size, data.y_size, data.z_size), (4, 3, 2));
+        assert_eq!(data.get_voxel(0, 0, 1).unwrap().red, 0);
+        assert_eq!(data.get_voxel(3, 0, 1).unwrap().red, 1);
+        assert_eq!(data.get_voxel(1, 2, 0).unwrap().red, 3);
+    }
+
+    #[test]
+    fn test_four_turns() {
+        for axis in [Axis::X, Axis::Y, Axis::Z] {
+            let original = grenade();
+            let mut data = original.clone();
+            for _ in 0..4 {
+                data.rotate90(axis, 1).unwrap();
+                assert!(data.validate().is_clean(), "{:?}", data.validate());
+            }
+            assert!(data.equivalent(&original));
+            assert_eq!(visibility(&data), visibility(&original));
+            assert_eq!(
+                (data.x_pivot, data.y_pivot, data.z_pivot),
+                (original.x_pivot, original.y_pivot, original.z_pivot)
+            );
+
+            let mut data = lopsided();
+            data.rotate90(axis, 4).unwrap();
+            assert_eq!(data, lopsided());
+            data.rotate90(axis, 6).unwrap();
+            let mut twice = lopsided();
+            twice.rotate90(axis, 1).unwrap();
+            twice.rotate90(axis, 1).unwrap();
+            // Normals are matched to the table once rather than twice, so
+            // they can come out differently.
+            assert!(data.equivalent(&twice));
+            assert_eq!(visibility(&data), visibility(&twice));
+        }
+    }
+
+    #[test]
+    fn test_rotated_projections() {
+        let original = grenade();
+        let mut data = original.clone();
+        data.rotate90(Axis::Z, 1).unwrap();
+
+        let (before, after) = (
+            original.project(Axis::Z).unwrap(),
+            data.project(Axis::Z).unwrap(),
+        );
+        assert_eq!((after.rows, after.columns), (before.columns, before.rows));
+        for x in 0..after.rows {
+            for y in 0..after.columns {
+                assert_eq!(after.get(x, y), before.get(y, before.columns - 1 - x));
+            }
+        }
+
+        // Looking along the new x is looking along the old y, and along the
+        // new y is looking along the old x from the other side.
+        assert_eq!(data.project(Axis::X), {
+            let mut side = original.project(Axis::Y).unwrap();
+            side.axis = Axis::X;
+            Ok(side)
+        });
+        let (before, after) = (
+            original.project(Axis::X).unwrap(),
+            data.project(Axis::Y).unwrap(),
+        );
+        for x in 0..after.rows {
+            for z in 0..after.columns {
+                assert_eq!(after.get(x, z), before.get(before.rows - 1 - x, z));
+            }
+        }
+
+        let mut visible = data.clone();
+        visible.recompute_visibility().unwrap();
+        assert_eq!(visibility(&visible), visibility(&data));
+    }
+
+    #[test]
+    fn test_rotate_limits() {
+        let mut data = KV6Format {
+            x_size: 1 << 17,
+            y_size: 1,
+            z_size: 1,
+            xlen: vec![0; 1 << 17],
+            ylen: vec![vec![0]; 1 << 17],
+            ..Default::default()
+        };
+        assert!(matches!(
+            data.rotate90(Axis::Y, 1),
+            Err(Kv6Error::LimitExceeded {
+                field: "z_size",
+                value: 131072,
+                ..
+            })
+        ));
+        assert_eq!(data.x_size, 1 << 17);
+        data.rotate90(Axis::Z, 1).unwrap();
+        assert_eq!((data.x_size, data.y_size), (1, 1 << 17));
+    }
+
+    /// Every valid mapping.
+    fn all_mappings() -> Vec<AxisMapping> {
+        let mut mappings = Vec::new();
+        for x in Axis::ALL {
+            for y in Axis::ALL {
+                for z in Axis::ALL {
+                    for signs in 0..8 {
+                        let sign = |bit: u8| signs & 1 << bit != 0;
+                        mappings.extend(AxisMapping::new((x, sign(0)), (y, sign(1)), (z, sign(2))));
+                    }
+                }
+            }
+        }
+        mappings
+    }
+
+    #[test]
+    fn test_mappings() {
+        let mappings = all_mappings();
+        assert_eq!(mappings.len(), 48);
+        assert_eq!(
+            AxisMapping::new((Axis::X, false), (Axis::X, true), (Axis::Z, false)),
+            None
+        );
+        for &mapping in &mappings {
+            assert_eq!(mapping.then(mapping.inverse()), AxisMapping::IDENTITY);
+            assert_eq!(mapping.inverse().then(mapping), AxisMapping::IDENTITY);
+        }
+        let turn = AxisMapping::quarter_turn(Axis::Z);
+        assert_eq!(turn.then(turn).then(turn), turn.inverse());
+        assert_eq!(
+            AxisMapping::SLAB6_TO_MAGICAVOXEL.then(AxisMapping::MAGICAVOXEL_TO_SLAB6),
+            AxisMapping::IDENTITY
+        );
+    }
+
+    #[test]
+    fn test_remap_inverse() {
+        let original = lopsided();
+        for mapping in all_mappings() {
+            let mut data = original.clone();
+            data.remap_axes(mapping).unwrap();
+            assert!(data.validate().is_clean(), "{:?}", data.validate());
+            let mut recomputed = data.clone();
+            recomputed.recompute_visibility().unwrap();
+            assert_eq!(visibility(&recomputed), visibility(&data));
+
+            data.remap_axes(mapping.inverse()).unwrap();
+            assert!(data.equivalent(&original), "{:?}", mapping);
+            assert_eq!(visibility(&data), visibility(&original));
+            assert_eq!(
+                (data.x_pivot, data.y_pivot, data.z_pivot),
+                (original.x_pivot, original.y_pivot, original.z_pivot)
+            );
+        }
+    }
+
+    #[test]
+    fn test_remap_presets() {
+        let mut data = lopsided();
+        data.remap_axes(AxisMapping::SLAB6_TO_MAGICAVOXEL).unwrap();
+        let mut flipped = lopsided();
+        flipped.flip(Axis::Z).unwrap();
+        assert_eq!(data, flipped);
+
+        // A transpose of x and y.
+        let mut data = lopsided();
+        let transpose = AxisMapping::new((Axis::Y, false), (Axis::X, false), (Axis::Z, false));
+        data.remap_axes(transpose.unwrap()).unwrap();
+        assert_eq!((data.x_size, data.y_size, data.z_size), (3, 2, 4));
+        assert_eq!(data.get_voxel(2, 1, 1).unwrap().red, 3);
+        assert_eq!((data.x_pivot, data.y_pivot), (1.0, 0.5));
+    }
+
+    #[test]
+    fn test_flip_voxlap_normals() {
+        let table = NormalTable::Voxlap;
+        let east = closest_index([1.0, 0.0, 0.0], table);
+        let south = closest_index([0.0, 1.0, 0.0], table);
+        let mut data = lopsided();
+        data.voxels[0].normalindex = east;
+        data.voxels[1].normalindex = south;
+        data.voxels[2].normalindex = DEFAULT_INDEX;
+
+        // Each normal is moved in the Voxlap table and matched back to it.
+        let [x, y, z] = table.get(east);
+        let mut flipped = data.clone();
+        flipped.flip_with(Axis::X, table).unwrap();
+        assert_eq!(
+            flipped.get_voxel(1, 0, 0).unwrap().normalindex,
+            closest_index([-x, y, z], table)
+        );
+        let [x, y, z] = table.get(south);
+        assert_eq!(
+            flipped.get_voxel(1, 0, 3).unwrap().normalindex,
+            closest_index([-x, y, z], table)
+        );
+        // The Voxlap table has no entry 255, the "no normal" index.
+        assert_eq!(
+            flipped.get_voxel(0, 0, 3).unwrap().normalindex,
+            DEFAULT_INDEX
+        );
+
+        // A quarter turn about z turns the normal with the model.
+        let mut turned = data.clone();
+        turned.rotate90_with(Axis::Z, 1, table).unwrap();
+        assert_ne!(turned, data);
+        let [x, y, z] = table.get(east);
+        assert_eq!(
+            turned.get_voxel(2, 0, 0).unwrap().normalindex,
+            closest_index([-y, x, z], table)
+        );
+
+        // Read against the Slab6 table, the same index points elsewhere.
+        let mut wrong = data.clone();
+        wrong.flip(Axis::X).unwrap();
+        assert_ne!(wrong, flipped);
+    }
+
+    fn visibility(data: &KV6Format) -> Vec<u8> {
+        data.voxels.iter().map(|voxel| voxel.visibility).collect()
+    }
+
+    #[test]
+    fn test_flip_twice() {
+        for axis in [Axis::X, Axis::Y, Axis::Z] {
+            let original = grenade();
+            let mut data = original.clone();
+            data.flip(axis).unwrap();
+            assert!(data.validate().is_clean(), "{:?}", data.validate());
+            data.flip(axis).unwrap();
+            assert!(data.equivalent(&original));
+            assert_eq!(visibility(&data), visibility(&original));
+            assert_eq!(
+                (data.x_pivot, data.y_pivot, data.z_pivot),
+                (original.x_pivot, original.y_pivot, original.z_pivot)
+            );
+        }
+
+        let mut flipped = lopsided();
+        flipped.flip(Axis::Y).unwrap();
+        assert!(!flipped.equivalent(&lopsided()));
+
+        // Visibility after a flip is what recomputing it gives.
+        let mut data = grenade();
+        data.flip(Axis::X).unwrap();
+        let mut recomputed = data.clone();
+        recomputed.recompute_visibility().unwrap();
+        assert_eq!(visibility(&recomputed), visibility(&data));
+
+        let mut data = grenade();
+
+        data.voxels[0].height = 9;
+        assert!(matches!(
+            data.flip(Axis::X),
+            Err(Kv6Error::OutOfBounds { .. })
+        ));
+        data.voxels.pop();
+        assert!(matches!(
+            data.flip(Axis::X),
+            Err(Kv6Error::InconsistentSizes(_))
+        ));
+    }
+}
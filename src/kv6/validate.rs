@@ -0,0 +1,300 @@
+use super::{normals::NormalTable, AlphaMode, CacheMismatch, KV6Format, VoxelData};
+use alloc::vec::Vec;
+use core::fmt;
+
+/// How bad a [`ValidationIssue`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Readers cope with it, but it's probably not what was intended.
+    Warning,
+    /// Voxlap and other readers may misbehave on it.
+    Error,
+}
+
+/// One problem found by [`KV6Format::validate`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationIssue {
+    /// The caches don't match the voxels. Column ordering isn't checked
+    /// when this is reported, since columns can't be found.
+    Caches(CacheMismatch),
+    /// Voxel `index`, in column `(x, y)`, isn't lower than the one before it.
+    Unsorted { x: u32, y: u32, index: usize },
+    /// Voxel `index` is at `height`, which isn't below `z_size`.
+    HeightOutOfRange { index: usize, height: u16 },
+    /// Voxel `index` has a dummy byte other than 128. Not reported for models
+    /// in [`AlphaMode::DummyByte`].
+    NonstandardDummy { index: usize, value: u8 },
+    /// Voxel `index` has a `normalindex` the table has no entry for.
+    NormalOutOfRange { index: usize, value: u8 },
+    /// A pivot is NaN or infinite.
+    PivotNotFinite { field: &'static str, value: f32 },
+    /// A pivot lies outside the model's grid along its axis.
+    PivotOutOfRange { field: &'static str, value: f32 },
+}
+
+impl ValidationIssue {
+    pub fn severity(&self) -> Severity {
+        match self {
+            ValidationIssue::Caches(_)
+            | ValidationIssue::Unsorted { .. }
+            | ValidationIssue::HeightOutOfRange { .. }
+            | ValidationIssue::PivotNotFinite { .. } => Severity::Error,
+            ValidationIssue::NonstandardDummy { .. }
+            | ValidationIssue::NormalOutOfRange { .. }
+            | ValidationIssue::PivotOutOfRange { .. } => Severity::Warning,
+        }
+    }
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationIssue::Caches(mismatch) => write!(f, "{}", mismatch),
+            ValidationIssue::Unsorted { x, y, index } => write!(
+                f,
+                "voxel {} in column {:?} isn't below the one before it",
+                index,
+                (x, y)
+            ),
+            ValidationIssue::HeightOutOfRange { index, height } => {
+                write!(
+                    f,
+                    "voxel {} is at height {}, outside the model",
+                    index, height
+                )
+            }
+            ValidationIssue::NonstandardDummy { index, value } => {
+                write!(f, "voxel {} has dummy byte {}, expected 128", index, value)
+            }
+            ValidationIssue::NormalOutOfRange { index, value } => {
+                write!(
+                    f,
+                    "voxel {} has normal index {}, which the table lacks",
+                    index, value
+                )
+            }
+            ValidationIssue::PivotNotFinite { field, value } => write!(f, "{} is {}", field, value),
+            ValidationIssue::PivotOutOfRange { field, value } => {
+                write!(f, "{} of {} is outside the model", field, value)
+            }
+        }
+    }
+}
+
+/// Everything [`KV6Format::validate`] found, in the order it was found.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ValidationReport {
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    /// Whether nothing at all was found, warnings included.
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Whether any issue is a [`Severity::Error`].
+    pub fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+
+    /// The issues that are errors.
+    pub fn errors(&self) -> impl Iterator<Item = &ValidationIssue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity() == Severity::Error)
+    }
+}
+
+impl KV6Format {
+    /// Checks the whole model, against [`NormalTable::Slab6`] for normals.
+    pub fn validate(&self) -> ValidationReport {
+        self.validate_with(NormalTable::default())
+    }
+
+    /// Like [`KV6Format::validate`], checking normal indices against `table`.
+    pub fn validate_with(&self, table: NormalTable) -> ValidationReport {
+        let mut issues = Vec::new();
+
+        match self.check_caches() {
+            Ok(()) => self.check_order(&mut issues),
+            Err(mismatch) => issues.push(ValidationIssue::Caches(mismatch)),
+        }
+
+        let normals = table.entries().len();
+        for (index, voxel) in self.voxels.iter().enumerate() {
+            if voxel.height as u32 >= self.z_size {
+                issues.push(ValidationIssue::HeightOutOfRange {
+                    index,
+                    height: voxel.height,
+                });
+            }
+            if self.alpha_mode == AlphaMode::Opaque && voxel.dummy != VoxelData::DUMMY {
+                issues.push(ValidationIssue::NonstandardDummy {
+                    index,
+                    value: voxel.dummy,
+                });
+            }
+            if voxel.normalindex as usize >= normals {
+                issues.push(ValidationIssue::NormalOutOfRange {
+                    index,
+                    value: voxel.normalindex,
+                });
+            }
+        }
+
+        let pivots = [
+            ("x_pivot", self.x_pivot, self.x_size),
+            ("y_pivot", self.y_pivot, self.y_size),
+            ("z_pivot", self.z_pivot, self.z_size),
+        ];
+        for (field, value, size) in pivots {
+            if !value.is_finite() {
+                issues.push(ValidationIssue::PivotNotFinite { field, value });
+            } else if !(0.0..=size as f32).contains(&value) {
+                issues.push(ValidationIssue::PivotOutOfRange { field, value });
+            }
+        }
+
+        ValidationReport { issues }
+    }
+
+    /// Reports every voxel that isn't strictly below the previous one in its
+    /// column. The caches must already be known to match.
+    fn check_order(&self, issues: &mut Vec<ValidationIssue>) {
+        let mut start = 0;
+        for (x, row) in (0..).zip(&self.ylen) {
+            for (y, &count) in (0..).zip(row) {
+                let end = start + count as usize;
+                for index in start + 1..end {
+                    if self.voxels[index].height <= self.voxels[index - 1].height {
+                        issues.push(ValidationIssue::Unsorted { x, y, index });
+                    }
+                }
+                start = end;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Severity, ValidationIssue};
+    use crate::kv6::testing::grenade;
+    use crate::kv6::{normals::NormalTable, AlphaMode, CacheMismatch, KV6Format, WriteOptions};
+    use crate::Kv6Error;
+
+    /// The first voxel of the first column with more than one voxel.
+    fn stacked(data: &KV6Format) -> (u32, u32, usize) {
+        let (x, y, _, _) = data
+            .iter_with_coords()
+            .unwrap()
+            .find(|&(x, y, _, _)| data.column(x, y).unwrap().len() > 1)
+            .unwrap();
+        (x, y, data.column_range(x, y).unwrap().start)
+    }
+
+    #[test]
+    fn test_grenade_clean() {
+        let report = grenade().validate();
+        assert!(report.is_clean(), "{:?}", report);
+        assert!(!report.has_errors());
+        assert!(grenade().validate_with(NormalTable::Voxlap).is_clean());
+    }
+
+    #[test]
+    fn test_each_issue() {
+        let mut data = grenade();
+        data.xlen[1] += 1;
+        assert!(matches!(
+            data.validate().issues[..],
+            [ValidationIssue::Caches(CacheMismatch::Column { x: 1, .. })]
+        ));
+
+        let mut data = grenade();
+        let (x, y, start) = stacked(&data);
+        data.voxels.swap(start, start + 1);
+        assert_eq!(
+            data.validate().issues,
+            [ValidationIssue::Unsorted {
+                x,
+                y,
+                index: start + 1
+            }]
+        );
+
+        let mut data = grenade();
+        data.z_size = 3;
+        data.z_pivot = 1.0;
+        let report = data.validate();
+        let heights = data.voxels.iter().filter(|v| v.height >= 3).count();
+        assert_eq!(report.errors().count(), heights);
+        assert!(report.issues.iter().all(|issue| matches!(
+            issue,
+            ValidationIssue::HeightOutOfRange { index, height }
+                if data.voxels[*index].height == *height && *height >= 3
+        )));
+
+        let mut data = grenade();
+        data.voxels[5].dummy = 0;
+        assert_eq!(
+            data.validate().issues,
+            [ValidationIssue::NonstandardDummy { index: 5, value: 0 }]
+        );
+        assert!(!data.validate().has_errors());
+        data.set_alpha_mode(AlphaMode::DummyByte);
+        assert!(data.validate().is_clean());
+
+        let mut data = grenade();
+        data.voxels[7].normalindex = 255;
+        assert!(data.validate().is_clean());
+        assert_eq!(
+            data.validate_with(NormalTable::Voxlap).issues,
+            [ValidationIssue::NormalOutOfRange {
+                index: 7,
+                value: 255
+            }]
+        );
+
+        let mut data = grenade();
+        data.x_pivot = f32::NAN;
+        data.z_pivot = -1.0;
+        let issues = data.validate().issues;
+        assert!(matches!(
+            issues[..],
+            [
+                ValidationIssue::PivotNotFinite {
+                    field: "x_pivot",
+                    ..
+                },
+                ValidationIssue::PivotOutOfRange {
+                    field: "z_pivot",
+                    value: -1.0
+                }
+            ]
+        ));
+        assert_eq!(issues[0].severity(), Severity::Error);
+        assert_eq!(issues[1].severity(), Severity::Warning);
+    }
+
+    #[test]
+    fn test_reject_invalid() {
+        let strict = WriteOptions {
+            reject_invalid: true,
+            ..Default::default()
+        };
+        let mut data = grenade();
+        data.voxels[0].dummy = 1;
+        assert_eq!(
+            data.to_bytes_with(&strict).unwrap(),
+            data.to_bytes().unwrap()
+        );
+
+        data.z_size = 1;
+        assert!(data.to_bytes_with(&WriteOptions::default()).is_ok());
+        assert!(matches!(
+            data.to_bytes_with(&strict),
+            Err(Kv6Error::Invalid(ValidationIssue::HeightOutOfRange { .. }))
+        ));
+    }
+}
@@ -0,0 +1,205 @@
+use super::{cache_len, KV6Format, Kv6Header, Palette, ParseOptions, Section, VoxelData};
+use crate::Kv6Error;
+use alloc::vec::Vec;
+use scroll::{Pread, LE};
+
+/// A read-only view of a model that decodes straight from the source bytes.
+///
+/// The header and the bounds of every section are checked once up front, so
+/// the accessors never fail; they return `None` for out of range indices.
+#[derive(Debug, Clone, Copy)]
+pub struct Kv6Ref<'a> {
+    src: &'a [u8],
+    header: Kv6Header,
+    xlen: usize,
+    ylen: usize,
+    palette: Option<usize>,
+    end: usize,
+}
+
+impl<'a> Kv6Ref<'a> {
+    /// Checks the model at the start of `src` with the default [`ParseOptions`].
+    pub fn new(src: &'a [u8]) -> Result<Self, Kv6Error> {
+        let offset = &mut 0;
+        let header = Kv6Header::read(src, offset, &ParseOptions::default(), &mut Vec::new())?;
+
+        let remaining = src.len() - *offset;
+        let voxel_bytes = header.num_voxels as usize * 8;
+        if voxel_bytes > remaining {
+            return Err(Kv6Error::Truncated {
+                section: Section::Voxels,
+                offset: *offset + remaining / 8 * 8,
+            });
+        }
+        let xlen = *offset + voxel_bytes;
+        let ylen = xlen + header.x_size as usize * 4;
+
+        let (x_size, y_size) = (header.x_size, header.y_size);
+        let caches = cache_len(x_size, y_size).ok_or(Kv6Error::CacheOverflow { x_size, y_size })?;
+        let mut end = xlen + caches;
+        if end > src.len() {
+            return Err(if src.len() < ylen {
+                Kv6Error::Truncated {
+                    section: Section::Xlen,
+                    offset: xlen + (src.len() - xlen) / 4 * 4,
+                }
+            } else {
+                Kv6Error::Truncated {
+                    section: Section::Ylen,
+                    offset: ylen + (src.len() - ylen) / 2 * 2,
+                }
+            });
+        }
+
+        let mut palette = None;
+        if src.get(end..end + 4) == Some(&Palette::MAGIC[..]) {
+            if src.len() < end + 4 + 256 * 3 {
+                return Err(Kv6Error::Truncated {
+                    section: Section::Palette,
+                    offset: end + 4,
+                });
+            }
+            palette = Some(end + 4);
+            end += 4 + 256 * 3;
+        }
+
+        Ok(Self {
+            src,
+            header,
+            xlen,
+            ylen,
+            palette,
+            end,
+        })
+    }
+
+    pub fn header(&self) -> &Kv6Header {
+        &self.header
+    }
+
+    /// Number of bytes the model takes up in the source.
+    pub fn byte_len(&self) -> usize {
+        self.end
+    }
+
+    pub fn voxel(&self, index: usize) -> Option<VoxelData> {
+        if index >= self.header.num_voxels as usize {
+            return None;
+        }
+        let start = Kv6Header::SIZE + index * 8;
+        let bytes = self.src[start..start + 8].try_into().unwrap();
+        Some(VoxelData::from_bytes(bytes))
+    }
+
+    pub fn voxels(&self) -> impl ExactSizeIterator<Item = VoxelData> + 'a {
+        self.src[Kv6Header::SIZE..self.xlen]
+            .chunks_exact(8)
+            .map(|chunk| VoxelData::from_bytes(chunk.try_into().unwrap()))
+    }
+
+    pub fn xlen(&self, x: usize) -> Option<u32> {
+        if x >= self.header.x_size as usize {
+            return None;
+        }
+        self.src.pread_with(self.xlen + x * 4, LE).ok()
+    }
+
+    pub fn ylen(&self, x: usize, y: usize) -> Option<u16> {
+        let y_size = self.header.y_size as usize;
+        if x >= self.header.x_size as usize || y >= y_size {
+            return None;
+        }
+        self.src
+            .pread_with(self.ylen + (x * y_size + y) * 2, LE)
+            .ok()
+    }
+
+    pub fn palette(&self) -> Option<Palette> {
+        self.src.pread_with(self.palette?, LE).ok()
+    }
+
+    /// Decodes everything into an owned, editable model.
+    pub fn to_owned(&self) -> KV6Format {
+        let (x_size, y_size) = (self.header.x_size as usize, self.header.y_size as usize);
+        KV6Format {
+            magic: self.header.magic,
+            x_size: self.header.x_size,
+            y_size: self.header.y_size,
+            z_size: self.header.z_size,
+            x_pivot: self.header.x_pivot,
+            y_pivot: self.header.y_pivot,
+            z_pivot: self.header.z_pivot,
+            voxels: self.voxels().collect(),
+            xlen: (0..x_size).filter_map(|x| self.xlen(x)).collect(),
+            ylen: (0..x_size)
+                .map(|x| (0..y_size).filter_map(|y| self.ylen(x, y)).collect())
+                .collect(),
+            palette: self.palette(),
+            alpha_mode: Default::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Kv6Ref;
+    use crate::{
+        kv6::{KV6Format, Section},
+        Kv6Error,
+    };
+
+    const GRENADE: &[u8] = include_bytes!("../../data/grenade.kv6");
+
+    #[test]
+    fn test_view_matches_parser() {
+        let buffer = GRENADE;
+        let (data, consumed) = KV6Format::parse(buffer).unwrap();
+        let view = Kv6Ref::new(buffer).unwrap();
+
+        assert_eq!(view.byte_len(), consumed);
+        assert_eq!(view.header().num_voxels, 74);
+        assert_eq!(view.voxels().len(), data.voxels.len());
+        for (i, voxel) in data.voxels.iter().enumerate() {
+            let viewed = view.voxel(i).unwrap();
+            assert_eq!(
+                (viewed.red, viewed.green, viewed.blue, viewed.height),
+                (voxel.red, voxel.green, voxel.blue, voxel.height)
+            );
+        }
+        for x in 0..6 {
+            assert_eq!(view.xlen(x), Some(data.xlen[x]));
+            for y in 0..6 {
+                assert_eq!(view.ylen(x, y), Some(data.ylen[x][y]));
+            }
+        }
+        assert_eq!(
+            view.palette().unwrap().entries,
+            data.palette.unwrap().entries
+        );
+        assert_eq!(view.to_owned().to_bytes().unwrap(), buffer);
+    }
+
+    #[test]
+    fn test_view_out_of_range() {
+        let buffer = GRENADE;
+        let view = Kv6Ref::new(buffer).unwrap();
+
+        assert!(view.voxel(74).is_none());
+        assert!(view.voxel(usize::MAX).is_none());
+        assert!(view.xlen(6).is_none());
+        assert!(view.ylen(0, 6).is_none());
+        assert!(view.ylen(6, 0).is_none());
+    }
+
+    #[test]
+    fn test_view_truncated() {
+        let buffer = GRENADE;
+        assert!(matches!(
+            Kv6Ref::new(&buffer[..700]),
+            Err(Kv6Error::Truncated {
+                section: Section::Ylen,
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,381 @@
+use super::{KV6Format, VoxelData};
+use crate::Kv6Error;
+use core::ops::BitOr;
+
+/// One of the six faces of a voxel.
+///
+/// Voxlap's axes are x to the right, y to the front and z down, so the top
+/// face looks towards lower heights.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Face {
+    /// -x
+    Left,
+    /// +x
+    Right,
+    /// -y
+    Back,
+    /// +y
+    Front,
+    /// -z
+    Top,
+    /// +z
+    Bottom,
+}
+
+impl Face {
+    /// Every face, in the order of their bits in [`Visibility`].
+    pub const ALL: [Face; 6] = [
+        Face::Left,
+        Face::Right,
+        Face::Back,
+        Face::Front,
+        Face::Top,
+        Face::Bottom,
+    ];
+
+    /// The visibility bit for this face.
+    pub const fn flag(self) -> Visibility {
+        Visibility(1 << self as u8)
+    }
+
+    /// The unit step from a voxel to the neighbour this face looks at.
+    pub const fn normal(self) -> (i32, i32, i32) {
+        match self {
+            Face::Left => (-1, 0, 0),
+            Face::Right => (1, 0, 0),
+            Face::Back => (0, -1, 0),
+            Face::Front => (0, 1, 0),
+            Face::Top => (0, 0, -1),
+            Face::Bottom => (0, 0, 1),
+        }
+    }
+
+    /// The face on the other side of the voxel.
+    pub const fn opposite(self) -> Face {
+        match self {
+            Face::Left => Face::Right,
+            Face::Right => Face::Left,
+            Face::Back => Face::Front,
+            Face::Front => Face::Back,
+            Face::Top => Face::Bottom,
+            Face::Bottom => Face::Top,
+        }
+    }
+
+    /// The cell this face looks at from `(x, y, z)`, or `None` if stepping
+    /// there leaves the range of the coordinate types.
+    pub(crate) fn neighbour(self, x: u32, y: u32, z: u16) -> Option<(u32, u32, u16)> {
+        let (dx, dy, dz) = self.normal();
+        Some((
+            x.checked_add_signed(dx)?,
+            y.checked_add_signed(dy)?,
+            z.checked_add_signed(dz as i16)?,
+        ))
+    }
+}
+
+/// The face-culling bits of [`VoxelData::visibility`]. A face's bit is set
+/// when the neighbouring cell on that side is air.
+///
+/// Only the low six bits have a meaning; the other two are carried along
+/// unchanged so that converting back to a `u8` gives the original byte.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Visibility(u8);
+
+impl Visibility {
+    pub const LEFT: Visibility = Face::Left.flag();
+    pub const RIGHT: Visibility = Face::Right.flag();
+    pub const BACK: Visibility = Face::Back.flag();
+    pub const FRONT: Visibility = Face::Front.flag();
+    pub const TOP: Visibility = Face::Top.flag();
+    pub const BOTTOM: Visibility = Face::Bottom.flag();
+
+    /// No face visible.
+    pub const NONE: Visibility = Visibility(0);
+    /// Every face visible.
+    pub const ALL: Visibility = Visibility(0x3f);
+
+    /// The raw byte, unused bits included.
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// Whether every face set in `other` is also set here.
+    pub const fn contains(self, other: Visibility) -> bool {
+        self.0 & other.0 & Self::ALL.0 == other.0 & Self::ALL.0
+    }
+
+    /// Whether `face` borders air.
+    pub fn is_visible(self, face: Face) -> bool {
+        self.contains(face.flag())
+    }
+
+    /// Sets or clears the bit for `face`, leaving the others alone.
+    pub fn set_visible(&mut self, face: Face, visible: bool) {
+        if visible {
+            self.0 |= face.flag().0;
+        } else {
+            self.0 &= !face.flag().0;
+        }
+    }
+}
+
+impl From<u8> for Visibility {
+    fn from(bits: u8) -> Self {
+        Visibility(bits)
+    }
+}
+
+impl From<Visibility> for u8 {
+    fn from(visibility: Visibility) -> Self {
+        visibility.0
+    }
+}
+
+impl BitOr for Visibility {
+    type Output = Visibility;
+
+    fn bitor(self, rhs: Visibility) -> Visibility {
+        Visibility(self.0 | rhs.0)
+    }
+}
+
+impl VoxelData {
+    /// The `visibility` byte as typed flags.
+    pub fn visibility(&self) -> Visibility {
+        Visibility(self.visibility)
+    }
+
+    /// Replaces the `visibility` byte, unused bits included.
+    pub fn set_visibility(&mut self, visibility: Visibility) {
+        self.visibility = visibility.0;
+    }
+
+    /// The faces that border air, in [`Face::ALL`] order.
+    pub fn visible_faces(&self) -> impl Iterator<Item = Face> {
+        let visibility = self.visibility();
+        Face::ALL
+            .into_iter()
+            .filter(move |&face| visibility.is_visible(face))
+    }
+
+    /// Whether `face` borders air.
+    pub fn is_face_visible(&self, face: Face) -> bool {
+        self.visibility().is_visible(face)
+    }
+
+    /// Clears the bit for `face`, leaving every other bit as it was.
+    pub fn hide_face(&mut self, face: Face) {
+        self.visibility &= !face.flag().0;
+    }
+
+    /// Sets the bit for `face`, leaving every other bit as it was.
+    pub fn show_face(&mut self, face: Face) {
+        self.visibility |= face.flag().0;
+    }
+}
+
+impl KV6Format {
+    /// Recomputes the six face bits of every voxel from the model's geometry,
+    /// keeping the two unused bits.
+    ///
+    /// A face is visible when the cell next to it is outside the grid or is
+    /// air. Cells that aren't stored count as air only if they connect to
+    /// the outside of the grid; enclosed ones are the solid interior that
+    /// KV6 files leave out. This is how slab6 sets the bits, so an unedited
+    /// file comes back unchanged.
+    pub fn recompute_visibility(&mut self) -> Result<(), Kv6Error> {
+        let air = self.occupancy()?.exterior();
+        let (x_size, y_size, z_size) = air.dimensions();
+
+        self.for_each_voxel_mut(|x, y, z, voxel| {
+            for face in Face::ALL {
+                let open = match face.neighbour(x, y, z) {
+                    Some((nx, ny, nz)) if nx < x_size && ny < y_size && (nz as u32) < z_size => {
+                        air.contains(nx, ny, nz)
+                    }
+                    _ => true,
+                };
+                if open {
+                    voxel.show_face(face);
+                } else {
+                    voxel.hide_face(face);
+                }
+            }
+        })
+        .map_err(Kv6Error::InconsistentSizes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Face, Visibility};
+    use crate::kv6::testing::grenade;
+    use crate::kv6::{KV6Format, VoxelData};
+    use crate::Kv6Error;
+    use alloc::{collections::BTreeSet, vec, vec::Vec};
+
+    #[test]
+    fn test_bits() {
+        assert_eq!(Visibility::LEFT.bits(), 1);
+        assert_eq!(Visibility::RIGHT.bits(), 2);
+        assert_eq!(Visibility::BACK.bits(), 4);
+        assert_eq!(Visibility::FRONT.bits(), 8);
+        assert_eq!(Visibility::TOP.bits(), 16);
+        assert_eq!(Visibility::BOTTOM.bits(), 32);
+
+        let all = Face::ALL
+            .iter()
+            .fold(Visibility::NONE, |all, face| all | face.flag());
+        assert_eq!(all, Visibility::ALL);
+    }
+
+    #[test]
+    fn test_unused_bits_preserved() {
+        let mut visibility = Visibility::from(0xc5);
+        assert!(visibility.is_visible(Face::Left));
+        assert!(!visibility.is_visible(Face::Right));
+        assert!(visibility.is_visible(Face::Back));
+
+        visibility.set_visible(Face::Right, true);
+        visibility.set_visible(Face::Left, false);
+        assert_eq!(u8::from(visibility), 0xc6);
+        assert!(Visibility::from(0xc0).contains(Visibility::NONE));
+        assert!(!Visibility::from(0xc0).is_visible(Face::Top));
+    }
+
+    /// slab6 sets a face's bit exactly when the neighbouring cell is air. Only
+    /// stored voxels and cells outside the grid are known for sure, which is
+    /// enough to pin every bit down.
+    #[test]
+    fn test_bits_match_grenade() {
+        let data = grenade();
+        let mut voxels = data.voxels.iter();
+        let mut cells = BTreeSet::new();
+        let mut placed = Vec::new();
+        for (x, row) in (0i32..).zip(&data.ylen) {
+            for (y, &count) in (0i32..).zip(row) {
+                for voxel in voxels.by_ref().take(count as usize) {
+                    let z = voxel.height as i32;
+                    cells.insert((x, y, z));
+                    placed.push(((x, y, z), voxel.visibility()));
+                }
+            }
+        }
+
+        let size = (data.x_size as i32, data.y_size as i32, data.z_size as i32);
+        let mut checked = [0; 6];
+        for ((x, y, z), visibility) in placed {
+            for (i, face) in Face::ALL.into_iter().enumerate() {
+                let (dx, dy, dz) = face.normal();
+                let n = (x + dx, y + dy, z + dz);
+                let outside = !(0..size.0).contains(&n.0)
+                    || !(0..size.1).contains(&n.1)
+                    || !(0..size.2).contains(&n.2);
+                if cells.contains(&n) {
+                    assert!(
+                        !visibility.is_visible(face),
+                        "{:?} of {:?}",
+                        face,
+                        (x, y, z)
+                    );
+                    checked[i] += 1;
+                } else if outside {
+                    assert!(visibility.is_visible(face), "{:?} of {:?}", face, (x, y, z));
+                    checked[i] += 1;
+                }
+            }
+        }
+        assert!(checked.iter().all(|&n| n > 0));
+    }
+
+    #[test]
+    fn test_recompute_grenade() {
+        let original = grenade();
+        let mut data = original.clone();
+        for voxel in &mut data.voxels {
+            voxel.visibility ^= 0x3f;
+        }
+        data.recompute_visibility().unwrap();
+        assert_eq!(data, original);
+    }
+
+    #[test]
+    fn test_recompute_enclosed() {
+        // A 3x3x3 shell with its center left out: the center is interior, so
+        // nothing faces it, and the high bits survive.
+        let mut data = KV6Format {
+            x_size: 3,
+            y_size: 3,
+            z_size: 3,
+            xlen: vec![0; 3],
+            ylen: vec![vec![0; 3]; 3],
+            ..Default::default()
+        };
+        for x in 0..3 {
+            for y in 0..3 {
+                for z in 0..3 {
+                    if (x, y, z) != (1, 1, 1) {
+                        data.insert_voxel(x, y, z, VoxelData::default()).unwrap();
+                    }
+                }
+            }
+        }
+        for voxel in &mut data.voxels {
+            voxel.visibility = 0x80;
+        }
+        data.recompute_visibility().unwrap();
+
+        let above = data.get_voxel(1, 1, 0).unwrap();
+        assert_eq!(above.visibility, 0x80 | Visibility::TOP.bits());
+        let corner = data.get_voxel(2, 2, 2).unwrap();
+        assert_eq!(
+            corner.visibility(),
+            Visibility::from(0x80) | Visibility::RIGHT | Visibility::FRONT | Visibility::BOTTOM
+        );
+
+        data.voxels.pop();
+        assert!(matches!(
+            data.recompute_visibility(),
+            Err(Kv6Error::InconsistentSizes(_))
+        ));
+    }
+
+    #[test]
+    fn test_face_geometry() {
+        for face in Face::ALL {
+            let (x, y, z) = face.normal();
+            assert_eq!(x.abs() + y.abs() + z.abs(), 1);
+            assert_eq!(face.opposite().normal(), (-x, -y, -z));
+            assert_eq!(face.opposite().opposite(), face);
+            assert_ne!(face.opposite(), face);
+        }
+        assert_eq!(Face::Top.normal(), (0, 0, -1));
+    }
+
+    #[test]
+    fn test_voxel_faces() {
+        for (i, face) in Face::ALL.into_iter().enumerate() {
+            for high in [0x00, 0x40, 0x80, 0xc0] {
+                let mut voxel = VoxelData {
+                    visibility: high,
+                    ..Default::default()
+                };
+                assert_eq!(voxel.visible_faces().count(), 0);
+
+                voxel.show_face(face);
+                assert_eq!(voxel.visibility, high | 1 << i);
+                assert!(voxel.is_face_visible(face));
+                assert!(!voxel.is_face_visible(face.opposite()));
+                assert_eq!(voxel.visible_faces().collect::<Vec<_>>(), [face]);
+
+                voxel.visibility |= 0x3f;
+                voxel.hide_face(face);
+                assert_eq!(voxel.visibility, high | (0x3f & !(1 << i)));
+                assert!(!voxel.is_face_visible(face));
+                assert_eq!(voxel.visible_faces().count(), 5);
+                assert!(voxel.visible_faces().all(|other| other != face));
+            }
+        }
+    }
+}
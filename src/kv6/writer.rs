@@ -0,0 +1,232 @@
+use super::{Kv6Header, VoxelData};
+use crate::{utils::write_vec, Kv6Error};
+use scroll::{Pwrite, LE};
+use std::io::{Seek, SeekFrom, Write};
+
+/// Writes a model column by column, without holding its voxels in memory.
+///
+/// Columns have to be pushed in the order they're stored, x-major and
+/// y-minor, and every one of them has to be pushed, empty or not. The voxel
+/// count in the header is patched once [`Kv6Writer::finish`] knows it, which
+/// is why the writer needs to seek.
+pub struct Kv6Writer<W> {
+    writer: W,
+    start: u64,
+    header: Kv6Header,
+    columns: usize,
+    ylen: Vec<u16>,
+}
+
+impl<W: Write + Seek> Kv6Writer<W> {
+    /// Writes `header` at the writer's current position. Its `num_voxels` is
+    /// ignored and replaced with the number of voxels actually pushed.
+    pub fn begin(mut writer: W, header: Kv6Header) -> Result<Self, Kv6Error> {
+        let columns = (header.x_size as usize)
+            .checked_mul(header.y_size as usize)
+            .ok_or(Kv6Error::CacheOverflow {
+                x_size: header.x_size,
+                y_size: header.y_size,
+            })?;
+        let start = writer.stream_position()?;
+        let header = Kv6Header {
+            num_voxels: 0,
+            ..header
+        };
+        let mut bytes = [0u8; Kv6Header::SIZE];
+        bytes.pwrite_with(header, 0, LE)?;
+        writer.write_all(&bytes)?;
+
+        // The header isn't trusted with an allocation; `ylen` grows as
+        // columns are pushed.
+        Ok(Self {
+            writer,
+            start,
+            header,
+            columns,
+            ylen: Vec::new(),
+        })
+    }
+
+    /// The column the writer expects next, or `None` once all were pushed.
+    pub fn next_column(&self) -> Option<(u32, u32)> {
+        let y_size = self.header.y_size as usize;
+        if self.ylen.len() >= self.columns {
+            return None;
+        }
+        let x = self.ylen.len() / y_size;
+        let y = self.ylen.len() % y_size;
+        Some((x as u32, y as u32))
+    }
+
+    /// Appends the voxels of column `(x, y)`, top to bottom.
+    pub fn push_column(&mut self, x: u32, y: u32, voxels: &[VoxelData]) -> Result<(), Kv6Error> {
+        let expected = self.next_column();
+        if expected != Some((x, y)) {
+            return Err(Kv6Error::ColumnOrder {
+                expected,
+                found: Some((x, y)),
+            });
+        }
+        let len = u16::try_from(voxels.len()).map_err(|_| Kv6Error::TooLarge {
+            field: "column",
+            value: voxels.len(),
+        })?;
+        let total = self.header.num_voxels as u64 + len as u64;
+        self.header.num_voxels = u32::try_from(total).map_err(|_| Kv6Error::TooLarge {
+            field: "voxels",
+            value: total as usize,
+        })?;
+
+        let mut bytes = vec![0u8; voxels.len() * 8];
+        write_vec::<_, _, scroll::Error>(&mut bytes, &mut 0, voxels, LE)?;
+        self.writer.write_all(&bytes)?;
+        self.ylen.push(len);
+        Ok(())
+    }
+
+    /// Writes the caches, patches the voxel count and hands the writer back,
+    /// positioned right after the model.
+    pub fn finish(mut self) -> Result<W, Kv6Error> {
+        if let Some(expected) = self.next_column() {
+            return Err(Kv6Error::ColumnOrder {
+                expected: Some(expected),
+                found: None,
+            });
+        }
+
+        // Every x gets an entry, even with no columns when `y_size` is 0.
+        let y_size = self.header.y_size as usize;
+        let xlen: Vec<u32> = (0..self.header.x_size as usize)
+            .map(|x| {
+                let row = &self.ylen[x * y_size..(x + 1) * y_size];
+                row.iter().map(|&n| n as u32).sum()
+            })
+            .collect();
+        let mut bytes = vec![0u8; xlen.len() * 4 + self.ylen.len() * 2];
+        let offset = &mut 0;
+        write_vec::<_, _, scroll::Error>(&mut bytes, offset, &xlen, LE)?;
+        write_vec::<_, _, scroll::Error>(&mut bytes, offset, &self.ylen, LE)?;
+        self.writer.write_all(&bytes)?;
+
+        let end = self.writer.stream_position()?;
+        let mut header = [0u8; Kv6Header::SIZE];
+        header.pwrite_with(self.header, 0, LE)?;
+        self.writer.seek(SeekFrom::Start(self.start))?;
+        self.writer.write_all(&header)?;
+        self.writer.seek(SeekFrom::Start(end))?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Kv6Writer;
+    use crate::kv6::testing::{empty, grenade_without_palette};
+    use crate::{
+        kv6::{KV6Format, Kv6Header},
+        Kv6Error,
+    };
+    use std::io::Cursor;
+
+    #[test]
+    fn test_writer_round_trip() {
+        let data = grenade_without_palette();
+        let header = KV6Format::peek_header(&data.to_bytes().unwrap()).unwrap();
+
+        let mut writer = Kv6Writer::begin(Cursor::new(Vec::new()), header).unwrap();
+        let mut voxels = &data.voxels[..];
+        for x in 0..data.x_size {
+            for y in 0..data.y_size {
+                let (column, rest) = voxels.split_at(data.ylen[x as usize][y as usize] as usize);
+                writer.push_column(x, y, column).unwrap();
+                voxels = rest;
+            }
+        }
+        let bytes = writer.finish().unwrap().into_inner();
+
+        let (parsed, consumed) = KV6Format::parse(&bytes).unwrap();
+        assert_eq!(consumed, bytes.len());
+        assert_eq!(parsed.check_caches(), Ok(()));
+        assert_eq!(bytes, data.to_bytes().unwrap());
+    }
+
+    #[test]
+    fn test_writer_column_order() {
+        let header = Kv6Header {
+            magic: KV6Format::MAGIC,
+            x_size: 2,
+            y_size: 2,
+            z_size: 1,
+            x_pivot: 0.0,
+            y_pivot: 0.0,
+            z_pivot: 0.0,
+            num_voxels: 0,
+        };
+
+        let mut writer = Kv6Writer::begin(Cursor::new(Vec::new()), header).unwrap();
+        writer.push_column(0, 0, &[]).unwrap();
+        assert!(matches!(
+            writer.push_column(1, 0, &[]),
+            Err(Kv6Error::ColumnOrder {
+                expected: Some((0, 1)),
+                found: Some((1, 0)),
+            })
+        ));
+        writer.push_column(0, 1, &[]).unwrap();
+        assert!(matches!(
+            writer.finish(),
+            Err(Kv6Error::ColumnOrder {
+                expected: Some((1, 0)),
+                found: None,
+            })
+        ));
+
+        let mut writer = Kv6Writer::begin(Cursor::new(Vec::new()), header).unwrap();
+        for (x, y) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+            writer.push_column(x, y, &[]).unwrap();
+        }
+        assert!(matches!(
+            writer.push_column(0, 0, &[]),
+            Err(Kv6Error::ColumnOrder {
+                expected: None,
+                found: Some((0, 0)),
+            })
+        ));
+        let bytes = writer.finish().unwrap().into_inner();
+        assert!(KV6Format::parse(&bytes).unwrap().0.voxels.is_empty());
+    }
+
+    #[test]
+    fn test_writer_no_rows() {
+        // An x_size by 0 grid has no columns but still has an xlen table.
+        let data = empty(3, 0, 4);
+        let header = KV6Format::peek_header(&data.to_bytes().unwrap()).unwrap();
+        let writer = Kv6Writer::begin(Cursor::new(Vec::new()), header).unwrap();
+        assert_eq!(writer.next_column(), None);
+        let bytes = writer.finish().unwrap().into_inner();
+
+        assert_eq!(bytes, data.to_bytes().unwrap());
+        assert_eq!(bytes.len(), 32 + 3 * 4);
+        let (parsed, consumed) = KV6Format::parse(&bytes).unwrap();
+        assert_eq!(consumed, bytes.len());
+        assert_eq!(parsed, data);
+    }
+
+    #[test]
+    fn test_writer_huge_header() {
+        // Nothing is allocated for columns that haven't been pushed.
+        let header = Kv6Header {
+            magic: KV6Format::MAGIC,
+            x_size: u32::MAX,
+            y_size: u32::MAX,
+            z_size: 1,
+            x_pivot: 0.0,
+            y_pivot: 0.0,
+            z_pivot: 0.0,
+            num_voxels: 0,
+        };
+        let mut writer = Kv6Writer::begin(Cursor::new(Vec::new()), header).unwrap();
+        writer.push_column(0, 0, &[]).unwrap();
+        assert_eq!(writer.next_column(), Some((0, 1)));
+    }
+}
@@ -1,6 +1,54 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod error;
+pub mod kv6;
 #[cfg(feature = "kvx")]
 pub mod kvx;
-#[cfg(feature = "kv6")]
-pub mod kv6;
 #[macro_use]
-pub mod utils;
\ No newline at end of file
+pub mod utils;
+
+pub use error::Kv6Error;
+pub use kv6::{KV6Format, VoxelData};
+
+use alloc::vec::Vec;
+
+/// Everything needed to read and write models, including the scroll traits
+/// for `pread`/`pwrite` style access.
+pub mod prelude {
+    pub use crate::kv6::{KV6Format, Kv6Header, Palette, ParseOptions, VoxelData};
+    pub use crate::Kv6Error;
+    pub use scroll::{Pread, Pwrite};
+}
+
+/// Parses a model from the start of `src` with the default
+/// [`ParseOptions`](kv6::ParseOptions). Anything after the model is ignored.
+pub fn parse(src: &[u8]) -> Result<KV6Format, Kv6Error> {
+    KV6Format::parse(src).map(|(data, _)| data)
+}
+
+/// Serializes a model, palette included if it has one.
+pub fn serialize(data: &KV6Format) -> Result<Vec<u8>, Kv6Error> {
+    data.to_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse, prelude::*, serialize};
+
+    #[test]
+    fn test_parse_serialize() {
+        let buffer = include_bytes!("../data/grenade.kv6");
+
+        let data = parse(buffer).unwrap();
+        assert_eq!(data.x_size, 6);
+        assert_eq!(serialize(&data).unwrap(), buffer);
+        assert_eq!(buffer.pread::<KV6Format>(0).unwrap(), data);
+
+        assert!(matches!(
+            parse(&buffer[..20]),
+            Err(Kv6Error::Truncated { .. })
+        ));
+    }
+}
@@ -1,14 +1,20 @@
+use alloc::vec::Vec;
+use scroll::{
+    ctx::{TryFromCtx, TryIntoCtx},
+    Pread, Pwrite,
+};
+
 #[macro_export]
 macro_rules! try_gread_vec_with {
     ($src:ident, $offset:ident, $cap:expr, $ctx:expr) => {{
-        let mut vec = Vec::with_capacity($cap as usize);
+        let mut vec = Vec::with_capacity(($cap as usize).min($src.len()));
         for _ in 0..$cap {
             vec.push($src.gread_with($offset, $ctx)?);
         }
         vec
     }};
     ($src:ident, $offset:ident, $cap:expr; ctx = offset) => {{
-        let mut vec = Vec::with_capacity($cap as usize);
+        let mut vec = Vec::with_capacity(($cap as usize).min($src.len()));
         for _ in 0..$cap {
             vec.push($src.gread_with($offset, *$offset)?);
         }
@@ -23,4 +29,159 @@ macro_rules! try_gwrite_vec_with {
             $dst.gwrite_with(item, $offset, $ctx)?;
         }
     };
-}
\ No newline at end of file
+}
+/// Converts a count read from a file, treating one that doesn't fit in a
+/// `usize` as more than the buffer could hold.
+fn to_usize<N: TryInto<usize>>(count: N, src_len: usize) -> Result<usize, scroll::Error> {
+    count.try_into().map_err(|_| scroll::Error::TooBig {
+        size: usize::MAX,
+        len: src_len,
+    })
+}
+
+/// Reads `count` values of `T` one after another, advancing `offset`.
+///
+/// Every value takes at least a byte, so the up front reservation is capped at
+/// what's left of `src` and a bogus count fails on the first short read
+/// instead of allocating for it.
+pub fn read_vec<'a, T, N, C, E>(
+    src: &'a [u8],
+    offset: &mut usize,
+    count: N,
+    ctx: C,
+) -> Result<Vec<T>, E>
+where
+    T: TryFromCtx<'a, C, Error = E>,
+    N: TryInto<usize>,
+    C: Copy,
+    E: From<scroll::Error>,
+{
+    let count = to_usize(count, src.len())?;
+    let mut vec = Vec::with_capacity(count.min(src.len().saturating_sub(*offset)));
+    for _ in 0..count {
+        vec.push(Pread::<C, E>::gread_with(src, offset, ctx)?);
+    }
+    Ok(vec)
+}
+
+/// Writes every item one after another, advancing `offset`.
+pub fn write_vec<I, C, E>(dst: &mut [u8], offset: &mut usize, items: I, ctx: C) -> Result<(), E>
+where
+    I: IntoIterator,
+    I::Item: TryIntoCtx<C, Error = E>,
+    C: Copy,
+    E: From<scroll::Error>,
+{
+    for item in items {
+        Pwrite::<C, E>::gwrite_with(dst, item, offset, ctx)?;
+    }
+    Ok(())
+}
+
+/// Reads a row-major table of `outer` rows of `inner` values each.
+pub fn read_nested<'a, T, N, M, C, E>(
+    src: &'a [u8],
+    offset: &mut usize,
+    outer: N,
+    inner: M,
+    ctx: C,
+) -> Result<Vec<Vec<T>>, E>
+where
+    T: TryFromCtx<'a, C, Error = E>,
+    N: TryInto<usize>,
+    M: TryInto<usize>,
+    C: Copy,
+    E: From<scroll::Error>,
+{
+    let outer = to_usize(outer, src.len())?;
+    let inner = to_usize(inner, src.len())?;
+    let mut rows = Vec::with_capacity(outer.min(src.len().saturating_sub(*offset)));
+    for _ in 0..outer {
+        rows.push(read_vec::<T, _, C, E>(src, offset, inner, ctx)?);
+    }
+    Ok(rows)
+}
+
+/// Writes a table row by row, the inverse of [`read_nested`].
+pub fn write_nested<I, C, E>(dst: &mut [u8], offset: &mut usize, rows: I, ctx: C) -> Result<(), E>
+where
+    I: IntoIterator,
+    I::Item: IntoIterator,
+    <I::Item as IntoIterator>::Item: TryIntoCtx<C, Error = E>,
+    C: Copy,
+    E: From<scroll::Error>,
+{
+    for row in rows {
+        write_vec(dst, offset, row, ctx)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_nested, read_vec, write_nested, write_vec};
+    use alloc::{vec, vec::Vec};
+    use scroll::LE;
+
+    #[test]
+    fn test_vec_round_trip() {
+        let values: Vec<u32> = vec![1, 0x0203_0405, u32::MAX];
+        let mut out = [0u8; 12];
+        let offset = &mut 0;
+        write_vec::<_, _, scroll::Error>(&mut out, offset, &values, LE).unwrap();
+        assert_eq!(*offset, 12);
+        assert_eq!(out[4..8], [0x05, 0x04, 0x03, 0x02]);
+
+        let offset = &mut 0;
+        let read: Vec<u32> = read_vec::<_, _, _, scroll::Error>(&out, offset, 3u8, LE).unwrap();
+        assert_eq!(read, values);
+        assert_eq!(*offset, 12);
+    }
+
+    #[test]
+    fn test_nested_round_trip() {
+        let rows: Vec<Vec<u16>> = vec![vec![1, 2, 3], vec![4, 5, 6]];
+        let mut out = [0u8; 12];
+        let offset = &mut 0;
+        write_nested::<_, _, scroll::Error>(&mut out, offset, &rows, LE).unwrap();
+        assert_eq!(out, [1, 0, 2, 0, 3, 0, 4, 0, 5, 0, 6, 0]);
+
+        let offset = &mut 0;
+        let read: Vec<Vec<u16>> =
+            read_nested::<_, _, _, _, scroll::Error>(&out, offset, 2u32, 3usize, LE).unwrap();
+        assert_eq!(read, rows);
+        assert_eq!(*offset, 12);
+    }
+
+    #[test]
+    fn test_read_vec_zero_count() {
+        let offset = &mut 2;
+        let read: Vec<u32> = read_vec::<_, _, _, scroll::Error>(&[1, 2, 3], offset, 0, LE).unwrap();
+        assert!(read.is_empty());
+        assert_eq!(*offset, 2);
+    }
+
+    #[test]
+    fn test_read_vec_exact_fit() {
+        let src = [1, 0, 2, 0, 3, 0];
+        let offset = &mut 0;
+        let read: Vec<u16> = read_vec::<_, _, _, scroll::Error>(&src, offset, 3, LE).unwrap();
+        assert_eq!(read, [1, 2, 3]);
+        assert_eq!(read.capacity(), 3);
+        assert_eq!(*offset, src.len());
+    }
+
+    #[test]
+    fn test_read_vec_short_buffer() {
+        let src = [1, 0, 2, 0, 3];
+        let result: Result<Vec<u16>, scroll::Error> = read_vec(&src, &mut 0, 3, LE);
+        assert!(result.is_err());
+
+        // A hostile count is refused without reserving room for it.
+        let result: Result<Vec<u16>, scroll::Error> = read_vec(&src, &mut 0, u32::MAX, LE);
+        assert!(result.is_err());
+        let result: Result<Vec<Vec<u16>>, scroll::Error> =
+            read_nested(&src, &mut 0, u32::MAX, u32::MAX, LE);
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,157 @@
+//! Serialization has to be byte-for-byte reproducible, so every way of
+//! writing a model is checked against files committed in `tests/golden/`.
+//! Those were produced independently of this crate, straight from the format
+//! description.
+
+use kv6::kv6::{KV6Format, Kv6Header, Kv6Writer, Palette, VoxelData};
+use scroll::{Pwrite, LE};
+use std::{fs, io::Cursor};
+
+/// `bgr` is in the order the bytes are stored.
+fn voxel(bgr: [u8; 3], dummy: u8, height: u16, visibility: u8, normalindex: u8) -> VoxelData {
+    VoxelData {
+        blue: bgr[0],
+        green: bgr[1],
+        red: bgr[2],
+        dummy,
+        height,
+        visibility,
+        normalindex,
+    }
+}
+
+/// Fills in the header and caches from `columns`, indexed `[x][y]`.
+fn model(size: [u32; 3], pivots: [f32; 3], columns: Vec<Vec<Vec<VoxelData>>>) -> KV6Format {
+    KV6Format {
+        magic: KV6Format::MAGIC,
+        x_size: size[0],
+        y_size: size[1],
+        z_size: size[2],
+        x_pivot: pivots[0],
+        y_pivot: pivots[1],
+        z_pivot: pivots[2],
+        xlen: columns
+            .iter()
+            .map(|row| row.iter().map(|column| column.len() as u32).sum())
+            .collect(),
+        ylen: columns
+            .iter()
+            .map(|row| row.iter().map(|column| column.len() as u16).collect())
+            .collect(),
+        voxels: columns.into_iter().flatten().flatten().collect(),
+        palette: None,
+        alpha_mode: Default::default(),
+    }
+}
+
+fn golden_models() -> Vec<(&'static str, KV6Format)> {
+    let column = model(
+        [1, 1, 4],
+        [0.5, 0.5, 2.0],
+        vec![vec![vec![
+            voxel([255, 0, 0], 128, 0, 0x3f, 0),
+            voxel([0, 255, 0], 128, 3, 0x01, 255),
+        ]]],
+    );
+
+    // Empty columns, an odd dummy byte and a negative zero pivot.
+    let grid = model(
+        [2, 3, 8],
+        [-0.0, 1.5, -3.25],
+        vec![
+            vec![
+                vec![],
+                vec![voxel([1, 2, 3], 128, 1, 0x10, 7)],
+                vec![
+                    voxel([10, 20, 30], 128, 0, 0x21, 8),
+                    voxel([40, 50, 60], 0, 5, 0x02, 9),
+                    voxel([70, 80, 90], 255, 7, 0x3f, 10),
+                ],
+            ],
+            vec![
+                vec![voxel([100, 110, 120], 128, 2, 0x04, 11)],
+                vec![],
+                vec![],
+            ],
+        ],
+    );
+
+    let mut palette = model(
+        [1, 1, 1],
+        [0.0; 3],
+        vec![vec![vec![voxel([12, 34, 56], 128, 0, 0x3f, 42)]]],
+    );
+    let mut entries = [[0u8; 3]; 256];
+    for (i, entry) in entries.iter_mut().enumerate() {
+        *entry = [i as u8 % 64, (i * 3) as u8 % 64, (i * 7) as u8 % 64];
+    }
+    palette.palette = Some(Palette { entries });
+
+    vec![
+        ("empty", KV6Format::default()),
+        ("column", column),
+        ("grid", grid),
+        ("palette", palette),
+    ]
+}
+
+fn golden(name: &str) -> Vec<u8> {
+    fs::read(format!("tests/golden/{}.kv6", name)).unwrap()
+}
+
+/// Every way the crate can write `data`, compared against `expected`.
+fn assert_writes(name: &str, data: &KV6Format, expected: &[u8]) {
+    assert_eq!(data.to_bytes().unwrap(), expected, "{}: to_bytes", name);
+
+    let mut out = Vec::new();
+    data.to_writer(&mut out).unwrap();
+    assert_eq!(out, expected, "{}: to_writer", name);
+
+    // Whatever was in the buffer before must not leak into the output.
+    let mut out = vec![0xaa; expected.len() + 8];
+    assert_eq!(out.pwrite_with(data, 0, LE).unwrap(), expected.len());
+    assert_eq!(&out[..expected.len()], expected, "{}: pwrite", name);
+    assert!(out[expected.len()..].iter().all(|&b| b == 0xaa));
+
+    // The column writer never emits a palette.
+    let header = KV6Format::peek_header(expected).unwrap();
+    let mut writer = Kv6Writer::begin(Cursor::new(Vec::new()), header).unwrap();
+    let mut voxels = &data.voxels[..];
+    for (x, row) in (0..).zip(&data.ylen) {
+        for (y, &count) in (0..).zip(row) {
+            let (column, rest) = voxels.split_at(count as usize);
+            writer.push_column(x, y, column).unwrap();
+            voxels = rest;
+        }
+    }
+    let out = writer.finish().unwrap().into_inner();
+    let len = header.required_len().unwrap();
+    assert_eq!(out, &expected[..len], "{}: Kv6Writer", name);
+}
+
+#[test]
+fn test_golden_files() {
+    for (name, data) in golden_models() {
+        let expected = golden(name);
+        assert_writes(name, &data, &expected);
+
+        let (parsed, consumed) = KV6Format::parse(&expected).unwrap();
+        assert_eq!(consumed, expected.len());
+        assert_eq!(parsed, data, "{}: parse", name);
+    }
+}
+
+#[test]
+fn test_grenade_rewrites_identically() {
+    let expected = fs::read("data/grenade.kv6").unwrap();
+    let (data, _) = KV6Format::parse(&expected).unwrap();
+    assert_writes("grenade", &data, &expected);
+}
+
+#[test]
+fn test_header_bytes() {
+    let header = KV6Format::peek_header(&golden("grid")).unwrap();
+    let mut out = [0u8; Kv6Header::SIZE];
+    out.pwrite_with(header, 0, LE).unwrap();
+    assert_eq!(out[..], golden("grid")[..Kv6Header::SIZE]);
+}